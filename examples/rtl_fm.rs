@@ -116,8 +116,8 @@ fn main() -> Result<()> {
     //let audio_filter = FIRFilter::new(prev, &taps);
     let prev = blehbleh![g, FftFilterFloat::new(prev, &taps)];
 
-    // Resample audio.
-    let new_samp_rate = opt.audio_rate as f32;
+    // Resample audio, to a rate the output supports.
+    let new_samp_rate = audio_rate(opt.audio_rate, opt.output.is_none())? as f32;
     let prev = blehbleh![
         g,
         RationalResampler::new(prev, new_samp_rate as usize, samp_rate as usize)?
@@ -162,3 +162,17 @@ fn main() -> Result<()> {
     eprintln!("{}", g.generate_stats(st.elapsed()));
     Ok(())
 }
+
+// Audio rate to output. When playing live, the closest the device supports.
+fn audio_rate(want: u32, live: bool) -> Result<u32> {
+    #[cfg(feature = "audio")]
+    if live {
+        let rate = AudioSink::device_rate(want as u64)? as u32;
+        if rate != want {
+            warn!("Audio device doesn't support {want} Hz, using {rate} Hz");
+        }
+        return Ok(rate);
+    }
+    let _ = live;
+    Ok(want)
+}
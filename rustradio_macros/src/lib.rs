@@ -31,6 +31,8 @@ static STRUCT_ATTRS: &[&str] = &[
     "custom_name",
     "noeof",
     "nevereof",
+    "flush",
+    "command",
 ];
 static FIELD_ATTRS: &[&str] = &["in", "out", "default"];
 
@@ -141,10 +143,16 @@ fn outer_type(ty: &syn::Type) -> syn::Type {
 ///   name.
 /// * `noeof`: Don't generate `eof()` logic.
 /// * `nevereof`: Generate `eof()` that always returns false.
+/// * `flush`: Generate `on_eof()` that calls the block's `flush()`.
+/// * `command`: Don't generate `BlockCommand`. The block implements
+///   `handle_command()` itself.
 ///
 /// Field attributes:
-/// * `in`: Input stream.
-/// * `out`: Output stream.
+/// * `in`: Input stream. Also used for `eof()`, `ports()`, and graph
+///   validation. May also be an `Option` or `Vec` of streams, but then
+///   `eof()` can't be generated.
+/// * `out`: Output stream. Also used for `ports()`, and graph validation.
+///   May also be an `Option` or `Vec` of streams.
 /// * `default`: Skip this field as arg for the `new()` function, and instead
 ///   default it.
 ///
@@ -190,40 +198,25 @@ pub fn derive_block(input: TokenStream) -> TokenStream {
     // Elements are of the form:
     // * in_names:          src
     // * in_name_types:     src: ReadStream<Complex>
-    // * inval_name_types:  src: Complex
     // * in_tag_names:      src_tag
-    // * intag_name_types:  src_tag: &'a [Vec]   or   src_tag: &[Vec]
-    //
-    // Only the first tag has lifetime marking, if it's a `sync` block. If it's
-    // a `sync_tag` block they're all lifetimed marked, though.
-    let (in_names, in_name_types, inval_name_types, in_tag_names, intag_name_types) = unzip_n![
+    let (in_names, in_name_types, in_tag_names) = unzip_n![
         fields_named
             .named
             .iter()
             .filter(|field| has_attr(&field.attrs, "in", FIELD_ATTRS))
-            .enumerate()
-            .map(|(i, field)| {
-                let inner = inner_type(&field.ty);
+            .map(|field| {
                 let ty = field.ty.clone();
                 let field_name = field.ident.clone().unwrap();
                 let tagname: syn::Ident = syn::parse_str(&format!("{field_name}_tag")).unwrap();
                 (
                     field_name.clone(),
                     quote! { #field_name: #ty },
-                    quote! { #field_name: #inner },
                     quote! { #tagname },
-                    if i == 0 || !has_attr(&input.attrs, "sync", STRUCT_ATTRS) {
-                        quote! { #tagname: &'a [#path::stream::Tag] }
-                    } else {
-                        quote! { #tagname: &[#path::stream::Tag] }
-                    },
                 )
             }),
         a,
         b,
-        c,
-        d,
-        e
+        c
     ];
 
     // Create vec of useful out expressions.
@@ -232,7 +225,7 @@ pub fn derive_block(input: TokenStream) -> TokenStream {
     // * out_names:          dst
     // * out_types_types:    WriteStream<Complex>
     // * outval_types:       Complex
-    let (out_names, _out_types, outval_types) = unzip_n![
+    let (out_names, out_types, outval_types) = unzip_n![
         fields_named
             .named
             .iter()
@@ -314,67 +307,80 @@ pub fn derive_block(input: TokenStream) -> TokenStream {
         } else {
             quote! { #first.iter().take(n)#(.zip(#rest.iter()))* }
         };
+        let read_inputs = quote! {
+            #( let #in_names = self.#in_names.read_buf()?;)*
+            #(let #in_tag_names = #in_names.1;)*
+            #(let #in_names = #in_names.0;)*
+
+            // Clamp n to be no more than the input available.
+            let n = [#(#in_names.len()),*].iter().fold(usize::MAX, |min, &x|min.min(x));
+            if n ==  0 {
+                return Ok(#path::block::BlockRet::Noop);
+            }
+            #( let mut #out_names = self.#out_names.write_buf()?;)*
+
+            // Clamp n to be no more than output space.
+            let n = [#(#out_names.len()),*].iter().fold(n, |min, &x|min.min(x));
+        };
         if has_attr(&input.attrs, "sync", STRUCT_ATTRS) {
+            // Fast path. Tags of the first input are passed through as is,
+            // so there's no need to look at them per sample.
             let first_tags = &in_tag_names[0];
             extra.push(quote! {
-                impl #impl_generics #struct_name #ty_generics #where_clause {
-                    fn process_sync_tags<'a>(&mut self, #(#inval_name_types, #intag_name_types,)*) -> (#(#outval_types,)* std::borrow::Cow<'a, [#path::stream::Tag]>) {
-                        (self.process_sync(#(#in_names,)*), std::borrow::Cow::Borrowed(#first_tags))
+                impl #impl_generics #path::block::Block for #struct_name #ty_generics #where_clause {
+                    fn work(&mut self) -> Result<#path::block::BlockRet, #path::Error> {
+                        #read_inputs
+                        let otags: Vec<_> = #first_tags.into_iter().filter(|t| t.pos() < n).collect();
+                        for ((#(#in_names),*), w) in #it.zip(#(#out_names.slice().iter_mut())*) {
+                            *w = self.process_sync(#(*#in_names),*);
+                        }
+                        #(#in_names.consume(n);)*
+                        #(#out_names.produce(n, &otags);)*
+                        Ok(#path::block::BlockRet::Ok)
                     }
                 }
             });
-        }
-        extra.push(quote! {
-            impl #impl_generics #path::block::Block for #struct_name #ty_generics #where_clause {
-                fn work(&mut self) -> Result<#path::block::BlockRet, #path::Error> {
-                    #( let #in_names = self.#in_names.read_buf()?;)*
-                    #(let #in_tag_names = #in_names.1;)*
-                    #(let #in_names = #in_names.0;)*
-
-                    // Clamp n to be no more than the input available.
-                    let n = [#(#in_names.len()),*].iter().fold(usize::MAX, |min, &x|min.min(x));
-                    if n ==  0 {
-                        return Ok(#path::block::BlockRet::Noop);
-                    }
-                    #( let mut #out_names = self.#out_names.write_buf()?;)*
-
-                    // Clamp n to be no more than output space.
-                    let n = [#(#out_names.len()),*].iter().fold(n, |min, &x|min.min(x));
-                    let mut otags = Vec::new();
-                    let empty_tags = true #(&&#in_tag_names.is_empty())*;
-                    let it = #it.enumerate().map(|(pos, (#(#in_names),*))| {
-                        if empty_tags {
-                            // Fast path for input without tags.
-                            // There may be opportunity to deduplicate some of
-                            // the next couple of lines with the !empty_tags
-                            // case.
-                            let (s, ts) = self.process_sync_tags(#(*#in_names, &[]),*);
-                            for tag in ts.iter() {
-                                otags.push(#path::stream::Tag::new(pos, tag.key().into(), tag.val().clone()));
-                            }
-                            s
-                        } else {
-                            // TODO: This tag filtering is quite expensive.
-                            #(let #in_tag_names: Vec<_> = #in_tag_names.iter()
-                              .filter(|t| t.pos() == pos)
-                              .map(|t| #path::stream::Tag::new(0, t.key().to_string(), t.val().clone()))
-                              .collect();)*
-                            let (s, ts) = self.process_sync_tags(#(*#in_names, &#in_tag_names),*);
-                            for tag in ts.iter() {
-                                otags.push(#path::stream::Tag::new(pos, tag.key().into(), tag.val().clone()));
+        } else {
+            extra.push(quote! {
+                impl #impl_generics #path::block::Block for #struct_name #ty_generics #where_clause {
+                    fn work(&mut self) -> Result<#path::block::BlockRet, #path::Error> {
+                        #read_inputs
+                        let mut otags = Vec::new();
+                        let empty_tags = true #(&&#in_tag_names.is_empty())*;
+                        let it = #it.enumerate().map(|(pos, (#(#in_names),*))| {
+                            if empty_tags {
+                                // Fast path for input without tags.
+                                // There may be opportunity to deduplicate some of
+                                // the next couple of lines with the !empty_tags
+                                // case.
+                                let (s, ts) = self.process_sync_tags(#(*#in_names, &[]),*);
+                                for tag in ts.iter() {
+                                    otags.push(#path::stream::Tag::new(pos, tag.key().into(), tag.val().clone()));
+                                }
+                                s
+                            } else {
+                                // TODO: This tag filtering is quite expensive.
+                                #(let #in_tag_names: Vec<_> = #in_tag_names.iter()
+                                  .filter(|t| t.pos() == pos)
+                                  .map(|t| #path::stream::Tag::new(0, t.key().to_string(), t.val().clone()))
+                                  .collect();)*
+                                let (s, ts) = self.process_sync_tags(#(*#in_names, &#in_tag_names),*);
+                                for tag in ts.iter() {
+                                    otags.push(#path::stream::Tag::new(pos, tag.key().into(), tag.val().clone()));
+                                }
+                                s
                             }
-                            s
+                        });
+                        for (samp, w) in it.zip(#(#out_names.slice().iter_mut())*) {
+                            *w = samp;
                         }
-                    });
-                    for (samp, w) in it.zip(#(#out_names.slice().iter_mut())*) {
-                        *w = samp;
+                        #(#in_names.consume(n);)*
+                        #(#out_names.produce(n, &otags);)*
+                        Ok(#path::block::BlockRet::Ok)
                     }
-                    #(#in_names.consume(n);)*
-                    #(#out_names.produce(n, &otags);)*
-                    Ok(#path::block::BlockRet::Ok)
                 }
-            }
-        });
+            });
+        }
     }
 
     {
@@ -392,10 +398,21 @@ pub fn derive_block(input: TokenStream) -> TokenStream {
         });
     }
 
+    let on_eof = if has_attr(&input.attrs, "flush", STRUCT_ATTRS) {
+        quote! {
+            fn on_eof(&mut self) -> Result<(), #path::Error> {
+                self.flush().map_err(Into::into)
+            }
+        }
+    } else {
+        quote! {}
+    };
     extra.push(match (in_names.is_empty(), has_attr(&input.attrs, "noeof", STRUCT_ATTRS), has_attr(&input.attrs, "nevereof", STRUCT_ATTRS)) {
         // No inputs.
         (true, _, _) => quote! {
-            impl #impl_generics #path::block::BlockEOF for #struct_name #ty_generics #where_clause {}
+            impl #impl_generics #path::block::BlockEOF for #struct_name #ty_generics #where_clause {
+                #on_eof
+            }
         },
         // Has inputs, eof generation (implicitly) requested.
         (false, false, false) => quote! {
@@ -407,18 +424,60 @@ pub fn derive_block(input: TokenStream) -> TokenStream {
                             false
                         }
                     }
+                    #on_eof
                  }
             },
         // Has inputs, no eof requested.
         (false, true, false) => quote! {},
         // Has inputs, "nevereof" requested.
         (false, false, true) => quote! {
-            impl #impl_generics #path::block::BlockEOF for #struct_name #ty_generics #where_clause {}
+            impl #impl_generics #path::block::BlockEOF for #struct_name #ty_generics #where_clause {
+                #on_eof
+            }
         },
         // Invalid combination.
         (false, true, true) => panic!("Providing noeof and nevereof is not valid"),
     });
 
+    if !has_attr(&input.attrs, "command", STRUCT_ATTRS) {
+        extra.push(quote! {
+            impl #impl_generics #path::block::BlockCommand for #struct_name #ty_generics #where_clause {}
+        });
+    }
+
+    let in_types: Vec<_> = fields_named
+        .named
+        .iter()
+        .filter(|field| has_attr(&field.attrs, "in", FIELD_ATTRS))
+        .map(|field| field.ty.clone())
+        .collect();
+    let in_strs: Vec<_> = in_names.iter().map(|n| n.to_string()).collect();
+    let out_strs: Vec<_> = out_names.iter().map(|n| n.to_string()).collect();
+    extra.push(quote! {
+        impl #impl_generics #path::block::BlockPorts for #struct_name #ty_generics #where_clause {
+            fn ports(&self) -> (Vec<#path::block::PortInfo>, Vec<#path::block::PortInfo>) {
+                (
+                    vec![#(#path::block::PortInfo {
+                        name: #in_strs,
+                        type_name: std::any::type_name::<#in_types>(),
+                        ids: #path::stream::StreamPort::stream_ids(&self.#in_names),
+                    }),*],
+                    vec![#(#path::block::PortInfo {
+                        name: #out_strs,
+                        type_name: std::any::type_name::<#out_types>(),
+                        ids: #path::stream::StreamPort::stream_ids(&self.#out_names),
+                    }),*],
+                )
+            }
+            fn samples_consumed(&self) -> u64 {
+                0 #(+ #path::stream::StreamPort::samples(&self.#in_names))*
+            }
+            fn samples_produced(&self) -> u64 {
+                0 #(+ #path::stream::StreamPort::samples(&self.#out_names))*
+            }
+        }
+    });
+
     TokenStream::from(quote! { #(#extra)* })
 }
 /* vim: textwidth=80
@@ -89,6 +89,27 @@ mod tests {
         assert_eq!(got, want);
         Ok(())
     }
+
+    #[test]
+    fn tags_from_first_input() -> crate::Result<()> {
+        use crate::stream::{Tag, TagValue};
+        let (aw, a) = crate::stream::new_stream();
+        let (bw, b) = crate::stream::new_stream();
+        let tag = |pos| Tag::new(pos, "x".into(), TagValue::U64(pos as u64));
+        let mut o = aw.write_buf()?;
+        o.fill_from_slice(&[1u32, 2, 3]);
+        o.produce(3, &[tag(0), tag(2)]);
+        let mut o = bw.write_buf()?;
+        o.fill_from_slice(&[10u32, 20]);
+        o.produce(2, &[tag(1)]);
+
+        let (mut add, os) = Add::new(a, b);
+        add.work()?;
+        let (res, tags) = os.read_buf()?;
+        assert_eq!(res.slice(), &[11, 22]);
+        assert_eq!(tags, &[tag(0)]);
+        Ok(())
+    }
 }
 /* vim: textwidth=80
  */
@@ -0,0 +1,188 @@
+/*! All-pass filter, for phase equalization.
+
+An all-pass filter has unity magnitude response at all frequencies, but
+delays some frequencies more than others. A cascade of all-pass sections
+can be used to flatten the group delay after e.g. IIR filters, without
+changing the amplitude response.
+
+Each section is a second order ("biquad") all-pass, from the [Audio EQ
+Cookbook][cookbook]. Its phase shifts by 360° around the center frequency
+`f0`, and `Q` sets how narrow that is. Higher Q gives more group delay, over
+a narrower band.
+
+[cookbook]: https://www.w3.org/TR/audio-eq-cookbook/
+*/
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+// One biquad all-pass section.
+//
+// For an all-pass, the numerator is the denominator reversed, so two
+// coefficients are enough.
+#[derive(Clone, Debug)]
+struct Section {
+    a1: Float,
+    a2: Float,
+    x1: Float,
+    x2: Float,
+    y1: Float,
+    y2: Float,
+}
+
+impl Section {
+    fn new(samp_rate: Float, f0: Float, q: Float) -> Self {
+        let w0 = 2.0 * std::f64::consts::PI as Float * f0 / samp_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Self {
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: Float) -> Float {
+        let y = self.a2 * x + self.a1 * self.x1 + self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+
+    // Frequency response at `w` radians per sample.
+    fn response(&self, w: Float) -> Complex {
+        let z1 = Complex::from_polar(1.0, -w);
+        let z2 = z1 * z1;
+        let num = self.a2 + z1 * self.a1 + z2;
+        let den = Complex::new(1.0, 0.0) + z1 * self.a1 + z2 * self.a2;
+        num / den
+    }
+}
+
+/// All-pass filter block, made of a cascade of biquad sections.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct AllPass {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    samp_rate: Float,
+    sections: Vec<Section>,
+}
+
+impl AllPass {
+    /// Create new AllPass block.
+    ///
+    /// Each section is given as `(f0, Q)`, with `f0` in the same unit as
+    /// `samp_rate`.
+    pub fn new(
+        src: ReadStream<Float>,
+        samp_rate: Float,
+        sections: &[(Float, Float)],
+    ) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                samp_rate,
+                sections: sections
+                    .iter()
+                    .map(|&(f0, q)| Section::new(samp_rate, f0, q))
+                    .collect(),
+            },
+            dr,
+        )
+    }
+
+    /// Return the group delay at `freq`, in samples.
+    ///
+    /// Useful for choosing sections that give the wanted correction.
+    pub fn group_delay(&self, freq: Float) -> Float {
+        // Numerical derivative of the phase.
+        let w = 2.0 * std::f64::consts::PI as Float * freq / self.samp_rate;
+        let dw = 1e-3;
+        let phase = |w: Float| -> Float {
+            self.sections
+                .iter()
+                .map(|s| s.response(w).arg())
+                .sum::<Float>()
+        };
+        let mut d = phase(w + dw) - phase(w - dw);
+        // Unwrap the phase difference.
+        let pi = std::f64::consts::PI as Float;
+        while d > pi {
+            d -= 2.0 * pi;
+        }
+        while d < -pi {
+            d += 2.0 * pi;
+        }
+        -d / (2.0 * dw)
+    }
+
+    fn process_sync(&mut self, x: Float) -> Float {
+        self.sections.iter_mut().fold(x, |x, s| s.process(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    fn rms(v: &[Float]) -> Float {
+        (v.iter().map(|x| x * x).sum::<Float>() / v.len() as Float).sqrt()
+    }
+
+    #[test]
+    fn unity_magnitude() -> crate::Result<()> {
+        let samp_rate = 48000.0;
+        let sections = [(1000.0, 0.7), (5000.0, 2.0)];
+        for freq in [100.0, 800.0, 1000.0, 3000.0, 5000.0, 12000.0] {
+            let input: Vec<Float> = (0..20000)
+                .map(|n| (2.0 * std::f32::consts::PI * freq * n as Float / samp_rate).sin())
+                .collect();
+            let (mut src, prev) = VectorSource::new(input.clone());
+            let (mut b, out) = AllPass::new(prev, samp_rate, &sections);
+            src.work()?;
+            b.work()?;
+            let (res, _) = out.read_buf()?;
+            // Skip the transient.
+            let got = &res.slice()[10000..];
+            let want = &input[10000..];
+            let ratio = rms(got) / rms(want);
+            assert!((ratio - 1.0).abs() < 0.01, "freq {freq}: gain {ratio}");
+            let diff = got
+                .iter()
+                .zip(want)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, Float::max);
+            assert!(diff > 0.05, "freq {freq}: phase not changed");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn group_delay() {
+        let (_, prev) = crate::stream::new_stream();
+        let (b, _) = AllPass::new(prev, 48000.0, &[(1000.0, 2.0)]);
+        // Most delay at the center frequency.
+        let center = b.group_delay(1000.0);
+        assert!(center > 0.0);
+        assert!(center > b.group_delay(200.0), "{center}");
+        assert!(center > b.group_delay(5000.0), "{center}");
+        // Biquad all-pass group delay at f0 is 2·Q / (π·f0) seconds,
+        // approximately, for high Q.
+        let want = 2.0 * 2.0 / (std::f32::consts::PI * 1000.0) * 48000.0;
+        assert!(
+            (center - want).abs() < 0.1 * want,
+            "got {center}, want {want}"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+/*! AM demodulation.
+
+An envelope detector, for demodulating AM. The magnitude of the complex
+signal is the envelope. What remains of the carrier is a DC offset, which
+can optionally be removed.
+
+Also see [QuadratureDemod][crate::quadrature_demod::QuadratureDemod] and
+[FastFM][crate::quadrature_demod::FastFM] for FM.
+*/
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+/// AM demodulator.
+///
+/// Outputs `gain * |s|`, minus the DC if DC blocking is enabled.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct AmDemod {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    gain: Float,
+    #[rustradio(default)]
+    dc_alpha: Option<Float>,
+    #[rustradio(default)]
+    dc: Float,
+}
+
+impl AmDemod {
+    /// Enable DC blocking, or disable it with `None`.
+    ///
+    /// The DC level is tracked by a single pole IIR filter with the given
+    /// alpha. Lower alpha means a lower cutoff frequency, but slower to
+    /// settle.
+    pub fn set_dc_block(&mut self, alpha: Option<Float>) {
+        self.dc_alpha = alpha;
+    }
+
+    fn process_sync(&mut self, s: Complex) -> Float {
+        let mag = s.norm();
+        let out = match self.dc_alpha {
+            None => mag,
+            Some(alpha) => {
+                self.dc += alpha * (mag - self.dc);
+                mag - self.dc
+            }
+        };
+        self.gain * out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+    use crate::tests::assert_almost_equal_float;
+
+    // Tone, and that tone AM modulated onto a spinning carrier.
+    fn modulated(len: usize) -> (Vec<Float>, Vec<Complex>) {
+        let tone: Vec<Float> = (0..len)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 0.01 * i as Float).sin())
+            .collect();
+        let sig = tone
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (1.0 + t) * Complex::from_polar(1.0, 0.3 * i as Float))
+            .collect();
+        (tone, sig)
+    }
+
+    fn run(
+        b: &mut AmDemod,
+        src: &mut VectorSource<Complex>,
+        out: &ReadStream<Float>,
+    ) -> Vec<Float> {
+        src.work().unwrap();
+        b.work().unwrap();
+        out.read_buf().unwrap().0.slice().to_vec()
+    }
+
+    #[test]
+    fn envelope() {
+        let (tone, sig) = modulated(1000);
+        let (mut src, prev) = VectorSource::new(sig);
+        let (mut b, out) = AmDemod::new(prev, 2.0);
+        let got = run(&mut b, &mut src, &out);
+        let want: Vec<_> = tone.iter().map(|t| 2.0 * (1.0 + t)).collect();
+        assert_almost_equal_float(&got, &want);
+    }
+
+    #[test]
+    fn dc_block() {
+        let (tone, sig) = modulated(20000);
+        let (mut src, prev) = VectorSource::new(sig);
+        let (mut b, out) = AmDemod::new(prev, 1.0);
+        b.set_dc_block(Some(0.001));
+        let got = run(&mut b, &mut src, &out);
+        // Skip until the DC tracking has settled.
+        for (i, (g, t)) in got.iter().zip(tone.iter()).enumerate().skip(15000) {
+            assert!((g - t).abs() < 0.05, "sample {i}: got {g}, want {t}");
+        }
+    }
+}
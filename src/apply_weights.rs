@@ -0,0 +1,93 @@
+//! Multiply a stream by per-sample weights from another stream.
+//!
+//! To multiply by a constant, instead use MultiplyConst.
+use crate::stream::{ReadStream, WriteStream};
+use crate::Complex;
+
+/// Multiply a stream by weights from a second stream, sample wise.
+///
+/// Like a one tap filter where the tap changes every sample, e.g. for
+/// adaptive beamforming, where the weights are calculated by another block.
+///
+/// Only as many samples as are available on both inputs are processed, so
+/// if the weights lag behind, the block waits for them.
+///
+/// Output tags are taken from the data stream. Tags from the weight stream
+/// are discarded.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct ApplyWeights {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+
+    #[rustradio(in)]
+    weights: ReadStream<Complex>,
+
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+}
+
+impl ApplyWeights {
+    fn process_sync(&self, s: Complex, w: Complex) -> Complex {
+        s * w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockRet};
+    use crate::blocks::{MultiplyConst, VectorSource};
+    use crate::tests::assert_almost_equal_complex;
+    use crate::Float;
+
+    fn input() -> Vec<Complex> {
+        (0..100)
+            .map(|i| Complex::new((i as Float * 0.1).cos(), (i as Float * 0.3).sin()))
+            .collect()
+    }
+
+    #[test]
+    fn constant() -> crate::Result<()> {
+        let w = Complex::new(0.5, -2.0);
+        let (mut src, prev) = VectorSource::new(input());
+        let (mut wsrc, weights) = VectorSource::new(vec![w; 100]);
+        let (mut b, out) = ApplyWeights::new(prev, weights);
+        src.work()?;
+        wsrc.work()?;
+        b.work()?;
+
+        let (mut src, prev) = VectorSource::new(input());
+        let (mut mc, want) = MultiplyConst::new(prev, w);
+        src.work()?;
+        mc.work()?;
+        assert_almost_equal_complex(out.read_buf()?.0.slice(), want.read_buf()?.0.slice());
+        Ok(())
+    }
+
+    #[test]
+    fn varying() -> crate::Result<()> {
+        let data = input();
+        let weights: Vec<_> = (0..100).map(|i| Complex::new(0.0, i as Float)).collect();
+        let (mut src, prev) = VectorSource::new(data.clone());
+        let (ww, wr) = crate::stream::new_stream();
+        let (mut b, out) = ApplyWeights::new(prev, wr);
+        src.work()?;
+
+        // Weights lagging behind.
+        assert_eq!(b.work()?, BlockRet::Noop);
+        let mut o = ww.write_buf()?;
+        o.fill_from_slice(&weights[..40]);
+        o.produce(40, &[]);
+        b.work()?;
+        assert_eq!(out.read_buf()?.0.len(), 40);
+        let mut o = ww.write_buf()?;
+        o.fill_from_slice(&weights[40..]);
+        o.produce(60, &[]);
+        b.work()?;
+
+        let want: Vec<_> = data.iter().zip(&weights).map(|(s, w)| s * w).collect();
+        assert_almost_equal_complex(out.read_buf()?.0.slice(), &want);
+        Ok(())
+    }
+}
@@ -12,20 +12,94 @@ It's also much simpler.
 use anyhow::Result;
 
 use crate::block::{Block, BlockRet};
+use crate::convert::SaturatingFromFloat;
 use crate::stream::{ReadStream, WriteStream};
 use crate::{Error, Float};
 
-/// Au support several encodings. This code currently has only one.
+/// Au support several encodings. This code implements a few of them.
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum Encoding {
+    /// 8 bit G.711 µ-law.
+    ///
+    /// Companded, so it has better dynamic range than 8 bit linear PCM.
+    /// Good for voice.
+    MuLaw = 1,
+
+    /// 8 bit linear PCM.
+    PCM8 = 2,
+
     /// 16 bit linear PCM.
     PCM16 = 3,
 }
 
+impl Encoding {
+    fn from_code(code: u32) -> Option<Self> {
+        match code {
+            1 => Some(Self::MuLaw),
+            2 => Some(Self::PCM8),
+            3 => Some(Self::PCM16),
+            _ => None,
+        }
+    }
+
+    /// Bytes per sample.
+    fn size(self) -> usize {
+        match self {
+            Self::MuLaw | Self::PCM8 => 1,
+            Self::PCM16 => 2,
+        }
+    }
+
+    // Encode one sample into `out`, which is `size()` bytes.
+    fn encode(self, x: Float, out: &mut [u8]) {
+        match self {
+            Self::MuLaw => out[0] = mulaw_encode(i16::saturating_from_float(x * 32767.0)),
+            Self::PCM8 => out[0] = i8::saturating_from_float(x * 127.0) as u8,
+            Self::PCM16 => out
+                .copy_from_slice(&i16::saturating_from_float(x * i16::MAX as Float).to_be_bytes()),
+        }
+    }
+
+    // Decode one sample from `size()` bytes.
+    fn decode(self, b: &[u8]) -> Float {
+        match self {
+            Self::MuLaw => mulaw_decode(b[0]) as Float / 32767.0,
+            Self::PCM8 => b[0] as i8 as Float / 127.0,
+            Self::PCM16 => i16::from_be_bytes([b[0], b[1]]) as Float / 32767.0,
+        }
+    }
+}
+
+const MULAW_BIAS: i32 = 0x84;
+const MULAW_CLIP: i32 = 32635;
+
+// G.711 µ-law encode a 16 bit linear sample.
+fn mulaw_encode(x: i16) -> u8 {
+    let sign = if x < 0 { 0x80 } else { 0 };
+    let mag = (x as i32).abs().min(MULAW_CLIP) + MULAW_BIAS;
+    // Position of highest set bit, from 7 to 14.
+    let exponent = (31 - mag.leading_zeros()) as i32 - 7;
+    let mantissa = (mag >> (exponent + 3)) & 0x0f;
+    !(sign | (exponent << 4) as u8 | mantissa as u8)
+}
+
+// G.711 µ-law decode into a 16 bit linear sample.
+fn mulaw_decode(u: u8) -> i16 {
+    let u = !u;
+    let exponent = (u >> 4) & 0x07;
+    let mantissa = (u & 0x0f) as i32;
+    let mag = (((mantissa << 3) + MULAW_BIAS) << exponent) - MULAW_BIAS;
+    if u & 0x80 != 0 {
+        -mag as i16
+    } else {
+        mag as i16
+    }
+}
+
 /** Au encoder block.
 
 This block takes a stream of floats between -1 and 1, and writes them
-as the bytes of an .au file.
+as the bytes of an .au file. Values outside that range are clamped.
 
 ```
 use rustradio::graph::{Graph, GraphRunner};
@@ -47,9 +121,10 @@ g.run()?;
 ```
 */
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
+#[rustradio(crate, flush)]
 pub struct AuEncode {
     header: Option<Vec<u8>>,
+    encoding: Encoding,
 
     #[rustradio(in)]
     src: ReadStream<Float>,
@@ -61,7 +136,7 @@ pub struct AuEncode {
 impl AuEncode {
     /// Create new Au encoder block.
     ///
-    /// * `encoding`: currently only `Encoding::PCM16` is implemented.
+    /// * `encoding`: `Encoding::PCM16`, `Encoding::PCM8`, or `Encoding::MuLaw`.
     /// * `bitrate`: E.g. 48000,
     /// * `channels`: Currently only mono (1) is implemented.
     pub fn new(
@@ -70,11 +145,6 @@ impl AuEncode {
         bitrate: u32,
         channels: u32,
     ) -> (Self, ReadStream<u8>) {
-        assert_eq!(
-            encoding,
-            Encoding::PCM16,
-            "only encoding supported is PCM16"
-        );
         assert_eq!(channels, 1, "only mono supported at the moment");
 
         let mut v = Vec::with_capacity(28);
@@ -104,6 +174,7 @@ impl AuEncode {
         (
             Self {
                 header: Some(v),
+                encoding,
                 src,
                 dst,
             },
@@ -112,6 +183,27 @@ impl AuEncode {
     }
 }
 
+impl AuEncode {
+    /// Encode all available input, and the header if not yet written.
+    ///
+    /// Called automatically on EOF, but can be called explicitly, e.g.
+    /// before reading back a file being written. The .au size field is
+    /// left as unknown, so there's no header to patch afterwards.
+    ///
+    /// Fails if the output stream doesn't have room.
+    pub fn flush(&mut self) -> Result<()> {
+        loop {
+            if self.header.is_none() && self.src.read_buf()?.0.is_empty() {
+                return Ok(());
+            }
+            if self.dst.free() < self.encoding.size() {
+                return Err(Error::new("AuEncode: no room in output to flush").into());
+            }
+            self.work()?;
+        }
+    }
+}
+
 impl Block for AuEncode {
     fn work(&mut self) -> Result<BlockRet, Error> {
         let mut o = self.dst.write_buf()?;
@@ -126,9 +218,7 @@ impl Block for AuEncode {
             return Ok(BlockRet::Ok);
         }
 
-        type S = i16;
-        let scale = S::MAX as Float;
-        let ss = std::mem::size_of::<S>();
+        let ss = self.encoding.size();
 
         let (i, _tags) = self.src.read_buf()?;
         if i.is_empty() {
@@ -139,9 +229,8 @@ impl Block for AuEncode {
             return Ok(BlockRet::Ok);
         }
 
-        for j in 0..n {
-            let val = (i.slice()[j] * scale) as S;
-            o.slice()[j * ss..(j + 1) * ss].clone_from_slice(&val.to_be_bytes());
+        for (x, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(ss)) {
+            self.encoding.encode(*x, out);
         }
         i.consume(n);
         o.produce(n * ss, &[]);
@@ -158,8 +247,8 @@ enum DecodeState {
 
 /// .au file decoder.
 ///
-/// Currently only accepts a very narrow header format of PCM16, mono,
-/// 44100 Hz.
+/// Accepts PCM16, PCM8, and µ-law encodings. Only mono, and the
+/// bitrate must match what the block was created with.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct AuDecode {
@@ -169,6 +258,7 @@ pub struct AuDecode {
     dst: WriteStream<Float>,
     state: DecodeState,
     bitrate: u32,
+    encoding: Encoding,
 }
 
 impl AuDecode {
@@ -181,6 +271,7 @@ impl AuDecode {
                 bitrate,
                 dst,
                 state: DecodeState::WaitingMagic,
+                encoding: Encoding::PCM16,
             },
             dr,
         )
@@ -222,9 +313,12 @@ impl Block for AuDecode {
                     return Ok(BlockRet::Noop);
                 }
                 let head = i.iter().take(header_rest_len).copied().collect::<Vec<_>>();
-                if Encoding::PCM16 as u32 != u32::from_be_bytes(head[4..8].try_into().unwrap()) {
-                    return Err(Error::new("only PCM16 encoding supported"));
-                }
+                i.consume(header_rest_len);
+                let code = u32::from_be_bytes(head[4..8].try_into().unwrap());
+                let Some(encoding) = Encoding::from_code(code) else {
+                    return Err(Error::new(&format!("AU encoding {code} not supported")));
+                };
+                self.encoding = encoding;
                 let bitrate = u32::from_be_bytes(head[8..12].try_into().unwrap());
                 if self.bitrate != bitrate {
                     return Err(Error::new(&format![
@@ -241,8 +335,9 @@ impl Block for AuDecode {
                 self.state = DecodeState::Data;
             }
             DecodeState::Data => {
-                let n = std::cmp::min(i.len(), o.len() * 2); // Bytes.
-                let n = n - (n & 1);
+                let ss = self.encoding.size();
+                let n = std::cmp::min(i.len(), o.len() * ss); // Bytes.
+                let n = n - (n % ss);
                 if n == 0 {
                     return Ok(BlockRet::Noop);
                 }
@@ -251,17 +346,132 @@ impl Block for AuDecode {
                     .take(n)
                     .copied()
                     .collect::<Vec<u8>>()
-                    .chunks_exact(2)
-                    .map(|chunk| {
-                        let bytes = [chunk[0], chunk[1]];
-                        (i16::from_be_bytes(bytes) as Float) / 32767.0
-                    })
+                    .chunks_exact(ss)
+                    .map(|chunk| self.encoding.decode(chunk))
                     .collect::<Vec<Float>>();
-                o.fill_from_iter(v);
-                o.produce(n / 2, &[]);
+                o.fill_from_slice(&v);
+                o.produce(n / ss, &[]);
                 i.consume(n);
             }
         };
         Ok(BlockRet::Ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    // Encode `input`, returning the bytes, and the decoded samples.
+    fn roundtrip(encoding: Encoding, input: &[Float]) -> Result<(Vec<u8>, Vec<Float>)> {
+        let (mut src, prev) = VectorSource::new(input.to_vec());
+        let (mut enc, prev) = AuEncode::new(prev, encoding, 8000, 1);
+        src.work()?;
+        enc.work()?;
+        enc.work()?;
+        let bytes = {
+            let (b, _) = prev.read_buf()?;
+            b.slice().to_vec()
+        };
+        let (mut dec, out) = AuDecode::new(prev, 8000);
+        for _ in 0..4 {
+            dec.work()?;
+        }
+        let (res, _) = out.read_buf()?;
+        Ok((bytes, res.slice().to_vec()))
+    }
+
+    fn ramp() -> Vec<Float> {
+        (-4..=4).map(|n| n as Float / 4.0).collect()
+    }
+
+    // Assert that `got` is within `tol` of the ramp.
+    fn assert_close(got: &[Float], tol: Float) {
+        assert_eq!(got.len(), 9);
+        for (g, w) in got.iter().zip(ramp()) {
+            assert!((g - w).abs() <= tol, "got {g}, want {w}");
+        }
+    }
+
+    #[test]
+    fn header() -> Result<()> {
+        for (encoding, code) in [
+            (Encoding::MuLaw, 1u8),
+            (Encoding::PCM8, 2),
+            (Encoding::PCM16, 3),
+        ] {
+            let (bytes, _) = roundtrip(encoding, &[])?;
+            assert_eq!(
+                bytes,
+                [
+                    0x2e, 0x73, 0x6e, 0x64, 0, 0, 0, 28, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, code, 0,
+                    0, 0x1f, 0x40, 0, 0, 0, 1, 0, 0, 0, 0
+                ]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pcm() -> Result<()> {
+        let (bytes, got) = roundtrip(Encoding::PCM8, &ramp())?;
+        assert_eq!(
+            &bytes[28..],
+            [0x81, 0xa1, 0xc1, 0xe1, 0, 0x1f, 0x3f, 0x5f, 0x7f]
+        );
+        assert_close(&got, 1.0 / 127.0);
+
+        let (bytes, got) = roundtrip(Encoding::PCM16, &ramp())?;
+        assert_eq!(&bytes[28..32], [0x80, 0x01, 0xa0, 0x01]);
+        assert_close(&got, 1.0 / 32767.0);
+        Ok(())
+    }
+
+    #[test]
+    fn mulaw() -> Result<()> {
+        let (bytes, got) = roundtrip(Encoding::MuLaw, &ramp())?;
+        assert_eq!(bytes.len(), 28 + 9);
+        // Full scale, zero, and full scale.
+        assert_eq!([bytes[28], bytes[32], bytes[36]], [0x00, 0xff, 0x80]);
+        for (g, w) in got.iter().zip(ramp()) {
+            // Quantization is relative to the amplitude.
+            assert!((g - w).abs() <= 0.04 * w.abs() + 0.001, "got {g}, want {w}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn flush_file() -> Result<()> {
+        use crate::file_sink::{FileSink, Mode};
+        let tmpd = tempfile::tempdir()?;
+        let tmpfn = tmpd.path().join("out.au");
+        let (mut src, prev) = VectorSource::new(ramp());
+        let (mut enc, prev) = AuEncode::new(prev, Encoding::PCM8, 8000, 1);
+        let mut sink = FileSink::new(prev, tmpfn.clone(), Mode::Create)?;
+        src.work()?;
+        enc.flush()?;
+        sink.work()?;
+        sink.flush()?;
+
+        // Blocks are still alive, so nothing relies on Drop.
+        let bytes = std::fs::read(&tmpfn)?;
+        assert_eq!(bytes.len(), 28 + 9);
+        assert_eq!(&bytes[..4], b".snd");
+        assert_eq!(&bytes[12..16], [0, 0, 0, 2]);
+        assert_eq!(
+            &bytes[28..],
+            [0x81, 0xa1, 0xc1, 0xe1, 0, 0x1f, 0x3f, 0x5f, 0x7f]
+        );
+        drop((src, enc, sink));
+        Ok(())
+    }
+
+    #[test]
+    fn mulaw_small() {
+        for x in [-1000i16, -33, -1, 0, 1, 33, 1000] {
+            let got = mulaw_decode(mulaw_encode(x));
+            assert!((got - x).abs() <= 16, "{x} became {got}");
+        }
+    }
+}
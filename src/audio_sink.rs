@@ -1,7 +1,7 @@
 use anyhow::Result;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Sample;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 
 use crate::block::{Block, BlockRet};
 use crate::graph::CancellationToken;
@@ -10,6 +10,37 @@ use crate::{Error, Float};
 
 use std::sync::mpsc::{sync_channel, SyncSender};
 
+// Max relative difference between the requested and the actual sample
+// rate, before AudioSink refuses to play.
+const MAX_RATE_MISMATCH: f64 = 0.01;
+
+// Pick the supported rate closest to `want`, from ranges of `(min, max)`.
+//
+// If there are no ranges, assume `want` is fine.
+fn choose_rate(want: u32, ranges: impl IntoIterator<Item = (u32, u32)>) -> u32 {
+    ranges
+        .into_iter()
+        .map(|(min, max)| want.clamp(min, max))
+        .min_by_key(|r| r.abs_diff(want))
+        .unwrap_or(want)
+}
+
+fn default_device() -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    host.default_output_device().ok_or(anyhow::Error::msg(
+        "audio sink: failed to find output device",
+    ))
+}
+
+// Sample rate the device supports that is closest to `want`.
+fn device_rate(device: &cpal::Device, want: u32) -> Result<u32> {
+    let ranges: Vec<_> = device
+        .supported_output_configs()?
+        .map(|c| (c.min_sample_rate().0, c.max_sample_rate().0))
+        .collect();
+    Ok(choose_rate(want, ranges))
+}
+
 struct CpalOutput {
     device: cpal::Device,
     config: cpal::StreamConfig,
@@ -30,9 +61,7 @@ impl CpalOutput {
                 debug!("Audio sink device: {:?}", dev.name()?);
             }
         }
-        let device = host.default_output_device().ok_or(anyhow::Error::msg(
-            "audio sink: failed to find output device",
-        ))?;
+        let device = default_device()?;
         info!("Audio sink output device: {}", device.name()?);
 
         trace!("Audio sink supported output configs:");
@@ -45,7 +74,11 @@ impl CpalOutput {
 
         let mut config: cpal::StreamConfig = config.into();
 
-        config.sample_rate = cpal::SampleRate(sample_rate);
+        let rate = device_rate(&device, sample_rate)?;
+        if rate != sample_rate {
+            warn!("Audio sink: device doesn't support {sample_rate} Hz, using {rate} Hz");
+        }
+        config.sample_rate = cpal::SampleRate(rate);
         config.channels = 1;
 
         Ok(Self { device, config })
@@ -86,12 +119,18 @@ impl CpalOutput {
     }
 }
 
+/// Play audio on the default output device.
+///
+/// The device may not support the requested sample rate. Use
+/// [AudioSink::device_rate] to find the closest rate it does support, and
+/// resample to that.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct AudioSink {
     #[rustradio(in)]
     src: ReadStream<Float>,
     sender: Option<SyncSender<f32>>,
+    actual_rate: u64,
 
     // The cpal::Stream is not Send, but needs to be kept alive for the duration
     // of this block's lifetime. So we spawn a thread just to own that stream.
@@ -102,8 +141,21 @@ pub struct AudioSink {
 }
 
 impl AudioSink {
+    /// Create new AudioSink block.
+    ///
+    /// Fails if the device doesn't support a sample rate within 1% of
+    /// `sample_rate`. Smaller differences are logged, and change the pitch
+    /// a bit.
     pub fn new(src: ReadStream<Float>, sample_rate: u64) -> Result<Self> {
         let output = CpalOutput::new(sample_rate as u32)?;
+        let actual_rate = output.config.sample_rate.0 as u64;
+        let mismatch = (actual_rate as f64 - sample_rate as f64).abs() / sample_rate as f64;
+        if mismatch > MAX_RATE_MISMATCH {
+            return Err(Error::new(&format!(
+                "audio sink: device doesn't support {sample_rate} Hz, closest is {actual_rate} Hz"
+            ))
+            .into());
+        }
         let (tx, rx) = std::sync::mpsc::channel();
         let cancel = CancellationToken::new();
         let c2 = cancel.clone();
@@ -140,12 +192,30 @@ impl AudioSink {
         Ok(Self {
             src,
             sender,
+            actual_rate,
             cancel,
             audio_thread: Some(audio_thread),
         })
     }
 }
 
+impl AudioSink {
+    /// Return the sample rate closest to `sample_rate` that the default
+    /// output device supports.
+    ///
+    /// Call this before building the graph, to resample to a rate the
+    /// device will accept.
+    pub fn device_rate(sample_rate: u64) -> Result<u64> {
+        let device = default_device()?;
+        Ok(device_rate(&device, sample_rate as u32)? as u64)
+    }
+
+    /// Return the sample rate the device is actually playing at.
+    pub fn actual_rate(&self) -> u64 {
+        self.actual_rate
+    }
+}
+
 impl Drop for AudioSink {
     fn drop(&mut self) {
         self.cancel.cancel(); // Allows the thread to end.
@@ -174,3 +244,30 @@ impl Block for AudioSink {
         Ok(BlockRet::Noop)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose() {
+        assert_eq!(choose_rate(48000, []), 48000);
+        assert_eq!(choose_rate(48000, [(8000, 96000)]), 48000);
+        assert_eq!(choose_rate(50000, [(44100, 44100), (48000, 48000)]), 48000);
+        assert_eq!(choose_rate(8000, [(44100, 192000), (22050, 22050)]), 22050);
+    }
+
+    #[test]
+    fn actual_rate() -> Result<()> {
+        // Needs an audio device, which test machines may not have.
+        if AudioSink::device_rate(48000).is_err() {
+            eprintln!("No audio device, skipping test");
+            return Ok(());
+        }
+        let (_w, r) = crate::stream::new_stream();
+        let sink = AudioSink::new(r, AudioSink::device_rate(48000)?)?;
+        assert!(sink.actual_rate() > 0);
+        assert_eq!(sink.actual_rate(), AudioSink::device_rate(48000)?);
+        Ok(())
+    }
+}
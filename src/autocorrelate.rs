@@ -0,0 +1,197 @@
+/*! Autocorrelation, for estimating the period of a signal.
+
+Useful for blind estimation of symbol rate. Feed it e.g. the magnitude of an
+ASK signal, or the absolute value of a
+[QuadratureDemod][crate::quadrature_demod::QuadratureDemod] output, and the
+dominant peak of the autocorrelation is the symbol period.
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Error, Float};
+
+/// Tag key for the lag of the dominant peak, in samples.
+pub const TAG_LAG: &str = "autocorrelate:lag";
+
+/// Default number of samples per window.
+pub const DEFAULT_WINDOW: usize = 4096;
+
+// A peak this close to the highest one counts as dominant, if it's at a
+// shorter lag. Multiples of the period correlate almost as well as the
+// period itself.
+const PEAK_RATIO: Float = 0.9;
+
+/// Autocorrelation block.
+///
+/// Samples pass through unchanged. Every `window` samples, the
+/// autocorrelation of the window is calculated for lags `min_lag..=max_lag`,
+/// and the lag of the dominant peak is tagged on the last sample of the
+/// window, and available from [Autocorrelate::peak_lag].
+///
+/// The mean of the window is removed first, so DC doesn't dominate. Only
+/// peaks strictly between `min_lag` and `max_lag` are found.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Autocorrelate {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    min_lag: usize,
+    max_lag: usize,
+    window: usize,
+    buf: Vec<Float>,
+    corr: Vec<Float>,
+    peak: Option<usize>,
+}
+
+impl Autocorrelate {
+    /// Create new Autocorrelate block, with the default window size.
+    pub fn new(
+        src: ReadStream<Float>,
+        min_lag: usize,
+        max_lag: usize,
+    ) -> Result<(Self, ReadStream<Float>), Error> {
+        if min_lag == 0 || min_lag > max_lag {
+            return Err(Error::new(&format!(
+                "Autocorrelate: bad lag range {min_lag}..={max_lag}"
+            )));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                min_lag,
+                max_lag,
+                window: DEFAULT_WINDOW,
+                buf: Vec::new(),
+                corr: Vec::new(),
+                peak: None,
+            },
+            dr,
+        ))
+    }
+
+    /// Set number of samples per window.
+    ///
+    /// Should be many times `max_lag`. Default is [DEFAULT_WINDOW].
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.buf.clear();
+    }
+
+    /// Return lag of the dominant peak of the last window, in samples.
+    pub fn peak_lag(&self) -> Option<usize> {
+        self.peak
+    }
+
+    /// Return the autocorrelation of the last window, normalized so that
+    /// lag zero would be 1.0.
+    ///
+    /// Index 0 is `min_lag`.
+    pub fn correlation(&self) -> &[Float] {
+        &self.corr
+    }
+
+    // Calculate autocorrelation of the window in `buf`, and find the peak.
+    fn correlate(&mut self) {
+        let x = &self.buf;
+        let mean = x.iter().sum::<Float>() / x.len() as Float;
+        let x: Vec<Float> = x.iter().map(|v| v - mean).collect();
+        let n = x.len() - self.max_lag;
+        let power: Float = x[..n].iter().map(|v| v * v).sum();
+        self.corr = (self.min_lag..=self.max_lag)
+            .map(|lag| {
+                let r: Float = x[..n].iter().zip(&x[lag..]).map(|(a, b)| a * b).sum();
+                if power > 0.0 {
+                    r / power
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let c = &self.corr;
+        let max = c.iter().copied().fold(0.0, Float::max);
+        self.peak = if max <= 0.0 {
+            None
+        } else {
+            // First local maximum that's close to the highest.
+            (1..c.len().saturating_sub(1))
+                .find(|&i| c[i] >= PEAK_RATIO * max && c[i] >= c[i - 1] && c[i] >= c[i + 1])
+                .map(|i| i + self.min_lag)
+        };
+    }
+}
+
+impl Block for Autocorrelate {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let mut tags: Vec<Tag> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        // The window needs `max_lag` extra samples to correlate against.
+        let need = self.window + self.max_lag;
+        for (pos, &v) in i.iter().take(n).enumerate() {
+            self.buf.push(v);
+            if self.buf.len() == need {
+                self.correlate();
+                if let Some(lag) = self.peak {
+                    tags.push(Tag::new(pos, TAG_LAG.into(), TagValue::U64(lag as u64)));
+                }
+                self.buf.drain(..self.window);
+            }
+        }
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    #[test]
+    fn periodic() -> crate::Result<()> {
+        for period in [5, 13, 20, 31] {
+            // A pseudo random pattern, repeated, plus some noise.
+            let mut state = 1u32;
+            let mut rnd = move || {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as Float / (1 << 23) as Float - 1.0
+            };
+            let pattern: Vec<Float> = (0..period).map(|_| rnd()).collect();
+            let input: Vec<Float> = (0..20000)
+                .map(|i| pattern[i % period] + 0.1 * rnd())
+                .collect();
+            let (mut src, prev) = VectorSource::new(input.clone());
+            let (mut b, out) = Autocorrelate::new(prev, 2, 50)?;
+            assert_eq!(b.peak_lag(), None);
+            src.work()?;
+            b.work()?;
+            let got = b.peak_lag().expect("no peak");
+            assert!(got.abs_diff(period) <= 1, "got {got}, want {period}");
+
+            let (res, tags) = out.read_buf()?;
+            assert_eq!(res.slice(), input);
+            let lags: Vec<_> = tags.iter().filter(|t| t.key() == TAG_LAG).collect();
+            assert_eq!(lags.len(), 4);
+            assert_eq!(lags[0].pos(), DEFAULT_WINDOW + 50 - 1);
+            for t in lags {
+                let TagValue::U64(lag) = t.val() else {
+                    panic!("bad tag {t:?}");
+                };
+                assert!((*lag as usize).abs_diff(period) <= 1);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+/*! Parse AX.25 frame headers.
+
+Takes frames, e.g. from [HdlcDeframer][crate::hdlc_deframer::HdlcDeframer]
+with the checksum stripped, and tags them with the decoded [AX.25][ax25]
+address header.
+
+[ax25]: https://en.wikipedia.org/wiki/AX.25
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
+use crate::Error;
+
+/// Tag key for the destination address, as `CALL-SSID`.
+pub const TAG_DST: &str = "ax25:dst";
+
+/// Tag key for the source address, as `CALL-SSID`.
+pub const TAG_SRC: &str = "ax25:src";
+
+/// Tag key for the digipeater path.
+///
+/// Comma separated addresses. Digipeaters that have repeated the frame are
+/// marked with `*`. Empty if there's no path.
+pub const TAG_PATH: &str = "ax25:path";
+
+/// Tag key for the control field.
+pub const TAG_CONTROL: &str = "ax25:control";
+
+/// Tag key for the PID. Only present on I and UI frames.
+pub const TAG_PID: &str = "ax25:pid";
+
+/// Tag key for parse errors. If present, no other AX.25 tags are.
+pub const TAG_ERROR: &str = "ax25:error";
+
+// Size of an encoded address.
+const ADDR_LEN: usize = 7;
+
+// Destination, source, and up to 8 digipeaters.
+const MAX_ADDRS: usize = 10;
+
+struct Address {
+    call: String,
+    ssid: u8,
+    // The H bit, for digipeaters.
+    repeated: bool,
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", self.call, self.ssid)
+    }
+}
+
+impl Address {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut call = String::new();
+        let mut padding = false;
+        for &b in &data[..6] {
+            if b & 1 != 0 {
+                return Err(format!("callsign byte {b:#04x} has extension bit set"));
+            }
+            let ch = (b >> 1) as char;
+            match ch {
+                ' ' => padding = true,
+                'A'..='Z' | '0'..='9' if !padding => call.push(ch),
+                _ => return Err(format!("bad callsign character {ch:?}")),
+            }
+        }
+        if call.is_empty() {
+            return Err("empty callsign".into());
+        }
+        Ok(Self {
+            call,
+            ssid: (data[6] >> 1) & 0xf,
+            repeated: data[6] & 0x80 != 0,
+        })
+    }
+}
+
+struct Header {
+    dst: Address,
+    src: Address,
+    path: Vec<Address>,
+    control: u8,
+    pid: Option<u8>,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut addrs = Vec::new();
+        let mut pos = 0;
+        loop {
+            if addrs.len() == MAX_ADDRS {
+                return Err("too many addresses".into());
+            }
+            let Some(a) = data.get(pos..pos + ADDR_LEN) else {
+                return Err(format!("frame too short: {} bytes", data.len()));
+            };
+            addrs.push(Address::parse(a)?);
+            pos += ADDR_LEN;
+            if a[6] & 1 != 0 {
+                break;
+            }
+        }
+        if addrs.len() < 2 {
+            return Err("missing source address".into());
+        }
+        let Some(&control) = data.get(pos) else {
+            return Err("missing control field".into());
+        };
+        // I frames, and UI frames, have a PID.
+        let pid = if control & 1 == 0 || control & 0xef == 0x03 {
+            match data.get(pos + 1) {
+                Some(&pid) => Some(pid),
+                None => return Err("missing PID".into()),
+            }
+        } else {
+            None
+        };
+        let mut addrs = addrs.into_iter();
+        Ok(Self {
+            dst: addrs.next().unwrap(),
+            src: addrs.next().unwrap(),
+            path: addrs.collect(),
+            control,
+            pid,
+        })
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        let path = self
+            .path
+            .iter()
+            .map(|a| format!("{a}{}", if a.repeated { "*" } else { "" }))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut tags = vec![
+            Tag::new(0, TAG_DST.into(), TagValue::String(self.dst.to_string())),
+            Tag::new(0, TAG_SRC.into(), TagValue::String(self.src.to_string())),
+            Tag::new(0, TAG_PATH.into(), TagValue::String(path)),
+            Tag::new(0, TAG_CONTROL.into(), TagValue::U64(self.control.into())),
+        ];
+        if let Some(pid) = self.pid {
+            tags.push(Tag::new(0, TAG_PID.into(), TagValue::U64(pid.into())));
+        }
+        tags
+    }
+}
+
+/// AX.25 header parser.
+///
+/// Frames are passed through unchanged, with the parsed header added as
+/// tags at position 0. Frames that fail to parse are passed through with
+/// a [TAG_ERROR] tag.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Ax25Parse {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<u8>>,
+}
+
+impl Ax25Parse {
+    /// Create new Ax25Parse block.
+    pub fn new(src: NCReadStream<Vec<u8>>) -> (Self, NCReadStream<Vec<u8>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { src, dst }, dr)
+    }
+}
+
+impl Block for Ax25Parse {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (frame, mut tags) = match self.src.pop() {
+            None => return Ok(BlockRet::Noop),
+            Some(x) => x,
+        };
+        match Header::parse(&frame) {
+            Ok(h) => tags.extend(h.tags()),
+            Err(e) => tags.push(Tag::new(0, TAG_ERROR.into(), TagValue::String(e))),
+        }
+        self.dst.push(frame, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::new_nocopy_stream;
+
+    // APRS position report from N0CALL-7 to APRS, via WIDE1-1* and WIDE2-1.
+    const APRS: &[u8] = &[
+        0x82, 0xa0, 0xa4, 0xa6, 0x40, 0x40, 0x60, 0x9c, 0x60, 0x86, 0x82, 0x98, 0x98, 0x6e, 0xae,
+        0x92, 0x88, 0x8a, 0x62, 0x40, 0xe2, 0xae, 0x92, 0x88, 0x8a, 0x64, 0x40, 0x63, 0x03, 0xf0,
+        0x21, 0x34, 0x39, 0x30, 0x33, 0x2e, 0x35, 0x30, 0x4e, 0x2f, 0x30, 0x37, 0x32, 0x30, 0x31,
+        0x2e, 0x37, 0x35, 0x57, 0x2d, 0x54, 0x65, 0x73, 0x74,
+    ];
+
+    fn parse(frame: &[u8]) -> Result<(Vec<u8>, Vec<Tag>), Error> {
+        let (w, r) = new_nocopy_stream();
+        let (mut b, out) = Ax25Parse::new(r);
+        w.push(frame.to_vec(), &[]);
+        b.work()?;
+        Ok(out.pop().expect("no frame output"))
+    }
+
+    fn tag<'a>(tags: &'a [Tag], key: &str) -> Option<&'a TagValue> {
+        tags.iter().find(|t| t.key() == key).map(|t| t.val())
+    }
+
+    #[test]
+    fn aprs() -> Result<(), Error> {
+        let (frame, tags) = parse(APRS)?;
+        assert_eq!(frame, APRS);
+        let s = |v: &str| Some(TagValue::String(v.into()));
+        assert_eq!(tag(&tags, TAG_DST).cloned(), s("APRS-0"));
+        assert_eq!(tag(&tags, TAG_SRC).cloned(), s("N0CALL-7"));
+        assert_eq!(tag(&tags, TAG_PATH).cloned(), s("WIDE1-1*,WIDE2-1"));
+        assert_eq!(tag(&tags, TAG_CONTROL), Some(&TagValue::U64(0x03)));
+        assert_eq!(tag(&tags, TAG_PID), Some(&TagValue::U64(0xf0)));
+        assert_eq!(tag(&tags, TAG_ERROR), None);
+        Ok(())
+    }
+
+    #[test]
+    fn malformed() -> Result<(), Error> {
+        // Too short.
+        let (_, tags) = parse(&APRS[..10])?;
+        assert!(tag(&tags, TAG_ERROR).is_some());
+        assert_eq!(tag(&tags, TAG_SRC), None);
+
+        // Space in the middle of the callsign.
+        let mut bad = APRS.to_vec();
+        bad[8] = b' ' << 1;
+        let (_, tags) = parse(&bad)?;
+        assert_eq!(
+            tag(&tags, TAG_ERROR),
+            Some(&TagValue::String("bad callsign character 'C'".into()))
+        );
+        Ok(())
+    }
+}
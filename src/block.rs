@@ -5,8 +5,12 @@ thing, and you connect them together with streams to process the data.
 
 */
 
+use std::cell::RefCell;
+use std::sync::Arc;
+
 use anyhow::Result;
 
+use crate::stream::StreamId;
 use crate::Error;
 
 /** Return type for all blocks.
@@ -50,6 +54,17 @@ pub enum BlockRet {
     /// * Head block reached its max.
     EOF,
 
+    /// Source block has produced all the data it ever will.
+    ///
+    /// Like EOF, but for sources, which have no input that could have
+    /// ended. The graph will not call `work()` again, and will drop the
+    /// block so that downstream blocks see EOF on their inputs.
+    ///
+    /// Examples:
+    /// * VectorSource has output all its data.
+    /// * FileSource, without repeating, reached end of file.
+    Finished,
+
     /// Internal state for two-phase done-detection.
     InternalAwaiting,
 }
@@ -70,16 +85,349 @@ pub trait BlockEOF {
     fn eof(&mut self) -> bool {
         false
     }
+
+    /// Called once when the block is done, before it's removed from the
+    /// graph.
+    ///
+    /// Blocks that buffer data can use this to emit their last output, or
+    /// finalize files.
+    ///
+    /// It's not only called on EOF: when every block in a
+    /// [Graph][crate::graph::Graph] is idle, the graph is about to stop, so
+    /// blocks are flushed one at a time even if their inputs haven't ended.
+    /// The block may then still see more input, and `work()` calls, after
+    /// `on_eof()`. It's still only called once.
+    ///
+    /// With the `flush` attribute, the derive macro implements this by
+    /// calling the block's `flush()`.
+    fn on_eof(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A command sent to a block, while the graph is running.
+///
+/// Blocks downcast it to the command types they support.
+pub type Command = Box<dyn std::any::Any + Send>;
+
+/// Changing block settings while the graph is running.
+///
+/// Commands are sent with
+/// [GraphRunner::send_command][crate::graph::GraphRunner::send_command],
+/// and are delivered on the thread running the block, so unlike handles
+/// they need no locking or atomics in the block.
+pub trait BlockCommand {
+    /// Handle a command.
+    ///
+    /// Called by the graph between calls to `work()`. By default blocks
+    /// don't handle any commands. With the `command` attribute the derive
+    /// macro leaves implementing this to the block.
+    ///
+    /// Commands the block doesn't know are logged and ignored. An error
+    /// stops the graph, so it's only for commands that were understood
+    /// but failed.
+    fn handle_command(&mut self, _cmd: Command) -> Result<(), Error> {
+        log::warn!("Ignoring command sent to a block that does not handle commands");
+        Ok(())
+    }
+}
+
+/// Description of a block input or output port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortInfo {
+    /// Name of the stream field.
+    pub name: &'static str,
+    /// Type name of the stream field, e.g. `ReadStream<Complex>`, with the
+    /// full paths.
+    pub type_name: &'static str,
+    /// Stream ids. One for a plain stream, none for an unconnected
+    /// optional port, and one per stream for a `Vec` of streams.
+    pub ids: Vec<StreamId>,
+}
+
+pub trait BlockPorts {
+    /// Return the input and output ports, in field order.
+    ///
+    /// The derive macro generates this from the fields marked `in` and
+    /// `out`.
+    fn ports(&self) -> (Vec<PortInfo>, Vec<PortInfo>) {
+        (Vec::new(), Vec::new())
+    }
+
+    /// Return the ids of the streams the block reads from.
+    fn input_streams(&self) -> Vec<StreamId> {
+        self.ports().0.into_iter().flat_map(|p| p.ids).collect()
+    }
+
+    /// Return the ids of the streams the block writes to.
+    fn output_streams(&self) -> Vec<StreamId> {
+        self.ports().1.into_iter().flat_map(|p| p.ids).collect()
+    }
+
+    /// Return the total number of samples consumed, summed over all
+    /// inputs.
+    ///
+    /// Noncopy streams count objects, not samples.
+    fn samples_consumed(&self) -> u64 {
+        0
+    }
+
+    /// Return the total number of samples produced, summed over all
+    /// outputs.
+    ///
+    /// Noncopy streams count objects, not samples.
+    fn samples_produced(&self) -> u64 {
+        0
+    }
+}
+
+thread_local! {
+    // Name of the `Named` block currently running on this thread, if any.
+    static INSTANCE_NAME: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+/** Logging with the block name as context.
+
+Implemented for all blocks. Messages are prefixed with the block name.
+
+If the block is wrapped in [Named], and the message is logged while the
+block is run by it, the instance label is included. That makes logs from
+several blocks of the same type distinguishable.
+
+```
+use rustradio::block::BlockLog;
+use rustradio::blocks::VectorSource;
+let (src, _) = VectorSource::new(vec![1u8, 2, 3]);
+src.log_debug(format_args!("has {} samples", 3));
+```
+*/
+pub trait BlockLog: BlockName {
+    /// Log a message at the given level.
+    fn log(&self, level: log::Level, args: std::fmt::Arguments) {
+        if log::log_enabled!(level) {
+            log::log!(level, "{}", log_message(self.block_name(), args));
+        }
+    }
+
+    /// Log a message at error level.
+    fn log_error(&self, args: std::fmt::Arguments) {
+        self.log(log::Level::Error, args);
+    }
+
+    /// Log a message at warn level.
+    fn log_warn(&self, args: std::fmt::Arguments) {
+        self.log(log::Level::Warn, args);
+    }
+
+    /// Log a message at info level.
+    fn log_info(&self, args: std::fmt::Arguments) {
+        self.log(log::Level::Info, args);
+    }
+
+    /// Log a message at debug level.
+    fn log_debug(&self, args: std::fmt::Arguments) {
+        self.log(log::Level::Debug, args);
+    }
+
+    /// Log a message at trace level.
+    fn log_trace(&self, args: std::fmt::Arguments) {
+        self.log(log::Level::Trace, args);
+    }
+}
+
+impl<T: BlockName + ?Sized> BlockLog for T {}
+
+// Prefix a log message with the instance name, if run by `Named`, or else
+// the given block name.
+fn log_message(block_name: &str, args: std::fmt::Arguments) -> String {
+    INSTANCE_NAME.with_borrow(|instance| {
+        let name = instance.as_deref().unwrap_or(block_name);
+        format!("{name}: {args}")
+    })
 }
 
 /// Block trait, that must be implemented for all blocks.
 ///
 /// Simpler blocks can use macros to avoid needing to implement `work()`.
-pub trait Block: BlockName + BlockEOF {
+pub trait Block: BlockName + BlockEOF + BlockCommand + BlockPorts {
     /// Block work function
     ///
     /// A block implementation keeps track of its own inputs and outputs.
     fn work(&mut self) -> Result<BlockRet, Error>;
 }
+
+/** Wrapper giving a block an instance label.
+
+A graph with many blocks of the same type will show identical names in the
+stats. Wrapping them gives them a name of the form `TypeName[label]`.
+
+```
+use rustradio::block::{BlockName, Named};
+use rustradio::blocks::VectorSource;
+let (src, _) = VectorSource::new(vec![1u8, 2, 3]);
+let src = Named::new(src, "first");
+assert_eq!(src.block_name(), "VectorSource[first]");
+```
+*/
+pub struct Named<B> {
+    block: B,
+    name: Arc<str>,
+}
+
+impl<B: BlockName> Named<B> {
+    /// Wrap a block, giving it an instance label.
+    pub fn new(block: B, label: &str) -> Self {
+        let name = format!("{}[{label}]", block.block_name()).into();
+        Self { block, name }
+    }
+
+    /// Change the instance label.
+    pub fn set_label(&mut self, label: &str) {
+        self.name = format!("{}[{label}]", self.block.block_name()).into();
+    }
+
+    /// Get a reference to the wrapped block.
+    pub fn inner(&self) -> &B {
+        &self.block
+    }
+
+    /// Get a mutable reference to the wrapped block.
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.block
+    }
+}
+
+impl<B> Named<B> {
+    // Run `f` on the wrapped block, with the instance name set for
+    // BlockLog.
+    fn with_name<T>(&mut self, f: impl FnOnce(&mut B) -> T) -> T {
+        let prev = INSTANCE_NAME.replace(Some(self.name.clone()));
+        let ret = f(&mut self.block);
+        INSTANCE_NAME.set(prev);
+        ret
+    }
+}
+
+impl<B> BlockName for Named<B> {
+    fn block_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<B: BlockEOF> BlockEOF for Named<B> {
+    fn eof(&mut self) -> bool {
+        self.block.eof()
+    }
+    fn on_eof(&mut self) -> Result<(), Error> {
+        self.with_name(|b| b.on_eof())
+    }
+}
+
+impl<B: BlockCommand> BlockCommand for Named<B> {
+    fn handle_command(&mut self, cmd: Command) -> Result<(), Error> {
+        self.with_name(|b| b.handle_command(cmd))
+    }
+}
+
+impl<B: BlockPorts> BlockPorts for Named<B> {
+    fn ports(&self) -> (Vec<PortInfo>, Vec<PortInfo>) {
+        self.block.ports()
+    }
+    fn samples_consumed(&self) -> u64 {
+        self.block.samples_consumed()
+    }
+    fn samples_produced(&self) -> u64 {
+        self.block.samples_produced()
+    }
+}
+
+impl<B: Block> Block for Named<B> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        self.with_name(|b| b.work())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::ReadStream;
+
+    // Block recording the log messages it would log when run.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate, nevereof)]
+    struct LogTest {
+        logged: Vec<String>,
+    }
+
+    impl Block for LogTest {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            let msg = log_message(self.block_name(), format_args!("hello"));
+            self.logged.push(msg);
+            Ok(BlockRet::Ok)
+        }
+    }
+
+    #[test]
+    fn log_prefix() -> Result<(), Error> {
+        let mut a = Named::new(LogTest { logged: vec![] }, "a");
+        a.work()?;
+        assert_eq!(a.inner().logged, ["LogTest[a]: hello"]);
+        let mut b = LogTest { logged: vec![] };
+        b.work()?;
+        assert_eq!(b.logged, ["LogTest: hello"]);
+        Ok(())
+    }
+
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate, nevereof)]
+    struct PortsTest {
+        #[rustradio(in)]
+        a: ReadStream<u8>,
+        #[rustradio(in)]
+        b: Option<ReadStream<crate::Float>>,
+        #[rustradio(out)]
+        c: crate::stream::WriteStream<u8>,
+    }
+
+    impl Block for PortsTest {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            Ok(BlockRet::EOF)
+        }
+    }
+
+    #[test]
+    fn ports() {
+        let (_, a) = crate::stream::new_stream();
+        let (c, _) = crate::stream::new_stream();
+        let want_a = a.id();
+        let want_c = c.id();
+        let b = Named::new(PortsTest { a, b: None, c }, "x");
+        let (ins, outs) = b.ports();
+        assert_eq!(
+            ins,
+            [
+                PortInfo {
+                    name: "a",
+                    type_name: std::any::type_name::<ReadStream<u8>>(),
+                    ids: vec![want_a],
+                },
+                PortInfo {
+                    name: "b",
+                    type_name: std::any::type_name::<Option<ReadStream<crate::Float>>>(),
+                    ids: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            outs,
+            [PortInfo {
+                name: "c",
+                type_name: std::any::type_name::<crate::stream::WriteStream<u8>>(),
+                ids: vec![want_c],
+            }]
+        );
+        assert_eq!(b.input_streams(), [want_a]);
+    }
+}
 /* vim: textwidth=80
  */
@@ -1,44 +1,86 @@
 //! Convenient mod collecting all standard library blocks for import.
 pub use crate::add::Add;
 pub use crate::add_const::{add_const, AddConst};
+pub use crate::all_pass::AllPass;
+pub use crate::am_demod::AmDemod;
+pub use crate::apply_weights::ApplyWeights;
 pub use crate::au::{AuDecode, AuEncode};
+pub use crate::autocorrelate::Autocorrelate;
+pub use crate::ax25_parse::Ax25Parse;
 pub use crate::binary_slicer::BinarySlicer;
+pub use crate::bpsk_mod::BpskMod;
 pub use crate::burst_tagger::BurstTagger;
+pub use crate::cic::{CicDecimator, CicInterpolator};
+pub use crate::clip_detect::ClipDetect;
+pub use crate::clock_sync::ClockSync;
 pub use crate::complex_to_mag2::ComplexToMag2;
+pub use crate::complex_to_phase::ComplexToPhase;
 pub use crate::constant_source::ConstantSource;
-pub use crate::convert::{FloatToComplex, MapBuilder};
+pub use crate::convert::{ComplexToImag, ComplexToReal, FloatToComplex, FloatToInt, MapBuilder};
 pub use crate::correlate_access_code::{CorrelateAccessCode, CorrelateAccessCodeTag};
 pub use crate::debug_sink::{DebugFilter, DebugSink, DebugSinkNoCopy};
+pub use crate::decimate::{Decimate, PeakDecimate};
+pub use crate::deframer_status::DeframerStatus;
 pub use crate::delay::Delay;
 pub use crate::descrambler::Descrambler;
-pub use crate::fft_filter::FftFilter;
+pub use crate::discontinuity_monitor::DiscontinuityMonitor;
+pub use crate::evm::Evm;
 pub use crate::fft_filter::FftFilterFloat;
+pub use crate::fft_filter::{FftFilter, FftFilterBuilder};
+pub use crate::fft_stream::FftStream;
 pub use crate::file_sink::{FileSink, NoCopyFileSink};
-pub use crate::file_source::FileSource;
+pub use crate::file_source::{FileSource, FileSourceBuilder};
 pub use crate::fir::FIRFilter;
+pub use crate::fractional_shift::FractionalShift;
+pub use crate::freq_estimate::FreqEstimate;
+pub use crate::freq_meter::FreqMeter;
+pub use crate::fsk_mod::FskMod;
 pub use crate::hdlc_deframer::HdlcDeframer;
+pub use crate::hdlc_framer::HdlcFramer;
 pub use crate::hilbert::Hilbert;
+pub use crate::histogram::Histogram;
 pub use crate::il2p_deframer::Il2pDeframer;
+pub use crate::iq_balance::IqBalance;
+pub use crate::latency::{LatencyProbe, TimestampTag};
+pub use crate::lut_map::{LutInterpolation, LutMap};
 pub use crate::multiply_const::MultiplyConst;
+pub use crate::mux::{Demux, Mux};
+pub use crate::nco_source::NcoSource;
 pub use crate::nrzi::NrziDecode;
 pub use crate::null_sink::NullSink;
+pub use crate::pack_bits::{BitOrder, BitsToBytes, BitsToSymbols, BytesToBits, SymbolsToBits};
 pub use crate::pdu_writer::PduWriter;
+pub use crate::peak_freq::PeakFreq;
+pub use crate::preamble_sync::PreambleSync;
+pub use crate::prepend_sequence::PrependSequence;
 pub use crate::quadrature_demod::{FastFM, QuadratureDemod};
-pub use crate::rational_resampler::RationalResampler;
+pub use crate::rational_resampler::{RationalResampler, RationalResamplerBuilder};
+pub use crate::reader_source::ReaderSource;
+pub use crate::reverse::{BitReverse, ByteReverse};
+pub use crate::rotate::Rotate;
 pub use crate::rtlsdr_decode::RtlSdrDecode;
 pub use crate::sigmf::SigMFSourceBuilder;
 pub use crate::signal_source::{SignalSourceComplex, SignalSourceFloat};
+pub use crate::silence_eof::SilenceEof;
 pub use crate::single_pole_iir_filter::SinglePoleIIRFilter;
-pub use crate::skip::Skip;
+pub use crate::skip::{Skip, SkipUntilTag};
+pub use crate::soft_demapper::SoftDemapper;
+pub use crate::spectral_mask::{SpectralMask, SpectralMaskBuilder};
 pub use crate::stream_to_pdu::StreamToPdu;
+pub use crate::strobe::{Strobe, StrobeInterval};
 pub use crate::symbol_sync::SymbolSync;
+pub use crate::tag_debounce::TagDebounce;
+pub use crate::tag_router::TagRouter;
 pub use crate::tcp_source::TcpSource;
 pub use crate::tee::Tee;
+pub use crate::timing_resampler::TimingResampler;
 pub use crate::to_text::ToText;
+pub use crate::triggered_recorder::TriggeredRecorder;
 pub use crate::vec_to_stream::VecToStream;
 pub use crate::vector_sink::VectorSink;
 pub use crate::vector_source::{VectorSource, VectorSourceBuilder};
 pub use crate::wpcr::{Midpointer, Wpcr, WpcrBuilder};
+pub use crate::writer_sink::WriterSink;
 pub use crate::xor::Xor;
 pub use crate::xor_const::XorConst;
 pub use crate::zero_crossing::ZeroCrossing;
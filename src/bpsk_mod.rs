@@ -0,0 +1,95 @@
+//! BPSK modulator.
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error};
+
+/// BPSK modulator.
+///
+/// Input samples are bits, and only the lowest bit of each is used. A one
+/// becomes `1+0j`, and a zero becomes `-1+0j`, each repeated for
+/// `samples_per_symbol` output samples.
+///
+/// Output can be demodulated by taking the real part, and slicing it with
+/// [BinarySlicer][crate::binary_slicer::BinarySlicer].
+///
+/// Tags are moved to the first sample of their symbol.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct BpskMod {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    sps: usize,
+}
+
+impl BpskMod {
+    /// Create new BpskMod block.
+    ///
+    /// Panics if `samples_per_symbol` is zero.
+    pub fn new(src: ReadStream<u8>, samples_per_symbol: usize) -> (Self, ReadStream<Complex>) {
+        assert!(
+            samples_per_symbol > 0,
+            "BpskMod: samples_per_symbol must be positive"
+        );
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                sps: samples_per_symbol,
+            },
+            dr,
+        )
+    }
+}
+
+impl Block for BpskMod {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len() / self.sps);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        for (bit, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(self.sps)) {
+            let v = if bit & 1 == 1 { 1.0 } else { -1.0 };
+            out.fill(Complex::new(v, 0.0));
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|t| Tag::new(t.pos() * self.sps, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(n);
+        o.produce(n * self.sps, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{BinarySlicer, ComplexToReal, VectorSource};
+
+    #[test]
+    fn loopback() -> Result<()> {
+        let bits = vec![1u8, 0, 0, 1, 1, 1, 0, 1, 0, 0];
+        let sps = 4;
+        let (mut src, prev) = VectorSource::new(bits.clone());
+        let (mut m, prev) = BpskMod::new(prev, sps);
+        let (mut re, prev) = ComplexToReal::new(prev);
+        let (mut slicer, out) = BinarySlicer::new(prev);
+        src.work()?;
+        m.work()?;
+        re.work()?;
+        slicer.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.len(), bits.len() * sps);
+        let got: Vec<u8> = res.iter().skip(sps / 2).step_by(sps).copied().collect();
+        assert_eq!(got, bits);
+        Ok(())
+    }
+}
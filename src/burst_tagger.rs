@@ -126,7 +126,7 @@ mod tests {
         let want: Vec<_> = (0..100).map(|i| i as u32).collect();
         assert_eq!(sink.data(), want);
         tag_compare(
-            &sink.tags(),
+            sink.tags(),
             &[
                 Tag::new(0, "VectorSource::start".to_string(), TagValue::Bool(true)),
                 Tag::new(0, "VectorSource::first".to_string(), TagValue::Bool(true)),
@@ -0,0 +1,413 @@
+/*! Cascaded integrator-comb (CIC) filters.
+
+CIC filters are a cheap way to do large rate changes, needing no
+multiplications. The cost is a passband that droops, so they're usually
+followed (when decimating) or preceded (when interpolating) by a small
+compensation FIR filter. See [CicDecimator::response].
+
+The filter has three parameters:
+* `R`: The rate change.
+* `M`: The differential delay, usually 1 or 2.
+* `N`: The number of stages.
+
+Integrators grow without bound, so the filters are implemented in wrapping
+fixed point integer arithmetic, where the overflows cancel out in the
+combs. Enough bits are reserved for the `N * log2(R * M)` bits of growth,
+plus input magnitudes up to [MAX_INPUT].
+
+The DC gain is `(R*M)^N` for the decimator, and `(R*M)^N / R` for the
+interpolator. It's not normalized away.
+*/
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Max magnitude of input samples (or their real and imaginary parts).
+///
+/// Larger values overflow, and give garbage output.
+pub const MAX_INPUT: Float = 128.0;
+
+// Bits in the accumulators for the input magnitude, above the binary point.
+const HEADROOM_BITS: u32 = 8;
+
+// No point in having more precision than this below the binary point.
+const MAX_FRAC_BITS: u32 = 48;
+
+type Acc = [i128; 2];
+
+/// Sample types that CIC filters can process.
+pub trait CicSample: Copy {
+    /// Split into real and imaginary parts.
+    fn to_parts(self) -> [Float; 2];
+    /// Create from real and imaginary parts.
+    fn from_parts(parts: [Float; 2]) -> Self;
+}
+
+impl CicSample for Float {
+    fn to_parts(self) -> [Float; 2] {
+        [self, 0.0]
+    }
+    fn from_parts(parts: [Float; 2]) -> Self {
+        parts[0]
+    }
+}
+
+impl CicSample for Complex {
+    fn to_parts(self) -> [Float; 2] {
+        [self.re, self.im]
+    }
+    fn from_parts(parts: [Float; 2]) -> Self {
+        Complex::new(parts[0], parts[1])
+    }
+}
+
+// Integrator and comb state shared by decimator and interpolator.
+struct Cic {
+    r: usize,
+    m: usize,
+    n: usize,
+    scale: f64,
+    integrators: Vec<Acc>,
+    combs: Vec<VecDeque<Acc>>,
+}
+
+impl Cic {
+    fn new(name: &str, r: usize, m: usize, n: usize) -> Result<Self, Error> {
+        if r == 0 || m == 0 || n == 0 {
+            return Err(Error::new(&format!("{name}: R, M, and N must be positive")));
+        }
+        let growth = (n as f64 * ((r * m) as f64).log2()).ceil() as u32;
+        let frac = std::cmp::min(MAX_FRAC_BITS, (127 - HEADROOM_BITS).saturating_sub(growth));
+        Ok(Self {
+            r,
+            m,
+            n,
+            scale: (frac as f64).exp2(),
+            integrators: vec![[0; 2]; n],
+            combs: (0..n).map(|_| vec![[0; 2]; m].into()).collect(),
+        })
+    }
+
+    fn widen<T: CicSample>(&self, s: T) -> Acc {
+        s.to_parts().map(|v| (v as f64 * self.scale) as i128)
+    }
+
+    fn narrow<T: CicSample>(&self, a: Acc) -> T {
+        T::from_parts(a.map(|v| (v as f64 / self.scale) as Float))
+    }
+
+    fn integrate(&mut self, mut v: Acc) -> Acc {
+        for i in &mut self.integrators {
+            for (a, b) in i.iter_mut().zip(v) {
+                *a = a.wrapping_add(b);
+            }
+            v = *i;
+        }
+        v
+    }
+
+    fn comb(&mut self, mut v: Acc) -> Acc {
+        for c in &mut self.combs {
+            let old = c.pop_front().unwrap();
+            c.push_back(v);
+            for (a, b) in v.iter_mut().zip(old) {
+                *a = a.wrapping_sub(b);
+            }
+        }
+        v
+    }
+
+    fn response(&self, freq: Float) -> Float {
+        let rm = (self.r * self.m) as f64;
+        let f = freq as f64;
+        if f.fract() == 0.0 {
+            return 1.0;
+        }
+        let pi = std::f64::consts::PI;
+        let h = (pi * self.m as f64 * f).sin() / (rm * (pi * f / self.r as f64).sin());
+        h.abs().powi(self.n as i32) as Float
+    }
+}
+
+/// CIC decimator.
+///
+/// Tag positions are divided by R.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct CicDecimator<T: CicSample> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    cic: Cic,
+    // Input samples since last output.
+    phase: usize,
+    // Tags on input samples of the output sample not yet complete.
+    pending_tags: Vec<Tag>,
+}
+
+impl<T: CicSample> CicDecimator<T> {
+    /// Create new CicDecimator block.
+    ///
+    /// Returns error if any of `r`, `m`, or `n` is zero.
+    pub fn new(
+        src: ReadStream<T>,
+        r: usize,
+        m: usize,
+        n: usize,
+    ) -> Result<(Self, ReadStream<T>), Error> {
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                cic: Cic::new("CicDecimator", r, m, n)?,
+                phase: 0,
+                pending_tags: Vec::new(),
+            },
+            dr,
+        ))
+    }
+
+    /// DC gain, `(R*M)^N`.
+    pub fn gain(&self) -> Float {
+        ((self.cic.r * self.cic.m) as Float).powi(self.cic.n as i32)
+    }
+
+    /// Magnitude response relative to DC gain.
+    ///
+    /// `freq` is in cycles per output sample, so 0.5 is the output Nyquist
+    /// frequency. A compensation filter should have the inverse of this
+    /// response, in the passband.
+    pub fn response(&self, freq: Float) -> Float {
+        self.cic.response(freq)
+    }
+}
+
+impl<T: CicSample> Block for CicDecimator<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let r = self.cic.r;
+        // Consume only input that makes output we have space for.
+        let n = std::cmp::min(i.len(), o.len() * r + (r - 1 - self.phase));
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let start_phase = self.phase;
+        let mut produced = 0;
+        for &s in i.iter().take(n) {
+            let v = self.cic.widen(s);
+            let v = self.cic.integrate(v);
+            self.phase += 1;
+            if self.phase == r {
+                self.phase = 0;
+                let v = self.cic.comb(v);
+                o.slice()[produced] = self.cic.narrow(v);
+                produced += 1;
+            }
+        }
+        let tags =
+            crate::pack_bits::group_tags(tags, n, start_phase, r, produced, &mut self.pending_tags);
+        i.consume(n);
+        o.produce(produced, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// CIC interpolator.
+///
+/// Tag positions are multiplied by R.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct CicInterpolator<T: CicSample> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    cic: Cic,
+}
+
+impl<T: CicSample> CicInterpolator<T> {
+    /// Create new CicInterpolator block.
+    ///
+    /// Returns error if any of `r`, `m`, or `n` is zero.
+    pub fn new(
+        src: ReadStream<T>,
+        r: usize,
+        m: usize,
+        n: usize,
+    ) -> Result<(Self, ReadStream<T>), Error> {
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                cic: Cic::new("CicInterpolator", r, m, n)?,
+            },
+            dr,
+        ))
+    }
+
+    /// DC gain, `(R*M)^N / R`.
+    pub fn gain(&self) -> Float {
+        ((self.cic.r * self.cic.m) as Float).powi(self.cic.n as i32) / self.cic.r as Float
+    }
+
+    /// Magnitude response relative to DC gain.
+    ///
+    /// `freq` is in cycles per input sample, so 0.5 is the input Nyquist
+    /// frequency. A compensation filter should have the inverse of this
+    /// response, in the passband.
+    pub fn response(&self, freq: Float) -> Float {
+        self.cic.response(freq)
+    }
+}
+
+impl<T: CicSample> Block for CicInterpolator<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let r = self.cic.r;
+        let n = std::cmp::min(i.len(), o.len() / r);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        for (&s, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(r)) {
+            let v = self.cic.widen(s);
+            let v = self.cic.comb(v);
+            for (k, to) in out.iter_mut().enumerate() {
+                // Zero stuffing.
+                let v = self.cic.integrate(if k == 0 { v } else { [0; 2] });
+                *to = self.cic.narrow(v);
+            }
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|t| Tag::new(t.pos() * r, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(n);
+        o.produce(n * r, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::TagValue;
+
+    fn run_decim<T: CicSample>(input: Vec<T>, r: usize, m: usize, n: usize) -> Result<Vec<T>> {
+        let (_, out, _) =
+            crate::tests::run_block(input, |prev| Ok(CicDecimator::new(prev, r, m, n)?))?;
+        Ok(out)
+    }
+
+    #[test]
+    fn decim_dc_gain() -> Result<()> {
+        for (r, m, n) in [(8, 1, 3), (10, 2, 4), (1, 1, 1), (100, 1, 5)] {
+            let (_, prev) = VectorSource::<Float>::new(vec![]);
+            let gain = CicDecimator::new(prev, r, m, n)?.0.gain();
+            assert_eq!(gain, ((r * m) as Float).powi(n as i32));
+            let got = run_decim(vec![0.5 as Float; r * 100], r, m, n)?;
+            assert_eq!(got.len(), 100);
+            // Skip until settled.
+            for g in &got[n * m..] {
+                assert!((g - 0.5 * gain).abs() < 1e-3 * gain, "{r} {m} {n}: {g}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decim_attenuates() -> Result<()> {
+        let (r, m, n) = (8, 1, 3);
+        let (_, prev) = VectorSource::<Complex>::new(vec![]);
+        let (b, _) = CicDecimator::new(prev, r, m, n)?;
+        for (freq, min, max) in [(0.01, 0.99, 1.0), (0.45, 0.0, 0.4)] {
+            // Tone at freq, relative to output rate.
+            let input: Vec<_> = (0..(r * 1000))
+                .map(|i| {
+                    Complex::from_polar(
+                        1.0,
+                        2.0 * std::f32::consts::PI * freq * i as Float / r as Float,
+                    )
+                })
+                .collect();
+            let got = run_decim(input, r, m, n)?;
+            let want = b.response(freq);
+            assert!(want >= min && want <= max, "{freq}: {want}");
+            for g in &got[n * m..] {
+                let rel = g.norm() / b.gain();
+                assert!((rel - want).abs() < 0.01, "{freq}: got {rel}, want {want}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decim_split_work() -> Result<()> {
+        // Same result, however the input is split.
+        let input: Vec<Float> = (0..1000).map(|i| ((i * 7) % 13) as Float - 6.0).collect();
+        let want = run_decim(input.clone(), 10, 1, 3)?;
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = CicDecimator::new(r, 10, 1, 3)?;
+        for chunk in input.chunks(37) {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(chunk);
+            wb.produce(chunk.len(), &[]);
+            b.work()?;
+        }
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), want);
+        Ok(())
+    }
+
+    #[test]
+    fn decim_partial_tags() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = CicDecimator::new(r, 4, 1, 1)?;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[1.0 as Float; 6]);
+        wb.produce(6, &[Tag::new(5, "t".into(), TagValue::Bool(true))]);
+        b.work()?;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[1.0 as Float; 2]);
+        wb.produce(2, &[]);
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 2);
+        assert_eq!(tags, &[Tag::new(1, "t".into(), TagValue::Bool(true))]);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_params() {
+        for (r, m, n) in [(0, 1, 1), (1, 0, 1), (1, 1, 0)] {
+            let (_, prev) = crate::stream::new_stream::<Float>();
+            assert!(CicDecimator::new(prev, r, m, n).is_err());
+            let (_, prev) = crate::stream::new_stream::<Float>();
+            assert!(CicInterpolator::new(prev, r, m, n).is_err());
+        }
+    }
+
+    #[test]
+    fn interp_dc_gain() -> Result<()> {
+        let (r, m, n) = (4, 1, 3);
+        let (b, res, _) = crate::tests::run_block(vec![1.0 as Float; 100], |prev| {
+            Ok(CicInterpolator::new(prev, r, m, n)?)
+        })?;
+        assert_eq!(res.len(), 400);
+        assert_eq!(b.gain(), 16.0);
+        for g in &res[r * n * m..] {
+            assert!((g - b.gain()).abs() < 1e-3, "{g}");
+        }
+        Ok(())
+    }
+}
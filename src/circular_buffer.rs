@@ -6,7 +6,8 @@
 
 use std::collections::BTreeMap;
 use std::os::fd::AsRawFd;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 
 use anyhow::Result;
 use libc::{c_uchar, c_void, size_t};
@@ -152,6 +153,18 @@ struct BufferState {
     circ_len: usize,    // In bytes.
     member_size: usize, // In bytes.
     tags: BTreeMap<TagPos, Vec<Tag>>,
+    ntags: usize,
+    max_tags: Option<usize>,
+    tag_overflow: TagOverflow,
+    dropped_tags: usize,
+    // Set when tags were dropped under TagOverflow::Error, until the next
+    // write_buf() reports it.
+    overflowed: bool,
+    // Total samples ever produced and consumed.
+    produced: u64,
+    consumed: u64,
+    // Threads blocked in wait_for_read() or wait_for_write().
+    waiters: usize,
 }
 
 impl BufferState {
@@ -186,6 +199,37 @@ impl BufferState {
     fn free(&self) -> usize {
         self.capacity() - self.used
     }
+
+    /// Remove the tag closest to the read position.
+    fn drop_oldest_tag(&mut self) {
+        let Some(&pos) = self
+            .tags
+            .range(self.rpos..)
+            .next()
+            .or_else(|| self.tags.iter().next())
+            .map(|(k, _)| k)
+        else {
+            return;
+        };
+        let ts = self.tags.get_mut(&pos).unwrap(); // unwrap: key just found.
+        ts.remove(0);
+        if ts.is_empty() {
+            self.tags.remove(&pos);
+        }
+        self.ntags -= 1;
+        self.dropped_tags += 1;
+    }
+}
+
+/// What to do when a buffer has as many tags as it's allowed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TagOverflow {
+    /// Drop the oldest unread tags, to make room for the new ones.
+    #[default]
+    DropOldest,
+
+    /// Drop the new tags, and fail the next `write_buf()`.
+    Error,
 }
 
 /// BufferReader is an RAII'd fixed window read slice with some helper functions.
@@ -260,6 +304,11 @@ impl<T: Copy> BufferWriter<T> {
 
     /// Shortcut to save typing for the common operation of copying
     /// from an iterator.
+    ///
+    /// Stops when either the iterator or the write buffer runs out.
+    ///
+    /// If the data is already in a slice (or `Vec`), `fill_from_slice()` is
+    /// faster.
     pub fn fill_from_iter(&mut self, src: impl IntoIterator<Item = T>) {
         for (place, item) in self.slice().iter_mut().zip(src) {
             *place = item;
@@ -267,7 +316,9 @@ impl<T: Copy> BufferWriter<T> {
     }
 
     /// Shortcut to save typing for the common operation of copying
-    /// from an iterator.
+    /// from a slice.
+    ///
+    /// Panics if `src` is larger than the write buffer.
     pub fn fill_from_slice(&mut self, src: &[T]) {
         self.slice()[..src.len()].copy_from_slice(src);
     }
@@ -292,10 +343,16 @@ impl<T: Copy> BufferWriter<T> {
     }
 }
 
+/// Default max time to block in `wait_for_read()` and `wait_for_write()`.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+
 /// Type aware buffer.
 #[derive(Debug)]
 pub struct Buffer<T> {
     state: Arc<Mutex<BufferState>>,
+    // Notified on produce and consume, if anyone is waiting.
+    cond: Condvar,
+    wait_timeout: Duration,
     circ: Circ,
     member_size: usize,
     dummy: std::marker::PhantomData<T>,
@@ -304,6 +361,15 @@ pub struct Buffer<T> {
 impl<T> Buffer<T> {
     /// Create a new Buffer.
     pub fn new(size: usize) -> Result<Self> {
+        Self::with_wait_timeout(size, DEFAULT_WAIT_TIMEOUT)
+    }
+
+    /// Create a new Buffer, with a max time to block when waiting for
+    /// data or space.
+    ///
+    /// A shorter timeout makes waiters more responsive to e.g.
+    /// cancellation, at the cost of more wakeups when idle.
+    pub fn with_wait_timeout(size: usize, wait_timeout: Duration) -> Result<Self> {
         Ok(Self {
             state: Arc::new(Mutex::new(BufferState {
                 rpos: 0,
@@ -312,7 +378,17 @@ impl<T> Buffer<T> {
                 circ_len: size,
                 member_size: std::mem::size_of::<T>(),
                 tags: BTreeMap::new(),
+                ntags: 0,
+                max_tags: None,
+                tag_overflow: TagOverflow::default(),
+                dropped_tags: 0,
+                overflowed: false,
+                produced: 0,
+                consumed: 0,
+                waiters: 0,
             })),
+            cond: Condvar::new(),
+            wait_timeout,
             member_size: std::mem::size_of::<T>(),
             circ: Circ::new(size)?,
             dummy: std::marker::PhantomData,
@@ -331,6 +407,86 @@ impl<T> Buffer<T> {
     pub fn free(&self) -> usize {
         self.state.lock().unwrap().free()
     }
+
+    /// Samples written but not yet consumed.
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.state.lock().unwrap().used
+    }
+
+    /// Fraction of the buffer that's written but not yet consumed.
+    ///
+    /// 0.0 is empty, and 1.0 is full.
+    #[must_use]
+    pub fn fill_fraction(&self) -> f32 {
+        let s = self.state.lock().unwrap();
+        s.used as f32 / s.capacity() as f32
+    }
+
+    /// Limit how many tags the buffer holds, and what to do when it's
+    /// reached.
+    ///
+    /// With no limit, which is the default, a writer tagging faster than
+    /// the reader consumes can grow the tags without bound.
+    pub fn set_tag_limit(&self, max: Option<usize>, overflow: TagOverflow) {
+        let mut s = self.state.lock().unwrap();
+        s.max_tags = max;
+        s.tag_overflow = overflow;
+    }
+
+    /// Total number of samples ever produced.
+    #[must_use]
+    pub fn total_produced(&self) -> u64 {
+        self.state.lock().unwrap().produced
+    }
+
+    /// Total number of samples ever consumed.
+    #[must_use]
+    pub fn total_consumed(&self) -> u64 {
+        self.state.lock().unwrap().consumed
+    }
+
+    /// Number of tags dropped because of the tag limit.
+    #[must_use]
+    pub fn dropped_tags(&self) -> usize {
+        self.state.lock().unwrap().dropped_tags
+    }
+
+    /// Max time to block when waiting for data or space.
+    #[must_use]
+    pub fn wait_timeout(&self) -> Duration {
+        self.wait_timeout
+    }
+
+    /// Block until at least `need` samples can be read, or the wait
+    /// timeout expires.
+    ///
+    /// Returns true if the samples are available.
+    pub fn wait_for_read(&self, need: usize) -> bool {
+        self.wait_until(|s| s.used >= need)
+    }
+
+    /// Block until at least `need` samples can be written, or the wait
+    /// timeout expires.
+    ///
+    /// Returns true if the space is available.
+    pub fn wait_for_write(&self, need: usize) -> bool {
+        self.wait_until(|s| s.free() >= need)
+    }
+
+    fn wait_until(&self, ready: impl Fn(&BufferState) -> bool) -> bool {
+        let mut s = self.state.lock().unwrap();
+        if ready(&s) {
+            return true;
+        }
+        s.waiters += 1;
+        let (mut s, _) = self
+            .cond
+            .wait_timeout_while(s, self.wait_timeout, |s| !ready(s))
+            .unwrap();
+        s.waiters -= 1;
+        ready(&s)
+    }
 }
 
 impl<T: Copy> Buffer<T> {
@@ -345,6 +501,11 @@ impl<T: Copy> Buffer<T> {
             n,
             s.used
         );
+        if n == 0 {
+            // Nothing to do. And below would treat it as wrapping all the
+            // way around, removing all tags.
+            return;
+        }
         let newpos = (s.rpos + n) % s.capacity();
         use std::ops::Bound::{Excluded, Included};
 
@@ -367,10 +528,16 @@ impl<T: Copy> Buffer<T> {
             t
         };
         for k in keys {
-            s.tags.remove(&k);
+            if let Some(ts) = s.tags.remove(&k) {
+                s.ntags -= ts.len();
+            }
         }
         s.rpos = newpos;
         s.used -= n;
+        s.consumed += n as u64;
+        if s.waiters > 0 {
+            self.cond.notify_all();
+        }
     }
 
     /// Produce samples (commit writes).
@@ -401,12 +568,32 @@ impl<T: Copy> Buffer<T> {
             n
         );
         for tag in tags {
+            if s.max_tags.is_some_and(|max| s.ntags >= max) {
+                match s.tag_overflow {
+                    TagOverflow::DropOldest if s.ntags > 0 => s.drop_oldest_tag(),
+                    // Limit is zero, so there's nothing older to drop.
+                    TagOverflow::DropOldest => {
+                        s.dropped_tags += 1;
+                        continue;
+                    }
+                    TagOverflow::Error => {
+                        s.dropped_tags += 1;
+                        s.overflowed = true;
+                        continue;
+                    }
+                }
+            }
             let pos = (tag.pos() + s.wpos) % s.capacity();
             let tag = Tag::new(pos, tag.key().into(), tag.val().clone());
             s.tags.entry(pos).or_default().push(tag);
+            s.ntags += 1;
         }
         s.wpos = (s.wpos + n) % s.capacity();
         s.used += n;
+        s.produced += n as u64;
+        if s.waiters > 0 {
+            self.cond.notify_all();
+        }
     }
 
     pub(crate) fn slice(&self, start: usize, end: usize) -> &[T] {
@@ -453,8 +640,19 @@ impl<T: Copy> Buffer<T> {
     }
 
     /// Get the write slice.
+    ///
+    /// Fails if tags were dropped under [`TagOverflow::Error`] since the
+    /// last call.
     pub fn write_buf(self: Arc<Self>) -> Result<BufferWriter<T>> {
-        let s = self.state.lock().unwrap();
+        let mut s = self.state.lock().unwrap();
+        if std::mem::take(&mut s.overflowed) {
+            return Err(Error::new(&format!(
+                "stream tag limit {} exceeded, {} tags dropped so far",
+                s.max_tags.unwrap_or_default(),
+                s.dropped_tags
+            ))
+            .into());
+        }
         let (start, end) = s.write_range();
         drop(s);
         Ok(BufferWriter::new(
@@ -620,6 +818,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn fill_same() -> Result<()> {
+        let data: Vec<Float> = (0..1000).map(|i| i as Float * 0.5).collect();
+        let a: Arc<Buffer<Float>> = Arc::new(Buffer::new(16384)?);
+        let b: Arc<Buffer<Float>> = Arc::new(Buffer::new(16384)?);
+        for _ in 0..3 {
+            let mut wa = a.clone().write_buf()?;
+            wa.fill_from_slice(&data);
+            wa.produce(data.len(), &[]);
+            let mut wb = b.clone().write_buf()?;
+            wb.fill_from_iter(data.iter().copied());
+            wb.produce(data.len(), &[]);
+        }
+        assert_eq!(a.clone().read_buf()?.0.slice().len(), 3 * data.len());
+        assert_eq!(
+            a.clone().read_buf()?.0.slice(),
+            b.clone().read_buf()?.0.slice()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn consume_zero_keeps_tags() -> Result<()> {
+        let b: Arc<Buffer<u8>> = Arc::new(Buffer::new(4096)?);
+        let buf = b.clone().write_buf()?;
+        buf.produce(10, &[Tag::new(1, "first".into(), TagValue::Bool(true))]);
+        b.clone().read_buf()?.0.consume(0);
+        assert_eq!(
+            b.clone().read_buf()?.1,
+            vec![Tag::new(1, "first".into(), TagValue::Bool(true))]
+        );
+        Ok(())
+    }
+
     #[test]
     fn two_writes() -> Result<()> {
         let b: Arc<Buffer<u8>> = Arc::new(Buffer::new(4096)?);
@@ -742,6 +974,59 @@ mod tests {
         assert_eq!(b.clone().write_buf()?.len(), 1024 - 100);
         Ok(())
     }
+
+    fn produce_tagged(b: &Arc<Buffer<u8>>, n: usize) -> Result<()> {
+        let mut wb = b.clone().write_buf()?;
+        let tags: Vec<_> = (0..n)
+            .map(|i| Tag::new(i, "n".into(), TagValue::U64(i as u64)))
+            .collect();
+        wb.fill_from_slice(&vec![0; n]);
+        wb.produce(n, &tags);
+        Ok(())
+    }
+
+    #[test]
+    fn tag_limit_drop_oldest() -> Result<()> {
+        let b = Arc::new(Buffer::<u8>::new(4096)?);
+        b.set_tag_limit(Some(10), TagOverflow::DropOldest);
+        produce_tagged(&b, 8)?;
+        assert_eq!(b.dropped_tags(), 0);
+        produce_tagged(&b, 5)?;
+        assert_eq!(b.dropped_tags(), 3);
+        let (rb, tags) = b.clone().read_buf()?;
+        assert_eq!(rb.len(), 13);
+        let want: Vec<_> = (3..8)
+            .chain(8..13)
+            .map(|pos| Tag::new(pos, "n".into(), TagValue::U64(pos as u64 % 8)))
+            .collect();
+        assert_eq!(tags, want);
+
+        // Consuming makes room again.
+        rb.consume(13);
+        produce_tagged(&b, 10)?;
+        assert_eq!(b.dropped_tags(), 3);
+        assert_eq!(b.clone().read_buf()?.1.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn tag_limit_error() -> Result<()> {
+        let b = Arc::new(Buffer::<u8>::new(4096)?);
+        b.set_tag_limit(Some(10), TagOverflow::Error);
+        produce_tagged(&b, 8)?;
+        produce_tagged(&b, 5)?;
+        assert_eq!(b.dropped_tags(), 3);
+        // Oldest tags are kept.
+        let tags = b.clone().read_buf()?.1;
+        assert_eq!(tags.len(), 10);
+        assert_eq!(tags[0].pos(), 0);
+        assert_eq!(tags[9].pos(), 9);
+
+        // The writer is told, once.
+        assert!(b.clone().write_buf().is_err());
+        assert!(b.clone().write_buf().is_ok());
+        Ok(())
+    }
 }
 /* vim: textwidth=80
  */
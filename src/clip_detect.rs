@@ -0,0 +1,140 @@
+/*! Detect clipping.
+
+When the front end is overloaded, samples get stuck at full scale. That
+distorts the signal, so it's good to know about.
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Tag key added when too many samples in a window are clipped. The value
+/// is the fraction of clipped samples.
+pub const TAG_CLIP_WARN: &str = "clip:warn";
+
+/// Clip detector block.
+///
+/// Samples pass through unchanged. A sample is clipped if the absolute
+/// value of I or Q is at least `threshold`, i.e. full scale.
+///
+/// Samples are counted in back to back windows of `window` samples. If
+/// more than `limit` (a fraction) of the samples in a window are clipped,
+/// the last sample of the window is tagged with [TAG_CLIP_WARN], and the
+/// warning counter is incremented.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct ClipDetect {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    threshold: Float,
+    window: usize,
+    limit: Float,
+    // Samples, and clipped samples, in the current window.
+    #[rustradio(default)]
+    in_window: usize,
+    #[rustradio(default)]
+    clipped_in_window: usize,
+    #[rustradio(default)]
+    clipped: u64,
+    #[rustradio(default)]
+    warnings: u64,
+}
+
+impl ClipDetect {
+    /// Return total number of clipped samples.
+    pub fn clipped(&self) -> u64 {
+        self.clipped
+    }
+
+    /// Return number of windows that had too many clipped samples.
+    pub fn warnings(&self) -> u64 {
+        self.warnings
+    }
+}
+
+impl Block for ClipDetect {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, mut tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        tags.retain(|t| t.pos() < n);
+        for (pos, s) in i.iter().take(n).enumerate() {
+            if s.re.abs() >= self.threshold || s.im.abs() >= self.threshold {
+                self.clipped += 1;
+                self.clipped_in_window += 1;
+            }
+            self.in_window += 1;
+            if self.in_window >= self.window {
+                let rate = self.clipped_in_window as Float / self.in_window as Float;
+                if rate > self.limit {
+                    self.warnings += 1;
+                    tags.push(Tag::new(pos, TAG_CLIP_WARN.into(), TagValue::Float(rate)));
+                }
+                self.in_window = 0;
+                self.clipped_in_window = 0;
+            }
+        }
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: Vec<Complex>) -> crate::Result<(ClipDetect, Vec<Tag>)> {
+        let (b, res, tags) = crate::tests::run_block(input.clone(), |prev| {
+            Ok(ClipDetect::new(prev, 1.0, 100, 0.01))
+        })?;
+        assert_eq!(res, input);
+        let tags = tags
+            .into_iter()
+            .filter(|t| t.key() == TAG_CLIP_WARN)
+            .collect();
+        Ok((b, tags))
+    }
+
+    #[test]
+    fn clipping() -> crate::Result<()> {
+        let sine = |n: usize, amp: Float| Complex::from_polar(amp, n as Float * 0.1);
+        // Clean.
+        let (b, tags) = run((0..1000).map(|n| sine(n, 0.9)).collect())?;
+        assert_eq!((b.clipped(), b.warnings()), (0, 0));
+        assert!(tags.is_empty());
+
+        // Clipped in the third window only, by a sine too loud and
+        // saturated at full scale.
+        let input: Vec<Complex> = (0..1000)
+            .map(|n| {
+                let s = sine(n, if (200..300).contains(&n) { 1.5 } else { 0.9 });
+                Complex::new(s.re.clamp(-1.0, 1.0), s.im.clamp(-1.0, 1.0))
+            })
+            .collect();
+        let want = input[200..300]
+            .iter()
+            .filter(|s| s.re.abs() >= 1.0 || s.im.abs() >= 1.0)
+            .count();
+        let (b, tags) = run(input)?;
+        assert_eq!(b.clipped(), want as u64);
+        assert_eq!(b.warnings(), 1);
+        assert_eq!(
+            tags,
+            [Tag::new(
+                299,
+                TAG_CLIP_WARN.into(),
+                TagValue::Float(want as Float / 100.0)
+            )]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+/*! Match the sample rate to a sink with a different clock.
+
+When samples come from one clock (e.g. an SDR), and are consumed by
+another (e.g. a sound card), the two never agree exactly on the sample
+rate. Over time the buffers between them either run dry or overflow.
+
+[ClockSync] resamples very slightly, keeping the fill of its output stream
+at a target level. The rate adjustment needed for that is the measured
+offset between the two clocks.
+
+Output samples are linearly interpolated between input samples, like in
+[TimingResampler][crate::timing_resampler::TimingResampler].
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagSlice, WriteStream};
+use crate::timing_resampler::LinearInterpolator;
+use crate::{Error, Float};
+
+/// Default max adjustment, in parts per million.
+pub const DEFAULT_MAX_PPM: f64 = 1000.0;
+
+/// Default gain, in rate adjustment per fill fraction error.
+pub const DEFAULT_GAIN: f64 = 0.01;
+
+/// Clock sync block.
+///
+/// On the first call to `work()`, the output stream is filled up to
+/// `target` (a fraction from 0.0 to 1.0) with default value samples, i.e.
+/// silence. That's the latency to give the sink some slack in both
+/// directions.
+///
+/// After that, the output rate is adjusted by `gain` times how far the
+/// output stream fill is from the target, limited to `max_ppm`. If the sink
+/// consumes slower than the input arrives, the stream fills up, and the
+/// output rate goes down. Since the adjustment is proportional to the
+/// error, the fill settles a little off target.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ClockSync<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    target: f64,
+    gain: f64,
+    max_ppm: f64,
+    // Output samples per input sample.
+    ratio: f64,
+    // Set once the output stream is prefilled.
+    interp: Option<LinearInterpolator<T>>,
+    // Tags from the first input sample, used up by the prefill, to be
+    // output with the first interpolated sample.
+    pending_tags: Vec<Tag>,
+}
+
+impl<T> ClockSync<T>
+where
+    T: Copy + Default + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+    T: std::ops::Mul<Float, Output = T>,
+{
+    /// Create new ClockSync block.
+    pub fn new(src: ReadStream<T>, target: f64) -> Result<(Self, ReadStream<T>), Error> {
+        if !(0.0..1.0).contains(&target) {
+            return Err(Error::new(&format!(
+                "ClockSync: target fill must be in [0, 1), got {target}"
+            )));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                target,
+                gain: DEFAULT_GAIN,
+                max_ppm: DEFAULT_MAX_PPM,
+                ratio: 1.0,
+                interp: None,
+                pending_tags: Vec::new(),
+            },
+            dr,
+        ))
+    }
+
+    /// Set gain, in rate adjustment per fill fraction error.
+    ///
+    /// Higher gain corrects faster, and settles closer to the target, but
+    /// jitter in how the sink consumes then turns into jitter in the rate.
+    pub fn set_gain(&mut self, gain: f64) {
+        self.gain = gain;
+    }
+
+    /// Set max adjustment, in parts per million.
+    pub fn set_max_ppm(&mut self, max_ppm: f64) {
+        self.max_ppm = max_ppm;
+    }
+
+    /// Return current rate adjustment, in parts per million.
+    ///
+    /// Once settled, this is the sink clock offset relative to the source
+    /// clock. Negative means the sink is slower.
+    pub fn ppm(&self) -> f64 {
+        (self.ratio - 1.0) * 1e6
+    }
+
+    fn update_ratio(&mut self) {
+        let err = self.dst.fill_fraction() as f64 - self.target;
+        let adj = (-self.gain * err).clamp(-self.max_ppm * 1e-6, self.max_ppm * 1e-6);
+        self.ratio = 1.0 + adj;
+    }
+}
+
+impl<T> Block for ClockSync<T>
+where
+    T: Copy + Default + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+    T: std::ops::Mul<Float, Output = T>,
+{
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (input, tags) = self.src.read_buf()?;
+        if input.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        if self.interp.is_none() {
+            // Prefill.
+            let n = (self.target * o.len() as f64) as usize;
+            o.fill_from_iter(std::iter::repeat(T::default()).take(n));
+            o.produce(n, &[]);
+            self.interp = Some(LinearInterpolator::new(input.slice()[0]));
+            self.pending_tags = tags
+                .tags_at(0)
+                .map(|t| Tag::new(0, t.key().into(), t.val().clone()))
+                .collect();
+            input.consume(1);
+            return Ok(BlockRet::Ok);
+        }
+        self.update_ratio();
+        let step = 1.0 / self.ratio;
+        let interp = self.interp.as_mut().unwrap(); // unwrap: checked above.
+        let oslice = o.slice();
+        let mut otags = Vec::new();
+        let mut opos = 0;
+        let mut taken = 0;
+        for (i, &cur) in input.iter().enumerate() {
+            let Some(count) = interp.push(cur, step, &mut oslice[opos..]) else {
+                break;
+            };
+            for tag in tags.tags_at(i) {
+                otags.push(Tag::new(opos, tag.key().into(), tag.val().clone()));
+            }
+            opos += count;
+            taken += 1;
+        }
+        if taken == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        if opos > 0 && !self.pending_tags.is_empty() {
+            otags.splice(0..0, std::mem::take(&mut self.pending_tags));
+        }
+        input.consume(taken);
+        o.produce(opos, &otags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatch() -> crate::Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = ClockSync::<Float>::new(r, 0.5)?;
+        b.set_gain(0.1);
+        b.set_max_ppm(5000.0);
+        // Sink is 2000ppm slow. Uncorrected, that's an extra 10000 samples
+        // in the buffer by the end, or about 10%.
+        let sink_rate = 1.0 - 2000e-6;
+        let chunk = 500;
+        let mut owed = 0.0;
+        let mut t = 0u64;
+        for step in 0..10_000 {
+            let mut o = w.write_buf()?;
+            o.fill_from_iter((0..chunk).map(|_| {
+                t += 1;
+                (t as Float * 0.01).sin()
+            }));
+            o.produce(chunk, &[]);
+            b.work()?;
+
+            owed += chunk as f64 * sink_rate;
+            let (i, _) = out.read_buf()?;
+            let n = owed as usize;
+            assert!(n <= i.len(), "underrun at step {step}");
+            i.consume(n);
+            owed -= n as f64;
+            let fill = out.fill_fraction();
+            assert!((0.45..0.55).contains(&fill), "fill {fill} at step {step}");
+        }
+        assert!((b.ppm() + 2000.0).abs() < 50.0, "ppm {}", b.ppm());
+        Ok(())
+    }
+
+    #[test]
+    fn first_sample_tags() -> crate::Result<()> {
+        use crate::stream::TagValue;
+        let tag = |pos, key: &str| Tag::new(pos, key.into(), TagValue::Bool(true));
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = ClockSync::<Float>::new(r, 0.5)?;
+        let mut o = w.write_buf()?;
+        o.fill_from_slice(&[1.0; 10]);
+        o.produce(10, &[tag(0, "first"), tag(5, "later")]);
+        // Prefill.
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        let prefill = res.len();
+        assert!(tags.is_empty());
+        drop(res);
+
+        // The first sample's tag goes with the first sample after the
+        // prefill.
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert!(res.len() > prefill);
+        assert_eq!(tags[0], tag(prefill, "first"));
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[1].key(), "later");
+        Ok(())
+    }
+}
@@ -2,18 +2,106 @@
 use crate::stream::{ReadStream, WriteStream};
 use crate::{Complex, Float};
 
+/// Output type of [ComplexToMag2].
+///
+/// Integer types round to nearest, and clamp to their range.
+pub trait Mag2Output: Copy + Send + Sync + 'static {
+    /// Convert (scaled) power to output type.
+    fn from_power(p: Float) -> Self;
+}
+
+impl Mag2Output for Float {
+    fn from_power(p: Float) -> Self {
+        p
+    }
+}
+
+macro_rules! mag2_int {
+    ($($t:ty),*) => {
+        $(
+            impl Mag2Output for $t {
+                fn from_power(p: Float) -> Self {
+                    // `as` saturates on overflow.
+                    p.round() as $t
+                }
+            }
+        )*
+    };
+}
+mag2_int!(u8, u16, u32, u64, i16, i32);
+
 /// Convert Complex numbers to square of their magnitude.
+///
+/// By default the output is [Float], unscaled. Use
+/// [`new_scaled()`][ComplexToMag2::new_scaled] for scaling, and other output
+/// types.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, new, sync)]
-pub struct ComplexToMag2 {
+#[rustradio(crate, sync)]
+pub struct ComplexToMag2<T: Mag2Output = Float> {
     #[rustradio(in)]
     src: ReadStream<Complex>,
     #[rustradio(out)]
-    dst: WriteStream<Float>,
+    dst: WriteStream<T>,
+    scale: Float,
 }
 
 impl ComplexToMag2 {
-    fn process_sync(&self, sample: Complex) -> Float {
-        sample.norm_sqr()
+    /// Create new ComplexToMag2 block, outputting Float.
+    pub fn new(src: ReadStream<Complex>) -> (Self, ReadStream<Float>) {
+        Self::new_scaled(src, 1.0)
+    }
+}
+
+impl<T: Mag2Output> ComplexToMag2<T> {
+    /// Create new ComplexToMag2 block, outputting `scale * |s|²`.
+    ///
+    /// E.g. for `u32` output:
+    ///
+    /// ```
+    /// use rustradio::blocks::ComplexToMag2;
+    /// let (_, src) = rustradio::stream::new_stream();
+    /// let (b, out) = ComplexToMag2::<u32>::new_scaled(src, 1000.0);
+    /// ```
+    pub fn new_scaled(src: ReadStream<Complex>, scale: Float) -> (Self, ReadStream<T>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (Self { src, dst, scale }, dr)
+    }
+
+    fn process_sync(&self, sample: Complex) -> T {
+        T::from_power(self.scale * sample.norm_sqr())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run<T: Mag2Output>(input: Vec<Complex>, scale: Float) -> crate::Result<Vec<T>> {
+        let (_, out, _) = crate::tests::run_block(input, |prev| {
+            Ok(ComplexToMag2::<T>::new_scaled(prev, scale))
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    fn scaled() -> crate::Result<()> {
+        let input = vec![
+            Complex::new(0.0, 0.0),
+            Complex::new(1.0, 0.0),
+            Complex::new(0.3, -0.4),
+            Complex::new(-0.01, 0.02),
+            // Overflows u32 after scaling.
+            Complex::new(100.0, 0.0),
+        ];
+        let scale = 1e6;
+        // round(scale * |s|²), clamped.
+        let want: Vec<u32> = vec![0, 1_000_000, 250_000, 500, u32::MAX];
+        assert_eq!(run::<u32>(input.clone(), scale)?, want);
+
+        let got = run::<Float>(input.clone(), 2.0)?;
+        for (g, s) in got.iter().zip(&input) {
+            assert_eq!(*g, 2.0 * s.norm_sqr());
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,95 @@
+//! Convert Complex numbers to their phase.
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+/// Convert Complex numbers to their phase, in radians.
+///
+/// By default the phase is in `(-π, π]`. With unwrapping enabled, the
+/// phase is instead kept continuous across samples, by adding multiples of
+/// 2π whenever it jumps by more than π.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct ComplexToPhase {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    #[rustradio(default)]
+    unwrap: bool,
+    // Previous wrapped phase.
+    #[rustradio(default)]
+    prev: Option<Float>,
+    // Multiple of 2π added to the wrapped phase.
+    #[rustradio(default)]
+    offset: Float,
+}
+
+impl ComplexToPhase {
+    /// Enable or disable unwrapping.
+    pub fn set_unwrap(&mut self, unwrap: bool) {
+        self.unwrap = unwrap;
+    }
+
+    fn process_sync(&mut self, sample: Complex) -> Float {
+        let phase = sample.im.atan2(sample.re);
+        if !self.unwrap {
+            return phase;
+        }
+        if let Some(prev) = self.prev {
+            let pi = std::f64::consts::PI as Float;
+            let delta = phase - prev;
+            if delta > pi {
+                self.offset -= 2.0 * pi;
+            } else if delta < -pi {
+                self.offset += 2.0 * pi;
+            }
+        }
+        self.prev = Some(phase);
+        phase + self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    fn phasor(n: usize, step: Float) -> Vec<Complex> {
+        (0..n)
+            .map(|i| Complex::from_polar(1.0, i as Float * step))
+            .collect()
+    }
+
+    #[test]
+    fn wrapped() -> anyhow::Result<()> {
+        let (mut src, prev) = VectorSource::new(phasor(100, 0.1));
+        let (mut b, out) = ComplexToPhase::new(prev);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.len(), 100);
+        let pi = std::f64::consts::PI as Float;
+        assert!(res.iter().all(|&p| p > -pi && p <= pi));
+        assert!((res.slice()[1] - 0.1).abs() < 1e-5);
+        Ok(())
+    }
+
+    #[test]
+    fn unwrapped() -> anyhow::Result<()> {
+        let (mut src, prev) = VectorSource::new(phasor(200, 0.1));
+        let (mut b, out) = ComplexToPhase::new(prev);
+        b.set_unwrap(true);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        let res = res.slice();
+        assert_eq!(res.len(), 200);
+        for (i, w) in res.windows(2).enumerate() {
+            assert!((w[1] - w[0] - 0.1).abs() < 1e-3, "{i}: {w:?}");
+        }
+        // Three full turns, and then some.
+        assert!((res[199] - 19.9).abs() < 1e-3, "{}", res[199]);
+        Ok(())
+    }
+}
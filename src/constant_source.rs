@@ -1,17 +1,31 @@
-//! Generate the same value, forever.
+//! Generate the same value, forever, or a given number of times.
 use anyhow::Result;
 
 use crate::block::{Block, BlockRet};
-use crate::stream::WriteStream;
+use crate::stream::{ReadStream, WriteStream};
 use crate::Error;
 
-/// Generate the same value, forever.
+/// Generate the same value, forever, or a given number of times.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new)]
 pub struct ConstantSource<T: Copy> {
     #[rustradio(out)]
     dst: WriteStream<T>,
     val: T,
+    // Samples left to produce, if limited.
+    #[rustradio(default)]
+    remaining: Option<u64>,
+}
+
+impl<T: Copy> ConstantSource<T> {
+    /// Create new ConstantSource, finishing after `count` samples.
+    ///
+    /// Handy for graphs that should end on their own, e.g. in tests.
+    pub fn with_count(val: T, count: u64) -> (Self, ReadStream<T>) {
+        let (mut b, r) = Self::new(val);
+        b.remaining = Some(count);
+        (b, r)
+    }
 }
 
 impl<T> Block for ConstantSource<T>
@@ -19,10 +33,41 @@ where
     T: Copy,
 {
     fn work(&mut self) -> Result<BlockRet, Error> {
+        if self.remaining == Some(0) {
+            return Ok(BlockRet::Finished);
+        }
         let mut o = self.dst.write_buf()?;
-        o.slice().fill(self.val);
-        let n = o.len();
+        let n = match self.remaining {
+            None => o.len(),
+            Some(r) => std::cmp::min(o.len() as u64, r) as usize,
+        };
+        o.slice()[..n].fill(self.val);
         o.produce(n, &[]);
+        if let Some(r) = &mut self.remaining {
+            *r -= n as u64;
+        }
         Ok(BlockRet::Ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count() -> crate::Result<()> {
+        // More than fits in the stream at once.
+        let n = 300_000;
+        let (mut src, out) = ConstantSource::with_count(7u8, n);
+        let mut got: Vec<u8> = Vec::new();
+        while src.work()? != BlockRet::Finished {
+            let (i, _) = out.read_buf()?;
+            got.extend(i.iter());
+            let l = i.len();
+            i.consume(l);
+        }
+        assert_eq!(got.len(), n as usize);
+        assert!(got.iter().all(|&v| v == 7));
+        Ok(())
+    }
+}
@@ -124,3 +124,217 @@ impl Block for FloatToComplex {
         Ok(BlockRet::Ok)
     }
 }
+
+/// Take the real part of complex samples.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct ComplexToReal {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+}
+
+impl ComplexToReal {
+    fn process_sync(&self, s: Complex) -> Float {
+        s.re
+    }
+}
+
+/// Take the imaginary part of complex samples.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct ComplexToImag {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+}
+
+impl ComplexToImag {
+    fn process_sync(&self, s: Complex) -> Float {
+        s.im
+    }
+}
+
+/// Integer types that floats can be converted to, clamping to the range.
+pub trait SaturatingFromFloat: Copy {
+    /// Convert, rounding towards zero, and clamping to the range of the
+    /// type. NaN becomes zero.
+    fn saturating_from_float(v: Float) -> Self;
+}
+
+macro_rules! impl_saturating_from_float {
+    ($($t:ty),*) => {
+        $(
+            impl SaturatingFromFloat for $t {
+                fn saturating_from_float(v: Float) -> Self {
+                    if v.is_nan() {
+                        return 0;
+                    }
+                    if v >= Self::MAX as Float {
+                        return Self::MAX;
+                    }
+                    if v <= Self::MIN as Float {
+                        return Self::MIN;
+                    }
+                    v as Self
+                }
+            }
+        )*
+    };
+}
+
+impl_saturating_from_float!(i8, i16, i32);
+
+// Small xorshift PRNG, for dithering. Not for anything needing good
+// randomness.
+struct Xorshift(u64);
+
+impl Xorshift {
+    const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift never leaves.
+        Self(if seed == 0 { Self::DEFAULT_SEED } else { seed })
+    }
+
+    // Uniform in [0, 1).
+    fn next(&mut self) -> Float {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        ((self.0 >> 11) as f64 / (1u64 << 53) as f64) as Float
+    }
+}
+
+impl Default for Xorshift {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SEED)
+    }
+}
+
+/// Convert floats to integers, scaling and clamping.
+///
+/// Each sample is multiplied by the scale, and then clamped to the range of
+/// the integer type, instead of wrapping. E.g. for 16 bit audio, use a
+/// scale of `i16::MAX as Float`.
+///
+/// Without dithering, values are rounded towards zero. With dithering,
+/// triangular noise of ±1 step is added, and values are rounded to nearest.
+/// That trades quantization harmonics for a slightly higher noise floor,
+/// and removes the quantization bias.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct FloatToInt<T: SaturatingFromFloat> {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    scale: Float,
+    #[rustradio(default)]
+    dither: bool,
+    #[rustradio(default)]
+    rng: Xorshift,
+}
+
+impl<T: SaturatingFromFloat> FloatToInt<T> {
+    /// Enable or disable dithering.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.dither = dither;
+    }
+
+    /// Seed the dither noise generator, for reproducible output.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Xorshift::new(seed);
+    }
+
+    fn process_sync(&mut self, s: Float) -> T {
+        let v = s * self.scale;
+        if !self.dither {
+            return T::saturating_from_float(v);
+        }
+        // Sum of two uniform variables has a triangular distribution.
+        let noise = self.rng.next() - self.rng.next();
+        T::saturating_from_float((v + noise).round())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    fn input() -> Vec<Complex> {
+        vec![
+            Complex::new(1.0, -2.0),
+            Complex::new(0.5, 3.5),
+            Complex::new(-4.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn complex_to_real() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(input());
+        let (mut b, out) = ComplexToReal::new(prev);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[1.0, 0.5, -4.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn complex_to_imag() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(input());
+        let (mut b, out) = ComplexToImag::new(prev);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[-2.0, 3.5, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn float_to_int() -> Result<()> {
+        let input = vec![0.0, 0.5, -0.5, 1.0, -1.0, 1.5, -2.0, 1e20, Float::NAN];
+        let (mut src, prev) = VectorSource::new(input.clone());
+        let (mut b, out) = FloatToInt::<i16>::new(prev, i16::MAX as Float);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(
+            res.slice(),
+            &[0, 16383, -16383, 32767, -32767, 32767, -32768, 32767, 0]
+        );
+
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = FloatToInt::<i8>::new(prev, 100.0);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0, 50, -50, 100, -100, 127, -128, 127, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn dither_removes_bias() -> Result<()> {
+        let n = 50_000;
+        let mean = |dither: bool| -> Result<f64> {
+            let (mut src, prev) = VectorSource::new(vec![0.3; n]);
+            let (mut b, out) = FloatToInt::<i16>::new(prev, 1.0);
+            b.set_dither(dither);
+            b.set_seed(42);
+            src.work()?;
+            b.work()?;
+            let (res, _) = out.read_buf()?;
+            assert_eq!(res.len(), n);
+            Ok(res.iter().map(|&x| x as f64).sum::<f64>() / n as f64)
+        };
+        // Without dither, 0.3 is always 0.
+        assert_eq!(mean(false)?, 0.0);
+        let m = mean(true)?;
+        assert!((m - 0.3).abs() < 0.01, "dithered mean {m}");
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 //! Print values to stdout, for debugging.
 use std::collections::HashMap;
+use std::io::Write;
 
 use anyhow::Result;
 
@@ -101,6 +102,10 @@ where
 }
 
 /// Print values to stdout, for debugging.
+///
+/// By default every sample is printed to stdout. Use the setters to print
+/// fewer samples, or to write somewhere else. Samples not printed are still
+/// consumed.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, new)]
 pub struct DebugSink<T>
@@ -109,9 +114,45 @@ where
 {
     #[rustradio(in)]
     src: ReadStream<T>,
+    #[rustradio(default)]
+    limit: Option<usize>,
+    #[rustradio(default)]
+    every: usize,
+    #[rustradio(default)]
+    index: bool,
+    #[rustradio(default)]
+    writer: Option<Box<dyn Write + Send>>,
+    // Samples seen, and printed.
+    #[rustradio(default)]
+    pos: usize,
+    #[rustradio(default)]
+    printed: usize,
 }
 
-//#[allow(clippy::new_without_default)]
+impl<T> DebugSink<T>
+where
+    T: Copy,
+{
+    /// Stop printing after this many samples have been printed.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    /// Only print every nth sample. 0 and 1 both mean print every sample.
+    pub fn set_every(&mut self, n: usize) {
+        self.every = n;
+    }
+
+    /// Prefix each line with the index of the sample in the stream.
+    pub fn set_index_prefix(&mut self, v: bool) {
+        self.index = v;
+    }
+
+    /// Write to the given writer, instead of stdout.
+    pub fn set_writer(&mut self, w: Box<dyn Write + Send>) {
+        self.writer = Some(w);
+    }
+}
 
 impl<T> Block for DebugSink<T>
 where
@@ -128,7 +169,12 @@ where
                     acc
                 });
 
-        i.iter().enumerate().for_each(|(n, s)| {
+        let every = self.every.max(1);
+        for (n, s) in i.iter().enumerate() {
+            let pos = self.pos + n;
+            if pos % every != 0 || self.limit.is_some_and(|l| self.printed >= l) {
+                continue;
+            }
             let ts = tags
                 .get(&(n as TagPos))
                 .map(|ts| {
@@ -138,10 +184,65 @@ where
                         .join(",")
                 })
                 .unwrap_or("".to_string());
-            println!("debug: {:?} {}", s, ts);
-        });
+            let prefix = if self.index {
+                format!("{pos}: ")
+            } else {
+                "".to_string()
+            };
+            let line = format!("debug: {prefix}{:?} {}", s, ts);
+            match &mut self.writer {
+                None => println!("{line}"),
+                Some(w) => writeln!(w, "{line}")?,
+            }
+            self.printed += 1;
+        }
         let l = i.slice().len();
+        self.pos += l;
         i.consume(l);
         Ok(BlockRet::Noop)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::blocks::VectorSource;
+
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buf {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend(data);
+            Ok(data.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn limit_every_index() -> Result<()> {
+        let (mut src, prev) = VectorSource::new((10u32..30).collect());
+        let mut sink = DebugSink::new(prev);
+        let buf = Buf::default();
+        sink.set_writer(Box::new(buf.clone()));
+        sink.set_every(3);
+        sink.set_limit(Some(4));
+        sink.set_index_prefix(true);
+        src.work()?;
+        sink.work()?;
+        let got = String::from_utf8(buf.0.lock().unwrap().clone())?;
+        let lines: Vec<_> = got.lines().collect();
+        assert_eq!(lines.len(), 4, "{got}");
+        // First sample has the VectorSource tags.
+        assert!(lines[0].starts_with("debug: 0: 10 VectorSource::"), "{got}");
+        assert_eq!(
+            lines[1..],
+            ["debug: 3: 13 ", "debug: 6: 16 ", "debug: 9: 19 "]
+        );
+        Ok(())
+    }
+}
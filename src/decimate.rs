@@ -0,0 +1,223 @@
+//! Decimate without filtering, keeping one sample out of every M.
+//!
+//! [Decimate] keeps every Mth sample. [PeakDecimate] keeps the largest
+//! sample of each group of M, e.g. so that short bursts still show in a
+//! waterfall.
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Keep every Mth sample, dropping the rest.
+///
+/// There is no filtering, so any anti aliasing filter must be applied
+/// before this block.
+///
+/// Tag positions are divided by M, so a tag anywhere within a group of M
+/// input samples ends up on the output sample for that group.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Decimate<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    deci: usize,
+}
+
+impl<T: Copy> Decimate<T> {
+    /// Create new Decimate block.
+    ///
+    /// Returns error if `deci` is zero.
+    pub fn new(src: ReadStream<T>, deci: usize) -> Result<(Self, ReadStream<T>), Error> {
+        if deci == 0 {
+            return Err(Error::new("Decimate: deci must be positive"));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((Self { src, dst, deci }, dr))
+    }
+}
+
+impl<T: Copy> Block for Decimate<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len() / self.deci, o.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        o.fill_from_iter(i.iter().step_by(self.deci).take(n).copied());
+        let taken = n * self.deci;
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < taken)
+            .map(|t| Tag::new(t.pos() / self.deci, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(taken);
+        o.produce(n, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// Sample types that [PeakDecimate] can compare.
+pub trait Peak: Copy {
+    /// Return how large the sample is. Only the ordering matters.
+    fn peak(&self) -> Float;
+}
+
+impl Peak for Float {
+    fn peak(&self) -> Float {
+        *self
+    }
+}
+
+impl Peak for Complex {
+    fn peak(&self) -> Float {
+        self.norm_sqr()
+    }
+}
+
+/// Keep the largest sample out of every M.
+///
+/// For Float that's the largest value, and for Complex the largest
+/// magnitude. Unlike averaging, this preserves short peaks, e.g. bursts in
+/// a downsampled waterfall.
+///
+/// Tags are moved the same way as in [Decimate].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct PeakDecimate<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    deci: usize,
+}
+
+impl<T: Copy> PeakDecimate<T> {
+    /// Create new PeakDecimate block.
+    ///
+    /// Returns error if `deci` is zero.
+    pub fn new(src: ReadStream<T>, deci: usize) -> Result<(Self, ReadStream<T>), Error> {
+        if deci == 0 {
+            return Err(Error::new("PeakDecimate: deci must be positive"));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((Self { src, dst, deci }, dr))
+    }
+}
+
+impl<T: Peak> Block for PeakDecimate<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len() / self.deci, o.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let taken = n * self.deci;
+        o.fill_from_iter(i.slice()[..taken].chunks_exact(self.deci).map(|group| {
+            // First of equal peaks wins.
+            *group
+                .iter()
+                .reduce(|a, b| if b.peak() > a.peak() { b } else { a })
+                .unwrap()
+        }));
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < taken)
+            .map(|t| Tag::new(t.pos() / self.deci, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(taken);
+        o.produce(n, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::TagValue;
+    use crate::testutil::PatternSource;
+    use crate::Float;
+
+    #[test]
+    fn ramp() -> Result<()> {
+        let input: Vec<_> = (0..14).map(|i| i as Float).collect();
+        let tag = |pos| Tag::new(pos, "t".into(), TagValue::U64(pos as u64));
+        let tags = vec![tag(0), tag(3), tag(5), tag(11), tag(13)];
+        let (mut src, r) = PatternSource::new(input, 1, tags);
+        let (mut b, out) = Decimate::new(r, 3)?;
+        src.work()?;
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0.0, 3.0, 6.0, 9.0]);
+        assert_eq!(
+            tags,
+            vec![
+                Tag::new(0, "t".into(), TagValue::U64(0)),
+                Tag::new(1, "t".into(), TagValue::U64(3)),
+                Tag::new(1, "t".into(), TagValue::U64(5)),
+                Tag::new(3, "t".into(), TagValue::U64(11)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn peak() -> Result<()> {
+        // Spike at 37, decimated by 16. Plain decimation misses it.
+        let small = Complex::new(0.1, -0.1);
+        let mut input = vec![small; 64];
+        input[37] = Complex::new(0.0, -5.0);
+        input[50] = Complex::new(0.3, 0.0);
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = PeakDecimate::new(prev, 16)?;
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(
+            res.slice(),
+            &[
+                small,
+                small,
+                Complex::new(0.0, -5.0),
+                Complex::new(0.3, 0.0)
+            ]
+        );
+
+        // Float compares values, not magnitudes.
+        let (mut src, prev) = VectorSource::new(vec![1.0 as Float, -3.0, 0.5, -1.0, -2.0, -0.5]);
+        let (mut b, out) = PeakDecimate::new(prev, 3)?;
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[1.0, -0.5]);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_deci() {
+        let (_, prev) = crate::stream::new_stream::<Float>();
+        assert!(Decimate::new(prev, 0).is_err());
+    }
+
+    #[test]
+    fn peak_zero_deci() {
+        let (_, prev) = crate::stream::new_stream::<Float>();
+        assert!(PeakDecimate::new(prev, 0).is_err());
+    }
+
+    #[test]
+    fn bytes() -> Result<()> {
+        let (mut src, src_out) = VectorSource::new((0..10u8).collect());
+        let (mut b, out) = Decimate::new(src_out, 2)?;
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0, 2, 4, 6, 8]);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+/*! Periodic status from deframers.
+
+Deframers like [HdlcDeframer][crate::hdlc_deframer::HdlcDeframer] count
+decoded frames and errors in a [DeframerCounters]. [DeframerStatus] reads
+those counters, and periodically outputs a [DeframerStats] snapshot, e.g.
+for a monitoring dashboard.
+
+```
+use std::time::Duration;
+use rustradio::blocks::{DeframerStatus, HdlcDeframer};
+let (_, bits) = rustradio::stream::new_stream();
+let (deframer, frames) = HdlcDeframer::new(bits, 10, 1500);
+let (status, stats) = DeframerStatus::new(deframer.counters(), Duration::from_secs(10));
+```
+*/
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream};
+use crate::Error;
+
+#[derive(Default, Debug)]
+struct Counters {
+    decoded: AtomicU64,
+    crc_error: AtomicU64,
+    bitfixed: AtomicU64,
+    closed: AtomicBool,
+}
+
+/// Counters shared between a deframer and whoever wants to read them.
+#[derive(Clone, Default, Debug)]
+pub struct DeframerCounters(Arc<Counters>);
+
+impl DeframerCounters {
+    /// Return number of frames decoded, including ones that needed fixing.
+    pub fn decoded(&self) -> u64 {
+        self.0.decoded.load(Ordering::Relaxed)
+    }
+
+    /// Return number of frames dropped because of checksum errors.
+    pub fn crc_error(&self) -> u64 {
+        self.0.crc_error.load(Ordering::Relaxed)
+    }
+
+    /// Return number of frames decoded after fixing bit errors.
+    pub fn bitfixed(&self) -> u64 {
+        self.0.bitfixed.load(Ordering::Relaxed)
+    }
+
+    /// Return true if the deframer is gone, so the counters won't change
+    /// anymore.
+    pub fn is_closed(&self) -> bool {
+        self.0.closed.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn close(&self) {
+        self.0.closed.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_decoded(&self) {
+        self.0.decoded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_crc_error(&self) {
+        self.0.crc_error.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_bitfixed(&self) {
+        self.0.bitfixed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Snapshot of deframer counters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeframerStats {
+    /// Frames decoded.
+    pub decoded: u64,
+    /// Frames dropped because of checksum errors.
+    pub crc_error: u64,
+    /// Frames decoded after fixing bit errors.
+    pub bitfixed: u64,
+    /// Frames decoded per second, since the previous status.
+    pub rate: f64,
+}
+
+impl std::fmt::Display for DeframerStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "decoded={} crc_error={} bitfixed={} rate={:.3}/s",
+            self.decoded, self.crc_error, self.bitfixed, self.rate
+        )
+    }
+}
+
+/// Deframer status block.
+///
+/// Outputs a [DeframerStats] every `interval`, starting one interval after
+/// the first call to `work()`.
+///
+/// When the deframer is dropped, e.g. because its input ended, a final
+/// status is output, and the block finishes.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct DeframerStatus {
+    #[rustradio(out)]
+    dst: NCWriteStream<DeframerStats>,
+    counters: DeframerCounters,
+    interval: Duration,
+    clock: Box<dyn Fn() -> Instant + Send>,
+    // Time and decoded count of the previous status.
+    last: Option<(Instant, u64)>,
+}
+
+impl DeframerStatus {
+    /// Create new DeframerStatus block.
+    pub fn new(
+        counters: DeframerCounters,
+        interval: Duration,
+    ) -> (Self, NCReadStream<DeframerStats>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (
+            Self {
+                dst,
+                counters,
+                interval,
+                clock: Box::new(Instant::now),
+                last: None,
+            },
+            dr,
+        )
+    }
+
+    /// Replace the clock. Mostly for tests.
+    pub fn set_clock(&mut self, clock: impl Fn() -> Instant + Send + 'static) {
+        self.clock = Box::new(clock);
+    }
+
+    fn push(&mut self, now: Instant) {
+        let decoded = self.counters.decoded();
+        let rate = match self.last {
+            Some((t, d)) if now > t => (decoded - d) as f64 / (now - t).as_secs_f64(),
+            _ => 0.0,
+        };
+        self.dst.push(
+            DeframerStats {
+                decoded,
+                crc_error: self.counters.crc_error(),
+                bitfixed: self.counters.bitfixed(),
+                rate,
+            },
+            &[],
+        );
+        self.last = Some((now, decoded));
+    }
+}
+
+impl Block for DeframerStatus {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let now = (self.clock)();
+        let Some((last, _)) = self.last else {
+            self.last = Some((now, self.counters.decoded()));
+            return Ok(BlockRet::Pending);
+        };
+        if self.counters.is_closed() {
+            self.push(now);
+            return Ok(BlockRet::Finished);
+        }
+        if now < last + self.interval {
+            return Ok(BlockRet::Pending);
+        }
+        if self.dst.remaining() == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        self.push(now);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn interval() -> crate::Result<()> {
+        let counters = DeframerCounters::default();
+        let (mut b, out) = DeframerStatus::new(counters.clone(), Duration::from_secs(10));
+        let start = Instant::now();
+        let now = Arc::new(Mutex::new(start));
+        {
+            let now = now.clone();
+            b.set_clock(move || *now.lock().unwrap());
+        }
+        let advance = |secs| *now.lock().unwrap() += Duration::from_secs(secs);
+
+        assert_eq!(b.work()?, BlockRet::Pending);
+        counters.add_decoded();
+        counters.add_decoded();
+        advance(9);
+        assert_eq!(b.work()?, BlockRet::Pending);
+        assert!(out.pop().is_none());
+
+        advance(1);
+        counters.add_crc_error();
+        assert_eq!(b.work()?, BlockRet::Ok);
+        assert_eq!(b.work()?, BlockRet::Pending);
+        let (stats, _) = out.pop().unwrap();
+        assert_eq!(
+            stats,
+            DeframerStats {
+                decoded: 2,
+                crc_error: 1,
+                bitfixed: 0,
+                rate: 0.2,
+            }
+        );
+
+        advance(20);
+        counters.add_bitfixed();
+        counters.add_decoded();
+        assert_eq!(b.work()?, BlockRet::Ok);
+        let (stats, _) = out.pop().unwrap();
+        assert_eq!((stats.decoded, stats.bitfixed), (3, 1));
+        assert_eq!(stats.rate, 0.05);
+
+        // Deframer gone.
+        counters.close();
+        assert_eq!(b.work()?, BlockRet::Finished);
+        assert_eq!(out.pop().unwrap().0.decoded, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn deframer_dropped() -> crate::Result<()> {
+        use crate::blocks::HdlcDeframer;
+        let (_, bits) = crate::stream::new_stream();
+        let (deframer, _frames) = HdlcDeframer::new(bits, 10, 1500);
+        // Another copy of the counters doesn't keep the status going.
+        let counters = deframer.counters();
+        let (mut b, out) = DeframerStatus::new(counters.clone(), Duration::from_secs(10));
+        assert_eq!(b.work()?, BlockRet::Pending);
+        assert_eq!(b.work()?, BlockRet::Pending);
+        drop(deframer);
+        assert!(counters.is_closed());
+        assert_eq!(b.work()?, BlockRet::Finished);
+        assert_eq!(out.pop().unwrap().0.decoded, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! Count stream discontinuities, e.g. dropped samples.
+//!
+//! SDR sources tag samples after an overflow, e.g. with `soapy:overflow`.
+//! [DiscontinuityMonitor] watches for such tags, and keeps track of how
+//! many there have been, and when the last one was.
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Error};
+
+/// A discontinuity seen in the stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Discontinuity {
+    /// Sample position in the stream, counting from the first sample seen.
+    pub pos: u64,
+
+    /// Key of the tag that marked the discontinuity.
+    pub key: String,
+
+    /// Wall clock time when the discontinuity was seen.
+    pub time: SystemTime,
+}
+
+/// Pass samples through, counting discontinuity tags.
+///
+/// Any tag with one of the configured keys counts as a discontinuity,
+/// regardless of value.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct DiscontinuityMonitor {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    keys: Vec<String>,
+    // Samples passed through so far.
+    #[rustradio(default)]
+    pos: u64,
+    #[rustradio(default)]
+    count: u64,
+    #[rustradio(default)]
+    last: Option<Discontinuity>,
+}
+
+impl DiscontinuityMonitor {
+    /// Return number of discontinuities seen.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the last discontinuity seen, if any.
+    pub fn last(&self) -> Option<&Discontinuity> {
+        self.last.as_ref()
+    }
+}
+
+impl Block for DiscontinuityMonitor {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        for tag in &tags {
+            if self.keys.iter().any(|k| k == tag.key()) {
+                self.count += 1;
+                self.last = Some(Discontinuity {
+                    pos: self.pos + tag.pos() as u64,
+                    key: tag.key().to_string(),
+                    time: SystemTime::now(),
+                });
+            }
+        }
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        self.pos += n as u64;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{Tag, TagValue};
+    use crate::testutil::PatternSource;
+
+    #[test]
+    fn count() -> Result<()> {
+        let tags = vec![
+            Tag::new(3, "other".into(), TagValue::Bool(true)),
+            Tag::new(12, "soapy:overflow".into(), TagValue::Bool(true)),
+            Tag::new(17, "time_jump".into(), TagValue::U64(1000)),
+        ];
+        let (mut src, prev) = PatternSource::new(vec![Complex::new(1.0, 0.0)], 20, tags);
+        src.set_max_chunk(15);
+        let (mut b, out) =
+            DiscontinuityMonitor::new(prev, vec!["soapy:overflow".into(), "time_jump".into()]);
+        assert_eq!(b.count(), 0);
+        assert!(b.last().is_none());
+
+        let before = SystemTime::now();
+        src.work()?;
+        b.work()?;
+        assert_eq!(b.count(), 1);
+        let last = b.last().unwrap();
+        assert_eq!(last.pos, 12);
+        assert_eq!(last.key, "soapy:overflow");
+        assert!(last.time >= before);
+
+        src.work()?;
+        b.work()?;
+        assert_eq!(b.count(), 2);
+        assert_eq!(b.last().unwrap().pos, 17);
+        assert_eq!(b.last().unwrap().key, "time_jump");
+
+        // Everything, including tags, passed through.
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 20);
+        assert_eq!(tags.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+/*! Measure error vector magnitude (EVM) against a known constellation.
+
+Each symbol is compared to the nearest ideal constellation point. EVM is the
+RMS of the error vectors, relative to the RMS of the ideal points:
+
+```text
+EVM = sqrt(mean |r - s|² / mean |s|²)
+```
+
+If the noise is the only impairment, the SNR is `1 / EVM²`.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Tag key for the EVM, as a ratio. Not percent.
+pub const TAG_EVM: &str = "evm";
+
+/// Tag key for the estimated SNR, in dB.
+pub const TAG_SNR: &str = "evm:snr_db";
+
+/// EVM meter.
+///
+/// Samples pass through unchanged. The running EVM is an exponential moving
+/// average, with the weight of each new symbol set by
+/// [`set_alpha()`][Evm::set_alpha].
+///
+/// With [`set_tag_interval()`][Evm::set_tag_interval], the EVM and SNR are
+/// also added as tags on the output.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Evm {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    constellation: Vec<Complex>,
+    alpha: Float,
+    // Averaged power of the error vectors, and of the ideal points.
+    err_power: Float,
+    ref_power: Float,
+    symbols: u64,
+    tag_interval: Option<usize>,
+    since_tag: usize,
+}
+
+impl Evm {
+    /// Create new Evm block.
+    pub fn new(
+        src: ReadStream<Complex>,
+        constellation: Vec<Complex>,
+    ) -> Result<(Self, ReadStream<Complex>)> {
+        if constellation.is_empty() {
+            return Err(Error::new("Evm: constellation must not be empty").into());
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                constellation,
+                alpha: 0.01,
+                err_power: 0.0,
+                ref_power: 0.0,
+                symbols: 0,
+                tag_interval: None,
+                since_tag: 0,
+            },
+            dr,
+        ))
+    }
+
+    /// Set the averaging weight of each new symbol, between 0 and 1.
+    ///
+    /// Default is 0.01. Lower is smoother, but slower to react.
+    pub fn set_alpha(&mut self, alpha: Float) {
+        self.alpha = alpha;
+    }
+
+    /// Add EVM and SNR tags every `interval` samples, or never if `None`.
+    pub fn set_tag_interval(&mut self, interval: Option<usize>) {
+        self.tag_interval = interval;
+        self.since_tag = 0;
+    }
+
+    /// Return the running EVM, as a ratio.
+    ///
+    /// Returns `None` before any symbols have been seen.
+    pub fn evm(&self) -> Option<Float> {
+        if self.symbols == 0 || self.ref_power == 0.0 {
+            return None;
+        }
+        Some((self.err_power / self.ref_power).sqrt())
+    }
+
+    /// Return the estimated SNR, in dB.
+    ///
+    /// Infinite if the symbols are all ideal.
+    pub fn snr_db(&self) -> Option<Float> {
+        self.evm().map(|evm| -20.0 * evm.log10())
+    }
+
+    /// Return the number of symbols measured.
+    pub fn symbols(&self) -> u64 {
+        self.symbols
+    }
+
+    fn update(&mut self, s: Complex) {
+        let ideal = self
+            .constellation
+            .iter()
+            .min_by(|a, b| (s - *a).norm_sqr().total_cmp(&(s - *b).norm_sqr()))
+            .unwrap(); // unwrap: checked in new() that it's not empty.
+        let err = (s - ideal).norm_sqr();
+        let pow = ideal.norm_sqr();
+        if self.symbols == 0 {
+            self.err_power = err;
+            self.ref_power = pow;
+        } else {
+            self.err_power += self.alpha * (err - self.err_power);
+            self.ref_power += self.alpha * (pow - self.ref_power);
+        }
+        self.symbols += 1;
+    }
+}
+
+impl Block for Evm {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let mut tags: Vec<Tag> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        for (pos, s) in i.iter().take(n).enumerate() {
+            self.update(*s);
+            let Some(interval) = self.tag_interval else {
+                continue;
+            };
+            self.since_tag += 1;
+            if self.since_tag >= interval {
+                self.since_tag = 0;
+                if let (Some(evm), Some(snr)) = (self.evm(), self.snr_db()) {
+                    tags.push(Tag::new(pos, TAG_EVM.into(), TagValue::Float(evm)));
+                    tags.push(Tag::new(pos, TAG_SNR.into(), TagValue::Float(snr)));
+                }
+            }
+        }
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qpsk() -> Vec<Complex> {
+        let a = std::f32::consts::FRAC_1_SQRT_2 as Float;
+        vec![
+            Complex::new(a, a),
+            Complex::new(-a, a),
+            Complex::new(-a, -a),
+            Complex::new(a, -a),
+        ]
+    }
+
+    // QPSK symbols, with uniform noise of amplitude `noise` on I and Q.
+    fn symbols(noise: Float) -> Vec<Complex> {
+        let mut state = 1u32;
+        let mut rnd = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as Float / (1 << 23) as Float - 1.0
+        };
+        let c = qpsk();
+        (0..5000)
+            .map(|i| c[(i * 7 + i / 3) % 4] + Complex::new(rnd(), rnd()) * noise)
+            .collect()
+    }
+
+    fn run(input: Vec<Complex>) -> Result<(Evm, Vec<Complex>, Vec<Tag>)> {
+        crate::tests::run_block(input, |prev| {
+            let (mut b, out) = Evm::new(prev, qpsk())?;
+            b.set_tag_interval(Some(1000));
+            Ok((b, out))
+        })
+    }
+
+    #[test]
+    fn ideal() -> Result<()> {
+        let input = symbols(0.0);
+        let (b, out, _) = run(input.clone())?;
+        assert_eq!(out, input);
+        assert_eq!(b.symbols(), 5000);
+        assert!(b.evm().unwrap() < 1e-6, "evm {:?}", b.evm());
+        Ok(())
+    }
+
+    #[test]
+    fn noisy() -> Result<()> {
+        let (_, prev) = crate::stream::new_stream();
+        let (b, _) = Evm::new(prev, qpsk())?;
+        assert_eq!(b.evm(), None);
+
+        let mut last = 0.0;
+        for noise in [0.05, 0.1, 0.2] {
+            let input = symbols(noise);
+            let (b, out, tags) = run(input.clone())?;
+            assert_eq!(out, input);
+            // Uniform noise has power 2·noise²/3, and the QPSK points 1.
+            let want = noise * (2.0 / 3.0 as Float).sqrt();
+            let got = b.evm().unwrap();
+            assert!((got - want).abs() < 0.2 * want, "got {got}, want {want}");
+            assert!(got > last);
+            last = got;
+            let snr = b.snr_db().unwrap();
+            assert!((snr + 20.0 * want.log10()).abs() < 2.0, "snr {snr}");
+
+            let evm_tags: Vec<_> = tags.iter().filter(|t| t.key() == TAG_EVM).collect();
+            assert_eq!(evm_tags.len(), 5);
+            assert_eq!(evm_tags[0].pos(), 999);
+            assert_eq!(tags.iter().filter(|t| t.key() == TAG_SNR).count(), 5);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+/*! FFT helpers.
+
+Mapping between FFT bins and frequencies, for e.g. spectrum displays.
+
+Bins are in the order FFT libraries like [rustfft] output them, with DC
+first, then positive frequencies, then negative frequencies. If `shifted`
+is true, bins are instead in "fftshift" order, with the most negative
+frequency first and DC in the middle, at bin `size/2`.
+
+The [FftBackend] trait lets blocks like
+[FftStream][crate::fft_stream::FftStream] use other FFT libraries than
+rustfft, e.g. FFTW, or a GPU.
+*/
+use std::sync::Arc;
+
+use crate::{Complex, Float};
+
+/// FFT implementation, of a fixed size.
+pub trait FftBackend: Send {
+    /// Number of samples per transform.
+    fn size(&self) -> usize;
+
+    /// Transform `size()` samples in place.
+    ///
+    /// The output is not normalized.
+    fn process(&mut self, buf: &mut [Complex]);
+}
+
+/// FFT backend using rustfft.
+///
+/// This is the default backend.
+pub struct RustFft {
+    fft: Arc<dyn rustfft::Fft<Float>>,
+    scratch: Vec<Complex>,
+}
+
+impl RustFft {
+    /// Create new forward FFT.
+    pub fn new(size: usize) -> Self {
+        Self::from_fft(rustfft::FftPlanner::new().plan_fft_forward(size))
+    }
+
+    /// Create new inverse FFT.
+    pub fn new_inverse(size: usize) -> Self {
+        Self::from_fft(rustfft::FftPlanner::new().plan_fft_inverse(size))
+    }
+
+    fn from_fft(fft: Arc<dyn rustfft::Fft<Float>>) -> Self {
+        let scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+        Self { fft, scratch }
+    }
+}
+
+impl FftBackend for RustFft {
+    fn size(&self) -> usize {
+        self.fft.len()
+    }
+
+    fn process(&mut self, buf: &mut [Complex]) {
+        self.fft.process_with_scratch(buf, &mut self.scratch);
+    }
+}
+
+// Frequency index range representable by an FFT of the given size.
+fn index_range(size: usize) -> std::ops::RangeInclusive<i64> {
+    let size = size as i64;
+    -(size / 2)..=(size - 1) / 2
+}
+
+/// Return center frequency of an FFT bin.
+///
+/// Negative frequencies are returned as negative.
+///
+/// ```
+/// use rustradio::fft::bin_to_freq;
+/// assert_eq!(bin_to_freq(0, 8, 800.0, false), 0.0);
+/// assert_eq!(bin_to_freq(7, 8, 800.0, false), -100.0);
+/// assert_eq!(bin_to_freq(0, 8, 800.0, true), -400.0);
+/// ```
+pub fn bin_to_freq(bin: usize, size: usize, samp_rate: f64, shifted: bool) -> f64 {
+    assert!(bin < size, "bin {bin} out of range for FFT size {size}");
+    let (bin, size) = (bin as i64, size as i64);
+    let index = if shifted {
+        bin - size / 2
+    } else if bin < (size + 1) / 2 {
+        bin
+    } else {
+        bin - size
+    };
+    index as f64 * samp_rate / size as f64
+}
+
+/// Return the FFT bin closest to a frequency.
+///
+/// Returns None if the frequency is outside what the FFT covers, i.e. not
+/// in `[-samp_rate/2, samp_rate/2)`, after rounding to the nearest bin.
+pub fn freq_to_bin(freq: f64, size: usize, samp_rate: f64, shifted: bool) -> Option<usize> {
+    let index = (freq * size as f64 / samp_rate).round() as i64;
+    if !index_range(size).contains(&index) {
+        return None;
+    }
+    let size = size as i64;
+    let bin = if shifted {
+        index + size / 2
+    } else {
+        index.rem_euclid(size)
+    };
+    Some(bin as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dc() {
+        for size in [7, 8] {
+            assert_eq!(bin_to_freq(0, size, 1000.0, false), 0.0);
+            assert_eq!(bin_to_freq(size / 2, size, 1000.0, true), 0.0);
+            assert_eq!(freq_to_bin(0.0, size, 1000.0, false), Some(0));
+            assert_eq!(freq_to_bin(0.0, size, 1000.0, true), Some(size / 2));
+        }
+    }
+
+    #[test]
+    fn positive() {
+        assert_eq!(bin_to_freq(1, 8, 800.0, false), 100.0);
+        assert_eq!(bin_to_freq(3, 8, 800.0, false), 300.0);
+        assert_eq!(bin_to_freq(7, 8, 800.0, true), 300.0);
+        assert_eq!(bin_to_freq(3, 7, 700.0, false), 300.0);
+        assert_eq!(bin_to_freq(6, 7, 700.0, true), 300.0);
+        assert_eq!(freq_to_bin(300.0, 8, 800.0, false), Some(3));
+        assert_eq!(freq_to_bin(310.0, 8, 800.0, true), Some(7));
+        // Nyquist is the negative edge.
+        assert_eq!(freq_to_bin(400.0, 8, 800.0, false), None);
+    }
+
+    #[test]
+    fn negative() {
+        assert_eq!(bin_to_freq(4, 8, 800.0, false), -400.0);
+        assert_eq!(bin_to_freq(7, 8, 800.0, false), -100.0);
+        assert_eq!(bin_to_freq(0, 8, 800.0, true), -400.0);
+        assert_eq!(bin_to_freq(4, 7, 700.0, false), -300.0);
+        assert_eq!(bin_to_freq(0, 7, 700.0, true), -300.0);
+        assert_eq!(freq_to_bin(-100.0, 8, 800.0, false), Some(7));
+        assert_eq!(freq_to_bin(-400.0, 8, 800.0, false), Some(4));
+        assert_eq!(freq_to_bin(-400.0, 8, 800.0, true), Some(0));
+        assert_eq!(freq_to_bin(-350.0, 7, 700.0, false), None);
+
+        // Round trip every bin.
+        for shifted in [false, true] {
+            for size in [7, 8] {
+                for bin in 0..size {
+                    let freq = bin_to_freq(bin, size, 1000.0, shifted);
+                    assert_eq!(freq_to_bin(freq, size, 1000.0, shifted), Some(bin));
+                }
+            }
+        }
+    }
+}
@@ -26,47 +26,124 @@ let sink = NullSink::new(fft_out);
 ## Further reading:
 * <https://en.wikipedia.org/wiki/Fast_Fourier_transform>
 * <https://en.wikipedia.org/wiki/Overlap%E2%80%93add_method>
+* <https://en.wikipedia.org/wiki/Overlap%E2%80%93save_method>
 */
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
 use log::trace;
 use rustfft::FftPlanner;
 
-use crate::block::{Block, BlockRet};
+use crate::block::{Block, BlockCommand, BlockLog, BlockRet, Command};
 use crate::stream::{ReadStream, WriteStream};
 use crate::{Complex, Error, Float};
 
-/// FFT filter. Like a FIR filter, but more efficient when there are many taps.
-#[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
-pub struct FftFilter {
+/// FFT filter engine.
+///
+/// An engine takes fixed size chunks of input, and produces the same number
+/// of filtered output samples.
+pub trait Engine: Send {
+    /// Number of samples consumed and produced per `filter()` call.
+    fn chunk_size(&self) -> usize;
+
+    /// Filter exactly `chunk_size()` samples from `input`, writing the same
+    /// number of samples into `out`.
+    fn filter(&mut self, input: &[Complex], out: &mut [Complex]);
+
+    /// Replace the filter taps.
+    ///
+    /// This may change `chunk_size()`. Engines should carry over their
+    /// state, so that there's no glitch in the output.
+    fn set_taps(&mut self, taps: &[Complex]);
+
+    /// Number of taps currently in use.
+    fn ntaps(&self) -> usize;
+}
+
+// Group delay of a linear phase filter.
+fn linear_phase_delay(ntaps: usize) -> Float {
+    ntaps.saturating_sub(1) as Float / 2.0
+}
+
+/// How to choose the FFT size, given the number of taps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FftSize {
+    /// Twice the number of taps, rounded up to a power of two.
+    ///
+    /// Just over a power of two, that's almost four times the taps.
+    #[default]
+    PowerOfTwo,
+
+    /// Smallest size of at least twice the number of taps, with no prime
+    /// factors other than 2, 3, and 5.
+    ///
+    /// rustfft is efficient for those sizes too, and they waste less
+    /// compute than rounding up to a power of two.
+    SmallPrimes,
+}
+
+fn calc_fft_size(from: usize, size: FftSize) -> usize {
+    match size {
+        FftSize::PowerOfTwo => 2 * from.next_power_of_two(),
+        FftSize::SmallPrimes => {
+            let smooth = |mut n: usize| {
+                for p in [2, 3, 5] {
+                    while n % p == 0 {
+                        n /= p;
+                    }
+                }
+                n == 1
+            };
+            let mut n = std::cmp::max(2 * from, 2);
+            while !smooth(n) {
+                n += 1;
+            }
+            n
+        }
+    }
+}
+
+// FFT the taps, and prepare them for multiplying with an FFT:ed input.
+fn prepare_taps(taps: &[Complex], fft_size: usize, fft: &dyn rustfft::Fft<Float>) -> Vec<Complex> {
+    let mut taps_fft = taps.to_vec();
+    taps_fft.resize(fft_size, Complex::default());
+    fft.process(&mut taps_fft);
+
+    // Normalization is actually the square root of this
+    // expression, but since we'll do two FFTs we can just skip
+    // the square root here and do it just once here in setup.
+    let f = 1.0 / taps_fft.len() as Float;
+    taps_fft.iter_mut().for_each(|s: &mut Complex| *s *= f);
+    taps_fft
+}
+
+/// Overlap-add FFT filter engine, using rustfft.
+///
+/// This is the default engine.
+pub struct RustFftEngine {
     buf: Vec<Complex>,
     taps_fft: Vec<Complex>,
     nsamples: usize,
     fft_size: usize,
+    size: FftSize,
+    ntaps: usize,
     tail: Vec<Complex>,
+    // Tail left over from before the taps were last changed.
+    carry: Vec<Complex>,
     fft: Arc<dyn rustfft::Fft<Float>>,
     ifft: Arc<dyn rustfft::Fft<Float>>,
-    #[rustradio(in)]
-    src: ReadStream<Complex>,
-    #[rustradio(out)]
-    dst: WriteStream<Complex>,
 }
 
-impl FftFilter {
-    fn calc_fft_size(from: usize) -> usize {
-        let mut n = 1;
-        while n < from {
-            n <<= 1;
-        }
-        2 * n
+impl RustFftEngine {
+    /// Create new engine, given filter taps.
+    pub fn new(taps: &[Complex]) -> Self {
+        Self::with_fft_size(taps, FftSize::default())
     }
 
-    /// Create new FftFilter, given filter taps.
-    pub fn new(src: ReadStream<Complex>, taps: &[Complex]) -> (Self, ReadStream<Complex>) {
+    /// Create new engine, given filter taps, and how to choose FFT size.
+    pub fn with_fft_size(taps: &[Complex], size: FftSize) -> Self {
         // Set up FFT / batch size.
-        let fft_size = Self::calc_fft_size(taps.len());
+        let fft_size = calc_fft_size(taps.len(), size);
         let nsamples = fft_size - taps.len();
 
         // Create FFT planners.
@@ -74,109 +151,396 @@ impl FftFilter {
         let fft = planner.plan_fft_forward(fft_size);
         let ifft = planner.plan_fft_inverse(fft_size);
 
-        // Pre-FFT the taps.
-        let mut taps_fft = taps.to_vec();
-        taps_fft.resize(fft_size, Complex::default());
-        fft.process(&mut taps_fft);
+        let taps_fft = prepare_taps(taps, fft_size, &*fft);
+        Self {
+            fft_size,
+            size,
+            ntaps: taps.len(),
+            taps_fft,
+            tail: vec![Complex::default(); taps.len()],
+            carry: Vec::new(),
+            fft,
+            ifft,
+            buf: Vec::with_capacity(fft_size),
+            nsamples,
+        }
+    }
+}
 
-        // Normalization is actually the square root of this
-        // expression, but since we'll do two FFTs we can just skip
-        // the square root here and do it just once here in setup.
-        {
-            let f = 1.0 / taps_fft.len() as Float;
-            taps_fft.iter_mut().for_each(|s: &mut Complex| *s *= f);
+impl Engine for RustFftEngine {
+    fn chunk_size(&self) -> usize {
+        self.nsamples
+    }
+
+    fn ntaps(&self) -> usize {
+        self.ntaps
+    }
+
+    fn filter(&mut self, input: &[Complex], out: &mut [Complex]) {
+        // Read so that self.buf contains exactly self.nsamples samples.
+        //
+        // Yes, this part is weird. It evolved into this, but any
+        // cleanup I do to the next few lines just made it slower,
+        // even though I removed needless logic.
+        //
+        // E.g.:
+        // * self.buf.len() is *always* empty here, so that's a
+        //   needless subtraction.
+        // * Why do we even have self.buf? It's cleared on every round.
+        //   (well, that means no heap allocation, sure)
+        //
+        // Why are these things not fixed: Because then it's
+        // slower, for some reason. At least as of 2023-10-07, on
+        // amd64, with Rust 1.7.1.
+        let add = std::cmp::min(input.len(), self.nsamples - self.buf.len());
+        self.buf.extend(input.iter().take(add).copied());
+
+        // Run FFT.
+        self.buf.resize(self.fft_size, Complex::default());
+        self.fft.process(&mut self.buf);
+
+        // Filter by array multiplication.
+        sum_vec(&mut self.buf, &self.taps_fft);
+
+        // IFFT back to the time domain.
+        self.ifft.process(&mut self.buf);
+
+        // Add overlapping tail.
+        for (i, t) in self.tail.iter().enumerate() {
+            self.buf[i] += t;
+        }
+
+        // Add what's left of the tail from before the taps were changed.
+        if !self.carry.is_empty() {
+            let n = std::cmp::min(self.carry.len(), self.nsamples);
+            for (b, c) in self.buf.iter_mut().zip(self.carry.drain(..n)) {
+                *b += c;
+            }
+        }
+
+        // Output.
+        // TODO: needless copy?
+        out[..self.nsamples].copy_from_slice(&self.buf[..self.nsamples]);
+
+        // Stash tail.
+        for i in 0..self.tail.len() {
+            self.tail[i] = self.buf[self.nsamples + i];
+        }
+
+        // Clear buffer. Per above performance comment.
+        self.buf.clear();
+    }
+
+    fn set_taps(&mut self, taps: &[Complex]) {
+        let fft_size = calc_fft_size(taps.len(), self.size);
+        if fft_size != self.fft_size {
+            let mut planner = FftPlanner::new();
+            self.fft = planner.plan_fft_forward(fft_size);
+            self.ifft = planner.plan_fft_inverse(fft_size);
+            self.fft_size = fft_size;
+        }
+        self.nsamples = fft_size - taps.len();
+        self.taps_fft = prepare_taps(taps, fft_size, &*self.fft);
+        self.ntaps = taps.len();
+        self.buf = Vec::with_capacity(fft_size);
+        // The old tail is the old filter still ringing out, so it's
+        // added to the next outputs.
+        let old = std::mem::replace(&mut self.tail, vec![Complex::default(); taps.len()]);
+        for (i, t) in old.into_iter().enumerate() {
+            match self.carry.get_mut(i) {
+                Some(c) => *c += t,
+                None => self.carry.push(t),
+            }
+        }
+    }
+}
+
+/// Overlap-save FFT filter engine, using rustfft.
+///
+/// Instead of accumulating the tail of each IFFT output, this engine keeps
+/// the last `ntaps-1` input samples, and discards the transient part of the
+/// output.
+///
+/// <https://en.wikipedia.org/wiki/Overlap%E2%80%93save_method>
+pub struct OverlapSaveEngine {
+    buf: Vec<Complex>,
+    history: Vec<Complex>,
+    taps_fft: Vec<Complex>,
+    nsamples: usize,
+    fft_size: usize,
+    size: FftSize,
+    ntaps: usize,
+    fft: Arc<dyn rustfft::Fft<Float>>,
+    ifft: Arc<dyn rustfft::Fft<Float>>,
+}
+
+impl OverlapSaveEngine {
+    /// Create new engine, given filter taps.
+    pub fn new(taps: &[Complex]) -> Self {
+        Self::with_fft_size(taps, FftSize::default())
+    }
+
+    /// Create new engine, given filter taps, and how to choose FFT size.
+    pub fn with_fft_size(taps: &[Complex], size: FftSize) -> Self {
+        let fft_size = calc_fft_size(taps.len(), size);
+        let overlap = taps.len().saturating_sub(1);
+        let nsamples = fft_size - overlap;
+
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+
+        let taps_fft = prepare_taps(taps, fft_size, &*fft);
+        Self {
+            fft_size,
+            size,
+            ntaps: taps.len(),
+            taps_fft,
+            history: vec![Complex::default(); overlap],
+            fft,
+            ifft,
+            buf: Vec::with_capacity(fft_size),
+            nsamples,
+        }
+    }
+}
+
+impl Engine for OverlapSaveEngine {
+    fn chunk_size(&self) -> usize {
+        self.nsamples
+    }
+
+    fn ntaps(&self) -> usize {
+        self.ntaps
+    }
+
+    fn filter(&mut self, input: &[Complex], out: &mut [Complex]) {
+        let overlap = self.history.len();
+        self.buf.extend(&self.history);
+        self.buf.extend(input.iter().take(self.nsamples).copied());
+        debug_assert_eq!(self.buf.len(), self.fft_size);
+
+        // Save input history for next round.
+        self.history
+            .copy_from_slice(&self.buf[self.fft_size - overlap..]);
+
+        self.fft.process(&mut self.buf);
+        sum_vec(&mut self.buf, &self.taps_fft);
+        self.ifft.process(&mut self.buf);
+
+        // The first `overlap` samples are circular convolution garbage.
+        out[..self.nsamples].copy_from_slice(&self.buf[overlap..]);
+        self.buf.clear();
+    }
+
+    fn set_taps(&mut self, taps: &[Complex]) {
+        let fft_size = calc_fft_size(taps.len(), self.size);
+        if fft_size != self.fft_size {
+            let mut planner = FftPlanner::new();
+            self.fft = planner.plan_fft_forward(fft_size);
+            self.ifft = planner.plan_fft_inverse(fft_size);
+            self.fft_size = fft_size;
+        }
+        let overlap = taps.len().saturating_sub(1);
+        self.nsamples = fft_size - overlap;
+        self.taps_fft = prepare_taps(taps, fft_size, &*self.fft);
+        self.ntaps = taps.len();
+        // Keep the most recent input. If the new filter needs more history
+        // than there is, pad with zeroes as at the start of the stream.
+        let old = self.history.len();
+        if overlap <= old {
+            self.history.drain(..old - overlap);
+        } else {
+            self.history.splice(
+                ..0,
+                std::iter::repeat(Complex::default()).take(overlap - old),
+            );
+        }
+        self.buf = Vec::with_capacity(fft_size);
+    }
+}
+
+/// Handle for changing the taps of a running [FftFilter].
+#[derive(Clone, Default)]
+pub struct FftFilterHandle {
+    // New taps, not yet picked up by the block.
+    taps: Arc<Mutex<Option<Vec<Complex>>>>,
+}
+
+impl FftFilterHandle {
+    /// Set new filter taps.
+    ///
+    /// The block switches over when it next runs. Output already in
+    /// flight from the old taps still rings out.
+    pub fn set_taps(&self, taps: &[Complex]) {
+        *self.taps.lock().unwrap() = Some(taps.to_vec());
+    }
+
+    fn take(&self) -> Option<Vec<Complex>> {
+        self.taps.lock().unwrap().take()
+    }
+}
+
+/// Builder for [FftFilter].
+pub struct FftFilterBuilder {
+    taps: Vec<Complex>,
+    size: FftSize,
+}
+
+impl FftFilterBuilder {
+    /// Create new builder, given filter taps.
+    pub fn new(taps: &[Complex]) -> Self {
+        Self {
+            taps: taps.to_vec(),
+            size: FftSize::default(),
         }
+    }
 
+    /// Choose how to pick the FFT size.
+    pub fn fft_size(mut self, size: FftSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Build FftFilter.
+    pub fn build(self, src: ReadStream<Complex>) -> (FftFilter, ReadStream<Complex>) {
+        FftFilter::new_engine(src, RustFftEngine::with_fft_size(&self.taps, self.size))
+    }
+}
+
+/// Command to set new filter taps.
+pub struct SetTaps(pub Vec<Complex>);
+
+/// FFT filter. Like a FIR filter, but more efficient when there are many taps.
+///
+/// The actual filtering is done by an [Engine]. By default that's the
+/// overlap-add [RustFftEngine].
+///
+/// The taps can be changed while running, either via an [FftFilterHandle],
+/// or by sending a [SetTaps] command.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, command)]
+pub struct FftFilter<E: Engine = RustFftEngine> {
+    engine: E,
+    handle: FftFilterHandle,
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+}
+
+impl FftFilter {
+    /// Create new FftFilter, given filter taps.
+    pub fn new(src: ReadStream<Complex>, taps: &[Complex]) -> (Self, ReadStream<Complex>) {
+        Self::new_engine(src, RustFftEngine::new(taps))
+    }
+
+    /// Create a builder, for more options.
+    ///
+    /// ```
+    /// use rustradio::Complex;
+    /// use rustradio::blocks::{ConstantSource, FftFilter};
+    /// use rustradio::fft_filter::FftSize;
+    /// let (src, prev) = ConstantSource::new(Complex::new(0.0, 0.0));
+    /// let taps = vec![Complex::new(0.001, 0.0); 1025];
+    /// let (fft, prev) = FftFilter::builder(&taps)
+    ///     .fft_size(FftSize::SmallPrimes)
+    ///     .build(prev);
+    /// ```
+    pub fn builder(taps: &[Complex]) -> FftFilterBuilder {
+        FftFilterBuilder::new(taps)
+    }
+}
+
+impl<E: Engine> FftFilter<E> {
+    /// Create new FftFilter, using the given engine.
+    ///
+    /// ```
+    /// use rustradio::Complex;
+    /// use rustradio::blocks::{ConstantSource, FftFilter};
+    /// use rustradio::fft_filter::OverlapSaveEngine;
+    /// let (src, prev) = ConstantSource::new(Complex::new(0.0, 0.0));
+    /// let taps = vec![Complex::new(0.5, 0.0); 10];
+    /// let (fft, prev) = FftFilter::new_engine(prev, OverlapSaveEngine::new(&taps));
+    /// ```
+    pub fn new_engine(src: ReadStream<Complex>, engine: E) -> (Self, ReadStream<Complex>) {
         let (dst, dr) = crate::stream::new_stream();
         (
             Self {
                 src,
                 dst,
-                fft_size,
-                taps_fft,
-                tail: vec![Complex::default(); taps.len()],
-                fft,
-                ifft,
-                buf: Vec::with_capacity(fft_size),
-                nsamples,
+                engine,
+                handle: FftFilterHandle::default(),
             },
             dr,
         )
     }
+
+    /// Return a handle for changing the taps.
+    pub fn handle(&self) -> FftFilterHandle {
+        self.handle.clone()
+    }
+
+    /// Set new filter taps.
+    pub fn set_taps(&mut self, taps: &[Complex]) {
+        self.engine.set_taps(taps);
+    }
+
+    /// Return the delay through the filter, in samples.
+    ///
+    /// This is the group delay of a linear phase filter, `(ntaps-1)/2`,
+    /// which is what e.g. [low_pass_complex][crate::fir::low_pass_complex]
+    /// creates. To keep a parallel path aligned, put a
+    /// [Delay][crate::blocks::Delay] of this many samples on it.
+    ///
+    /// Taps set via an [FftFilterHandle] are only counted once the block
+    /// has picked them up.
+    pub fn delay_samples(&self) -> Float {
+        linear_phase_delay(self.engine.ntaps())
+    }
+}
+
+impl<E: Engine> BlockCommand for FftFilter<E> {
+    fn handle_command(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd.downcast::<SetTaps>() {
+            Ok(c) => {
+                let SetTaps(taps) = *c;
+                self.set_taps(&taps);
+            }
+            Err(_) => self.log_warn(format_args!("Ignoring unknown command")),
+        }
+        Ok(())
+    }
 }
 
 fn sum_vec(left: &mut [Complex], right: &[Complex]) {
     left.iter_mut().zip(right.iter()).for_each(|(x, y)| *x *= y)
 }
 
-impl Block for FftFilter {
+impl<E: Engine> Block for FftFilter<E> {
     fn work(&mut self) -> Result<BlockRet, Error> {
         // TODO: multithread this.
+        if let Some(taps) = self.handle.take() {
+            self.engine.set_taps(&taps);
+        }
         let mut produced = false;
+        let n = self.engine.chunk_size();
         loop {
             let (input, tags) = self.src.read_buf()?;
             let mut o = self.dst.write_buf()?;
 
-            if self.nsamples > o.len() {
-                trace!(
-                    "FftFilter: Need {} output space, only have {}",
-                    self.nsamples,
-                    o.len()
-                );
+            if n > o.len() {
+                trace!("FftFilter: Need {} output space, only have {}", n, o.len());
                 break;
             }
-            // Read so that self.buf contains exactly self.nsamples samples.
-            //
-            // Yes, this part is weird. It evolved into this, but any
-            // cleanup I do to the next few lines just made it slower,
-            // even though I removed needless logic.
-            //
-            // E.g.:
-            // * self.buf.len() is *always* empty here, so that's a
-            //   needless subtraction.
-            // * We break if input available less than nsamples, so
-            //   why not just compare that?
-            // * Why do we even have self.buf? It's cleared on every round.
-            //   (well, that means no heap allocation, sure)
-            //
-            // Why are these things not fixed: Because then it's
-            // slower, for some reason. At least as of 2023-10-07, on
-            // amd64, with Rust 1.7.1.
-            let add = std::cmp::min(input.len(), self.nsamples - self.buf.len());
-            if add < self.nsamples {
+            if input.len() < n {
                 break;
             }
-            self.buf.extend(input.iter().take(add).copied());
-            input.consume(add);
-
-            // Run FFT.
-            self.buf.resize(self.fft_size, Complex::default());
-            self.fft.process(&mut self.buf);
-
-            // Filter by array multiplication.
-            sum_vec(&mut self.buf, &self.taps_fft);
-
-            // IFFT back to the time domain.
-            self.ifft.process(&mut self.buf);
-
-            // Add overlapping tail.
-            for (i, t) in self.tail.iter().enumerate() {
-                self.buf[i] += t;
-            }
-
-            // Output.
-            // TODO: needless copy?
-            o.fill_from_slice(&self.buf[..self.nsamples]);
-            o.produce(self.nsamples, &tags);
+            self.engine.filter(&input.slice()[..n], &mut o.slice()[..n]);
+            input.consume(n);
+            let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+            o.produce(n, &tags);
             produced = true;
-
-            // Stash tail.
-            for i in 0..self.tail.len() {
-                self.tail[i] = self.buf[self.nsamples + i];
-            }
-
-            // Clear buffer. Per above performance comment.
-            self.buf.clear();
         }
         if produced {
             Ok(BlockRet::Ok)
@@ -226,6 +590,21 @@ impl FftFilterFloat {
     }
 }
 
+impl FftFilterFloat {
+    /// Set new filter taps.
+    pub fn set_taps(&mut self, taps: &[Float]) {
+        let ctaps: Vec<Complex> = taps.iter().copied().map(|f| Complex::new(f, 0.0)).collect();
+        self.complex.set_taps(&ctaps);
+    }
+
+    /// Return the delay through the filter, in samples.
+    ///
+    /// See [`FftFilter::delay_samples()`].
+    pub fn delay_samples(&self) -> Float {
+        self.complex.delay_samples()
+    }
+}
+
 impl Block for FftFilterFloat {
     fn work(&mut self) -> Result<BlockRet, Error> {
         // Convert input to Complex.
@@ -317,6 +696,160 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn overlap_save_same_as_add() -> Result<()> {
+        let taps = low_pass_complex(8_000.0, 1000.0, 100.0, &WindowType::Hamming);
+        let input: Vec<_> = (0..20_000)
+            .map(|i| {
+                let i = i as Float;
+                Complex::new((i * 0.1).sin() + (i * 2.5).cos(), (i * 0.37).sin())
+            })
+            .collect();
+        let (mut src, prev) = crate::blocks::VectorSource::new(input);
+        let (mut tee, a, b) = crate::blocks::Tee::new(prev);
+        let (mut add, add_out) = FftFilter::new(a, &taps);
+        let (mut save, save_out) = FftFilter::new_engine(b, OverlapSaveEngine::new(&taps));
+        src.work()?;
+        tee.work()?;
+        add.work()?;
+        save.work()?;
+        let (add_out, _) = add_out.read_buf()?;
+        let (save_out, _) = save_out.read_buf()?;
+        let n = std::cmp::min(add_out.len(), save_out.len());
+        assert!(n > 10_000, "too little output: {n}");
+        crate::tests::assert_almost_equal_complex(&add_out.slice()[..n], &save_out.slice()[..n]);
+        Ok(())
+    }
+
+    // Return where the center of a filtered impulse ends up, relative to
+    // the input.
+    fn impulse_delay<E: Engine>(engine: E) -> Result<(Float, Float)> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = FftFilter::new_engine(r, engine);
+        let mut input = vec![Complex::default(); 4096];
+        input[1000] = Complex::new(1.0, 0.0);
+        let mut o = w.write_buf()?;
+        o.fill_from_slice(&input);
+        o.produce(input.len(), &[]);
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        let nonzero: Vec<usize> = (0..res.len())
+            .filter(|&i| res.slice()[i].norm() > 1e-4)
+            .collect();
+        let center = (nonzero[0] + nonzero[nonzero.len() - 1]) as Float / 2.0;
+        Ok((center - 1000.0, b.delay_samples()))
+    }
+
+    #[test]
+    fn delay() -> Result<()> {
+        for ntaps in [5, 24, 101] {
+            let taps = vec![Complex::new(1.0 / ntaps as Float, 0.0); ntaps];
+            let want = (ntaps - 1) as Float / 2.0;
+            assert_eq!(impulse_delay(RustFftEngine::new(&taps))?, (want, want));
+            assert_eq!(impulse_delay(OverlapSaveEngine::new(&taps))?, (want, want));
+        }
+        let (_w, r) = crate::stream::new_stream();
+        let (mut b, _) = FftFilterFloat::new(r, &[1.0; 7]);
+        assert_eq!(b.delay_samples(), 3.0);
+        b.set_taps(&[1.0; 2]);
+        assert_eq!(b.delay_samples(), 0.5);
+        Ok(())
+    }
+
+    #[test]
+    fn small_primes() -> Result<()> {
+        assert_eq!(calc_fft_size(1025, FftSize::PowerOfTwo), 4096);
+        assert_eq!(calc_fft_size(1025, FftSize::SmallPrimes), 2160);
+        assert_eq!(calc_fft_size(1024, FftSize::SmallPrimes), 2048);
+
+        let taps = low_pass_complex(8_000.0, 1000.0, 100.0, &WindowType::Custom(vec![1.0; 1025]));
+        assert_eq!(taps.len(), 1025);
+        let input: Vec<_> = (0..20_000)
+            .map(|i| {
+                let i = i as Float;
+                Complex::new((i * 0.1).sin() + (i * 2.5).cos(), (i * 0.37).sin())
+            })
+            .collect();
+        let (mut src, prev) = crate::blocks::VectorSource::new(input);
+        let (mut tee, a, b) = crate::blocks::Tee::new(prev);
+        let (mut pow2, pow2_out) = FftFilter::new(a, &taps);
+        let (mut small, small_out) = FftFilter::builder(&taps)
+            .fft_size(FftSize::SmallPrimes)
+            .build(b);
+        src.work()?;
+        tee.work()?;
+        pow2.work()?;
+        small.work()?;
+        let (pow2_out, _) = pow2_out.read_buf()?;
+        let (small_out, _) = small_out.read_buf()?;
+        let n = std::cmp::min(pow2_out.len(), small_out.len());
+        assert!(n > 10_000, "too little output: {n}");
+        crate::tests::assert_almost_equal_complex(&pow2_out.slice()[..n], &small_out.slice()[..n]);
+        Ok(())
+    }
+
+    fn switch_taps<E: Engine>(engine: E, taps: &[Complex]) -> Result<()> {
+        let samp_rate = 8_000.0;
+        let input: Vec<_> = (0..40_000)
+            .map(|i| {
+                let t = i as Float * 2.0 * std::f64::consts::PI as Float * 3000.0 / samp_rate;
+                Complex::new(t.cos(), t.sin())
+            })
+            .collect();
+        let (w, r) = crate::stream::new_stream();
+        let (mut fft, out) = FftFilter::new_engine(r, engine);
+        let write = |data: &[Complex]| -> Result<()> {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(data);
+            wb.produce(data.len(), &[]);
+            Ok(())
+        };
+
+        // Low pass filter suppresses the signal.
+        write(&input[..20_000])?;
+        fft.work()?;
+        let (res, _) = out.read_buf()?;
+        let before = res.len();
+        assert!(before > 10_000, "too little output: {before}");
+        let m = res
+            .iter()
+            .skip(taps.len())
+            .map(|x| x.norm())
+            .max_by(|a, b| a.total_cmp(b))
+            .unwrap();
+        assert!(
+            m < 0.01,
+            "Signal insufficiently suppressed. Got magnitude {m}"
+        );
+        drop(res);
+
+        // All pass filter lets it through unchanged, once the old filter has
+        // rung out.
+        let mut allpass = vec![Complex::default(); 64];
+        allpass[0] = Complex::new(1.0, 0.0);
+        fft.handle().set_taps(&allpass);
+        write(&input[20_000..])?;
+        fft.work()?;
+        let (res, _) = out.read_buf()?;
+        assert!(res.len() > 30_000, "too little output: {}", res.len());
+        let start = before + taps.len();
+        let diff = res.slice()[start..]
+            .iter()
+            .zip(&input[start..])
+            .map(|(a, b)| (a - b).norm())
+            .max_by(|a, b| a.total_cmp(b))
+            .unwrap();
+        assert!(diff < 0.0001, "all pass output differs by {diff}");
+        Ok(())
+    }
+
+    #[test]
+    fn set_taps() -> Result<()> {
+        let taps = low_pass_complex(8_000.0, 1000.0, 100.0, &WindowType::Hamming);
+        switch_taps(RustFftEngine::new(&taps), &taps)?;
+        switch_taps(OverlapSaveEngine::new(&taps), &taps)
+    }
+
     #[allow(dead_code)]
     fn write_vec(filename: &str, v: &[Complex]) -> Result<()> {
         use std::io::BufWriter;
@@ -0,0 +1,125 @@
+/*! FFT a stream, in fixed size chunks.
+
+Useful for e.g. spectrum displays. See [crate::fft] for mapping bins to
+frequencies.
+*/
+use crate::block::{Block, BlockRet};
+use crate::fft::{FftBackend, RustFft};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Error};
+
+/// FFT stream block.
+///
+/// Every `size` input samples are transformed into `size` output bins, in
+/// the order the backend outputs them. For the default [RustFft] backend
+/// that's DC first, and the output is not normalized.
+///
+/// Tags keep their position within the chunk.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct FftStream<B: FftBackend = RustFft> {
+    backend: B,
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+}
+
+impl FftStream {
+    /// Create new FftStream, using rustfft.
+    pub fn new(src: ReadStream<Complex>, size: usize) -> (Self, ReadStream<Complex>) {
+        Self::new_backend(src, RustFft::new(size))
+    }
+}
+
+impl<B: FftBackend> FftStream<B> {
+    /// Create new FftStream, using the given FFT backend.
+    pub fn new_backend(src: ReadStream<Complex>, backend: B) -> (Self, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (Self { backend, src, dst }, dr)
+    }
+}
+
+impl<B: FftBackend> Block for FftStream<B> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let n = self.backend.size();
+        let mut produced = false;
+        loop {
+            let (input, tags) = self.src.read_buf()?;
+            let mut o = self.dst.write_buf()?;
+            if input.len() < n || o.len() < n {
+                break;
+            }
+            let out = &mut o.slice()[..n];
+            out.copy_from_slice(&input.slice()[..n]);
+            self.backend.process(out);
+            input.consume(n);
+            let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+            o.produce(n, &tags);
+            produced = true;
+        }
+        Ok(if produced {
+            BlockRet::Ok
+        } else {
+            BlockRet::Noop
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::run_block;
+    use crate::Float;
+
+    #[test]
+    fn rustfft() -> anyhow::Result<()> {
+        let size = 16;
+        // A tone in bin 3, then an impulse.
+        let tone = (0..size).map(|n| {
+            Complex::from_polar(
+                1.0,
+                2.0 * std::f32::consts::PI * 3.0 * n as Float / size as Float,
+            )
+        });
+        let mut impulse = vec![Complex::default(); size];
+        impulse[0] = Complex::new(1.0, 0.0);
+        let input: Vec<Complex> = tone.chain(impulse).collect();
+
+        let (_, got, _) = run_block(input, |prev| Ok(FftStream::new(prev, size)))?;
+        assert_eq!(got.len(), 2 * size);
+        for (bin, v) in got[..size].iter().enumerate() {
+            let want = if bin == 3 { size as Float } else { 0.0 };
+            assert!((v.norm() - want).abs() < 1e-3, "bin {bin}: {v}");
+        }
+        crate::tests::assert_almost_equal_complex(
+            &got[size..],
+            &vec![Complex::new(1.0, 0.0); size],
+        );
+        Ok(())
+    }
+
+    // Not an FFT at all, but shows that other backends can be used.
+    struct Reverse(usize);
+
+    impl FftBackend for Reverse {
+        fn size(&self) -> usize {
+            self.0
+        }
+        fn process(&mut self, buf: &mut [Complex]) {
+            buf.reverse();
+        }
+    }
+
+    #[test]
+    fn custom_backend() -> anyhow::Result<()> {
+        let input: Vec<Complex> = (0..8).map(|n| Complex::new(n as Float, 0.0)).collect();
+        let (_, got, _) = run_block(input, |prev| Ok(FftStream::new_backend(prev, Reverse(4))))?;
+        let want: Vec<Complex> = [3, 2, 1, 0, 7, 6, 5, 4]
+            .iter()
+            .map(|&n| Complex::new(n as Float, 0.0))
+            .collect();
+        assert_eq!(got, want);
+        Ok(())
+    }
+}
@@ -23,7 +23,7 @@ pub enum Mode {
 
 /// Send stream to raw file.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
+#[rustradio(crate, flush)]
 pub struct FileSink<T: Copy> {
     f: BufWriter<std::fs::File>,
     #[rustradio(in)]
@@ -49,7 +49,11 @@ impl<T: Copy> FileSink<T> {
         Ok(Self { f, src })
     }
 
-    /// Flush the write buffer.
+    /// Flush the write buffer, so that the file is complete.
+    ///
+    /// Called automatically on EOF, but can be called explicitly, to make
+    /// sure the file can be read without relying on the block being
+    /// dropped.
     pub fn flush(&mut self) -> Result<()> {
         Ok(self.f.flush()?)
     }
@@ -78,7 +82,7 @@ where
 
 /// Send stream to raw file.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
+#[rustradio(crate, flush)]
 pub struct NoCopyFileSink<T> {
     f: BufWriter<std::fs::File>,
     #[rustradio(in)]
@@ -1,4 +1,7 @@
 //! Read stream from raw file.
+//!
+//! With the `gzip` feature, [FileSourceBuilder] can also read gzip
+//! compressed files.
 use std::io::BufReader;
 use std::io::Read;
 
@@ -6,16 +9,94 @@ use anyhow::Result;
 use log::{debug, trace, warn};
 
 use crate::block::{Block, BlockRet};
-use crate::stream::{ReadStream, WriteStream};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream, TAG_REPEAT};
 use crate::{Error, Sample};
 
+/// FileSource builder.
+pub struct FileSourceBuilder {
+    filename: String,
+    repeat: bool,
+    #[cfg(feature = "gzip")]
+    gzip: bool,
+}
+
+impl FileSourceBuilder {
+    /// New FileSource builder.
+    pub fn new(filename: &str) -> Self {
+        Self {
+            filename: filename.to_string(),
+            repeat: false,
+            #[cfg(feature = "gzip")]
+            gzip: false,
+        }
+    }
+
+    /// Set whether to repeat the file.
+    ///
+    /// When repeating, the first sample of each pass through the file is
+    /// tagged with [TAG_REPEAT].
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Set whether the file is gzip compressed.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self, gzip: bool) -> Self {
+        self.gzip = gzip;
+        self
+    }
+
+    /// Build the FileSource.
+    ///
+    /// Compressed files can't be repeated, so that's an error.
+    pub fn build<T: Default + Copy>(self) -> Result<(FileSource<T>, ReadStream<T>)> {
+        #[cfg(feature = "gzip")]
+        if self.gzip && self.repeat {
+            return Err(Error::new(&format!(
+                "FileSource: can't repeat gzip compressed file {}",
+                self.filename
+            ))
+            .into());
+        }
+        let file = std::fs::File::open(&self.filename)?;
+        debug!("Opening source {}", self.filename);
+        #[cfg(feature = "gzip")]
+        let f: Box<dyn Read + Send> = if self.gzip {
+            Box::new(BufReader::new(flate2::read::GzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        #[cfg(not(feature = "gzip"))]
+        let f: Box<dyn Read + Send> = Box::new(BufReader::new(file));
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            FileSource {
+                filename: self.filename,
+                f,
+                repeat: self.repeat,
+                repeat_count: 0,
+                pass_pos: 0,
+                buf: Vec::new(),
+                dst,
+            },
+            dr,
+        ))
+    }
+}
+
 /// Read stream from raw file.
+///
+/// Use [FileSourceBuilder] to read gzip compressed files.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct FileSource<T: Copy> {
     filename: String,
-    f: BufReader<std::fs::File>,
+    f: Box<dyn Read + Send>,
     repeat: bool,
+    repeat_count: u64,
+    // Samples produced in the current pass through the file.
+    pass_pos: u64,
     buf: Vec<u8>,
     #[rustradio(out)]
     dst: WriteStream<T>,
@@ -24,19 +105,40 @@ pub struct FileSource<T: Copy> {
 impl<T: Default + Copy> FileSource<T> {
     /// Create new FileSource block.
     pub fn new(filename: &str, repeat: bool) -> Result<(Self, ReadStream<T>)> {
-        let f = BufReader::new(std::fs::File::open(filename)?);
-        debug!("Opening source {filename}");
-        let (dst, dr) = crate::stream::new_stream();
-        Ok((
-            Self {
-                filename: filename.to_string(),
-                f,
-                repeat,
-                buf: Vec::new(),
-                dst,
-            },
-            dr,
-        ))
+        FileSourceBuilder::new(filename).repeat(repeat).build()
+    }
+}
+
+impl<T: Copy> FileSource<T> {
+    // Return tags for the next n samples, and count them.
+    fn pass_tags(&mut self, n: usize) -> Vec<Tag> {
+        let tags = if self.repeat && self.pass_pos == 0 {
+            vec![Tag::new(
+                0,
+                TAG_REPEAT.to_string(),
+                TagValue::U64(self.repeat_count),
+            )]
+        } else {
+            vec![]
+        };
+        self.pass_pos += n as u64;
+        tags
+    }
+
+    // Start the next pass through the file.
+    fn rewind(&mut self) -> Result<()> {
+        debug!("Repeating {}", self.filename);
+        self.f = Box::new(BufReader::new(std::fs::File::open(&self.filename)?));
+        if !self.buf.is_empty() {
+            warn!(
+                "FileSource: {} ends in a partial sample, dropping it",
+                self.filename
+            );
+            self.buf.clear();
+        }
+        self.repeat_count += 1;
+        self.pass_pos = 0;
+        Ok(())
     }
 }
 
@@ -63,8 +165,13 @@ where
                 .read(&mut buffer[..])
                 .map_err(|e| -> anyhow::Error { e.into() })?;
             if n == 0 {
+                // Don't spin on files too short to hold even one sample.
+                if self.repeat && self.pass_pos > 0 {
+                    self.rewind()?;
+                    return Ok(BlockRet::Ok);
+                }
                 warn!("EOF on {}. Repeat: {}", self.filename, self.repeat);
-                return Ok(BlockRet::EOF);
+                return Ok(BlockRet::Finished);
             }
             if self.buf.is_empty() && (n % sample_size) == 0 {
                 // Fast path when reading only whole samples.
@@ -74,7 +181,8 @@ where
                         .map(|d| T::parse(d).unwrap()),
                 );
                 trace!("FileSource: Produced {} in fast path", n / sample_size);
-                o.produce(n / sample_size, &[]);
+                let tags = self.pass_tags(n / sample_size);
+                o.produce(n / sample_size, &tags);
                 return Ok(BlockRet::Ok);
             }
             self.buf.extend(&buffer[..n]);
@@ -93,9 +201,10 @@ where
             .collect::<Result<Vec<_>>>()?;
         self.buf.drain(0..(have * sample_size));
         let n = v.len();
-        o.fill_from_iter(v);
+        o.fill_from_slice(&v);
         trace!("FileSource: Produced {}", n);
-        o.produce(n, &[]);
+        let tags = self.pass_tags(n);
+        o.produce(n, &tags);
         Ok(BlockRet::Ok)
     }
 }
@@ -145,4 +254,80 @@ mod tests {
         assert_eq!(res.slice(), correct);
         Ok(())
     }
+
+    #[test]
+    fn source_repeat() -> Result<()> {
+        let tmpd = tempfile::tempdir()?;
+        let tmpfn = tmpd.path().join("delme.bin").display().to_string();
+        std::fs::write(&tmpfn, [1u8, 2, 3])?;
+
+        let (mut src, out) = FileSource::<u8>::new(&tmpfn, true)?;
+        let mut got: Vec<u8> = Vec::new();
+        let mut tags = Vec::new();
+        while got.len() < 9 {
+            assert_eq!(src.work()?, BlockRet::Ok);
+            let (res, t) = out.read_buf()?;
+            for t in t {
+                assert_eq!(t.key(), TAG_REPEAT);
+                tags.push((got.len() + t.pos(), t.val().clone()));
+            }
+            got.extend(res.slice());
+            let n = res.len();
+            res.consume(n);
+        }
+        assert_eq!(got, [1, 2, 3, 1, 2, 3, 1, 2, 3]);
+        assert_eq!(
+            tags,
+            [
+                (0, TagValue::U64(0)),
+                (3, TagValue::U64(1)),
+                (6, TagValue::U64(2)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn source_gzip() -> Result<()> {
+        use std::io::Write;
+        let tmpd = tempfile::tempdir()?;
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let raw = tmpd.path().join("delme.bin").display().to_string();
+        std::fs::write(&raw, &data)?;
+        let gz = tmpd.path().join("delme.bin.gz").display().to_string();
+        let mut enc = flate2::write::GzEncoder::new(
+            std::fs::File::create(&gz)?,
+            flate2::Compression::default(),
+        );
+        enc.write_all(&data)?;
+        enc.finish()?;
+
+        let read_all = |mut src: FileSource<u8>, out: ReadStream<u8>| -> Result<Vec<u8>> {
+            let mut got = Vec::new();
+            while src.work()? != BlockRet::Finished {
+                let (res, _) = out.read_buf()?;
+                got.extend(res.slice());
+                let n = res.len();
+                res.consume(n);
+            }
+            Ok(got)
+        };
+        let (src, out) = FileSource::<u8>::new(&raw, false)?;
+        assert_eq!(read_all(src, out)?, data);
+        let (src, out) = FileSourceBuilder::new(&gz).gzip(true).build()?;
+        assert_eq!(read_all(src, out)?, data);
+
+        // Not decompressed unless asked to, whatever the name.
+        let (src, out) = FileSource::<u8>::new(&gz, false)?;
+        assert_eq!(read_all(src, out)?, std::fs::read(&gz)?);
+        assert!(FileSource::<u8>::new(&gz, true).is_ok());
+
+        assert!(FileSourceBuilder::new(&gz)
+            .gzip(true)
+            .repeat(true)
+            .build::<u8>()
+            .is_err());
+        Ok(())
+    }
 }
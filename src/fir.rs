@@ -111,6 +111,38 @@ where
         let n = input.len() - self.taps.len() + 1;
         (0..n).map(|i| self.filter(&input[i..])).collect()
     }
+
+    /// Filter and decimate into `out`, starting at input index
+    /// `start_offset`.
+    ///
+    /// Output sample `n` is the filter applied to the input starting at
+    /// `start_offset + n*deci`. Stops when `out` is full, or there is not
+    /// enough input for another output sample.
+    ///
+    /// Returns the input index where the next output sample starts. The
+    /// number of samples written is `(next - start_offset) / deci`.
+    ///
+    /// To continue across buffers, consume any number of input samples up
+    /// to the returned index, and pass the returned index minus the number
+    /// consumed as `start_offset` next time.
+    pub fn filter_n_inplace_offset(
+        &self,
+        input: &[T],
+        deci: usize,
+        start_offset: usize,
+        out: &mut [T],
+    ) -> usize {
+        assert!(deci > 0, "decimation must be at least 1");
+        let mut pos = start_offset;
+        for o in out.iter_mut() {
+            if pos + self.taps.len() > input.len() {
+                break;
+            }
+            *o = self.filter(&input[pos..]);
+            pos += deci;
+        }
+        pos
+    }
 }
 
 /// Finite impulse response filter block.
@@ -142,6 +174,19 @@ where
             dr,
         )
     }
+
+    /// Return the delay through the filter, in samples, relative to the
+    /// input stream.
+    ///
+    /// Unlike [FftFilter][crate::fft_filter::FftFilter], this block doesn't
+    /// output anything until it has `ntaps` input samples, so the output
+    /// is *ahead* of the input by half the filter length, and this is
+    /// negative: `-(ntaps-1)/2`, for a linear phase filter. To keep a
+    /// parallel path aligned, [Skip][crate::blocks::Skip] this many samples
+    /// on it.
+    pub fn delay_samples(&self) -> Float {
+        -(self.ntaps.saturating_sub(1) as Float) / 2.0
+    }
 }
 
 impl<T> Block for FIRFilter<T>
@@ -159,7 +204,7 @@ where
         assert!(v.len() <= n);
         let n = v.len();
         input.consume(n);
-        out.fill_from_iter(v);
+        out.fill_from_slice(&v);
         out.produce(n, &tags);
         Ok(BlockRet::Ok)
     }
@@ -169,7 +214,7 @@ where
 ///
 /// TODO: this is untested.
 pub fn multiband(bands: &[(Float, Float)], taps: usize, window: &Window) -> Option<Vec<Complex>> {
-    if taps != window.0.len() {
+    if taps != window.len() {
         return None;
     }
     use rustfft::FftPlanner;
@@ -194,7 +239,7 @@ pub fn multiband(bands: &[(Float, Float)], taps: usize, window: &Window) -> Opti
         ideal
             .into_iter()
             .enumerate()
-            .map(|(n, v)| v * window.0[n] / Complex::new(scale, 0.0))
+            .map(|(n, v)| v * window.coeffs()[n] / Complex::new(scale, 0.0))
             .collect(),
     )
 }
@@ -213,6 +258,9 @@ pub fn low_pass_complex(
 }
 
 fn compute_ntaps(samp_rate: Float, twidth: Float, window_type: &WindowType) -> usize {
+    if let Some(n) = window_type.fixed_len() {
+        return n;
+    }
     let a = window_type.max_attenuation();
     let t = (a * samp_rate / (22.0 * twidth)) as usize;
     if (t & 1) == 0 {
@@ -224,6 +272,9 @@ fn compute_ntaps(samp_rate: Float, twidth: Float, window_type: &WindowType) -> u
 
 /// Create taps for a low pass filter.
 ///
+/// If the window type has a fixed length (e.g. `WindowType::Custom`), then
+/// that's the number of taps, and `twidth` is ignored.
+///
 /// TODO: this could be faster if we supported filtering a Complex by a Float.
 /// A low pass filter doesn't actually need complex taps.
 pub fn low_pass(
@@ -238,7 +289,7 @@ pub fn low_pass(
     let m = (ntaps - 1) / 2;
     let fwt0 = 2.0 * pi * cutoff / samp_rate;
     let taps: Vec<_> = window
-        .0
+        .coeffs()
         .iter()
         .enumerate()
         .map(|(nm, win)| {
@@ -264,15 +315,16 @@ pub fn low_pass(
 
 /// Generate hilbert transformer filter.
 pub fn hilbert(window: &Window) -> Vec<Float> {
-    let ntaps = window.0.len();
+    let ntaps = window.len();
+    let window = window.coeffs();
     let mid = (ntaps - 1) / 2;
     let mut gain = 0.0;
     let mut taps = vec![0.0; ntaps];
     for i in 1..=mid {
         if i & 1 == 1 {
             let x = 1.0 / (i as Float);
-            taps[mid + i] = x * window.0[mid + i];
-            taps[mid - i] = -x * window.0[mid - i];
+            taps[mid + i] = x * window[mid + i];
+            taps[mid - i] = -x * window[mid - i];
             gain = taps[mid + i] - gain;
         } else {
             taps[mid + i] = 0.0;
@@ -286,7 +338,7 @@ pub fn hilbert(window: &Window) -> Vec<Float> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::assert_almost_equal_complex;
+    use crate::tests::{assert_almost_equal_complex, assert_almost_equal_float};
 
     #[test]
     fn test_complex() {
@@ -315,6 +367,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filter_n_inplace_offset() {
+        let input: Vec<Float> = (0..1000).map(|i| ((i * 7919) % 113) as Float).collect();
+        let taps: Vec<Float> = (1..=7).map(|t| t as Float * 0.1).collect();
+        let deci = 3;
+        let filter = FIR::new(&taps);
+
+        let mut want = vec![0.0; 1000];
+        let next = filter.filter_n_inplace_offset(&input, deci, 0, &mut want);
+        want.truncate(next / deci);
+        assert_eq!(want.len(), (input.len() - taps.len()) / deci + 1);
+
+        // Same input, in chunks of varying size, with a small output buffer.
+        let mut got: Vec<Float> = Vec::new();
+        let (mut base, mut offset) = (0, 0);
+        for chunk in [10, 7, 50, 8, 100].into_iter().cycle() {
+            let input = &input[base..std::cmp::min(base + chunk, input.len())];
+            let mut out = [0.0; 5];
+            let next = filter.filter_n_inplace_offset(input, deci, offset, &mut out);
+            got.extend(&out[..(next - offset) / deci]);
+            // Consume what the next output sample doesn't need.
+            let consumed = std::cmp::min(next, input.len().saturating_sub(taps.len() - 1));
+            base += consumed;
+            offset = next - consumed;
+            if input.len() < taps.len() {
+                break;
+            }
+        }
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn custom_window_low_pass() {
+        let (samp_rate, cutoff, twidth) = (10000.0, 1000.0, 1000.0);
+        let hamming = low_pass(samp_rate, cutoff, twidth, &WindowType::Hamming);
+        let ntaps = hamming.len();
+        let window = WindowType::Hamming.make_window(ntaps).into_coeffs();
+
+        // Custom window with the same coefficients as a built in one.
+        let custom = low_pass(
+            samp_rate,
+            cutoff,
+            twidth,
+            &WindowType::Custom(window.clone()),
+        );
+        assert_almost_equal_float(&custom, &hamming);
+
+        // Rectangular window, with the window applied by hand after.
+        let rect = low_pass(
+            samp_rate,
+            cutoff,
+            twidth,
+            &WindowType::Custom(vec![1.0; ntaps]),
+        );
+        let by_hand: Vec<_> = rect.iter().zip(&window).map(|(t, w)| t * w).collect();
+        let sum: Float = by_hand.iter().sum();
+        let by_hand: Vec<_> = by_hand.into_iter().map(|t| t / sum).collect();
+        assert_almost_equal_float(&by_hand, &hamming);
+    }
+
+    #[test]
+    fn delay() -> crate::Result<()> {
+        for ntaps in [5, 25, 101] {
+            let taps = vec![1.0 / ntaps as Float; ntaps];
+            let (w, r) = crate::stream::new_stream();
+            let (mut b, out) = FIRFilter::new(r, &taps);
+            assert_eq!(b.delay_samples(), -((ntaps - 1) as Float) / 2.0);
+            // Impulse at 200 comes out centered at 200 + delay.
+            let mut input = vec![0.0; 400];
+            input[200] = 1.0;
+            let mut o = w.write_buf()?;
+            o.fill_from_slice(&input);
+            o.produce(input.len(), &[]);
+            b.work()?;
+            let (res, _) = out.read_buf()?;
+            let nonzero: Vec<usize> = (0..res.len()).filter(|&i| res.slice()[i] > 0.0).collect();
+            let center = (nonzero[0] + nonzero[nonzero.len() - 1]) as Float / 2.0;
+            assert_eq!(center, 200.0 + b.delay_samples());
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    fn custom_window_wrong_len() {
+        let _ = WindowType::Custom(vec![1.0; 3]).make_window(4);
+    }
+
     #[test]
     fn test_filter_generator() {
         let taps = low_pass_complex(10000.0, 1000.0, 1000.0, &WindowType::Hamming);
@@ -0,0 +1,165 @@
+/*! Delay a stream by a fraction of a sample.
+
+E.g. to align two captures known to be offset by less than a sample.
+Unlike a resampler, the sample rate is unchanged.
+
+The interpolation is cubic Lagrange, in Farrow form, so the offset can be
+changed while running without recalculating any taps.
+
+<https://en.wikipedia.org/wiki/Lagrange_polynomial>
+*/
+use crate::block::{Block, BlockRet};
+use crate::param::Param;
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Fixed part of the delay, in samples.
+///
+/// The interpolator needs input on both sides of the output position, so
+/// the total delay is this plus the offset.
+pub const BASE_DELAY: usize = 2;
+
+/// Handle for changing the offset of a running [FractionalShift].
+#[derive(Clone)]
+pub struct FractionalShiftHandle {
+    offset: Param,
+}
+
+impl FractionalShiftHandle {
+    /// Set new offset, in samples. Clamped to [-1, 1].
+    pub fn set_offset(&self, offset: f64) {
+        self.offset.set(offset.clamp(-1.0, 1.0));
+    }
+
+    /// Get current offset, in samples.
+    pub fn offset(&self) -> f64 {
+        self.offset.get()
+    }
+}
+
+/// Fractional shift block.
+///
+/// Output is the input delayed by [BASE_DELAY] plus `offset` samples,
+/// where the offset is from -1 to 1.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct FractionalShift {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    handle: FractionalShiftHandle,
+    // The three most recent input samples, oldest first.
+    #[rustradio(default)]
+    history: [Complex; 3],
+}
+
+impl FractionalShift {
+    /// Create new FractionalShift block.
+    ///
+    /// `offset` is in samples, and is clamped to [-1, 1].
+    pub fn new(src: ReadStream<Complex>, offset: f64) -> (Self, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        let handle = FractionalShiftHandle {
+            offset: Param::new(offset.clamp(-1.0, 1.0)),
+        };
+        (
+            Self {
+                src,
+                dst,
+                handle,
+                history: Default::default(),
+            },
+            dr,
+        )
+    }
+
+    /// Set new offset, in samples. Clamped to [-1, 1].
+    pub fn set_offset(&self, offset: f64) {
+        self.handle.set_offset(offset);
+    }
+
+    /// Return a handle for changing the offset.
+    pub fn handle(&self) -> FractionalShiftHandle {
+        self.handle.clone()
+    }
+
+    /// Return the total delay through the block, in samples.
+    pub fn delay_samples(&self) -> Float {
+        (BASE_DELAY as f64 + self.handle.offset()) as Float
+    }
+}
+
+impl Block for FractionalShift {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        // Position relative to the sample BASE_DELAY back.
+        let u = -self.handle.offset() as Float;
+        let [mut p0, mut p1, mut p2] = self.history;
+        o.fill_from_iter(i.iter().take(n).map(|&p3| {
+            let c0 = p1;
+            let c1 = p0 * (-1.0 / 3.0) - p1 * 0.5 + p2 - p3 * (1.0 / 6.0);
+            let c2 = (p0 + p2) * 0.5 - p1;
+            let c3 = (p3 - p0) * (1.0 / 6.0) + (p1 - p2) * 0.5;
+            (p0, p1, p2) = (p1, p2, p3);
+            ((c3 * u + c2) * u + c1) * u + c0
+        }));
+        self.history = [p0, p1, p2];
+        let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tone_delay() -> crate::Result<()> {
+        // Radians per sample.
+        let w = 0.1;
+        let tone = |n: usize| Complex::from_polar(1.0, w * n as Float);
+        let (tx, r) = crate::stream::new_stream();
+        let (mut b, out) = FractionalShift::new(r, 0.3);
+        let h = b.handle();
+        let mut n = 0;
+        for offset in [0.3, -0.7, 1.0] {
+            h.set_offset(offset);
+            assert_eq!(b.delay_samples(), (2.0 + offset) as Float);
+            let mut o = tx.write_buf()?;
+            o.fill_from_iter((n..n + 100).map(tone));
+            o.produce(100, &[]);
+            b.work()?;
+
+            // A delay of D samples lags the tone by w*D radians.
+            let (res, _) = out.read_buf()?;
+            for (k, y) in res.iter().enumerate().skip(10) {
+                let lag = -(y * tone(n + k).conj()).arg();
+                let delay = lag / w;
+                assert!(
+                    (delay - (2.0 + offset) as Float).abs() < 0.001,
+                    "offset {offset}: delay {delay} at sample {k}"
+                );
+                assert!((y.norm() - 1.0).abs() < 0.001, "magnitude {}", y.norm());
+            }
+            res.consume(100);
+            n += 100;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn partial_output_tags() -> crate::Result<()> {
+        crate::tests::check_partial_output_tags(|prev| Ok(FractionalShift::new(prev, 0.25)))
+    }
+}
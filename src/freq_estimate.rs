@@ -0,0 +1,157 @@
+/*! Estimate dominant frequency levels.
+
+Bins the output of e.g. [QuadratureDemod][crate::quadrature_demod::QuadratureDemod]
+into a histogram, to find the dominant instantaneous frequencies. For FSK
+these are the tones, giving the shift.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::ReadStream;
+use crate::{Error, Float};
+
+/// Histogram based frequency level estimator.
+///
+/// Values in `[min, max)` are counted in `bins` equal width bins. Values
+/// outside the range are ignored. Every `window` samples the histogram is
+/// saved, for [FreqEstimate::peaks], and reset.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct FreqEstimate {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    min: Float,
+    max: Float,
+    bins: usize,
+    window: usize,
+    histogram: Vec<usize>,
+    count: usize,
+    // Histogram of the last complete window.
+    last: Vec<usize>,
+}
+
+impl FreqEstimate {
+    /// Create new FreqEstimate block.
+    pub fn new(
+        src: ReadStream<Float>,
+        min: Float,
+        max: Float,
+        bins: usize,
+        window: usize,
+    ) -> Result<Self, Error> {
+        if bins == 0 || window == 0 || min >= max {
+            return Err(Error::new(&format!(
+                "FreqEstimate: bad parameters min={min} max={max} bins={bins} window={window}"
+            )));
+        }
+        Ok(Self {
+            src,
+            min,
+            max,
+            bins,
+            window,
+            histogram: Vec::new(),
+            count: 0,
+            last: Vec::new(),
+        })
+    }
+
+    /// Return width of a bin.
+    pub fn bin_width(&self) -> Float {
+        (self.max - self.min) / self.bins as Float
+    }
+
+    /// Return the histogram of the last complete window.
+    ///
+    /// Empty if no window has completed yet.
+    pub fn last_histogram(&self) -> &[usize] {
+        &self.last
+    }
+
+    /// Return up to `n` peaks of the last complete window, highest first.
+    ///
+    /// Peaks are local maxima of the histogram, returned as the bin center
+    /// value and the count.
+    pub fn peaks(&self, n: usize) -> Vec<(Float, usize)> {
+        let h = &self.last;
+        let mut peaks: Vec<_> = (0..h.len())
+            .filter(|&i| {
+                h[i] > 0 && (i == 0 || h[i] >= h[i - 1]) && (i + 1 == h.len() || h[i] > h[i + 1])
+            })
+            .map(|i| (self.min + (i as Float + 0.5) * self.bin_width(), h[i]))
+            .collect();
+        peaks.sort_by_key(|p| std::cmp::Reverse(p.1));
+        peaks.truncate(n);
+        peaks
+    }
+}
+
+impl Block for FreqEstimate {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, _tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let width = self.bin_width();
+        for &v in i.iter() {
+            if self.histogram.is_empty() {
+                self.histogram.resize(self.bins, 0);
+            }
+            if v >= self.min && v < self.max {
+                let bin = ((v - self.min) / width) as usize;
+                self.histogram[bin.min(self.bins - 1)] += 1;
+            }
+            self.count += 1;
+            if self.count == self.window {
+                self.last = std::mem::take(&mut self.histogram);
+                self.count = 0;
+            }
+        }
+        let n = i.len();
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    #[test]
+    fn two_level() -> Result<()> {
+        // Noisy FSK, with levels -0.3 and 0.5.
+        let input: Vec<Float> = (0..10000)
+            .map(|i| {
+                let level = if (i / 7) % 3 == 0 { -0.3 } else { 0.5 };
+                level + 0.01 * ((i * 37 % 11) as Float - 5.0) / 5.0
+            })
+            .collect();
+        let (mut src, prev) = VectorSource::new(input);
+        let mut b = FreqEstimate::new(prev, -1.0, 1.0, 100, 1000)?;
+        assert!(b.peaks(2).is_empty());
+        src.work()?;
+        b.work()?;
+        assert_eq!(b.last_histogram().iter().sum::<usize>(), 1000);
+        let peaks = b.peaks(2);
+        assert_eq!(peaks.len(), 2);
+        // Twice as much time spent on the higher level.
+        assert!(peaks[0].1 > peaks[1].1);
+        for (got, want) in peaks.iter().zip([0.5, -0.3]) {
+            assert!(
+                (got.0 - want).abs() <= b.bin_width(),
+                "got {got:?}, want {want}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bad_parameters() {
+        for (min, max, bins, window) in [(-1.0, 1.0, 0, 10), (1.0, 1.0, 10, 10), (-1.0, 1.0, 10, 0)]
+        {
+            let (_, prev) = crate::stream::new_stream();
+            assert!(FreqEstimate::new(prev, min, max, bins, window).is_err());
+        }
+    }
+}
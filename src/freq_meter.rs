@@ -0,0 +1,181 @@
+/*! Precise frequency measurement of a tone.
+
+Measures the average frequency of a complex signal, over a long time, by
+adding up the phase change between samples.
+
+Over millions of samples, adding up the phase in 32 bit floats loses a lot
+of precision, since each phase change is tiny compared to the total. So the
+accumulators are `f64` by default, regardless of what
+[Float][crate::Float] is. The samples themselves are still [Complex].
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::ReadStream;
+use crate::{Complex, Error};
+
+/// Type used to accumulate phase in [FreqMeter].
+pub trait Accumulator: Copy + Default + std::ops::AddAssign + Send + 'static {
+    /// Convert from f64.
+    fn from_f64(v: f64) -> Self;
+
+    /// Convert to f64.
+    fn to_f64(self) -> f64;
+}
+
+impl Accumulator for f32 {
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Accumulator for f64 {
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// Frequency meter block.
+///
+/// The measured frequency is the total phase change since the start (or
+/// last [`reset()`][FreqMeter::reset]), divided by the time. For a clean
+/// tone this is much more precise than e.g. an FFT, given enough samples.
+///
+/// Phase changes of more than half a turn per sample can't be told apart
+/// from negative ones, so the frequency must be within `±samp_rate/2`.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct FreqMeter<A: Accumulator = f64> {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    samp_rate: f64,
+    last: Option<Complex>,
+    // Total phase change, in radians.
+    phase: A,
+    steps: u64,
+}
+
+impl FreqMeter {
+    /// Create new FreqMeter block, accumulating in f64.
+    pub fn new(src: ReadStream<Complex>, samp_rate: f64) -> Self {
+        Self::with_accumulator(src, samp_rate)
+    }
+}
+
+impl<A: Accumulator> FreqMeter<A> {
+    /// Create new FreqMeter block, with a custom accumulator type.
+    pub fn with_accumulator(src: ReadStream<Complex>, samp_rate: f64) -> Self {
+        Self {
+            src,
+            samp_rate,
+            last: None,
+            phase: A::default(),
+            steps: 0,
+        }
+    }
+
+    /// Return the average frequency so far, in Hz.
+    ///
+    /// Returns `None` until at least two samples have been seen.
+    pub fn frequency(&self) -> Option<f64> {
+        if self.steps == 0 {
+            return None;
+        }
+        let turns = self.phase.to_f64() / (2.0 * std::f64::consts::PI);
+        Some(turns * self.samp_rate / self.steps as f64)
+    }
+
+    /// Return the number of samples measured.
+    pub fn samples(&self) -> u64 {
+        self.steps + u64::from(self.last.is_some())
+    }
+
+    /// Restart the measurement.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.phase = A::default();
+        self.steps = 0;
+    }
+}
+
+impl<A: Accumulator> Block for FreqMeter<A> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, _tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let to64 = |s: &Complex| num_complex::Complex::<f64>::new(s.re as f64, s.im as f64);
+        for s in i.iter() {
+            if let Some(last) = &self.last {
+                let d = to64(s) * to64(last).conj();
+                self.phase += A::from_f64(d.im.atan2(d.re));
+                self.steps += 1;
+            }
+            self.last = Some(*s);
+        }
+        let n = i.len();
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::Float;
+
+    fn measure<A: Accumulator>(freq: f64, samp_rate: f64, n: usize) -> crate::Result<f64> {
+        let input: Vec<Complex> = (0..n)
+            .map(|i| {
+                // Phase calculated exactly, modulo a turn.
+                let turns = (freq * i as f64 / samp_rate).fract();
+                let ph = 2.0 * std::f64::consts::PI * turns;
+                Complex::new(ph.cos() as Float, ph.sin() as Float)
+            })
+            .collect();
+        let (mut src, prev) = VectorSource::new(input);
+        let mut b = FreqMeter::<A>::with_accumulator(prev, samp_rate);
+        assert_eq!(b.frequency(), None);
+        while src.work()? != BlockRet::Finished {
+            b.work()?;
+        }
+        b.work()?;
+        assert_eq!(b.samples(), n as u64);
+        Ok(b.frequency().unwrap())
+    }
+
+    #[test]
+    fn precision() -> crate::Result<()> {
+        let freq = 1234.5678;
+        let samp_rate = 48000.0;
+        let n = 2_000_000;
+        let err64 = (measure::<f64>(freq, samp_rate, n)? - freq).abs();
+        let err32 = (measure::<f32>(freq, samp_rate, n)? - freq).abs();
+        assert!(err64 < 1e-3, "f64 error {err64}");
+        assert!(err64 * 10.0 < err32, "f64 error {err64}, f32 error {err32}");
+        Ok(())
+    }
+
+    #[test]
+    fn reset() -> crate::Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![
+            Complex::new(1.0, 0.0),
+            Complex::new(0.0, 1.0),
+            Complex::new(-1.0, 0.0),
+        ]);
+        let mut b = FreqMeter::new(prev, 4.0);
+        src.work()?;
+        b.work()?;
+        // Quarter turn per sample, at 4 samples per second.
+        assert!((b.frequency().unwrap() - 1.0).abs() < 1e-6);
+        b.reset();
+        assert_eq!(b.frequency(), None);
+        assert_eq!(b.samples(), 0);
+        Ok(())
+    }
+}
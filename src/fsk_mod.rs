@@ -0,0 +1,142 @@
+//! FSK modulator.
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// FSK modulator.
+///
+/// Input samples are bits, and only the lowest bit of each is used. A one
+/// is sent as the `mark` frequency, and a zero as the `space` frequency,
+/// each for `samples_per_symbol` output samples. Frequencies are in Hz,
+/// relative to the center, and may be negative.
+///
+/// Phase is kept continuous across symbols, and across calls to `work()`.
+///
+/// Output can be demodulated with
+/// [QuadratureDemod][crate::quadrature_demod::QuadratureDemod], and
+/// sliced with [BinarySlicer][crate::binary_slicer::BinarySlicer], if mark
+/// is above space.
+///
+/// Tags are moved to the first sample of their symbol.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct FskMod {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    sps: usize,
+    // Phase step per sample, in radians.
+    mark: Float,
+    space: Float,
+    phase: Float,
+}
+
+impl FskMod {
+    /// Create new FskMod block.
+    ///
+    /// Panics if `samples_per_symbol` is zero.
+    pub fn new(
+        src: ReadStream<u8>,
+        samples_per_symbol: usize,
+        samp_rate: Float,
+        mark: Float,
+        space: Float,
+    ) -> (Self, ReadStream<Complex>) {
+        assert!(
+            samples_per_symbol > 0,
+            "FskMod: samples_per_symbol must be positive"
+        );
+        let step = |f: Float| 2.0 * std::f64::consts::PI as Float * f / samp_rate;
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                sps: samples_per_symbol,
+                mark: step(mark),
+                space: step(space),
+                phase: 0.0,
+            },
+            dr,
+        )
+    }
+}
+
+impl Block for FskMod {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len() / self.sps);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let pi = std::f64::consts::PI as Float;
+        for (bit, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(self.sps)) {
+            let step = if bit & 1 == 1 { self.mark } else { self.space };
+            for s in out {
+                *s = Complex::from_polar(1.0, self.phase);
+                self.phase += step;
+                // Keep the phase small, so precision is not lost.
+                if self.phase > pi {
+                    self.phase -= 2.0 * pi;
+                } else if self.phase < -pi {
+                    self.phase += 2.0 * pi;
+                }
+            }
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|t| Tag::new(t.pos() * self.sps, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(n);
+        o.produce(n * self.sps, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{BinarySlicer, QuadratureDemod, VectorSource};
+
+    #[test]
+    fn loopback() -> Result<()> {
+        let bits = vec![1u8, 0, 0, 1, 1, 1, 0, 1, 0, 0];
+        let sps = 40;
+        let (mut src, prev) = VectorSource::new(bits.clone());
+        let (mut m, prev) = FskMod::new(prev, sps, 48000.0, 1200.0, -1200.0);
+        let (mut demod, prev) = QuadratureDemod::new(prev, 1.0);
+        let (mut slicer, out) = BinarySlicer::new(prev);
+        src.work()?;
+        m.work()?;
+        demod.work()?;
+        slicer.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.len(), bits.len() * sps);
+        let got: Vec<u8> = res.iter().skip(sps / 2).step_by(sps).copied().collect();
+        assert_eq!(got, bits);
+        Ok(())
+    }
+
+    #[test]
+    fn phase_continuous() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![1u8, 0, 1, 1, 0]);
+        let (mut m, out) = FskMod::new(prev, 7, 10000.0, 1300.0, -700.0);
+        src.work()?;
+        m.work()?;
+        let (res, _) = out.read_buf()?;
+        // Every sample is on the unit circle, and no step between samples
+        // is bigger than the biggest frequency.
+        let max = 2.0 * std::f64::consts::PI as Float * 1300.0 / 10000.0;
+        for w in res.slice().windows(2) {
+            assert!((w[0].norm() - 1.0).abs() < 1e-5);
+            let d = (w[1] * w[0].conj()).arg().abs();
+            assert!(d <= max + 1e-5, "phase jump of {d}");
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,15 @@
 /*! Graphs contain blocks connected by streams, and run them.
  */
+use std::collections::HashSet;
 use std::time::Instant;
 
 use anyhow::Result;
 use log::{info, trace};
 
-use crate::block::{Block, BlockRet};
+use crate::block::{Block, BlockRet, Command};
+use crate::stream::{ReadStream, StreamId, WriteStream};
+
+pub use crate::graph_config::from_config;
 
 /**
 Abstraction over graph executors.
@@ -14,6 +18,19 @@ pub trait GraphRunner {
     /// Add a block to the graph.
     fn add(&mut self, b: Box<dyn Block + Send>);
 
+    /// Check that the blocks are connected.
+    ///
+    /// Every input stream of every block should be written to by another
+    /// block in the graph, and every output stream read by one. Call this
+    /// before `run()`, to catch wiring mistakes that would otherwise make
+    /// the graph silently stall.
+    ///
+    /// Returns an error listing all the dangling ports. The default
+    /// implementation checks nothing.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Run the graph.
     ///
     /// Runs the graph until all the blocks are "done", or until the graph is
@@ -40,6 +57,84 @@ pub trait GraphRunner {
     /// # Ok::<(), anyhow::Error>(())
     /// ```
     fn cancel_token(&self) -> CancellationToken;
+
+    /// Pause the graph.
+    ///
+    /// While paused, no block `work()` functions are called, but streams
+    /// keep any data in them. Sources are simply not polled, so they don't
+    /// overflow.
+    fn pause(&self) {
+        self.cancel_token().pause();
+    }
+
+    /// Resume a paused graph.
+    fn resume(&self) {
+        self.cancel_token().resume();
+    }
+
+    /// Return a progress tracker, for checking which blocks are making
+    /// progress, from another thread.
+    ///
+    /// The default implementation tracks no blocks.
+    fn progress_tracker(&self) -> ProgressTracker {
+        ProgressTracker::new()
+    }
+
+    /// Return block names and time since they last made progress.
+    ///
+    /// A block makes progress when its work function returns `BlockRet::Ok`.
+    /// Blocks that have not made progress since the graph started report
+    /// the time since the graph started.
+    fn last_progress(&self) -> Vec<(String, std::time::Duration)> {
+        self.progress_tracker().last_progress()
+    }
+
+    /// Return a command sender, for sending commands to blocks from
+    /// another thread.
+    ///
+    /// The default implementation knows no blocks, so sending fails.
+    fn command_sender(&self) -> CommandSender {
+        CommandSender::new()
+    }
+
+    /// Send a command to a block.
+    ///
+    /// Blocks are identified by the order they were added, starting at
+    /// zero. The command is delivered to the block's `handle_command()`
+    /// between calls to `work()`.
+    fn send_command(&self, block_id: usize, cmd: Command) -> Result<()> {
+        self.command_sender().send(block_id, cmd)
+    }
+
+    /// Return the total number of samples a block has produced, summed
+    /// over its outputs.
+    ///
+    /// Blocks are identified by the order they were added, starting at
+    /// zero. Returns `None` if there's no such block, or if the runner
+    /// doesn't count samples, which is the default.
+    fn total_produced(&self, _block_id: usize) -> Option<u64> {
+        None
+    }
+
+    /// Return the total number of samples a block has consumed, summed
+    /// over its inputs.
+    ///
+    /// Blocks are identified by the order they were added, starting at
+    /// zero. Returns `None` if there's no such block, or if the runner
+    /// doesn't count samples, which is the default.
+    fn total_consumed(&self, _block_id: usize) -> Option<u64> {
+        None
+    }
+
+    /// Return true if every block has reached EOF.
+    ///
+    /// A graph that was cancelled, or whose `run()` returned because all
+    /// blocks were idle without their inputs ending, is not done.
+    ///
+    /// The default implementation always returns false.
+    fn is_done(&self) -> bool {
+        false
+    }
 }
 
 /**
@@ -66,9 +161,19 @@ g.run()?;
 ```
 */
 pub struct Graph {
-    blocks: Vec<Box<dyn Block>>,
+    // Blocks are dropped, and replaced with None, once done.
+    blocks: Vec<Option<Box<dyn Block>>>,
+    names: Vec<String>,
     cancel_token: CancellationToken,
+    progress: ProgressTracker,
+    commands: CommandSender,
+    command_rx: Vec<std::sync::mpsc::Receiver<Command>>,
     times: Vec<std::time::Duration>,
+    // Sample counts of blocks that have been dropped.
+    produced: Vec<u64>,
+    consumed: Vec<u64>,
+    // Streams written to from outside the graph.
+    external: Vec<StreamId>,
 }
 
 impl Graph {
@@ -76,8 +181,79 @@ impl Graph {
     pub fn new() -> Self {
         Self {
             blocks: Vec::new(),
+            names: Vec::new(),
             times: Vec::new(),
             cancel_token: CancellationToken::new(),
+            progress: ProgressTracker::new(),
+            commands: CommandSender::new(),
+            command_rx: Vec::new(),
+            produced: Vec::new(),
+            consumed: Vec::new(),
+            external: Vec::new(),
+        }
+    }
+
+    /// Create a stream to feed samples into the graph from outside.
+    ///
+    /// Connect the `ReadStream` to the input of a block, and write to the
+    /// `WriteStream` directly, e.g. in a test. The stream counts as written
+    /// to when validating the graph, so no source block is needed.
+    ///
+    /// ```
+    /// use rustradio::graph::{Graph, GraphRunner};
+    /// use rustradio::blocks::AddConst;
+    /// let mut g = Graph::new();
+    /// let (input, prev) = g.input_stream();
+    /// let (add, out) = AddConst::new(prev, 1.0f32);
+    /// g.add(Box::new(add));
+    /// {
+    ///     let mut o = input.write_buf()?;
+    ///     o.fill_from_slice(&[1.0, 2.0]);
+    ///     o.produce(2, &[]);
+    /// }
+    /// g.run_until_stalled()?;
+    /// let (res, _) = out.read_buf()?;
+    /// assert_eq!(res.slice(), &[2.0, 3.0]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn input_stream<T>(&mut self) -> (WriteStream<T>, ReadStream<T>) {
+        let (w, r) = crate::stream::new_stream();
+        self.external.push(w.id());
+        (w, r)
+    }
+
+    /// Run the blocks until none of them make progress, then return.
+    ///
+    /// Unlike `run()`, this doesn't wait for the graph to reach EOF, and
+    /// doesn't call `on_eof()`. This makes it useful in tests, to push some
+    /// input through a chain of blocks and then inspect the output stream.
+    /// It can be called again after more input has been provided.
+    ///
+    /// A block makes progress when its work function returns
+    /// `BlockRet::Ok`, so this never returns if a block always does.
+    pub fn run_until_stalled(&mut self) -> Result<()> {
+        self.times
+            .resize(self.blocks.len(), std::time::Duration::default());
+        loop {
+            let mut progress = false;
+            for (n, b) in self.blocks.iter_mut().enumerate() {
+                let Some(b) = b else {
+                    continue;
+                };
+                while let Ok(cmd) = self.command_rx[n].try_recv() {
+                    b.handle_command(cmd).map_err(|e| e.wrap(b.block_name()))?;
+                }
+                let st = Instant::now();
+                let ret = b.work().map_err(|e| e.wrap(b.block_name()))?;
+                self.times[n] += st.elapsed();
+                if ret == BlockRet::Ok {
+                    self.progress.record(n);
+                    progress = true;
+                }
+            }
+            if !progress || self.cancel_token.is_canceled() {
+                return Ok(());
+            }
         }
     }
 }
@@ -85,53 +261,98 @@ impl Graph {
 impl GraphRunner for Graph {
     /// Add a block to the flowgraph.
     fn add(&mut self, b: Box<dyn Block + Send>) {
-        self.blocks.push(b);
+        self.progress.add(b.block_name());
+        self.command_rx.push(self.commands.add());
+        self.names.push(b.block_name().to_string());
+        self.produced.push(0);
+        self.consumed.push(0);
+        self.blocks.push(Some(b));
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_ports(
+            self.blocks.iter().flatten().map(|b| b.as_ref()),
+            &self.external,
+        )
     }
 
     /// Run the graph until completion.
     fn run(&mut self) -> Result<()> {
         let st = Instant::now();
+        self.progress.reset();
         self.times
             .resize(self.blocks.len(), std::time::Duration::default());
-        let mut eof = vec![false; self.blocks.len()];
+        let mut finalized = vec![false; self.blocks.len()];
         loop {
             let mut done = true;
             let mut all_idle = true;
             if self.cancel_token.is_canceled() {
                 break;
             }
-            for (n, b) in self.blocks.iter_mut().enumerate() {
-                if eof[n] {
+            if self.cancel_token.is_paused() {
+                std::thread::sleep(PAUSE_SLEEP);
+                continue;
+            }
+            for (n, slot) in self.blocks.iter_mut().enumerate() {
+                let Some(b) = slot else {
                     continue;
+                };
+                while let Ok(cmd) = self.command_rx[n].try_recv() {
+                    b.handle_command(cmd).map_err(|e| e.wrap(b.block_name()))?;
                 }
                 let st = Instant::now();
-                let ret = b.work()?;
+                let ret = b.work().map_err(|e| e.wrap(b.block_name()))?;
                 self.times[n] += st.elapsed();
-                match ret {
+                let eof = match ret {
                     BlockRet::Ok => {
                         // Block did something.
                         trace!("… {} was not starved", b.block_name());
+                        self.progress.record(n);
                         done = false;
                         all_idle = false;
+                        false
                     }
                     BlockRet::Pending | BlockRet::OutputFull => {
                         done = false;
+                        false
                     }
-                    BlockRet::Noop => {
-                        if b.eof() {
-                            eof[n] = true;
-                        }
-                    }
-                    BlockRet::EOF => {
-                        eof[n] = true;
-                    }
+                    BlockRet::Noop => b.eof(),
+                    BlockRet::EOF | BlockRet::Finished => true,
                     BlockRet::InternalAwaiting => {
                         panic!("blocks must never return InternalAwaiting")
                     }
                 };
+                if eof {
+                    if !finalized[n] {
+                        b.on_eof().map_err(|e| e.wrap(b.block_name()))?;
+                    }
+                    // Drop the block, and thus its streams, so that
+                    // downstream blocks see EOF.
+                    self.produced[n] = b.samples_produced();
+                    self.consumed[n] = b.samples_consumed();
+                    *slot = None;
+                    done = false;
+                }
             }
             if done {
-                break;
+                // Everything is idle. Finalize the first remaining block,
+                // and go around again to let later blocks process whatever
+                // it flushed. The block is kept, since its inputs may not
+                // have ended.
+                let next = self
+                    .blocks
+                    .iter()
+                    .zip(&finalized)
+                    .position(|(b, f)| b.is_some() && !f);
+                match next {
+                    Some(n) => {
+                        let b = self.blocks[n].as_mut().unwrap(); // unwrap: just checked.
+                        b.on_eof().map_err(|e| e.wrap(b.block_name()))?;
+                        finalized[n] = true;
+                        continue;
+                    }
+                    None => break,
+                }
             }
             if all_idle {
                 let idle_sleep = std::time::Duration::from_millis(10);
@@ -155,12 +376,7 @@ impl GraphRunner for Graph {
             .cloned()
             .sum::<std::time::Duration>()
             .as_secs_f64();
-        let ml = self
-            .blocks
-            .iter()
-            .map(|b| b.block_name().len())
-            .max()
-            .unwrap(); // unwrap: can only fail if block list is empty.
+        let ml = self.names.iter().map(|name| name.len()).max().unwrap(); // unwrap: can only fail if block list is empty.
         let ml = std::cmp::max(ml, "Elapsed seconds".len());
         let elapsed = elapsed.as_secs_f64();
 
@@ -170,10 +386,10 @@ impl GraphRunner for Graph {
 
         let mut s: String = format!("{:<width$}    Seconds  Percent\n", "Block name", width = ml);
         s.push_str(&dashes);
-        for (n, b) in self.blocks.iter().enumerate() {
+        for (n, name) in self.names.iter().enumerate() {
             s.push_str(&format!(
                 "{:<width$} {:secw$.secd$} {:>pw$.pd$}%\n",
-                b.block_name(),
+                name,
                 self.times[n].as_secs_f32(),
                 100.0 * self.times[n].as_secs_f64() / total,
                 width = ml,
@@ -221,6 +437,32 @@ impl GraphRunner for Graph {
     fn cancel_token(&self) -> CancellationToken {
         self.cancel_token.clone()
     }
+
+    fn progress_tracker(&self) -> ProgressTracker {
+        self.progress.clone()
+    }
+
+    fn command_sender(&self) -> CommandSender {
+        self.commands.clone()
+    }
+
+    fn total_produced(&self, block_id: usize) -> Option<u64> {
+        Some(match self.blocks.get(block_id)? {
+            Some(b) => b.samples_produced(),
+            None => self.produced[block_id],
+        })
+    }
+
+    fn total_consumed(&self, block_id: usize) -> Option<u64> {
+        Some(match self.blocks.get(block_id)? {
+            Some(b) => b.samples_consumed(),
+            None => self.consumed[block_id],
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.blocks.iter().all(Option::is_none)
+    }
 }
 
 impl Default for Graph {
@@ -229,9 +471,56 @@ impl Default for Graph {
     }
 }
 
+/// Check that all block ports are connected to other blocks.
+pub(crate) fn validate_ports<'a>(
+    blocks: impl Iterator<Item = &'a dyn Block> + Clone,
+    external: &[StreamId],
+) -> Result<()> {
+    let writers: HashSet<StreamId> = blocks
+        .clone()
+        .flat_map(|b| b.output_streams())
+        .chain(external.iter().copied())
+        .collect();
+    let readers: HashSet<StreamId> = blocks.clone().flat_map(|b| b.input_streams()).collect();
+    let mut dangling = Vec::new();
+    for (n, b) in blocks.enumerate() {
+        for (i, id) in b.input_streams().iter().enumerate() {
+            if !writers.contains(id) {
+                dangling.push(format!(
+                    "{} (block {n}) input {i} has no writer",
+                    b.block_name()
+                ));
+            }
+        }
+        for (i, id) in b.output_streams().iter().enumerate() {
+            if !readers.contains(id) {
+                dangling.push(format!(
+                    "{} (block {n}) output {i} has no reader",
+                    b.block_name()
+                ));
+            }
+        }
+    }
+    if !dangling.is_empty() {
+        return Err(crate::Error::new(&format!("dangling ports: {}", dangling.join(", "))).into());
+    }
+    Ok(())
+}
+
+// How long to sleep between checks while paused.
+pub(crate) const PAUSE_SLEEP: std::time::Duration = std::time::Duration::from_millis(10);
+
+// States of a CancellationToken.
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const CANCELED: u8 = 2;
+
 /** A handle to be able to stop the Graph. For example when the user
 presses Ctrl-C.
 
+The graph can also be paused and resumed. Cancelling a paused graph stops
+it.
+
 ```
 use rustradio::graph::CancellationToken;
 use std::thread;
@@ -256,25 +545,51 @@ while !token.is_canceled() {}
 */
 #[derive(Clone)]
 pub struct CancellationToken {
-    inner: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    inner: std::sync::Arc<std::sync::atomic::AtomicU8>,
 }
 
 impl CancellationToken {
     /// Create new cancellation token.
     pub fn new() -> Self {
         CancellationToken {
-            inner: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            inner: std::sync::Arc::new(std::sync::atomic::AtomicU8::new(RUNNING)),
         }
     }
 
     /// Mark the token cancelled.
     pub fn cancel(&self) {
-        self.inner.store(true, std::sync::atomic::Ordering::SeqCst);
+        self.inner
+            .store(CANCELED, std::sync::atomic::Ordering::SeqCst);
     }
 
     /// Check if the token is cancelled.
     pub fn is_canceled(&self) -> bool {
-        self.inner.load(std::sync::atomic::Ordering::SeqCst)
+        self.inner.load(std::sync::atomic::Ordering::SeqCst) == CANCELED
+    }
+
+    /// Mark the token paused. Does nothing if already cancelled.
+    pub fn pause(&self) {
+        let _ = self.inner.compare_exchange(
+            RUNNING,
+            PAUSED,
+            std::sync::atomic::Ordering::SeqCst,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+    }
+
+    /// Resume after pause. Does nothing if cancelled.
+    pub fn resume(&self) {
+        let _ = self.inner.compare_exchange(
+            PAUSED,
+            RUNNING,
+            std::sync::atomic::Ordering::SeqCst,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+    }
+
+    /// Check if the token is paused.
+    pub fn is_paused(&self) -> bool {
+        self.inner.load(std::sync::atomic::Ordering::SeqCst) == PAUSED
     }
 }
 
@@ -283,5 +598,538 @@ impl Default for CancellationToken {
         Self::new()
     }
 }
+
+/** A handle to see when blocks last made progress.
+
+Can be used by a watchdog thread to detect e.g. a stalled source.
+
+```no_run
+use rustradio::graph::GraphRunner;
+let mut g = rustradio::graph::Graph::new();
+let progress = g.progress_tracker();
+std::thread::spawn(move || loop {
+    std::thread::sleep(std::time::Duration::from_secs(10));
+    for (name, since) in progress.last_progress() {
+        if since.as_secs() > 60 {
+            eprintln!("{name} has stalled");
+        }
+    }
+});
+g.run()?;
+# Ok::<(), anyhow::Error>(())
+```
+*/
+#[derive(Clone, Default)]
+pub struct ProgressTracker {
+    inner: std::sync::Arc<std::sync::Mutex<Vec<(String, Instant)>>>,
+}
+
+impl ProgressTracker {
+    /// Create new progress tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block to track. Blocks are identified by their index.
+    pub(crate) fn add(&self, name: &str) {
+        self.inner
+            .lock()
+            .unwrap()
+            .push((name.to_string(), Instant::now()));
+    }
+
+    /// Mark all blocks as having just made progress.
+    pub(crate) fn reset(&self) {
+        let now = Instant::now();
+        self.inner
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .for_each(|(_, t)| *t = now);
+    }
+
+    /// Record progress for the block with the given index.
+    pub(crate) fn record(&self, index: usize) {
+        self.inner.lock().unwrap()[index].1 = Instant::now();
+    }
+
+    /// Return block names and time since they last made progress.
+    pub fn last_progress(&self) -> Vec<(String, std::time::Duration)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, t)| (name.clone(), t.elapsed()))
+            .collect()
+    }
+}
+
+/// A handle for sending commands to blocks in a graph.
+#[derive(Clone, Default)]
+pub struct CommandSender {
+    inner: std::sync::Arc<std::sync::Mutex<Vec<std::sync::mpsc::Sender<Command>>>>,
+}
+
+impl CommandSender {
+    /// Create new command sender.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a block, returning the receiving end of its command channel.
+    pub(crate) fn add(&self) -> std::sync::mpsc::Receiver<Command> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        self.inner.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send a command to a block.
+    ///
+    /// Fails if there's no such block, or if the block is done.
+    pub fn send(&self, block_id: usize, cmd: Command) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let tx = inner
+            .get(block_id)
+            .ok_or_else(|| crate::Error::new(&format!("no block with id {block_id}")))?;
+        tx.send(cmd)
+            .map_err(|_| crate::Error::new(&format!("block {block_id} is done")))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlockRet, Named};
+    use crate::blocks::{ConstantSource, NullSink, Tee, VectorSink, VectorSource};
+    use crate::Error;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Source that produces a few samples, then stalls.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate)]
+    struct StallingSource {
+        #[rustradio(out)]
+        dst: WriteStream<u8>,
+        left: usize,
+    }
+
+    impl Block for StallingSource {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            if self.left == 0 {
+                return Ok(BlockRet::Pending);
+            }
+            self.left -= 1;
+            let mut o = self.dst.write_buf()?;
+            o.slice()[0] = 1;
+            o.produce(1, &[]);
+            Ok(BlockRet::Ok)
+        }
+    }
+
+    // Sink that counts the samples it's consumed.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate)]
+    struct CountingSink {
+        #[rustradio(in)]
+        src: ReadStream<u8>,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl Block for CountingSink {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            let (i, _) = self.src.read_buf()?;
+            let n = i.len();
+            if n == 0 {
+                return Ok(BlockRet::Noop);
+            }
+            i.consume(n);
+            self.count.fetch_add(n, Ordering::SeqCst);
+            Ok(BlockRet::Ok)
+        }
+    }
+
+    // Outputs the previous sample, holding on to the latest one until EOF.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate, new, flush)]
+    struct OneBehind {
+        #[rustradio(in)]
+        src: ReadStream<u8>,
+        #[rustradio(out)]
+        dst: WriteStream<u8>,
+        #[rustradio(default)]
+        held: Option<u8>,
+    }
+
+    impl OneBehind {
+        fn flush(&mut self) -> Result<(), Error> {
+            if let Some(v) = self.held.take() {
+                let mut o = self.dst.write_buf()?;
+                o.slice()[0] = v;
+                o.produce(1, &[]);
+            }
+            Ok(())
+        }
+    }
+
+    impl Block for OneBehind {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            let (i, _) = self.src.read_buf()?;
+            if i.is_empty() {
+                return Ok(BlockRet::Noop);
+            }
+            let mut o = self.dst.write_buf()?;
+            let n = std::cmp::min(i.len(), o.len());
+            for (to, from) in o.slice().iter_mut().zip(i.iter().take(n)) {
+                *to = self.held.replace(*from).unwrap_or_default();
+            }
+            o.produce(n, &[]);
+            i.consume(n);
+            Ok(BlockRet::Ok)
+        }
+    }
+
+    fn run_flush(mut g: Box<dyn GraphRunner>) -> Result<()> {
+        let (src, prev) = VectorSource::new(vec![1u8, 2, 3]);
+        let (b, out) = OneBehind::new(prev);
+        g.add(Box::new(src));
+        g.add(Box::new(b));
+        g.run()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0, 1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_on_eof() -> Result<()> {
+        run_flush(Box::new(Graph::new()))?;
+        run_flush(Box::new(crate::mtgraph::MTGraph::new()))
+    }
+
+    fn run_counted(mut g: Box<dyn GraphRunner>) -> Result<()> {
+        let (src, prev) = VectorSource::new(vec![1u32; 10_000]);
+        let (tee, a, b) = Tee::new(prev);
+        let (add, prev) = crate::blocks::AddConst::new(a, 1);
+        let sink = NullSink::new(prev);
+        let null = NullSink::new(b);
+        g.add(Box::new(src));
+        g.add(Box::new(tee));
+        g.add(Box::new(add));
+        g.add(Box::new(sink));
+        g.add(Box::new(null));
+        assert!(!g.is_done());
+        assert_eq!(g.total_produced(0), Some(0));
+        g.run()?;
+        assert!(g.is_done());
+        assert_eq!(g.total_produced(0), Some(10_000));
+        assert_eq!(g.total_consumed(1), Some(10_000));
+        // Tee counts both outputs.
+        assert_eq!(g.total_produced(1), Some(20_000));
+        assert_eq!(g.total_consumed(3), Some(10_000));
+        assert_eq!(g.total_produced(3), Some(0));
+        assert_eq!(g.total_consumed(4), Some(10_000));
+        assert_eq!(g.total_produced(5), None);
+        Ok(())
+    }
+
+    #[test]
+    fn sample_counts() -> Result<()> {
+        run_counted(Box::new(Graph::new()))?;
+        run_counted(Box::new(crate::mtgraph::MTGraph::new()))
+    }
+
+    // Counts the number of times the wrapped source is polled.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate)]
+    struct CountPolls {
+        inner: VectorSource<u8>,
+        polls: Arc<AtomicUsize>,
+    }
+
+    impl Block for CountPolls {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            self.polls.fetch_add(1, Ordering::SeqCst);
+            self.inner.work()
+        }
+    }
+
+    // Consumes everything, and records if it saw EOF on its input.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate)]
+    struct EofProbe {
+        #[rustradio(in)]
+        src: ReadStream<u8>,
+        seen_eof: Arc<AtomicBool>,
+    }
+
+    impl Block for EofProbe {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            let (i, _) = self.src.read_buf()?;
+            let n = i.len();
+            i.consume(n);
+            if n > 0 {
+                return Ok(BlockRet::Ok);
+            }
+            if self.src.eof() {
+                self.seen_eof.store(true, Ordering::SeqCst);
+            }
+            Ok(BlockRet::Noop)
+        }
+    }
+
+    #[test]
+    fn finished_source() -> Result<()> {
+        for mut g in [
+            Box::new(Graph::new()) as Box<dyn GraphRunner>,
+            Box::new(crate::mtgraph::MTGraph::new()),
+        ] {
+            let (inner, prev) = VectorSource::new(vec![1u8, 2, 3]);
+            let polls = Arc::new(AtomicUsize::new(0));
+            let seen_eof = Arc::new(AtomicBool::new(false));
+            g.add(Box::new(CountPolls {
+                inner,
+                polls: polls.clone(),
+            }));
+            g.add(Box::new(EofProbe {
+                src: prev,
+                seen_eof: seen_eof.clone(),
+            }));
+            g.run()?;
+            // Once with data, once returning Finished.
+            assert_eq!(polls.load(Ordering::SeqCst), 2);
+            assert!(seen_eof.load(Ordering::SeqCst));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> Result<()> {
+        for mut g in [
+            Box::new(Graph::new()) as Box<dyn GraphRunner>,
+            Box::new(crate::mtgraph::MTGraph::new()),
+        ] {
+            let (src, prev) = VectorSource::new(vec![1u8, 2, 3]);
+            let (tee, a, b) = Tee::new(prev);
+            g.add(Box::new(src));
+            g.add(Box::new(Named::new(tee, "split")));
+            g.add(Box::new(NullSink::new(a)));
+            let err = g.validate().unwrap_err().to_string();
+            assert_eq!(
+                err,
+                "RustRadio Error: dangling ports: Tee[split] (block 1) output 1 has no reader"
+            );
+
+            g.add(Box::new(NullSink::new(b)));
+            g.validate()?;
+
+            // Writer not in the graph.
+            let (_dst, r) = crate::stream::new_stream::<u8>();
+            g.add(Box::new(NullSink::new(r)));
+            let err = g.validate().unwrap_err().to_string();
+            assert!(
+                err.contains("NullSink (block 4) input 0 has no writer"),
+                "{err}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate_vec_ports() -> Result<()> {
+        use crate::blocks::{Mux, ZeroCrossing};
+        let mut g = Graph::new();
+        let (src, prev) = VectorSource::new(vec![1.0 as crate::Float, -1.0, 1.0, -1.0]);
+        let (mut zc, data) = ZeroCrossing::new(prev, 2.0, 0.1);
+        let clock = zc.out_clock();
+        let (mux, prev) = Mux::new(vec![data, clock]);
+        g.add(Box::new(src));
+        g.add(Box::new(zc));
+        g.add(Box::new(mux));
+        let err = g.validate().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "RustRadio Error: dangling ports: Mux (block 2) output 0 has no reader"
+        );
+
+        g.add(Box::new(NullSink::new(prev)));
+        g.validate()?;
+        g.run()?;
+        // Counted over all the Mux inputs.
+        let consumed = g.total_consumed(2).unwrap();
+        assert!(consumed > 0);
+        assert_eq!(g.total_produced(2), Some(consumed));
+
+        // Writer not in the graph, and reader dropped.
+        let mut g = Graph::new();
+        let (_w, unused) = crate::stream::new_stream::<crate::Float>();
+        let (mux, _) = Mux::new(vec![unused]);
+        g.add(Box::new(mux));
+        let err = g.validate().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "RustRadio Error: dangling ports: Mux (block 0) input 0 has no writer, Mux (block 0) output 0 has no reader"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_until_stalled() -> Result<()> {
+        let (src, prev) = VectorSource::new(vec![1u8, 2, 3]);
+        let (b, out) = OneBehind::new(prev);
+        let mut g = Graph::new();
+        g.add(Box::new(src));
+        g.add(Box::new(b));
+        g.run_until_stalled()?;
+        // Not finalized, so the last sample is still held.
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn input_stream() -> Result<()> {
+        let mut g = Graph::new();
+        let (input, prev) = g.input_stream();
+        let (b, out) = OneBehind::new(prev);
+        g.add(Box::new(b));
+        // Without the input stream, the block input would be dangling.
+        let err = g.validate().unwrap_err().to_string();
+        assert!(!err.contains("input"), "{err}");
+        assert!(err.contains("output 0 has no reader"), "{err}");
+
+        for chunk in [[1u8, 2, 3], [4, 5, 6]] {
+            let mut o = input.write_buf()?;
+            o.fill_from_slice(&chunk);
+            o.produce(chunk.len(), &[]);
+            g.run_until_stalled()?;
+        }
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0, 1, 2, 3, 4, 5]);
+        Ok(())
+    }
+
+    // Block that fails on its first work() call.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate, new)]
+    struct Failing {
+        #[rustradio(in)]
+        src: ReadStream<u8>,
+    }
+
+    impl Block for Failing {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            Err(Error::new("deliberate failure"))
+        }
+    }
+
+    #[test]
+    fn error_names_block() -> Result<()> {
+        for mut g in [
+            Box::new(Graph::new()) as Box<dyn GraphRunner>,
+            Box::new(crate::mtgraph::MTGraph::new()),
+        ] {
+            let (src, prev) = VectorSource::new(vec![1u8, 2, 3]);
+            g.add(Box::new(src));
+            g.add(Box::new(Named::new(Failing::new(prev), "x")));
+            let err = g.run().unwrap_err().to_string();
+            assert!(err.contains("Failing[x]: deliberate failure"), "{err}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn named_stats() -> Result<()> {
+        let mut g = Graph::new();
+        for label in ["first", "second"] {
+            let (src, prev) = VectorSource::new(vec![1u8, 2, 3]);
+            g.add(Box::new(Named::new(src, label)));
+            g.add(Box::new(NullSink::new(prev)));
+        }
+        g.run()?;
+        let stats = g.generate_stats(std::time::Duration::from_secs(1));
+        assert!(stats.contains("VectorSource[first] "), "{stats}");
+        assert!(stats.contains("VectorSource[second] "), "{stats}");
+        Ok(())
+    }
+
+    #[test]
+    fn pause_resume() -> Result<()> {
+        // Wait for the count to pass `n`.
+        fn wait_past(count: &AtomicUsize, n: usize) -> usize {
+            let deadline = Instant::now() + std::time::Duration::from_secs(10);
+            loop {
+                let got = count.load(Ordering::SeqCst);
+                if got > n {
+                    return got;
+                }
+                assert!(Instant::now() < deadline, "stuck at {got}");
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+        for mut g in [
+            Box::new(Graph::new()) as Box<dyn GraphRunner>,
+            Box::new(crate::mtgraph::MTGraph::new()),
+        ] {
+            let (src, prev) = ConstantSource::new(1u8);
+            let count = Arc::new(AtomicUsize::new(0));
+            g.add(Box::new(src));
+            g.add(Box::new(CountingSink {
+                src: prev,
+                count: count.clone(),
+            }));
+            let cancel = g.cancel_token();
+            let controller = std::thread::spawn(move || {
+                let ms = std::time::Duration::from_millis;
+                wait_past(&count, 0);
+                cancel.pause();
+                // Let any running work() calls finish.
+                std::thread::sleep(ms(50));
+                let first = count.load(Ordering::SeqCst);
+                std::thread::sleep(ms(50));
+                let second = count.load(Ordering::SeqCst);
+                cancel.resume();
+                let third = wait_past(&count, second);
+                cancel.cancel();
+                (first, second, third)
+            });
+            g.run()?;
+            let (first, second, third) = controller.join().unwrap();
+            // No progress while paused.
+            assert_eq!(first, second);
+            // Progress again after resume.
+            assert!(third > second);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stalled_source() -> Result<()> {
+        let (dst, prev): (_, ReadStream<u8>) = crate::stream::new_stream();
+        let src = StallingSource { dst, left: 10 };
+        let sink = VectorSink::new(prev, 100);
+        let mut g = Graph::new();
+        g.add(Box::new(src));
+        g.add(Box::new(sink));
+        let progress = g.progress_tracker();
+        let cancel = g.cancel_token();
+        let watchdog = std::thread::spawn(move || {
+            let ms = std::time::Duration::from_millis;
+            std::thread::sleep(ms(50));
+            let first = progress.last_progress();
+            std::thread::sleep(ms(100));
+            let second = progress.last_progress();
+            cancel.cancel();
+            (first, second)
+        });
+        g.run()?;
+        let (first, second) = watchdog.join().unwrap();
+        assert_eq!(first[0].0, "StallingSource");
+        assert_eq!(first[1].0, "VectorSink");
+        assert!(second[0].1 >= first[0].1 + std::time::Duration::from_millis(90));
+        assert!(second[1].1 < std::time::Duration::from_millis(20));
+        Ok(())
+    }
+}
 /* vim: textwidth=80
  */
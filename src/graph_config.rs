@@ -0,0 +1,328 @@
+/*! Build a graph from a declarative config.
+
+Only simple linear chains are supported, with a source first, a sink last,
+and each block in between reading the output of the one before it. Only a
+whitelist of common blocks can be used.
+
+The config is JSON:
+
+```
+use rustradio::graph::GraphRunner;
+let mut g = rustradio::graph::from_config(r#"{
+  "blocks": [
+    {"block": "FileSource", "filename": "/dev/null", "type": "complex"},
+    {"block": "FftFilter", "samp_rate": 48000, "cutoff": 5000, "twidth": 1000},
+    {"block": "RationalResampler", "interp": 1, "deci": 4},
+    {"block": "QuadratureDemod", "gain": 1.0},
+    {"block": "FileSink", "filename": "/dev/null"}
+  ]
+}"#)?;
+g.run()?;
+# Ok::<(), anyhow::Error>(())
+```
+*/
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::block::Block;
+use crate::blocks::{
+    FftFilter, FftFilterFloat, FileSink, FileSource, MultiplyConst, QuadratureDemod,
+    RationalResampler,
+};
+use crate::file_sink::Mode;
+use crate::fir::{low_pass, low_pass_complex};
+use crate::graph::{Graph, GraphRunner};
+use crate::stream::ReadStream;
+use crate::window::WindowType;
+use crate::{Complex, Error, Float};
+
+/// Sample type of a stream.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleType {
+    /// [Float] samples.
+    Float,
+
+    /// [Complex] samples.
+    Complex,
+}
+
+/// Config for one block.
+///
+/// The `block` field selects the block, and the other fields are its
+/// parameters. Blocks that work on both float and complex streams take the
+/// type from their input.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "block", deny_unknown_fields)]
+pub enum BlockConfig {
+    /// [FileSource] reading raw samples of the given type.
+    FileSource {
+        /// File to read.
+        filename: String,
+        /// Sample type in the file.
+        #[serde(rename = "type")]
+        sample_type: SampleType,
+        /// Restart from the beginning at end of file.
+        #[serde(default)]
+        repeat: bool,
+    },
+
+    /// [FileSink] writing raw samples.
+    FileSink {
+        /// File to write. Overwritten if it exists.
+        filename: PathBuf,
+        /// Append to the file, instead of overwriting it.
+        #[serde(default)]
+        append: bool,
+    },
+
+    /// Low pass filter, using [FftFilter] or [FftFilterFloat].
+    FftFilter {
+        /// Sample rate.
+        samp_rate: Float,
+        /// Cutoff frequency.
+        cutoff: Float,
+        /// Transition width.
+        twidth: Float,
+    },
+
+    /// [RationalResampler].
+    RationalResampler {
+        /// Interpolation.
+        interp: usize,
+        /// Decimation.
+        deci: usize,
+    },
+
+    /// [QuadratureDemod], from complex to float.
+    QuadratureDemod {
+        /// Gain.
+        gain: Float,
+    },
+
+    /// [MultiplyConst] by a real value.
+    MultiplyConst {
+        /// Value to multiply by.
+        value: Float,
+    },
+}
+
+/// Config for a linear graph.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GraphConfig {
+    /// Blocks, in order from source to sink.
+    pub blocks: Vec<BlockConfig>,
+}
+
+// Output of the previous block in the chain.
+enum Port {
+    None,
+    Float(ReadStream<Float>),
+    Complex(ReadStream<Complex>),
+}
+
+impl Port {
+    fn name(&self) -> &'static str {
+        match self {
+            Port::None => "nothing",
+            Port::Float(_) => "float",
+            Port::Complex(_) => "complex",
+        }
+    }
+}
+
+fn boxed<B: Block + Send + 'static>(b: B) -> Box<dyn Block + Send> {
+    Box::new(b)
+}
+
+impl BlockConfig {
+    fn name(&self) -> &'static str {
+        match self {
+            BlockConfig::FileSource { .. } => "FileSource",
+            BlockConfig::FileSink { .. } => "FileSink",
+            BlockConfig::FftFilter { .. } => "FftFilter",
+            BlockConfig::RationalResampler { .. } => "RationalResampler",
+            BlockConfig::QuadratureDemod { .. } => "QuadratureDemod",
+            BlockConfig::MultiplyConst { .. } => "MultiplyConst",
+        }
+    }
+
+    // Create the block, connected to `input`.
+    fn build(self, input: Port) -> Result<(Box<dyn Block + Send>, Port)> {
+        let name = self.name();
+        Ok(match (self, input) {
+            (
+                BlockConfig::FileSource {
+                    filename,
+                    sample_type,
+                    repeat,
+                },
+                Port::None,
+            ) => match sample_type {
+                SampleType::Float => {
+                    let (b, o) = FileSource::<Float>::new(&filename, repeat)?;
+                    (boxed(b), Port::Float(o))
+                }
+                SampleType::Complex => {
+                    let (b, o) = FileSource::<Complex>::new(&filename, repeat)?;
+                    (boxed(b), Port::Complex(o))
+                }
+            },
+            (BlockConfig::FileSink { filename, append }, input) => {
+                let mode = if append {
+                    Mode::Append
+                } else {
+                    Mode::Overwrite
+                };
+                let b = match input {
+                    Port::Float(i) => boxed(FileSink::new(i, filename, mode)?),
+                    Port::Complex(i) => boxed(FileSink::new(i, filename, mode)?),
+                    Port::None => return Err(Error::new("FileSink: no input").into()),
+                };
+                (b, Port::None)
+            }
+            (
+                BlockConfig::FftFilter {
+                    samp_rate,
+                    cutoff,
+                    twidth,
+                },
+                Port::Complex(i),
+            ) => {
+                let taps = low_pass_complex(samp_rate, cutoff, twidth, &WindowType::Hamming);
+                let (b, o) = FftFilter::new(i, &taps);
+                (boxed(b), Port::Complex(o))
+            }
+            (
+                BlockConfig::FftFilter {
+                    samp_rate,
+                    cutoff,
+                    twidth,
+                },
+                Port::Float(i),
+            ) => {
+                let taps = low_pass(samp_rate, cutoff, twidth, &WindowType::Hamming);
+                let (b, o) = FftFilterFloat::new(i, &taps);
+                (boxed(b), Port::Float(o))
+            }
+            (BlockConfig::RationalResampler { interp, deci }, Port::Float(i)) => {
+                let (b, o) = RationalResampler::new(i, interp, deci)?;
+                (boxed(b), Port::Float(o))
+            }
+            (BlockConfig::RationalResampler { interp, deci }, Port::Complex(i)) => {
+                let (b, o) = RationalResampler::new(i, interp, deci)?;
+                (boxed(b), Port::Complex(o))
+            }
+            (BlockConfig::QuadratureDemod { gain }, Port::Complex(i)) => {
+                let (b, o) = QuadratureDemod::new(i, gain);
+                (boxed(b), Port::Float(o))
+            }
+            (BlockConfig::MultiplyConst { value }, Port::Float(i)) => {
+                let (b, o) = MultiplyConst::new(i, value);
+                (boxed(b), Port::Float(o))
+            }
+            (BlockConfig::MultiplyConst { value }, Port::Complex(i)) => {
+                let (b, o) = MultiplyConst::new(i, Complex::new(value, 0.0));
+                (boxed(b), Port::Complex(o))
+            }
+            (_, input) => {
+                return Err(Error::new(&format!(
+                    "graph config: {name} can't take {} as input",
+                    input.name()
+                ))
+                .into())
+            }
+        })
+    }
+}
+
+impl GraphConfig {
+    /// Parse JSON config.
+    pub fn parse(config: &str) -> Result<Self> {
+        Ok(serde_json::from_str(config)?)
+    }
+
+    /// Create and connect the blocks, returning the graph.
+    pub fn build(self) -> Result<Graph> {
+        let mut g = Graph::new();
+        let mut port = Port::None;
+        for b in self.blocks {
+            let (block, out) = b.build(port)?;
+            g.add(block);
+            port = out;
+        }
+        if !matches!(port, Port::None) {
+            return Err(Error::new(&format!(
+                "graph config: last block outputs {}, must be a sink",
+                port.name()
+            ))
+            .into());
+        }
+        Ok(g)
+    }
+}
+
+/// Build a graph from a JSON config.
+///
+/// See the [module documentation][self] for the format.
+pub fn from_config(config: &str) -> Result<Graph> {
+    GraphConfig::parse(config)?.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_file() -> Result<()> {
+        let tmpd = tempfile::tempdir()?;
+        let infile = tmpd.path().join("in.bin");
+        let outfile = tmpd.path().join("out.bin");
+        let input: Vec<u8> = (0..10).flat_map(|n| (n as Float).to_le_bytes()).collect();
+        std::fs::write(&infile, input)?;
+
+        let config = serde_json::json!({
+            "blocks": [
+                {"block": "FileSource", "filename": infile, "type": "float"},
+                {"block": "RationalResampler", "interp": 1, "deci": 2},
+                {"block": "MultiplyConst", "value": 10.0},
+                {"block": "FileSink", "filename": outfile},
+            ]
+        });
+        let mut g = from_config(&config.to_string())?;
+        g.run()?;
+
+        let got: Vec<Float> = std::fs::read(&outfile)?
+            .chunks_exact(4)
+            .map(|c| Float::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(got, [0.0, 20.0, 40.0, 60.0, 80.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_config() {
+        for (config, want) in [
+            (
+                r#"{"blocks": [{"block": "FileSource", "filename": "/dev/null", "type": "float"},
+                               {"block": "QuadratureDemod", "gain": 1.0}]}"#,
+                "QuadratureDemod can't take float",
+            ),
+            (
+                r#"{"blocks": [{"block": "FileSource", "filename": "/dev/null", "type": "float"}]}"#,
+                "must be a sink",
+            ),
+            (r#"{"blocks": [{"block": "Bogus"}]}"#, "unknown variant"),
+            (
+                r#"{"blocks": [{"block": "MultiplyConst", "value": 1.0, "bogus": 1}]}"#,
+                "unknown field",
+            ),
+        ] {
+            let err = from_config(config).err().expect(config).to_string();
+            assert!(err.contains(want), "{err}");
+        }
+    }
+}
@@ -7,11 +7,12 @@ therefore [APRS][aprs].
 [ax25]: https://en.wikipedia.org/wiki/AX.25
 [aprs]: https://en.wikipedia.org/wiki/Automatic_Packet_Reporting_System
  */
-use log::{debug, info, trace};
+use log::debug;
 
-use crate::block::{Block, BlockRet};
+use crate::block::{Block, BlockEOF, BlockLog, BlockRet};
+use crate::deframer_status::DeframerCounters;
 use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
-use crate::{Error, Result};
+use crate::{Error, Float, Result};
 
 enum State {
     /// Looking for flag pattern.
@@ -28,11 +29,19 @@ enum State {
 // Calculate CRC. If a bitflip helps the CRC match, then return the
 // new data with the CRC.
 //
+// If per-bit confidences are provided, bits are tried in ascending
+// confidence order. Otherwise they're tried in stream order.
+//
 // Return tuple of:
 // * new data, if modified.
 // * correct CRC.
 // * true/false if a bit was flipped or not.
-fn find_right_crc(data: &[u8], got: u16, fix_bits: bool) -> (Option<Vec<u8>>, u16, bool) {
+fn find_right_crc(
+    data: &[u8],
+    got: u16,
+    fix_bits: bool,
+    confidence: Option<&[Float]>,
+) -> (Option<Vec<u8>>, u16, bool) {
     let crc = calc_crc(data);
     if got == crc {
         // Fast path: CRC matches.
@@ -41,18 +50,9 @@ fn find_right_crc(data: &[u8], got: u16, fix_bits: bool) -> (Option<Vec<u8>>, u1
     if !fix_bits {
         return (None, crc, false);
     }
-    let mut copy = data.to_vec();
-    for byte in 0..data.len() {
-        for bit in 0..8 {
-            let x = 1 << bit;
-            copy[byte] ^= x;
-            let crc = calc_crc(&copy);
-            if crc == got {
-                debug!("Fixed bitflip successfully");
-                return (Some(copy), crc, true);
-            }
-            copy[byte] ^= x;
-        }
+    if let Some((copy, _tries)) = try_bitflips(data, got, &flip_order(data.len(), confidence)) {
+        debug!("Fixed bitflip successfully");
+        return (Some(copy), got, true);
     }
     for crcbit in 0..16 {
         let newcrc = got ^ (1 << crcbit);
@@ -64,35 +64,78 @@ fn find_right_crc(data: &[u8], got: u16, fix_bits: bool) -> (Option<Vec<u8>>, u1
     (None, crc, false)
 }
 
+// Return the order in which to try flipping bits of `bytes` bytes.
+//
+// Bit index `i` is bit `i % 8` (LSB first) of byte `i / 8`, which is
+// also the order the bits arrived in.
+fn flip_order(bytes: usize, confidence: Option<&[Float]>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..bytes * 8).collect();
+    if let Some(confidence) = confidence {
+        assert!(
+            confidence.len() >= order.len(),
+            "confidence len {} less than {} bits",
+            confidence.len(),
+            order.len()
+        );
+        order.sort_by(|a, b| confidence[*a].total_cmp(&confidence[*b]));
+    }
+    order
+}
+
+// Try flipping single bits in the given order, until the CRC matches.
+//
+// Return the fixed data, and how many flips were tried.
+fn try_bitflips(data: &[u8], got: u16, order: &[usize]) -> Option<(Vec<u8>, usize)> {
+    let mut copy = data.to_vec();
+    for (tries, bit) in order.iter().enumerate() {
+        let (byte, x) = (bit / 8, 1 << (bit % 8));
+        copy[byte] ^= x;
+        if calc_crc(&copy) == got {
+            return Some((copy, tries + 1));
+        }
+        copy[byte] ^= x;
+    }
+    None
+}
+
 /** HDLC Deframer block.
 
 This block takes a stream of bits (as u8), and outputs any HDLC frames
 found as Vec<u8>.
+
+Optionally, created with `new_soft()`, it also takes a stream of
+per-bit confidence values. Higher means more confident. When fixing
+bits (see `set_fix_bits()`), the least confident bits are then tried
+first.
 */
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
+#[rustradio(crate, noeof)]
 pub struct HdlcDeframer {
     #[rustradio(in)]
     src: ReadStream<u8>,
+    #[rustradio(in)]
+    confidence: Option<ReadStream<Float>>,
     #[rustradio(out)]
     dst: NCWriteStream<Vec<u8>>,
     state: State,
+    confs: Vec<Float>,
     min_size: usize,
     max_size: usize,
     strip_checksum: bool,
-    decoded: usize,
-    crc_error: usize,
-    bitfixed: usize,
+    counters: DeframerCounters,
     stream_pos: u64,
     fix_bits: bool,
 }
 
 impl Drop for HdlcDeframer {
     fn drop(&mut self) {
-        info!(
-            "HDLC Deframer: Decoded {} (incl {} bitfixes), CRC error {}",
-            self.decoded, self.bitfixed, self.crc_error
-        );
+        self.counters.close();
+        self.log_info(format_args!(
+            "Decoded {} (incl {} bitfixes), CRC error {}",
+            self.counters.decoded(),
+            self.counters.bitfixed(),
+            self.counters.crc_error()
+        ));
     }
 }
 
@@ -109,14 +152,14 @@ impl HdlcDeframer {
         (
             Self {
                 src,
+                confidence: None,
                 dst,
                 min_size,
                 max_size,
                 state: State::Unsynced(0xff),
+                confs: Vec::new(),
                 strip_checksum: true,
-                decoded: 0,
-                crc_error: 0,
-                bitfixed: 0,
+                counters: DeframerCounters::default(),
                 stream_pos: 0,
                 fix_bits: false,
             },
@@ -124,6 +167,22 @@ impl HdlcDeframer {
         )
     }
 
+    /// Create new HdlcDeframer taking soft bit input.
+    ///
+    /// `confidence` is a stream running in parallel with `src`, with one
+    /// confidence value per bit. Only the relative order of the values
+    /// matters.
+    pub fn new_soft(
+        src: ReadStream<u8>,
+        confidence: ReadStream<Float>,
+        min_size: usize,
+        max_size: usize,
+    ) -> (Self, NCReadStream<Vec<u8>>) {
+        let (mut block, dr) = Self::new(src, min_size, max_size);
+        block.confidence = Some(confidence);
+        (block, dr)
+    }
+
     /// Set fix bits.
     pub fn set_fix_bits(&mut self, v: bool) {
         self.fix_bits = v;
@@ -134,12 +193,19 @@ impl HdlcDeframer {
         self.strip_checksum = val;
     }
 
-    fn update_state(&mut self, bit: u8, stream_pos: u64) -> Result<State> {
+    /// Return the decode counters, e.g. for
+    /// [DeframerStatus][crate::deframer_status::DeframerStatus].
+    pub fn counters(&self) -> DeframerCounters {
+        self.counters.clone()
+    }
+
+    fn update_state(&mut self, bit: u8, conf: Option<Float>, stream_pos: u64) -> Result<State> {
         Ok(match &mut self.state {
             State::Unsynced(v) => {
                 let n = (*v >> 1) | (bit << 7);
                 if n == 0x7e {
-                    debug!("HdlcDeframer: Found flag!");
+                    self.log_debug(format_args!("Found flag!"));
+                    self.confs.clear();
                     State::Synced((0, Vec::with_capacity(self.max_size)))
                 } else {
                     State::Unsynced(n)
@@ -155,16 +221,18 @@ impl HdlcDeframer {
                 }
                 if bit > 0 {
                     bits.push(1);
+                    self.confs.extend(conf);
                     if *ones == 5 {
                         State::FinalCheck(bits)
                     } else {
                         State::Synced((*ones + 1, bits))
                     }
                 } else if *ones == 5 {
-                    trace!("discarding stuffed bit {:?}", bits);
+                    self.log_trace(format_args!("discarding stuffed bit {:?}", bits));
                     State::Synced((0, bits))
                 } else {
                     bits.push(0);
+                    self.confs.extend(conf);
                     State::Synced((0, bits))
                 }
             }
@@ -184,28 +252,35 @@ impl HdlcDeframer {
 
                 // Remove partial flag.
                 bits.truncate(bits.len() - 7);
+                self.confs.truncate(bits.len());
 
                 if bits.len() % 8 != 0 {
-                    trace!(
-                        "HdlcDeframer: Packet len not multiple of 8: {} {:?}",
+                    self.log_trace(format_args!(
+                        "Packet len not multiple of 8: {} {:?}",
                         bits.len(),
                         bits
-                    );
+                    ));
                 } else if bits.len() / 8 < self.min_size {
-                    trace!("Packet too short: {} < {}", bits.len() / 8, self.min_size);
+                    self.log_trace(format_args!(
+                        "Packet too short: {} < {}",
+                        bits.len() / 8,
+                        self.min_size
+                    ));
                 } else {
                     let bytes: Vec<u8> = (0..bits.len())
                         .step_by(8)
                         .map(|i| bits2byte(&bits[i..i + 8]))
                         .collect();
-                    debug!("HdlcDeframer: Captured packet: {:0>2x?}", bytes);
+                    self.log_debug(format_args!("Captured packet: {:0>2x?}", bytes));
                     let tags = &[Tag::new(0, "packet_pos".into(), TagValue::U64(stream_pos))];
                     if self.strip_checksum {
                         let data = &bytes[..bytes.len() - 2];
                         let got_crc = u16::from_le_bytes(bytes[bytes.len() - 2..].try_into()?);
-                        let (newdata, crc, fixed) = find_right_crc(data, got_crc, self.fix_bits);
+                        let confs = self.confidence.as_ref().map(|_| &self.confs[..]);
+                        let (newdata, crc, fixed) =
+                            find_right_crc(data, got_crc, self.fix_bits, confs);
                         if fixed {
-                            self.bitfixed += 1;
+                            self.counters.add_bitfixed();
                         }
                         let (data, crc) = match &newdata {
                             None => (data, crc),
@@ -213,40 +288,71 @@ impl HdlcDeframer {
                         };
 
                         if crc != got_crc {
-                            self.crc_error += 1;
-                            debug!("want crc {:0>4x}, got {:0>4x}", crc, got_crc);
+                            self.counters.add_crc_error();
+                            self.log_debug(format_args!(
+                                "want crc {:0>4x}, got {:0>4x}",
+                                crc, got_crc
+                            ));
+                            self.confs.clear();
                             return Ok(State::Synced((0, Vec::with_capacity(self.max_size))));
                         }
-                        self.decoded += 1;
+                        self.counters.add_decoded();
                         self.dst.push(data.to_vec(), tags);
                     } else {
-                        self.decoded += 1;
+                        self.counters.add_decoded();
                         self.dst.push(bytes, tags);
                     }
                 }
 
                 // We may or may not have seen a valid packet, but we
                 // did see a valid flag. So back to synced.
+                self.confs.clear();
                 State::Synced((0, Vec::with_capacity(self.max_size)))
             }
         })
     }
 }
 
+impl BlockEOF for HdlcDeframer {
+    fn eof(&mut self) -> bool {
+        self.src.eof()
+    }
+
+    /// If the input ends right after six ones, the frame is complete
+    /// except for the last bit of the closing flag. Emit it.
+    fn on_eof(&mut self) -> Result<(), Error> {
+        if matches!(self.state, State::FinalCheck(_)) {
+            self.state = self.update_state(0, None, self.stream_pos)?;
+        }
+        Ok(())
+    }
+}
+
 impl Block for HdlcDeframer {
     fn work(&mut self) -> Result<BlockRet, Error> {
         let (input, _tags) = self.src.read_buf()?;
-        if input.is_empty() {
+        let confidence = match &self.confidence {
+            None => None,
+            Some(c) => Some(c.read_buf()?.0),
+        };
+        let n = match &confidence {
+            None => input.len(),
+            Some(c) => std::cmp::min(input.len(), c.len()),
+        };
+        if n == 0 {
             return Ok(BlockRet::Noop);
         }
-        for bit in input.iter().copied() {
+        for (i, bit) in input.iter().take(n).copied().enumerate() {
             // This is a bit ugly in that it destructively creates the
             // new state. The old state is moved from.
-            self.state = self.update_state(bit, self.stream_pos)?;
+            let conf = confidence.as_ref().map(|c| c[i]);
+            self.state = self.update_state(bit, conf, self.stream_pos)?;
             self.stream_pos += 1;
         }
-        let n = input.len();
         input.consume(n);
+        if let Some(c) = confidence {
+            c.consume(n);
+        }
         Ok(BlockRet::Ok)
     }
 }
@@ -298,7 +404,7 @@ const FCSTAB: &[u16] = &[
 ];
 
 // Calculate checksum. Code ported from RFC1662.
-fn calc_crc(data: &[u8]) -> u16 {
+pub(crate) fn calc_crc(data: &[u8]) -> u16 {
     data.iter().fold(0xffffu16, |fcs, byte| {
         let byte = *byte as u16;
         let ofs = ((fcs ^ byte) & 0xff) as usize;
@@ -341,6 +447,19 @@ mod tests {
         Ok(())
     }
     #[test]
+    fn frame_at_eof() -> Result<()> {
+        // Closing flag missing its last bit.
+        let s = ReadStream::from_slice(&str2bits("0111111001010101111000000111111"));
+        let (mut b, o) = HdlcDeframer::new(s, 1, 10);
+        b.set_checksum(false);
+        b.work()?;
+        assert!(o.pop().is_none());
+        b.on_eof()?;
+        let (res, _) = o.pop().unwrap();
+        assert_eq!(res, vec![0xaa, 0x7]);
+        Ok(())
+    }
+    #[test]
     fn find_simple_frames() -> Result<()> {
         for bits in &[
             "01111110010101011110000001111110010101011010101001111110",
@@ -429,4 +548,83 @@ mod tests {
         }
         Ok(())
     }
+
+    // Encode bytes into a flag delimited, bit stuffed, HDLC bit stream.
+    fn frame_bits(data: &[u8]) -> Vec<u8> {
+        let flag = str2bits("01111110");
+        let mut bits = flag.clone();
+        let mut ones = 0;
+        for byte in data {
+            for bit in 0..8 {
+                let b = (byte >> bit) & 1;
+                bits.push(b);
+                if b == 1 {
+                    ones += 1;
+                    if ones == 5 {
+                        bits.push(0);
+                        ones = 0;
+                    }
+                } else {
+                    ones = 0;
+                }
+            }
+        }
+        bits.extend(flag);
+        bits
+    }
+
+    #[test]
+    fn flip_least_confident_first() -> Result<()> {
+        let data = b"hello world".to_vec();
+        let crc = calc_crc(&data);
+        let mut broken = data.clone();
+        broken[3] ^= 0x10;
+        let bad = 3 * 8 + 4;
+        let mut confidence = vec![1.0; data.len() * 8];
+        confidence[bad] = 0.1;
+
+        // Hard decision brute force has to walk through all earlier bits.
+        let (fixed, tries) = try_bitflips(&broken, crc, &flip_order(data.len(), None)).unwrap();
+        assert_eq!(fixed, data);
+        assert_eq!(tries, bad + 1);
+
+        // Soft decision tries the least confident bit first.
+        let order = flip_order(data.len(), Some(&confidence));
+        assert_eq!(order[0], bad);
+        let (fixed, tries) = try_bitflips(&broken, crc, &order).unwrap();
+        assert_eq!(fixed, data);
+        assert_eq!(tries, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn soft_fix_bits() -> Result<()> {
+        let data = b"hello world".to_vec();
+        let mut frame = data.clone();
+        frame.extend(calc_crc(&data).to_le_bytes());
+        let mut bits = frame_bits(&frame);
+
+        // Flip a one to a zero in the data (after the flag), and mark it as
+        // unsure. Flipping a zero to a one could create a false stuffing
+        // pattern.
+        let bad = 8 + 19;
+        assert_eq!(bits[bad], 1);
+        bits[bad] ^= 1;
+        let mut confidence = vec![1.0 as Float; bits.len()];
+        confidence[bad] = 0.01;
+
+        let (mut b, o) = HdlcDeframer::new_soft(
+            ReadStream::from_slice(&bits),
+            ReadStream::from_slice(&confidence),
+            1,
+            100,
+        );
+        b.set_fix_bits(true);
+        b.work()?;
+        let (res, _tags) = o.pop().unwrap();
+        assert_eq!(res, data);
+        assert!(o.pop().is_none());
+        assert_eq!(b.counters().bitfixed(), 1);
+        Ok(())
+    }
 }
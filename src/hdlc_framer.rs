@@ -0,0 +1,182 @@
+/*! HDLC Framer.
+
+The transmit side of [HdlcDeframer][crate::hdlc_deframer::HdlcDeframer].
+Takes frames, and outputs the bits to send, ready for NRZI encoding and
+modulation.
+
+Each frame gets a checksum (FCS) appended, is bit stuffed, and is put
+between an opening and a closing flag.
+*/
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::hdlc_deframer::calc_crc;
+use crate::stream::{NCReadStream, ReadStream, Tag, WriteStream};
+use crate::Error;
+
+const FLAG: u8 = 0x7e;
+
+/// HDLC framer block.
+///
+/// Outputs one bit per byte, least significant bit first, like the input
+/// of `HdlcDeframer`. Tags on a frame are attached to its first bit.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, noeof)]
+pub struct HdlcFramer {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<u8>>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    preamble: usize,
+    // Bits of the frame being written, its tags, and how much is written.
+    current: Option<(Vec<u8>, Vec<Tag>)>,
+    pos: usize,
+}
+
+impl HdlcFramer {
+    /// Create new HdlcFramer.
+    pub fn new(src: NCReadStream<Vec<u8>>) -> (Self, ReadStream<u8>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                preamble: 0,
+                current: None,
+                pos: 0,
+            },
+            dr,
+        )
+    }
+
+    /// Set number of extra flags to send before the opening flag.
+    ///
+    /// Gives the receiver time to lock on. For AX.25 this is often called
+    /// TXDELAY. Default is 0.
+    pub fn set_preamble(&mut self, flags: usize) {
+        self.preamble = flags;
+    }
+
+    // Return the bits for a frame.
+    fn frame(&self, data: &[u8]) -> Vec<u8> {
+        let mut bits = Vec::with_capacity((self.preamble + 2 + data.len() + 2) * 10);
+        for _ in 0..=self.preamble {
+            push_byte(&mut bits, FLAG);
+        }
+        let crc = calc_crc(data).to_le_bytes();
+        let mut ones = 0;
+        for byte in data.iter().chain(&crc) {
+            for n in 0..8 {
+                let bit = (byte >> n) & 1;
+                bits.push(bit);
+                if bit == 0 {
+                    ones = 0;
+                    continue;
+                }
+                ones += 1;
+                if ones == 5 {
+                    bits.push(0);
+                    ones = 0;
+                }
+            }
+        }
+        push_byte(&mut bits, FLAG);
+        bits
+    }
+}
+
+// Append bits of a byte, least significant bit first.
+fn push_byte(bits: &mut Vec<u8>, byte: u8) {
+    bits.extend((0..8).map(|n| (byte >> n) & 1));
+}
+
+impl BlockEOF for HdlcFramer {
+    fn eof(&mut self) -> bool {
+        self.current.is_none() && self.src.eof()
+    }
+}
+
+impl Block for HdlcFramer {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let mut ret = BlockRet::Noop;
+        loop {
+            if self.current.is_none() {
+                let Some((data, tags)) = self.src.pop() else {
+                    return Ok(ret);
+                };
+                let tags = tags
+                    .into_iter()
+                    .map(|t| Tag::new(0, t.key().into(), t.val().clone()))
+                    .collect();
+                self.current = Some((self.frame(&data), tags));
+                self.pos = 0;
+            }
+            let (bits, tags) = self.current.as_ref().unwrap();
+            let mut o = self.dst.write_buf()?;
+            if o.is_empty() {
+                return Ok(BlockRet::OutputFull);
+            }
+            let n = std::cmp::min(o.len(), bits.len() - self.pos);
+            o.fill_from_slice(&bits[self.pos..self.pos + n]);
+            if self.pos == 0 {
+                o.produce(n, tags);
+            } else {
+                o.produce(n, &[]);
+            }
+            self.pos += n;
+            if self.pos == bits.len() {
+                self.current = None;
+            }
+            ret = BlockRet::Ok;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::HdlcDeframer;
+    use crate::stream::{new_nocopy_stream, TagValue};
+
+    #[test]
+    fn loopback() -> anyhow::Result<()> {
+        let frames: Vec<Vec<u8>> = vec![
+            b"hello world".to_vec(),
+            // Lots of ones, for bit stuffing.
+            vec![0xff, 0xff, 0x7e, 0x7e, 0xfe, 0x3f],
+            vec![0x00, 0x01],
+        ];
+        let (w, r) = new_nocopy_stream();
+        for f in &frames {
+            w.push(f.clone(), &[Tag::new(0, "id".into(), TagValue::U64(1))]);
+        }
+        let (mut b, bits) = HdlcFramer::new(r);
+        b.set_preamble(3);
+        b.work()?;
+        {
+            let (i, tags) = bits.read_buf()?;
+            // Preamble and opening flags.
+            assert_eq!(&i.slice()[..32], [0, 1, 1, 1, 1, 1, 1, 0].repeat(4));
+            assert!(tags.iter().any(|t| t.pos() == 0 && t.key() == "id"));
+        }
+
+        let (mut deframer, out) = HdlcDeframer::new(bits, 1, 100);
+        deframer.work()?;
+        for f in &frames {
+            let (got, _) = out.pop().expect("frame missing");
+            assert_eq!(&got, f);
+        }
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn stuffing() {
+        let (_, r) = new_nocopy_stream();
+        let (b, _) = HdlcFramer::new(r);
+        let bits = b.frame(&[0xff]);
+        // No more than five ones in a row, except in flags.
+        let inner = &bits[8..bits.len() - 8];
+        assert!(!inner.windows(6).any(|w| w.iter().all(|&b| b == 1)));
+        // 0xff is stuffed after the fifth one.
+        assert_eq!(&inner[..9], [1, 1, 1, 1, 1, 0, 1, 1, 1]);
+    }
+}
@@ -0,0 +1,185 @@
+/*! Histogram of sample values.
+
+For debugging, e.g. to check the level going into a quantizer, or to spot
+clipping.
+*/
+use crate::block::{Block, BlockLog, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Error, Float};
+
+/// Histogram block.
+///
+/// Samples pass through unchanged. Values in `[min, max)` are counted in
+/// `bins` equal width bins. Values below `min` are counted as underflow,
+/// and values at or above `max` (and NaN) as overflow.
+///
+/// With [`set_dump_on_drop()`][Histogram::set_dump_on_drop], the histogram
+/// is logged at info level when the block is dropped, e.g. when the graph
+/// is done.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Histogram {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    min: Float,
+    max: Float,
+    counts: Vec<u64>,
+    underflow: u64,
+    overflow: u64,
+    dump: bool,
+}
+
+impl Histogram {
+    /// Create new Histogram block.
+    pub fn new(
+        src: ReadStream<Float>,
+        bins: usize,
+        min: Float,
+        max: Float,
+    ) -> Result<(Self, ReadStream<Float>), Error> {
+        if bins == 0 || min >= max {
+            return Err(Error::new(&format!(
+                "Histogram: bad parameters bins={bins} min={min} max={max}"
+            )));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                min,
+                max,
+                counts: vec![0; bins],
+                underflow: 0,
+                overflow: 0,
+                dump: false,
+            },
+            dr,
+        ))
+    }
+
+    /// Log the histogram when the block is dropped.
+    pub fn set_dump_on_drop(&mut self, dump: bool) {
+        self.dump = dump;
+    }
+
+    /// Return the counts per bin.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Return number of values below `min`.
+    pub fn underflow(&self) -> u64 {
+        self.underflow
+    }
+
+    /// Return number of values at or above `max`, or NaN.
+    pub fn overflow(&self) -> u64 {
+        self.overflow
+    }
+
+    /// Return the `[start, end)` range of a bin.
+    pub fn bin_range(&self, bin: usize) -> (Float, Float) {
+        let width = self.bin_width();
+        (
+            self.min + bin as Float * width,
+            self.min + (bin + 1) as Float * width,
+        )
+    }
+
+    /// Reset all counts to zero.
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.underflow = 0;
+        self.overflow = 0;
+    }
+
+    fn bin_width(&self) -> Float {
+        (self.max - self.min) / self.counts.len() as Float
+    }
+
+    fn add(&mut self, v: Float) {
+        if v < self.min {
+            self.underflow += 1;
+        } else if v < self.max {
+            let bin = ((v - self.min) / self.bin_width()) as usize;
+            // Rounding could put values just below max out of range.
+            let last = self.counts.len() - 1;
+            self.counts[bin.min(last)] += 1;
+        } else {
+            self.overflow += 1;
+        }
+    }
+}
+
+impl Drop for Histogram {
+    fn drop(&mut self) {
+        if !self.dump {
+            return;
+        }
+        self.log_info(format_args!("underflow: {}", self.underflow));
+        for (bin, count) in self.counts.iter().enumerate() {
+            let (start, end) = self.bin_range(bin);
+            self.log_info(format_args!("[{start}, {end}): {count}"));
+        }
+        self.log_info(format_args!("overflow: {}", self.overflow));
+    }
+}
+
+impl Block for Histogram {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, mut tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        for &v in i.iter().take(n) {
+            self.add(v);
+        }
+        o.fill_from_slice(&i.slice()[..n]);
+        tags.retain(|t| t.pos() < n);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    #[test]
+    fn ramp() -> crate::Result<()> {
+        // Ramp from -1.5 to 1.5, in steps of 0.01, around a range of
+        // [-1, 1).
+        let input: Vec<Float> = (-150..150).map(|n| n as Float / 100.0).collect();
+        let (mut src, prev) = VectorSource::new(input.clone());
+        let (mut b, out) = Histogram::new(prev, 4, -1.0, 1.0)?;
+        src.work()?;
+        b.work()?;
+        assert_eq!(out.read_buf()?.0.slice(), input);
+        assert_eq!(b.counts(), &[50, 50, 50, 50]);
+        assert_eq!(b.underflow(), 50);
+        // Including exactly 1.0.
+        assert_eq!(b.overflow(), 50);
+        assert_eq!(b.bin_range(1), (-0.5, 0.0));
+        b.clear();
+        assert_eq!(b.counts(), &[0; 4]);
+        assert!(Histogram::new(out, 4, 1.0, 1.0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn partial_output_tags() -> crate::Result<()> {
+        crate::tests::check_partial_output_tags::<Float, _>(|prev| {
+            Ok(Histogram::new(prev, 4, -1.0, 1.0)?)
+        })
+    }
+}
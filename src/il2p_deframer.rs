@@ -4,6 +4,7 @@
 use log::info;
 
 use crate::block::{Block, BlockRet};
+use crate::deframer_status::DeframerCounters;
 use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag};
 use crate::{Error, Result};
 
@@ -154,13 +155,18 @@ pub struct Il2pDeframer {
     src: ReadStream<u8>,
     #[rustradio(out)]
     dst: NCWriteStream<Vec<u8>>,
-    decoded: usize,
+    counters: DeframerCounters,
     state: State,
 }
 
 impl Drop for Il2pDeframer {
     fn drop(&mut self) {
-        info!("IL2P Deframer: Decoded {}", self.decoded);
+        self.counters.close();
+        info!(
+            "IL2P Deframer: Decoded {}, header errors {}",
+            self.counters.decoded(),
+            self.counters.crc_error()
+        );
     }
 }
 impl Il2pDeframer {
@@ -171,12 +177,20 @@ impl Il2pDeframer {
             Self {
                 src,
                 dst,
-                decoded: 0,
+                counters: DeframerCounters::default(),
                 state: State::Unsynced,
             },
             dr,
         )
     }
+
+    /// Return the decode counters, e.g. for
+    /// [DeframerStatus][crate::deframer_status::DeframerStatus].
+    ///
+    /// Headers that fail to parse are counted as CRC errors.
+    pub fn counters(&self) -> DeframerCounters {
+        self.counters.clone()
+    }
 }
 
 impl Block for Il2pDeframer {
@@ -236,8 +250,10 @@ impl Block for Il2pDeframer {
             info!("  payload_size: {}", header.payload_size);
 
             // TODO: push something useful.
+            self.counters.add_decoded();
             self.dst.push(Vec::new(), &[]);
         } else if let Some(Err(e)) = header {
+            self.counters.add_crc_error();
             info!("Failed to parse header: {}", e);
         }
         Ok(BlockRet::Ok)
@@ -0,0 +1,127 @@
+/*! IQ imbalance correction.
+
+Cheap SDRs have gain and phase mismatch between the I and Q branches, which
+shows up as an image of every signal, mirrored around DC.
+
+The received signal is modelled as
+
+```text
+I = cos(θ)
+Q = g·sin(θ + φ)
+```
+
+where `g` is the gain imbalance and `φ` the phase imbalance. Since `Q - g
+sin(φ)·I = g cos(φ)·sin(θ)`, the correction is
+
+```text
+Q' = (Q - p·I)·s
+```
+
+where `p` is adapted to decorrelate `Q'` from `I`, and `s` is adapted to
+make `Q'` the same power as `I`.
+*/
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Float};
+
+// Correction coefficients.
+#[derive(Clone, Copy, Debug)]
+struct Correction {
+    // Amount of I leaked into Q, i.e. g·sin(φ).
+    p: Float,
+    // Q scaling, i.e. 1/(g·cos(φ)).
+    s: Float,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Self { p: 0.0, s: 1.0 }
+    }
+}
+
+/// IQ imbalance correction.
+///
+/// Adaptively estimates and corrects gain and phase imbalance. The rate is
+/// the adaptation step size, relative to the signal power. Larger adapts
+/// faster, but is noisier. Something like 0.0001 to 0.01 is typical.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct IqBalance {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    rate: Float,
+    #[rustradio(default)]
+    correction: Correction,
+}
+
+impl IqBalance {
+    /// Set adaptation rate.
+    pub fn set_rate(&mut self, rate: Float) {
+        self.rate = rate;
+    }
+
+    /// Return current estimates of gain imbalance and phase imbalance.
+    ///
+    /// Gain is the ratio of Q to I amplitude, and phase is in radians.
+    pub fn estimates(&self) -> (Float, Float) {
+        let Correction { p, s } = self.correction;
+        let c = 1.0 / s;
+        ((p * p + c * c).sqrt(), p.atan2(c))
+    }
+
+    fn process_sync(&mut self, sample: Complex) -> Complex {
+        let (i, q) = (sample.re, sample.im);
+        let c = &mut self.correction;
+        let q2 = (q - c.p * i) * c.s;
+        c.p += self.rate * (q - c.p * i) * i;
+        c.s += self.rate * (i * i - q2 * q2);
+        Complex::new(i, q2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::blocks::VectorSource;
+
+    // Power of the component at the given frequency, in radians per sample.
+    fn power(data: &[Complex], rad: Float) -> Float {
+        let sum: Complex = data
+            .iter()
+            .enumerate()
+            .map(|(n, x)| x * Complex::from_polar(1.0, -rad * n as Float))
+            .sum();
+        (sum / data.len() as Float).norm_sqr()
+    }
+
+    #[test]
+    fn suppress_image() -> anyhow::Result<()> {
+        let (gain, phase) = (1.2, 0.1);
+        let rad = 0.3;
+        let input: Vec<_> = (0..20000)
+            .map(|n| {
+                let t = rad * n as Float;
+                Complex::new(t.cos(), gain * (t + phase).sin())
+            })
+            .collect();
+        let tail = input.len() - 2000;
+        let before = power(&input[tail..], -rad) / power(&input[tail..], rad);
+        assert!(before > 0.005, "{before}");
+
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = IqBalance::new(prev, 0.005);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        let res = res.slice();
+        let after = power(&res[tail..], -rad) / power(&res[tail..], rad);
+        assert!(after < before / 1000.0, "before {before} after {after}");
+
+        let (g, p) = b.estimates();
+        assert!((g - gain).abs() < 0.02, "gain {g}");
+        assert!((p - phase).abs() < 0.02, "phase {p}");
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+/*! Measure latency through a graph.
+
+[TimestampTag] tags samples with the current time, and [LatencyProbe]
+measures how long ago that was, when the tag reaches it.
+
+Timestamps are nanoseconds on a monotonic clock, counted from when the first
+timestamp was taken in this process. So they're only meaningful within a
+single process.
+*/
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::Error;
+
+/// Tag key used for timestamps.
+pub const TIMESTAMP_TAG: &str = "latency:timestamp";
+
+/// Default number of measurements kept by [LatencyProbe].
+pub const DEFAULT_HISTORY: usize = 1000;
+
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+fn now_nanos() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Tag samples with the current time.
+///
+/// Passes samples through unchanged, adding a [TIMESTAMP_TAG] tag every
+/// `interval` samples.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct TimestampTag<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    interval: usize,
+    // Samples until next tag.
+    next: usize,
+}
+
+impl<T: Copy> TimestampTag<T> {
+    /// Create new TimestampTag block.
+    ///
+    /// Panics if `interval` is zero.
+    pub fn new(src: ReadStream<T>, interval: usize) -> (Self, ReadStream<T>) {
+        assert!(interval > 0, "TimestampTag: interval must be positive");
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                interval,
+                next: 0,
+            },
+            dr,
+        )
+    }
+}
+
+impl<T: Copy> Block for TimestampTag<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, mut tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        tags.retain(|t| t.pos() < n);
+        let now = now_nanos();
+        let mut pos = self.next;
+        while pos < n {
+            tags.push(Tag::new(pos, TIMESTAMP_TAG.into(), TagValue::U64(now)));
+            pos += self.interval;
+        }
+        self.next = pos - n;
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// Handle for reading measurements from a [LatencyProbe].
+#[derive(Clone)]
+pub struct LatencyHandle {
+    inner: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+impl LatencyHandle {
+    /// Return the kept measurements, oldest first.
+    pub fn latencies(&self) -> Vec<Duration> {
+        self.inner.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Return the highest kept measurement.
+    pub fn max(&self) -> Option<Duration> {
+        self.inner.lock().unwrap().iter().max().copied()
+    }
+
+    /// Return the mean of the kept measurements.
+    pub fn mean(&self) -> Option<Duration> {
+        let l = self.inner.lock().unwrap();
+        if l.is_empty() {
+            return None;
+        }
+        Some(l.iter().sum::<Duration>() / l.len() as u32)
+    }
+}
+
+/// Measure latency of samples tagged by [TimestampTag].
+///
+/// Consumes the stream, keeping the last `history` measurements.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct LatencyProbe<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    history: usize,
+    handle: LatencyHandle,
+}
+
+impl<T: Copy> LatencyProbe<T> {
+    /// Create new LatencyProbe block.
+    pub fn new(src: ReadStream<T>, history: usize) -> Self {
+        Self {
+            src,
+            history,
+            handle: LatencyHandle {
+                inner: Arc::new(Mutex::new(VecDeque::with_capacity(history))),
+            },
+        }
+    }
+
+    /// Return a handle for reading the measurements.
+    pub fn handle(&self) -> LatencyHandle {
+        self.handle.clone()
+    }
+}
+
+impl<T: Copy> Block for LatencyProbe<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let now = now_nanos();
+        let mut l = self.handle.inner.lock().unwrap();
+        for tag in tags.iter().filter(|t| t.key() == TIMESTAMP_TAG) {
+            if let TagValue::U64(ts) = tag.val() {
+                if l.len() == self.history {
+                    l.pop_front();
+                }
+                l.push_back(Duration::from_nanos(now.saturating_sub(*ts)));
+            }
+        }
+        let n = i.len();
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Delay;
+
+    #[test]
+    fn interval() -> Result<()> {
+        let (w, r) = crate::stream::new_stream::<u8>();
+        let (mut b, out) = TimestampTag::new(r, 3);
+        for len in [4, 4] {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(&vec![0; len]);
+            wb.produce(len, &[]);
+            b.work()?;
+        }
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 8);
+        let pos: Vec<_> = tags.iter().map(|t| t.pos()).collect();
+        assert_eq!(pos, vec![0, 3, 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn through_delay() -> Result<()> {
+        let (w, r) = crate::stream::new_stream::<u8>();
+        let (mut ts, prev) = TimestampTag::new(r, 1000);
+        let (mut delay, delayed) = Delay::new(prev, 10);
+        let (pw, pr) = crate::stream::new_stream::<u8>();
+        let mut probe = LatencyProbe::new(pr, DEFAULT_HISTORY);
+        let h = probe.handle();
+
+        let mut wb = w.write_buf()?;
+        wb.slice()[0] = 1;
+        wb.produce(1, &[]);
+        ts.work()?;
+        delay.work()?;
+
+        // Play out the delayed stream in "real time", one sample per
+        // millisecond. The timestamped sample is after the 10 sample delay,
+        // so should take at least 10ms.
+        loop {
+            let (i, tags) = delayed.read_buf()?;
+            if i.is_empty() {
+                break;
+            }
+            let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() == 0).collect();
+            let mut o = pw.write_buf()?;
+            o.slice()[0] = i.slice()[0];
+            o.produce(1, &tags);
+            i.consume(1);
+            probe.work()?;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let l = h.latencies();
+        assert_eq!(l.len(), 1);
+        assert!(l[0] >= Duration::from_millis(10), "{l:?}");
+        assert_eq!(h.max(), Some(l[0]));
+        assert_eq!(h.mean(), Some(l[0]));
+        Ok(())
+    }
+}
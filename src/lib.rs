@@ -119,44 +119,87 @@ pub use rustradio_macros;
 // Blocks.
 pub mod add;
 pub mod add_const;
+pub mod all_pass;
+pub mod am_demod;
+pub mod apply_weights;
 pub mod au;
+pub mod autocorrelate;
+pub mod ax25_parse;
 pub mod binary_slicer;
+pub mod bpsk_mod;
 pub mod burst_tagger;
+pub mod cic;
+pub mod clip_detect;
+pub mod clock_sync;
 pub mod complex_to_mag2;
+pub mod complex_to_phase;
 pub mod constant_source;
 pub mod convert;
 pub mod correlate_access_code;
 pub mod debug_sink;
+pub mod decimate;
+pub mod deframer_status;
 pub mod delay;
 pub mod descrambler;
+pub mod discontinuity_monitor;
+pub mod evm;
+pub mod fft;
 pub mod fft_filter;
+pub mod fft_stream;
 pub mod file_sink;
 pub mod file_source;
 pub mod fir;
+pub mod fractional_shift;
+pub mod freq_estimate;
+pub mod freq_meter;
+pub mod fsk_mod;
 pub mod hdlc_deframer;
+pub mod hdlc_framer;
 pub mod hilbert;
+pub mod histogram;
 pub mod iir_filter;
 pub mod il2p_deframer;
+pub mod iq_balance;
+pub mod latency;
+pub mod lut_map;
 pub mod multiply_const;
+pub mod mux;
+pub mod nco_source;
 pub mod nrzi;
 pub mod null_sink;
+pub mod pack_bits;
 pub mod pdu_writer;
+pub mod peak_freq;
+pub mod preamble_sync;
+pub mod prepend_sequence;
 pub mod quadrature_demod;
 pub mod rational_resampler;
+pub mod reader_source;
+pub mod reverse;
+pub mod rotate;
 pub mod rtlsdr_decode;
 pub mod sigmf;
 pub mod signal_source;
+pub mod silence_eof;
 pub mod single_pole_iir_filter;
 pub mod skip;
+pub mod soft_demapper;
+pub mod spectral_mask;
 pub mod stream_to_pdu;
+pub mod strobe;
 pub mod symbol_sync;
+pub mod tag_debounce;
+pub mod tag_router;
 pub mod tcp_source;
 pub mod tee;
+pub mod timing_resampler;
 pub mod to_text;
+pub mod triggered_recorder;
 pub mod vec_to_stream;
 pub mod vector_sink;
 pub mod vector_source;
 pub mod wpcr;
+pub mod writer_sink;
 pub mod xor;
 pub mod xor_const;
 pub mod zero_crossing;
@@ -177,10 +220,15 @@ pub mod block;
 pub mod blocks;
 pub mod circular_buffer;
 pub mod graph;
+pub mod graph_config;
 pub mod mtgraph;
+pub mod param;
 pub mod stream;
 pub mod window;
 
+#[cfg(test)]
+pub(crate) mod testutil;
+
 /// Float type used. Usually f32, but not guaranteed.
 pub type Float = f32;
 
@@ -188,9 +236,30 @@ pub type Float = f32;
 pub type Complex = num_complex::Complex<Float>;
 
 /// RustRadio error.
+///
+/// Blocks that want to return their own error type can wrap it with
+/// [Error::custom], and callers can get it back out with
+/// [Error::as_custom].
+///
+/// ```
+/// use rustradio::Error;
+///
+/// #[derive(Debug)]
+/// struct MyError(u32);
+/// impl std::fmt::Display for MyError {
+///     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+///         write!(f, "my error {}", self.0)
+///     }
+/// }
+/// impl std::error::Error for MyError {}
+///
+/// let e = Error::custom(MyError(42));
+/// assert_eq!(e.as_custom::<MyError>().unwrap().0, 42);
+/// ```
 #[derive(Debug, Clone)]
 pub struct Error {
     msg: String,
+    custom: Option<std::sync::Arc<dyn std::error::Error + Send + Sync>>,
 }
 
 impl Error {
@@ -198,8 +267,39 @@ impl Error {
     pub fn new(msg: &str) -> Self {
         Self {
             msg: msg.to_string(),
+            custom: None,
+        }
+    }
+
+    /// Create new error wrapping a user defined error.
+    pub fn custom<E: std::error::Error + Send + Sync + 'static>(e: E) -> Self {
+        Self {
+            msg: e.to_string(),
+            custom: Some(std::sync::Arc::new(e)),
+        }
+    }
+
+    /// Add context to the error message, e.g. where it happened.
+    ///
+    /// Any wrapped user defined error is kept.
+    ///
+    /// ```
+    /// use rustradio::Error;
+    /// let e = Error::new("bad taps").wrap("FftFilter[ch0]");
+    /// assert_eq!(e.to_string(), "RustRadio Error: FftFilter[ch0]: bad taps");
+    /// ```
+    #[must_use]
+    pub fn wrap(self, context: &str) -> Self {
+        Self {
+            msg: format!("{context}: {}", self.msg),
+            custom: self.custom,
         }
     }
+
+    /// Return the wrapped user defined error, if it's of type `E`.
+    pub fn as_custom<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        self.custom.as_deref()?.downcast_ref::<E>()
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -208,11 +308,21 @@ impl std::fmt::Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.custom
+            .as_deref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
 
 impl From<anyhow::Error> for Error {
     fn from(e: anyhow::Error) -> Error {
-        Error::new(&format!("{}", e))
+        // Don't lose any wrapped custom error.
+        match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => Error::new(&format!("{}", e)),
+        }
     }
 }
 impl From<std::io::Error> for Error {
@@ -439,6 +549,93 @@ impl<T> Len for Vec<T> {
 pub mod tests {
     //! Test helper functions.
     use super::*;
+    use crate::block::Block;
+
+    #[derive(Debug, PartialEq)]
+    struct CustomError {
+        code: u32,
+    }
+
+    impl std::fmt::Display for CustomError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "custom error {}", self.code)
+        }
+    }
+
+    impl std::error::Error for CustomError {}
+
+    #[test]
+    fn custom_error() {
+        let e = Error::custom(CustomError { code: 42 });
+        assert_eq!(e.to_string(), "RustRadio Error: custom error 42");
+        assert_eq!(e.as_custom(), Some(&CustomError { code: 42 }));
+        assert!(e.as_custom::<std::io::Error>().is_none());
+        assert!(Error::new("plain").as_custom::<CustomError>().is_none());
+
+        // Through anyhow, as returned by graph.run().
+        let e: anyhow::Error = e.into();
+        let e = e.downcast_ref::<Error>().unwrap();
+        assert_eq!(e.as_custom(), Some(&CustomError { code: 42 }));
+        let e = Error::from(anyhow::Error::from(e.clone()));
+        assert_eq!(e.as_custom(), Some(&CustomError { code: 42 }));
+        use std::error::Error as _;
+        assert!(e.source().unwrap().is::<CustomError>());
+    }
+
+    /// For testing, run a block once on the given input.
+    ///
+    /// `make` creates the block from its input stream. The input is fed by
+    /// a [VectorSource][crate::blocks::VectorSource], so it must fit in one
+    /// stream buffer.
+    ///
+    /// Returns the block, for inspecting its state, and what it output.
+    pub fn run_block<I: Copy, O: Copy, B: Block>(
+        input: Vec<I>,
+        make: impl FnOnce(stream::ReadStream<I>) -> Result<(B, stream::ReadStream<O>)>,
+    ) -> Result<(B, Vec<O>, Vec<stream::Tag>)> {
+        let (mut src, prev) = blocks::VectorSource::new(input);
+        let (mut b, out) = make(prev)?;
+        src.work()?;
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        Ok((b, res.slice().to_vec(), tags))
+    }
+
+    /// For testing, check that a one-in-one-out block only forwards the
+    /// tags of the samples it actually outputs.
+    ///
+    /// The output stream is first filled up to 10 samples short of full.
+    /// Then 20 samples are written, with tags on the 5th and 15th. Only
+    /// the first tag may come out with the first 10 samples, and the second
+    /// tag must come out, once, with the rest.
+    pub fn check_partial_output_tags<T: Copy + Default, B: Block>(
+        make: impl FnOnce(stream::ReadStream<T>) -> Result<(B, stream::ReadStream<T>)>,
+    ) -> Result<()> {
+        use stream::{Tag, TagValue};
+        let (w, r) = stream::new_stream();
+        let (mut b, out) = make(r)?;
+        let room = w.write_buf()?.len() - 10;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_iter(std::iter::repeat(T::default()).take(room));
+        wb.produce(room, &[]);
+        b.work()?;
+
+        let tag = |pos, key: &str| Tag::new(pos, key.into(), TagValue::Bool(true));
+        let mut wb = w.write_buf()?;
+        wb.fill_from_iter(std::iter::repeat(T::default()).take(20));
+        wb.produce(20, &[tag(5, "a"), tag(15, "b")]);
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), room + 10);
+        assert_eq!(tags, [tag(room + 5, "a")]);
+        res.consume(room + 10);
+
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 10);
+        assert_eq!(tags, [tag(5, "b")]);
+        Ok(())
+    }
 
     /// For testing, assert that two slices are almost equal.
     ///
@@ -0,0 +1,142 @@
+//! Map samples through a lookup table.
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Error, Float};
+
+/// How to look up values that fall between table entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LutInterpolation {
+    /// Use the nearest table entry.
+    Nearest,
+
+    /// Interpolate linearly between the two nearest entries.
+    #[default]
+    Linear,
+}
+
+/// Map samples through a lookup table.
+///
+/// The table entries are evenly spaced over the input range `[min, max]`,
+/// with the first entry at `min` and the last at `max`. Inputs outside the
+/// range are clamped to it.
+///
+/// Useful for nonlinear corrections like companding or gamma, where the
+/// function is too expensive to calculate per sample.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, sync)]
+pub struct LutMap {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    min: Float,
+    max: Float,
+    table: Vec<Float>,
+    interp: LutInterpolation,
+}
+
+impl LutMap {
+    /// Create new LutMap block.
+    ///
+    /// Fails if the table is empty, or the range is empty.
+    pub fn new(
+        src: ReadStream<Float>,
+        min: Float,
+        max: Float,
+        table: Vec<Float>,
+        interp: LutInterpolation,
+    ) -> Result<(Self, ReadStream<Float>), Error> {
+        check(min, max, &table)?;
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                min,
+                max,
+                table,
+                interp,
+            },
+            dr,
+        ))
+    }
+
+    /// Replace the table and its input range.
+    pub fn set_table(&mut self, min: Float, max: Float, table: Vec<Float>) -> Result<(), Error> {
+        check(min, max, &table)?;
+        self.min = min;
+        self.max = max;
+        self.table = table;
+        Ok(())
+    }
+
+    /// Change the interpolation.
+    pub fn set_interpolation(&mut self, interp: LutInterpolation) {
+        self.interp = interp;
+    }
+
+    fn process_sync(&self, x: Float) -> Float {
+        let last = self.table.len() - 1;
+        if last == 0 {
+            return self.table[0];
+        }
+        // Position in the table, as a fractional index.
+        let pos = (x.clamp(self.min, self.max) - self.min) / (self.max - self.min) * last as Float;
+        match self.interp {
+            LutInterpolation::Nearest => self.table[(pos.round() as usize).min(last)],
+            LutInterpolation::Linear => {
+                let i = (pos.floor() as usize).min(last - 1);
+                let frac = pos - i as Float;
+                self.table[i] + (self.table[i + 1] - self.table[i]) * frac
+            }
+        }
+    }
+}
+
+fn check(min: Float, max: Float, table: &[Float]) -> Result<(), Error> {
+    if table.is_empty() {
+        return Err(Error::new("LutMap table must not be empty"));
+    }
+    if min.partial_cmp(&max) != Some(std::cmp::Ordering::Less) {
+        return Err(Error::new(&format!(
+            "LutMap range must have min < max, got [{min}, {max}]"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(interp: LutInterpolation, input: &[Float]) -> Result<Vec<Float>, Error> {
+        // x², sampled at 0, 1, 2, 3, 4.
+        let table = vec![0.0, 1.0, 4.0, 9.0, 16.0];
+        let (_, out, _) = crate::tests::run_block(input.to_vec(), |prev| {
+            Ok(LutMap::new(prev, 0.0, 4.0, table, interp)?)
+        })
+        .map_err(Error::from)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn interpolation() -> Result<(), Error> {
+        let input = [-1.0, 0.0, 1.0, 1.4, 2.5, 3.75, 4.0, 10.0];
+        assert_eq!(
+            run(LutInterpolation::Nearest, &input)?,
+            [0.0, 0.0, 1.0, 1.0, 9.0, 16.0, 16.0, 16.0]
+        );
+        crate::tests::assert_almost_equal_float(
+            &run(LutInterpolation::Linear, &input)?,
+            &[0.0, 0.0, 1.0, 2.2, 6.5, 14.25, 16.0, 16.0],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bad_table() {
+        let (_, r) = crate::stream::new_stream();
+        assert!(LutMap::new(r, 0.0, 1.0, vec![], LutInterpolation::Linear).is_err());
+        let (_, r) = crate::stream::new_stream();
+        assert!(LutMap::new(r, 1.0, 1.0, vec![1.0], LutInterpolation::Linear).is_err());
+    }
+}
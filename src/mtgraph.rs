@@ -6,8 +6,56 @@ use std::time::Instant;
 use anyhow::Result;
 use log::{debug, error, info, trace};
 
-use crate::block::{Block, BlockRet};
-use crate::graph::CancellationToken;
+use crate::block::{Block, BlockRet, Command};
+use crate::graph::{CancellationToken, CommandSender, ProgressTracker};
+use crate::Error;
+
+/// Set real-time (`SCHED_FIFO`) priority of the current thread.
+///
+/// Priority is 1 (lowest) to 99 (highest). Usually needs root, or the
+/// `CAP_SYS_NICE` capability, or a raised `RLIMIT_RTPRIO`.
+#[cfg(target_os = "linux")]
+pub fn set_realtime_priority(priority: i32) -> Result<(), Error> {
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    // SAFETY: pthread_self() is always valid for the calling thread, and
+    // param outlives the call.
+    let rc = unsafe { libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) };
+    if rc != 0 {
+        return Err(Error::new(&format!(
+            "failed to set SCHED_FIFO priority {priority}: {}",
+            std::io::Error::from_raw_os_error(rc)
+        )));
+    }
+    Ok(())
+}
+
+/// Set real-time (`SCHED_FIFO`) priority of the current thread.
+///
+/// Not supported on this platform, so always returns an error.
+#[cfg(not(target_os = "linux"))]
+pub fn set_realtime_priority(priority: i32) -> Result<(), Error> {
+    Err(Error::new(&format!(
+        "real-time priority {priority} not supported on this platform"
+    )))
+}
+
+// Try to set real-time priority, falling back to normal priority.
+//
+// Returns true if the priority was set.
+fn try_realtime_priority(name: &str, priority: i32) -> bool {
+    match set_realtime_priority(priority) {
+        Ok(()) => {
+            debug!("Thread {name} running with real-time priority {priority}");
+            true
+        }
+        Err(e) => {
+            debug!("Thread {name} running with normal priority: {e}");
+            false
+        }
+    }
+}
 
 /**
 A graph is a thing that RustRadio runs, to let blocks "talk to each
@@ -35,25 +83,223 @@ g.run()?;
 */
 pub struct MTGraph {
     blocks: Vec<Box<dyn Block + Send>>,
+    pinned: Vec<bool>,
+    threads: Option<usize>,
+    // Per block, as of the end of the last run.
+    produced: Vec<u64>,
+    consumed: Vec<u64>,
+    done: Vec<bool>,
     cancel_token: CancellationToken,
+    progress: ProgressTracker,
+    commands: CommandSender,
+    command_rx: Vec<std::sync::mpsc::Receiver<Command>>,
     times: BTreeMap<(usize, String), std::time::Duration>,
+    source_priority: Option<i32>,
 }
 
 impl MTGraph {
     /// Create a new flowgraph.
+    ///
+    /// Each block gets its own thread.
     pub fn new() -> Self {
         Self {
             blocks: Vec::new(),
+            pinned: Vec::new(),
+            threads: None,
+            produced: Vec::new(),
+            consumed: Vec::new(),
+            done: Vec::new(),
             times: BTreeMap::new(),
             cancel_token: CancellationToken::new(),
+            progress: ProgressTracker::new(),
+            commands: CommandSender::new(),
+            command_rx: Vec::new(),
+            source_priority: None,
+        }
+    }
+
+    /// Run source blocks with real-time (`SCHED_FIFO`) priority.
+    ///
+    /// Makes it less likely that e.g. an SDR source drops samples when the
+    /// machine is busy. Source blocks are blocks without input streams. If
+    /// they share a worker thread with other blocks, the whole thread gets
+    /// the priority.
+    ///
+    /// If the priority can't be set, e.g. because of missing permissions
+    /// or platform support, the threads silently run with normal priority.
+    /// See [set_realtime_priority].
+    pub fn set_source_priority(&mut self, priority: Option<i32>) {
+        self.source_priority = priority;
+    }
+
+    /// Create a new flowgraph, running blocks on `n` worker threads.
+    ///
+    /// Blocks are split into `n` groups of neighbouring blocks, and each
+    /// group is run by one thread. Blocks added with
+    /// [`add_pinned()`][Self::add_pinned] get a dedicated thread on top of
+    /// the `n`.
+    ///
+    /// Returns error if `n` is zero.
+    pub fn with_threads(n: usize) -> Result<Self, Error> {
+        if n == 0 {
+            return Err(Error::new("MTGraph needs at least one worker thread"));
+        }
+        Ok(Self {
+            threads: Some(n),
+            ..Self::new()
+        })
+    }
+
+    /// Add a block that gets its own dedicated thread.
+    ///
+    /// Useful for CPU heavy blocks, like
+    /// [`FftFilter`][crate::fft_filter::FftFilter], when the graph has a
+    /// limited number of worker threads. Without
+    /// [`with_threads()`][Self::with_threads] every block gets its own
+    /// thread anyway, so this is the same as
+    /// [`add()`][crate::graph::GraphRunner::add].
+    pub fn add_pinned(&mut self, b: Box<dyn Block + Send>) {
+        use crate::graph::GraphRunner;
+        self.add(b);
+        *self.pinned.last_mut().unwrap() = true; // unwrap: just added.
+    }
+
+    // Split block indices into groups, one group per thread.
+    fn thread_groups(&self) -> Vec<Vec<usize>> {
+        let Some(threads) = self.threads else {
+            return (0..self.blocks.len()).map(|i| vec![i]).collect();
+        };
+        let (pinned, shared): (Vec<usize>, Vec<usize>) =
+            (0..self.blocks.len()).partition(|&i| self.pinned[i]);
+        let mut groups: Vec<Vec<usize>> = pinned.into_iter().map(|i| vec![i]).collect();
+        if !shared.is_empty() {
+            let size = shared.len().div_ceil(threads);
+            groups.extend(shared.chunks(size).map(<[usize]>::to_vec));
+        }
+        groups
+    }
+}
+
+// A block, and its state, while running in a worker thread.
+struct Worker {
+    index: usize,
+    name: String,
+    block: Box<dyn Block + Send>,
+    command_rx: std::sync::mpsc::Receiver<Command>,
+    time: std::time::Duration,
+}
+
+impl Worker {
+    // Stop running the block, dropping it.
+    fn finish(self, eof: bool) -> Finished {
+        Finished {
+            index: self.index,
+            name: self.name,
+            time: self.time,
+            produced: self.block.samples_produced(),
+            consumed: self.block.samples_consumed(),
+            eof,
+        }
+    }
+}
+
+// What's left of a block after its worker thread is done with it.
+#[derive(Debug)]
+struct Finished {
+    index: usize,
+    name: String,
+    time: std::time::Duration,
+    produced: u64,
+    consumed: u64,
+    eof: bool,
+}
+
+// Run the blocks of one worker thread, until they're all done, or the graph
+// is cancelled.
+fn run_workers(
+    mut workers: Vec<Worker>,
+    cancel_token: CancellationToken,
+    em_tx: std::sync::mpsc::SyncSender<(usize, BlockRet)>,
+    progress: ProgressTracker,
+) -> Result<Vec<Finished>> {
+    let idle_sleep = std::time::Duration::from_millis(1);
+    let mut done = Vec::new();
+    while !workers.is_empty() && !cancel_token.is_canceled() {
+        if cancel_token.is_paused() {
+            std::thread::sleep(crate::graph::PAUSE_SLEEP);
+            continue;
+        }
+        let mut idle = true;
+        let mut i = 0;
+        while i < workers.len() {
+            let w = &mut workers[i];
+            while let Ok(cmd) = w.command_rx.try_recv() {
+                w.block
+                    .handle_command(cmd)
+                    .map_err(|e| e.wrap(w.block.block_name()))?;
+            }
+            let st = Instant::now();
+            let ret = w.block.work().map_err(|e| e.wrap(w.block.block_name()))?;
+            if matches!(ret, BlockRet::EOF | BlockRet::Finished)
+                || (ret == BlockRet::Noop && w.block.eof())
+            {
+                w.block.on_eof().map_err(|e| e.wrap(w.block.block_name()))?;
+                w.time += st.elapsed();
+                // Drop the block, and thus its streams, before reporting
+                // EOF. That way downstream blocks see EOF on their inputs,
+                // and anything flushed is processed before the graph is
+                // considered done.
+                let f = workers.remove(i).finish(true);
+                let index = f.index;
+                em_tx
+                    .send((index, BlockRet::Ok))
+                    .expect("mpsc status send failed");
+                em_tx
+                    .send((index, BlockRet::EOF))
+                    .expect("mpsc status send failed");
+                done.push(f);
+                idle = false;
+                continue;
+            }
+            w.time += st.elapsed();
+            em_tx
+                .send((w.index, ret.clone()))
+                .expect("mpsc status send failed");
+            match ret {
+                BlockRet::Ok => {
+                    progress.record(w.index);
+                    idle = false;
+                }
+                BlockRet::EOF | BlockRet::Finished => unreachable!(),
+                BlockRet::Noop | BlockRet::OutputFull | BlockRet::Pending => {}
+                BlockRet::InternalAwaiting => {
+                    panic!("blocks must never return InternalAwaiting")
+                }
+            }
+            i += 1;
+        }
+        if idle {
+            std::thread::sleep(idle_sleep);
         }
     }
+    done.extend(workers.into_iter().map(|w| w.finish(false)));
+    Ok(done)
 }
 
 impl crate::graph::GraphRunner for MTGraph {
     /// Add a block to the flowgraph.
     fn add(&mut self, b: Box<dyn Block + Send>) {
+        self.progress.add(b.block_name());
+        self.command_rx.push(self.commands.add());
         self.blocks.push(b);
+        self.pinned.push(false);
+        self.produced.push(0);
+        self.consumed.push(0);
+        self.done.push(false);
+    }
+
+    fn validate(&self) -> Result<()> {
+        crate::graph::validate_ports(self.blocks.iter().map(|b| b.as_ref() as &dyn Block), &[])
     }
 
     /// Run the graph until completion.
@@ -77,7 +323,7 @@ impl crate::graph::GraphRunner for MTGraph {
                              first_phase = true;
                              false
                          },
-                         BlockRet::Noop | BlockRet::EOF => true,
+                         BlockRet::Noop | BlockRet::EOF | BlockRet::Finished => true,
                          BlockRet::InternalAwaiting => panic!("InternalAwaiting should never be received"),
                      };
 
@@ -97,7 +343,7 @@ impl crate::graph::GraphRunner for MTGraph {
                                  maybe_done = false;
                                  break;
                              },
-                             BlockRet::Noop |BlockRet::EOF => {},
+                             BlockRet::Noop |BlockRet::EOF | BlockRet::Finished => {},
                              BlockRet::InternalAwaiting => {
                                  maybe_done = false;
                                  // We can safely break here, without
@@ -136,42 +382,54 @@ impl crate::graph::GraphRunner for MTGraph {
         };
 
         let st = Instant::now();
+        self.progress.reset();
         let mut threads = Vec::new();
-        let mut index = self.blocks.len();
-        while let Some(mut b) = self.blocks.pop() {
-            index -= 1;
+        let groups = self.thread_groups();
+        let mut blocks: Vec<_> = self
+            .blocks
+            .drain(..)
+            .zip(self.command_rx.drain(..))
+            .map(Some)
+            .collect();
+        self.pinned.clear();
+        for (n, group) in groups.into_iter().enumerate() {
+            let workers: Vec<Worker> = group
+                .into_iter()
+                .map(|index| {
+                    let (block, command_rx) = blocks[index].take().unwrap(); // unwrap: each index is in one group.
+                    Worker {
+                        index,
+                        name: block.block_name().to_string(),
+                        block,
+                        command_rx,
+                        time: std::time::Duration::new(0, 0),
+                    }
+                })
+                .collect();
             let cancel_token = self.cancel_token.clone();
+            let cancel_on_error = self.cancel_token.clone();
             let em_tx = em_tx.clone();
-            debug!("Starting thread {}", b.block_name());
+            let progress = self.progress.clone();
+            let name = match workers.as_slice() {
+                [w] => w.name.clone(),
+                _ => format!("mtgraph worker {n}"),
+            };
+            let priority = self
+                .source_priority
+                .filter(|_| workers.iter().any(|w| w.block.input_streams().is_empty()));
+            debug!("Starting thread {name}");
             let th = std::thread::Builder::new()
-                .name(b.block_name().to_string())
-                .spawn(move || -> Result<std::time::Duration> {
-                    let idle_sleep = std::time::Duration::from_millis(1);
-                    let mut tt = std::time::Duration::new(0, 0);
-                    while !cancel_token.is_canceled() {
-                        let st = Instant::now();
-                        let ret = b.work()?;
-                        tt += st.elapsed();
-                        em_tx
-                            .send((index, ret.clone()))
-                            .expect("mpsc status send failed");
-                        match ret {
-                            BlockRet::Ok => {}
-                            BlockRet::EOF => {
-                                return Ok(tt);
-                            }
-                            BlockRet::Noop | BlockRet::OutputFull => {
-                                std::thread::sleep(idle_sleep);
-                            }
-                            BlockRet::Pending => {
-                                std::thread::sleep(idle_sleep);
-                            }
-                            BlockRet::InternalAwaiting => {
-                                panic!("blocks must never return InternalAwaiting")
-                            }
-                        }
+                .name(name.clone())
+                .spawn(move || {
+                    if let Some(priority) = priority {
+                        try_realtime_priority(&name, priority);
                     }
-                    Ok(tt)
+                    let ret = run_workers(workers, cancel_token, em_tx, progress);
+                    if ret.is_err() {
+                        // Other blocks may never finish without this one.
+                        cancel_on_error.cancel();
+                    }
+                    ret
                 });
             let th = match th {
                 Err(x) => {
@@ -185,15 +443,25 @@ impl crate::graph::GraphRunner for MTGraph {
         }
         drop(em_tx);
         debug!("Joining threads");
-        for (n, th) in threads.into_iter().rev().enumerate() {
+        let mut first_err = None;
+        for th in threads {
             let name = th.thread().name().unwrap().to_string();
             debug!("Waiting for {}", name);
-            let j = th
-                .join()
-                .expect("joining thread")
-                .expect("block exit status");
+            let j = match th.join().expect("joining thread") {
+                Ok(j) => j,
+                Err(e) => {
+                    error!("Thread {name} failed: {e}");
+                    first_err.get_or_insert(e);
+                    continue;
+                }
+            };
             debug!("Thread {} finished with {:?}", name, j);
-            self.times.insert((n, name), j);
+            for f in j {
+                self.produced[f.index] = f.produced;
+                self.consumed[f.index] = f.consumed;
+                self.done[f.index] = f.eof;
+                self.times.insert((f.index, f.name), f.time);
+            }
         }
         exit_monitor.join().unwrap().unwrap();
         for line in self.generate_stats(st.elapsed()).split('\n') {
@@ -201,7 +469,10 @@ impl crate::graph::GraphRunner for MTGraph {
                 info!("{}", line);
             }
         }
-        Ok(())
+        match first_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
     /// Return a string with stats about where time went.
@@ -278,6 +549,34 @@ impl crate::graph::GraphRunner for MTGraph {
     fn cancel_token(&self) -> CancellationToken {
         self.cancel_token.clone()
     }
+
+    fn progress_tracker(&self) -> ProgressTracker {
+        self.progress.clone()
+    }
+
+    fn command_sender(&self) -> CommandSender {
+        self.commands.clone()
+    }
+
+    /// Return the total number of samples a block has produced.
+    ///
+    /// Blocks run in their own threads, so this is as of when the last
+    /// `run()` finished.
+    fn total_produced(&self, block_id: usize) -> Option<u64> {
+        self.produced.get(block_id).copied()
+    }
+
+    /// Return the total number of samples a block has consumed.
+    ///
+    /// Blocks run in their own threads, so this is as of when the last
+    /// `run()` finished.
+    fn total_consumed(&self, block_id: usize) -> Option<u64> {
+        self.consumed.get(block_id).copied()
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.iter().all(|&d| d)
+    }
 }
 
 impl Default for MTGraph {
@@ -285,3 +584,104 @@ impl Default for MTGraph {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{AddConst, VectorSource};
+    use crate::graph::GraphRunner;
+    use crate::stream::ReadStream;
+    use std::sync::{Arc, Mutex};
+
+    // Sink saving samples to somewhere the test can see them after the
+    // graph has consumed the block.
+    #[derive(rustradio_macros::Block)]
+    #[rustradio(crate)]
+    struct SharedSink {
+        #[rustradio(in)]
+        src: ReadStream<u32>,
+        out: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Block for SharedSink {
+        fn work(&mut self) -> Result<BlockRet, Error> {
+            let (i, _) = self.src.read_buf()?;
+            if i.is_empty() {
+                return Ok(BlockRet::Noop);
+            }
+            self.out.lock().unwrap().extend(i.iter());
+            let n = i.len();
+            i.consume(n);
+            Ok(BlockRet::Ok)
+        }
+    }
+
+    #[test]
+    fn with_threads() -> Result<()> {
+        let input: Vec<u32> = (0..100_000).collect();
+        let want: Vec<u32> = input.iter().map(|x| x + 3).collect();
+        for (threads, pin) in [(1, false), (2, false), (2, true), (10, false)] {
+            let (src, prev) = VectorSource::new(input.clone());
+            let (a1, prev) = AddConst::new(prev, 1);
+            let (a2, prev) = AddConst::new(prev, 2);
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let sink = SharedSink {
+                src: prev,
+                out: out.clone(),
+            };
+            let mut g = MTGraph::with_threads(threads)?;
+            g.add(Box::new(src));
+            if pin {
+                g.add_pinned(Box::new(a1));
+            } else {
+                g.add(Box::new(a1));
+            }
+            g.add(Box::new(a2));
+            g.add(Box::new(sink));
+            g.run()?;
+            assert_eq!(*out.lock().unwrap(), want, "threads={threads} pin={pin}");
+        }
+        assert!(MTGraph::with_threads(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn source_priority() -> Result<()> {
+        let input: Vec<u32> = (0..10_000).collect();
+        for threads in [None, Some(2)] {
+            let (src, prev) = VectorSource::new(input.clone());
+            let (add, prev) = AddConst::new(prev, 1);
+            let out = Arc::new(Mutex::new(Vec::new()));
+            let sink = SharedSink {
+                src: prev,
+                out: out.clone(),
+            };
+            let mut g = match threads {
+                None => MTGraph::new(),
+                Some(n) => MTGraph::with_threads(n)?,
+            };
+            // Works whether or not the priority can actually be set.
+            g.set_source_priority(Some(10));
+            g.add(Box::new(src));
+            g.add(Box::new(add));
+            g.add(Box::new(sink));
+            g.run()?;
+            assert!(g.is_done());
+            let want: Vec<u32> = input.iter().map(|x| x + 1).collect();
+            assert_eq!(*out.lock().unwrap(), want);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bad_priority() {
+        std::thread::spawn(|| {
+            // Out of range, so fails even with permissions.
+            let err = set_realtime_priority(1000).unwrap_err().to_string();
+            assert!(err.contains("1000"), "{err}");
+            assert!(!try_realtime_priority("test", 1000));
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -1,12 +1,20 @@
 //! Multiply stream by a constant value.
+use crate::block::{BlockCommand, BlockLog, Command};
 use crate::stream::{ReadStream, WriteStream};
+use crate::Error;
+
+/// Command to change the value to multiply by.
+pub struct SetMultiplier<T>(pub T);
 
 /// Multiply stream by a constant value.
 ///
+/// The value can be changed while running, by sending a [SetMultiplier]
+/// command.
+///
 /// TODO: replace with a mapper?
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, new, sync)]
-pub struct MultiplyConst<T: Copy + std::ops::Mul<Output = T>> {
+#[rustradio(crate, new, sync, command)]
+pub struct MultiplyConst<T: Copy + std::ops::Mul<Output = T> + 'static> {
     val: T,
     #[rustradio(in)]
     src: ReadStream<T>,
@@ -16,9 +24,74 @@ pub struct MultiplyConst<T: Copy + std::ops::Mul<Output = T>> {
 
 impl<T> MultiplyConst<T>
 where
-    T: Copy + std::ops::Mul<Output = T>,
+    T: Copy + std::ops::Mul<Output = T> + 'static,
 {
     fn process_sync(&self, x: T) -> T {
         x * self.val
     }
 }
+
+impl<T> BlockCommand for MultiplyConst<T>
+where
+    T: Copy + std::ops::Mul<Output = T> + 'static,
+{
+    fn handle_command(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd.downcast::<SetMultiplier<T>>() {
+            Ok(c) => {
+                let SetMultiplier(val) = *c;
+                self.val = val;
+            }
+            Err(_) => self.log_warn(format_args!("Ignoring unknown command")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::graph::{Graph, GraphRunner};
+    use crate::stream::new_stream;
+
+    #[test]
+    fn set_multiplier() -> anyhow::Result<()> {
+        let (w, r) = new_stream::<u32>();
+        let (b, out) = MultiplyConst::new(r, 2);
+        let write = |v: &[u32]| -> anyhow::Result<()> {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(v);
+            wb.produce(v.len(), &[]);
+            Ok(())
+        };
+        let mut g = Graph::new();
+        g.add(Box::new(b));
+
+        write(&[1, 2])?;
+        g.run()?;
+        g.send_command(0, Box::new(SetMultiplier(10u32)))?;
+        write(&[3, 4])?;
+        g.run()?;
+        assert_eq!(out.read_buf()?.0.slice(), &[2, 4, 30, 40]);
+
+        // Wrong type of command is ignored.
+        g.send_command(0, Box::new(SetMultiplier(1.0f32)))?;
+        write(&[5])?;
+        g.run()?;
+        assert_eq!(out.read_buf()?.0.slice(), &[2, 4, 30, 40, 50]);
+        Ok(())
+    }
+
+    #[test]
+    fn direct() -> anyhow::Result<()> {
+        let (w, r) = new_stream::<u32>();
+        let (mut b, out) = MultiplyConst::new(r, 2);
+        b.handle_command(Box::new(SetMultiplier(3u32)))?;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[1]);
+        wb.produce(1, &[]);
+        b.work()?;
+        assert_eq!(out.read_buf()?.0.slice(), &[3]);
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! Interleave samples from several streams into one, and split them up
+//! again.
+use anyhow::Result;
+
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::Error;
+
+/// Interleave samples from several streams into one.
+///
+/// One sample is taken from each input in turn, so inputs `[1, 3, 5]` and
+/// `[2, 4, 6]` become `[1, 2, 3, 4, 5, 6]`. To keep the interleave aligned,
+/// nothing is output until every input has a sample.
+///
+/// Tags are moved to the output position of their sample.
+///
+/// The block reaches EOF when any input does, since no more whole rounds
+/// can be output.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, noeof)]
+pub struct Mux<T: Copy> {
+    #[rustradio(in)]
+    srcs: Vec<ReadStream<T>>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+}
+
+impl<T: Copy> Mux<T> {
+    /// Create new Mux block.
+    ///
+    /// Panics if there are no inputs.
+    pub fn new(srcs: Vec<ReadStream<T>>) -> (Self, ReadStream<T>) {
+        assert!(!srcs.is_empty(), "Mux: needs at least one input");
+        let (dst, dr) = crate::stream::new_stream();
+        (Self { srcs, dst }, dr)
+    }
+}
+
+impl<T: Copy> BlockEOF for Mux<T> {
+    fn eof(&mut self) -> bool {
+        self.srcs.iter().any(ReadStream::eof)
+    }
+}
+
+impl<T: Copy> Block for Mux<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let ins = self
+            .srcs
+            .iter()
+            .map(ReadStream::read_buf)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut o = self.dst.write_buf()?;
+        let k = ins.len();
+        let n = ins
+            .iter()
+            .map(|(i, _)| i.len())
+            .fold(o.len() / k, std::cmp::min);
+        if n == 0 {
+            // Release the buffers, or the inputs never look like EOF.
+            drop(ins);
+            if self.srcs.iter().any(ReadStream::eof) {
+                return Ok(BlockRet::EOF);
+            }
+            return Ok(BlockRet::Noop);
+        }
+        let out = o.slice();
+        let mut otags = Vec::new();
+        for (j, (i, tags)) in ins.iter().enumerate() {
+            for (pos, s) in i.iter().take(n).enumerate() {
+                out[pos * k + j] = *s;
+            }
+            otags.extend(
+                tags.iter()
+                    .filter(|t| t.pos() < n)
+                    .map(|t| Tag::new(t.pos() * k + j, t.key().into(), t.val().clone())),
+            );
+        }
+        otags.sort_by_key(|t| t.pos());
+        for (i, _) in ins {
+            i.consume(n);
+        }
+        o.produce(n * k, &otags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// Split a stream round-robin into several streams.
+///
+/// The inverse of [Mux]. Consecutive samples go to successive outputs, so
+/// with two outputs `[1, 2, 3, 4]` becomes `[1, 3]` and `[2, 4]`. Only
+/// whole rounds are output, and if any output is full, all of them wait.
+///
+/// Tags go with their sample, to the output it goes to.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Demux<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dsts: Vec<WriteStream<T>>,
+}
+
+impl<T: Copy> Demux<T> {
+    /// Create new Demux block, with `outputs` outputs.
+    ///
+    /// Panics if `outputs` is zero.
+    pub fn new(src: ReadStream<T>, outputs: usize) -> (Self, Vec<ReadStream<T>>) {
+        assert!(outputs > 0, "Demux: needs at least one output");
+        let (dsts, drs) = (0..outputs).map(|_| crate::stream::new_stream()).unzip();
+        (Self { src, dsts }, drs)
+    }
+}
+
+impl<T: Copy> Block for Demux<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let k = self.dsts.len();
+        let rounds = i.len() / k;
+        if rounds == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let mut outs = self
+            .dsts
+            .iter()
+            .map(WriteStream::write_buf)
+            .collect::<Result<Vec<_>, _>>()?;
+        let n = outs.iter().map(|o| o.len()).fold(rounds, std::cmp::min);
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        for (j, o) in outs.iter_mut().enumerate() {
+            for (to, from) in o
+                .slice()
+                .iter_mut()
+                .zip(i.iter().skip(j).step_by(k).take(n))
+            {
+                *to = *from;
+            }
+        }
+        let mut otags = vec![Vec::new(); k];
+        for t in tags.into_iter().filter(|t| t.pos() < n * k) {
+            otags[t.pos() % k].push(Tag::new(t.pos() / k, t.key().into(), t.val().clone()));
+        }
+        i.consume(n * k);
+        for (o, tags) in outs.into_iter().zip(otags) {
+            o.produce(n, &tags);
+        }
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TagValue;
+
+    fn write(w: &WriteStream<u32>, data: &[u32], tags: &[Tag]) -> Result<()> {
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(data);
+        wb.produce(data.len(), tags);
+        Ok(())
+    }
+
+    #[test]
+    fn interleave() -> Result<()> {
+        let (w1, r1) = crate::stream::new_stream();
+        let (w2, r2) = crate::stream::new_stream();
+        let (mut b, out) = Mux::new(vec![r1, r2]);
+
+        // Nothing until both inputs have data.
+        write(&w1, &[1, 3, 5], &[])?;
+        assert_eq!(b.work()?, BlockRet::Noop);
+        assert!(out.read_buf()?.0.is_empty());
+
+        let tag = Tag::new(1, "t".into(), TagValue::Bool(true));
+        write(&w2, &[2, 4], &[tag])?;
+        assert_eq!(b.work()?, BlockRet::Ok);
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[1, 2, 3, 4]);
+        assert_eq!(tags, &[Tag::new(3, "t".into(), TagValue::Bool(true))]);
+        res.consume(4);
+
+        // 5 waits for its partner.
+        assert_eq!(b.work()?, BlockRet::Noop);
+        write(&w2, &[6], &[])?;
+        assert_eq!(b.work()?, BlockRet::Ok);
+        assert_eq!(out.read_buf()?.0.slice(), &[5, 6]);
+
+        drop(w1);
+        assert_eq!(b.work()?, BlockRet::EOF);
+        Ok(())
+    }
+
+    #[test]
+    fn demux() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, outs) = Demux::new(r, 2);
+        let tag = Tag::new(3, "t".into(), TagValue::Bool(true));
+        write(&w, &[1, 2, 3, 4, 5], &[tag])?;
+        assert_eq!(b.work()?, BlockRet::Ok);
+        let (res, _) = outs[0].read_buf()?;
+        assert_eq!(res.slice(), &[1, 3]);
+        let (res, tags) = outs[1].read_buf()?;
+        assert_eq!(res.slice(), &[2, 4]);
+        assert_eq!(tags, &[Tag::new(1, "t".into(), TagValue::Bool(true))]);
+
+        // 5 waits for the rest of its round.
+        assert_eq!(b.work()?, BlockRet::Noop);
+        write(&w, &[6], &[])?;
+        assert_eq!(b.work()?, BlockRet::Ok);
+        assert_eq!(outs[0].read_buf()?.0.slice(), &[1, 3, 5]);
+        assert_eq!(outs[1].read_buf()?.0.slice(), &[2, 4, 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn demux_backpressure() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, outs) = Demux::new(r, 2);
+        // Fill up both outputs, then drain the first.
+        let size = w.free();
+        for _ in 0..2 {
+            write(&w, &vec![7; size], &[])?;
+            assert_eq!(b.work()?, BlockRet::Ok);
+        }
+        assert_eq!(outs[1].available(), size);
+        let (res, _) = outs[0].read_buf()?;
+        res.consume(size);
+
+        write(&w, &[1, 2], &[])?;
+        assert_eq!(b.work()?, BlockRet::OutputFull);
+        // Making room in the full output lets the block continue.
+        let (res, _) = outs[1].read_buf()?;
+        res.consume(1);
+        assert_eq!(b.work()?, BlockRet::Ok);
+        assert_eq!(outs[0].read_buf()?.0.slice(), &[1]);
+        Ok(())
+    }
+}
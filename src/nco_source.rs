@@ -0,0 +1,218 @@
+/*! Numerically controlled oscillator source.
+
+Generates a complex tone whose frequency can be changed while the graph is
+running, via an [NcoHandle]. Typically used as a mixing reference.
+
+The first sample generated after a frequency change is tagged with
+[TAG_FREQUENCY], so that downstream blocks know exactly where the change
+took effect.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockCommand, BlockLog, BlockRet, Command};
+use crate::param::Param;
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Tag key for the new frequency, in Hz, after a frequency change.
+pub const TAG_FREQUENCY: &str = "signal:frequency";
+
+/// Handle for changing the frequency of a running [NcoSource].
+#[derive(Clone)]
+pub struct NcoHandle {
+    samp_rate: f64,
+    // Radians per sample.
+    rad_per_sample: Param,
+}
+
+impl NcoHandle {
+    /// Set new frequency, in Hz.
+    ///
+    /// Takes effect from the next call to the block's `work()`.
+    pub fn set_frequency(&self, freq: f64) {
+        self.rad_per_sample
+            .set(2.0 * std::f64::consts::PI * freq / self.samp_rate);
+    }
+
+    /// Get current frequency, in Hz.
+    pub fn frequency(&self) -> f64 {
+        self.to_frequency(self.rad())
+    }
+
+    fn to_frequency(&self, rad: f64) -> f64 {
+        rad * self.samp_rate / (2.0 * std::f64::consts::PI)
+    }
+
+    fn rad(&self) -> f64 {
+        self.rad_per_sample.get()
+    }
+}
+
+/// Command to set a new frequency, in Hz.
+pub struct SetFrequency(pub f64);
+
+/// Generate a unit amplitude complex tone, at a runtime settable frequency.
+///
+/// The frequency can be changed either via an [NcoHandle], or by sending
+/// a [SetFrequency] command. The phase is continuous across frequency
+/// changes.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, command)]
+pub struct NcoSource {
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    handle: NcoHandle,
+    phase: f64,
+    // Radians per sample used in the last call to work().
+    last_rad: f64,
+}
+
+impl NcoSource {
+    /// Create new NcoSource block.
+    pub fn new(samp_rate: f64, freq: f64) -> (Self, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        let handle = NcoHandle {
+            samp_rate,
+            rad_per_sample: Param::new(0.0),
+        };
+        handle.set_frequency(freq);
+        let last_rad = handle.rad();
+        (
+            Self {
+                dst,
+                handle,
+                phase: 0.0,
+                last_rad,
+            },
+            dr,
+        )
+    }
+
+    /// Return a handle for changing the frequency.
+    pub fn handle(&self) -> NcoHandle {
+        self.handle.clone()
+    }
+}
+
+impl Block for NcoSource {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let rad = self.handle.rad();
+        let mut o = self.dst.write_buf()?;
+        let n = o.len();
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let mut tags = Vec::new();
+        if rad != self.last_rad {
+            let freq = self.handle.to_frequency(rad);
+            tags.push(Tag::new(
+                0,
+                TAG_FREQUENCY.into(),
+                TagValue::Float(freq as Float),
+            ));
+            self.last_rad = rad;
+        }
+        for to in o.slice().iter_mut() {
+            *to = Complex::new(self.phase.cos() as Float, self.phase.sin() as Float);
+            self.phase = (self.phase + rad).rem_euclid(2.0 * std::f64::consts::PI);
+        }
+        o.produce(n, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+impl BlockCommand for NcoSource {
+    fn handle_command(&mut self, cmd: Command) -> Result<(), Error> {
+        match cmd.downcast::<SetFrequency>() {
+            Ok(c) => {
+                let SetFrequency(freq) = *c;
+                self.handle.set_frequency(freq);
+            }
+            Err(_) => self.log_warn(format_args!("Ignoring unknown command")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(r: &ReadStream<Complex>) -> Result<Vec<Complex>> {
+        let (b, _) = r.read_buf()?;
+        let ret = b.slice().to_vec();
+        b.consume(ret.len());
+        Ok(ret)
+    }
+
+    #[test]
+    fn change_frequency() -> Result<()> {
+        let (mut b, out) = NcoSource::new(1000.0, 10.0);
+        let h = b.handle();
+        b.work()?;
+        let first = read_all(&out)?;
+        h.set_frequency(-50.0);
+        assert!((h.frequency() + 50.0).abs() < 1e-9);
+        b.work()?;
+        let second = read_all(&out)?;
+
+        let step = |a: Complex, b: Complex| (b * a.conj()).arg();
+        let old = 2.0 * std::f32::consts::PI * 10.0 / 1000.0;
+        let new = -2.0 * std::f32::consts::PI * 50.0 / 1000.0;
+        for (i, w) in first.windows(2).enumerate() {
+            assert!((step(w[0], w[1]) - old).abs() < 1e-4, "first {i}");
+            assert!((w[1].norm() - 1.0).abs() < 1e-4);
+        }
+        // The last sample generated before the change already advanced the
+        // phase with the old frequency. No jump.
+        let boundary = step(*first.last().unwrap(), second[0]);
+        assert!((boundary - old).abs() < 1e-4, "boundary {boundary}");
+        for (i, w) in second.windows(2).enumerate() {
+            assert!((step(w[0], w[1]) - new).abs() < 1e-4, "second {i}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn frequency_tag() -> Result<()> {
+        let (mut b, out) = NcoSource::new(1000.0, 10.0);
+        let h = b.handle();
+        b.work()?;
+        {
+            let (i, tags) = out.read_buf()?;
+            assert!(tags.is_empty(), "{tags:?}");
+            // Leave some samples unread, so the tag isn't at the start of
+            // the read window.
+            let n = i.len();
+            i.consume(n - 7);
+        }
+        h.set_frequency(-50.0);
+        b.work()?;
+        let (i, tags) = out.read_buf()?;
+        assert_eq!(tags.len(), 1, "{tags:?}");
+        assert_eq!(tags[0].pos(), 7);
+        assert_eq!(tags[0].key(), TAG_FREQUENCY);
+        let TagValue::Float(freq) = tags[0].val() else {
+            panic!("wrong tag type {:?}", tags[0]);
+        };
+        assert!((freq + 50.0).abs() < 1e-3, "{freq}");
+        let n = i.len();
+        i.consume(n);
+
+        // No change, no tag.
+        b.work()?;
+        assert!(out.read_buf()?.1.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn command() -> Result<()> {
+        let (mut b, _out) = NcoSource::new(1000.0, 10.0);
+        b.handle_command(Box::new(SetFrequency(20.0)))?;
+        assert!((b.handle().frequency() - 20.0).abs() < 1e-9);
+        // Unknown commands are ignored.
+        b.handle_command(Box::new(30.0f64))?;
+        assert!((b.handle().frequency() - 20.0).abs() < 1e-9);
+        Ok(())
+    }
+}
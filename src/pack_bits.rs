@@ -0,0 +1,544 @@
+/*! Pack bits into bytes or symbols, and unpack them into bits.
+
+Demodulators output one bit per `u8`, with value 0 or 1. Framers and files
+often want packed bytes instead.
+
+For M-ary modulation, [BitsToSymbols] groups `log2(M)` bits into a symbol
+index, and [SymbolsToBits] splits them up again.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::Error;
+
+/// Order of bits within a byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitOrder {
+    /// Most significant bit first.
+    #[default]
+    MsbFirst,
+
+    /// Least significant bit first.
+    LsbFirst,
+}
+
+/// Pack bits into bytes.
+///
+/// Input samples are bits, and only the lowest bit of each is used. Bits
+/// that don't yet make a whole byte are kept until the next call to
+/// `work()`.
+///
+/// Tags are moved to the byte their bit ends up in. Tags on bits of a byte
+/// not yet complete are kept, and output with the byte.
+///
+/// On EOF, a trailing partial byte is padded with zero bits and output,
+/// along with its tags.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, flush)]
+pub struct BitsToBytes {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    order: BitOrder,
+    // Bits received, but not yet output.
+    #[rustradio(default)]
+    partial: u8,
+    #[rustradio(default)]
+    partial_bits: usize,
+    #[rustradio(default)]
+    pending_tags: Vec<Tag>,
+}
+
+// Move tags to the output sample their bit ends up in, given that
+// `start_bits` bits were already kept from before, and `bits` bits make an
+// output sample. Also used by decimators, with input samples as "bits".
+//
+// Tags on the bits of an output sample not yet complete are kept in
+// `pending`, and returned when it's output.
+pub(crate) fn group_tags(
+    tags: Vec<Tag>,
+    n: usize,
+    start_bits: usize,
+    bits: usize,
+    produced: usize,
+    pending: &mut Vec<Tag>,
+) -> Vec<Tag> {
+    let (out, rest): (Vec<_>, Vec<_>) = std::mem::take(pending)
+        .into_iter()
+        .chain(tags.into_iter().filter(|t| t.pos() < n).map(|t| {
+            Tag::new(
+                (start_bits + t.pos()) / bits,
+                t.key().into(),
+                t.val().clone(),
+            )
+        }))
+        .partition(|t| t.pos() < produced);
+    // The incomplete output sample is the first one next time.
+    *pending = rest
+        .into_iter()
+        .map(|t| Tag::new(0, t.key().into(), t.val().clone()))
+        .collect();
+    out
+}
+
+impl BitsToBytes {
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.partial_bits == 0 {
+            return Ok(());
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.is_empty() {
+            return Ok(());
+        }
+        let pad = 8 - self.partial_bits;
+        o.slice()[0] = match self.order {
+            BitOrder::MsbFirst => self.partial << pad,
+            BitOrder::LsbFirst => self.partial >> pad,
+        };
+        o.produce(1, &std::mem::take(&mut self.pending_tags));
+        self.partial = 0;
+        self.partial_bits = 0;
+        Ok(())
+    }
+}
+
+impl Block for BitsToBytes {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        // Consume no more than fits in the output.
+        let n = std::cmp::min(i.len(), (o.len() * 8).saturating_sub(self.partial_bits));
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let start_bits = self.partial_bits;
+        let mut produced = 0;
+        for &bit in i.iter().take(n) {
+            let bit = bit & 1;
+            self.partial = match self.order {
+                BitOrder::MsbFirst => (self.partial << 1) | bit,
+                BitOrder::LsbFirst => (self.partial >> 1) | (bit << 7),
+            };
+            self.partial_bits += 1;
+            if self.partial_bits == 8 {
+                o.slice()[produced] = self.partial;
+                produced += 1;
+                self.partial = 0;
+                self.partial_bits = 0;
+            }
+        }
+        let tags = group_tags(tags, n, start_bits, 8, produced, &mut self.pending_tags);
+        i.consume(n);
+        o.produce(produced, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// Unpack bytes into bits.
+///
+/// Each input byte becomes eight output samples, each 0 or 1. Tags are
+/// moved to the first bit of their byte.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct BytesToBits {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    order: BitOrder,
+}
+
+impl Block for BytesToBits {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len() / 8);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        for (byte, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(8)) {
+            for (bit, to) in out.iter_mut().enumerate() {
+                let shift = match self.order {
+                    BitOrder::MsbFirst => 7 - bit,
+                    BitOrder::LsbFirst => bit,
+                };
+                *to = (byte >> shift) & 1;
+            }
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|t| Tag::new(t.pos() * 8, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(n);
+        o.produce(n * 8, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+// Return bits per symbol, for M symbols.
+fn bits_per_symbol(name: &str, m: usize) -> Result<usize> {
+    if !m.is_power_of_two() || !(2..=256).contains(&m) {
+        return Err(Error::new(&format!(
+            "{name}: M must be a power of two between 2 and 256, got {m}"
+        ))
+        .into());
+    }
+    Ok(m.trailing_zeros() as usize)
+}
+
+/// Group bits into M-ary symbols.
+///
+/// Each output sample is a symbol index from `0` to `M-1`, made from
+/// `log2(M)` input bits. `order` is the order of the bits within the
+/// symbol. Like [BitsToBytes], bits of a symbol not yet complete are kept
+/// until the next call to `work()`, and tags are moved to the symbol their
+/// bit ends up in. On EOF, a trailing partial symbol is padded with zero
+/// bits and output, along with its tags.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, flush)]
+pub struct BitsToSymbols {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    order: BitOrder,
+    bits: usize,
+    partial: u8,
+    partial_bits: usize,
+    pending_tags: Vec<Tag>,
+}
+
+impl BitsToSymbols {
+    /// Create new BitsToSymbols block.
+    pub fn new(src: ReadStream<u8>, m: usize, order: BitOrder) -> Result<(Self, ReadStream<u8>)> {
+        let bits = bits_per_symbol("BitsToSymbols", m)?;
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                order,
+                bits,
+                partial: 0,
+                partial_bits: 0,
+                pending_tags: Vec::new(),
+            },
+            dr,
+        ))
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.partial_bits == 0 {
+            return Ok(());
+        }
+        let mut o = self.dst.write_buf()?;
+        if o.is_empty() {
+            return Ok(());
+        }
+        o.slice()[0] = match self.order {
+            BitOrder::MsbFirst => self.partial << (self.bits - self.partial_bits),
+            // Missing high bits are already zero.
+            BitOrder::LsbFirst => self.partial,
+        };
+        o.produce(1, &std::mem::take(&mut self.pending_tags));
+        self.partial = 0;
+        self.partial_bits = 0;
+        Ok(())
+    }
+}
+
+impl Block for BitsToSymbols {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        // Consume no more than fits in the output.
+        let n = std::cmp::min(
+            i.len(),
+            (o.len() * self.bits).saturating_sub(self.partial_bits),
+        );
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let start_bits = self.partial_bits;
+        let mut produced = 0;
+        for &bit in i.iter().take(n) {
+            let bit = bit & 1;
+            self.partial = match self.order {
+                BitOrder::MsbFirst => (self.partial << 1) | bit,
+                BitOrder::LsbFirst => self.partial | (bit << self.partial_bits),
+            };
+            self.partial_bits += 1;
+            if self.partial_bits == self.bits {
+                o.slice()[produced] = self.partial;
+                produced += 1;
+                self.partial = 0;
+                self.partial_bits = 0;
+            }
+        }
+        let tags = group_tags(
+            tags,
+            n,
+            start_bits,
+            self.bits,
+            produced,
+            &mut self.pending_tags,
+        );
+        i.consume(n);
+        o.produce(produced, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+/// Split M-ary symbols into bits.
+///
+/// Each input symbol index becomes `log2(M)` output samples, each 0 or 1.
+/// Bits above `log2(M)` are ignored. Tags are moved to the first bit of
+/// their symbol.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct SymbolsToBits {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    order: BitOrder,
+    bits: usize,
+}
+
+impl SymbolsToBits {
+    /// Create new SymbolsToBits block.
+    pub fn new(src: ReadStream<u8>, m: usize, order: BitOrder) -> Result<(Self, ReadStream<u8>)> {
+        let bits = bits_per_symbol("SymbolsToBits", m)?;
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                order,
+                bits,
+            },
+            dr,
+        ))
+    }
+}
+
+impl Block for SymbolsToBits {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len() / self.bits);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        for (sym, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(self.bits)) {
+            for (bit, to) in out.iter_mut().enumerate() {
+                let shift = match self.order {
+                    BitOrder::MsbFirst => self.bits - 1 - bit,
+                    BitOrder::LsbFirst => bit,
+                };
+                *to = (sym >> shift) & 1;
+            }
+        }
+        let tags: Vec<_> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n)
+            .map(|t| Tag::new(t.pos() * self.bits, t.key().into(), t.val().clone()))
+            .collect();
+        i.consume(n);
+        o.produce(n * self.bits, &tags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::stream::TagValue;
+
+    fn write(w: &WriteStream<u8>, data: &[u8]) -> Result<()> {
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(data);
+        wb.produce(data.len(), &[]);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let input = vec![0x00u8, 0xff, 0x80, 0x01, 0xa5, 0x3c];
+        for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let (mut src, prev) = VectorSource::new(input.clone());
+            let (mut unpack, prev) = BytesToBits::new(prev, order);
+            let (mut pack, out) = BitsToBytes::new(prev, order);
+            src.work()?;
+            unpack.work()?;
+            pack.work()?;
+            let (res, _) = out.read_buf()?;
+            assert_eq!(res.slice(), input, "{order:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unpack_order() -> Result<()> {
+        for (order, want) in [
+            (BitOrder::MsbFirst, [1, 0, 1, 1, 0, 0, 0, 0]),
+            (BitOrder::LsbFirst, [0, 0, 0, 0, 1, 1, 0, 1]),
+        ] {
+            let (mut src, prev) = VectorSource::new(vec![0xb0u8]);
+            let (mut b, out) = BytesToBits::new(prev, order);
+            src.work()?;
+            b.work()?;
+            let (res, _) = out.read_buf()?;
+            assert_eq!(res.slice(), want, "{order:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn partial_byte() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = BitsToBytes::new(r, BitOrder::MsbFirst);
+        write(&w, &[1, 0, 1])?;
+        b.work()?;
+        assert!(out.read_buf()?.0.is_empty());
+        write(&w, &[1, 0, 0, 0, 0, 1, 1, 1, 1])?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0xb0]);
+        res.consume(1);
+        // Four bits left over.
+        write(&w, &[0, 0, 0, 0])?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0xf0]);
+        Ok(())
+    }
+
+    #[test]
+    fn partial_byte_tags() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = BitsToBytes::new(r, BitOrder::MsbFirst);
+        let tag = Tag::new(0, "t".into(), TagValue::Bool(true));
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[1, 0, 1, 1, 0, 0, 0, 0, 1, 1]);
+        wb.produce(10, &[Tag::new(9, "t".into(), TagValue::Bool(true))]);
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0xb0]);
+        assert!(tags.is_empty());
+        res.consume(1);
+
+        // The tag goes with the byte its bit is in.
+        write(&w, &[1, 1])?;
+        b.work()?;
+        assert!(out.read_buf()?.1.is_empty());
+        write(&w, &[0, 0, 0, 0])?;
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0xf0]);
+        assert_eq!(tags, &[tag]);
+        Ok(())
+    }
+
+    // Run a block on tagged input until EOF, in a graph, so that any
+    // trailing partial output is flushed.
+    fn run_to_eof<B: Block + Send + 'static>(
+        input: &[u8],
+        tags: &[Tag],
+        make: impl FnOnce(ReadStream<u8>) -> Result<(B, ReadStream<u8>)>,
+    ) -> Result<(Vec<u8>, Vec<Tag>)> {
+        use crate::graph::{Graph, GraphRunner};
+        let (w, r) = crate::stream::new_stream();
+        let (b, out) = make(r)?;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(input);
+        wb.produce(input.len(), tags);
+        drop(w);
+        let mut g = Graph::new();
+        g.add(Box::new(b));
+        g.run()?;
+        let (res, tags) = out.read_buf()?;
+        Ok((res.slice().to_vec(), tags))
+    }
+
+    #[test]
+    fn partial_byte_eof() -> Result<()> {
+        let tag = |pos| Tag::new(pos, "t".into(), TagValue::Bool(true));
+        for (order, want) in [
+            (BitOrder::MsbFirst, [0xb0, 0xc0]),
+            (BitOrder::LsbFirst, [0x0d, 0x03]),
+        ] {
+            let (res, tags) = run_to_eof(&[1, 0, 1, 1, 0, 0, 0, 0, 1, 1], &[tag(9)], |prev| {
+                Ok(BitsToBytes::new(prev, order))
+            })?;
+            assert_eq!(res, want, "{order:?}");
+            assert_eq!(tags, &[tag(1)], "{order:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn symbols_round_trip() -> Result<()> {
+        // Pseudo random bits.
+        let bits: Vec<u8> = (0..1200u32).map(|i| ((i * 7919) >> 3) as u8 & 1).collect();
+        for m in [4, 16] {
+            for order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                let (w, r) = crate::stream::new_stream();
+                let (mut group, prev) = BitsToSymbols::new(r, m, order)?;
+                let (mut ungroup, out) = SymbolsToBits::new(prev, m, order)?;
+                // Odd sized writes, splitting symbols across calls.
+                for chunk in bits.chunks(7) {
+                    write(&w, chunk)?;
+                    group.work()?;
+                    ungroup.work()?;
+                }
+                let (res, _) = out.read_buf()?;
+                assert_eq!(res.slice(), bits, "M={m} {order:?}");
+            }
+        }
+        // First symbols of M=16 are the nibbles of the first byte.
+        let (mut src, prev) = VectorSource::new(vec![1, 0, 1, 1, 0, 0, 1, 0]);
+        let (mut b, out) = BitsToSymbols::new(prev, 16, BitOrder::MsbFirst)?;
+        src.work()?;
+        b.work()?;
+        assert_eq!(out.read_buf()?.0.slice(), &[0xb, 0x2]);
+        assert!(BitsToSymbols::new(out, 6, BitOrder::MsbFirst).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn partial_symbol_tags() -> Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = BitsToSymbols::new(r, 4, BitOrder::MsbFirst)?;
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[1, 0, 1]);
+        wb.produce(3, &[Tag::new(2, "t".into(), TagValue::Bool(true))]);
+        b.work()?;
+        write(&w, &[1])?;
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[2, 3]);
+        assert_eq!(tags, &[Tag::new(1, "t".into(), TagValue::Bool(true))]);
+        Ok(())
+    }
+
+    #[test]
+    fn partial_symbol_eof() -> Result<()> {
+        let tag = |pos| Tag::new(pos, "t".into(), TagValue::Bool(true));
+        for (order, want) in [
+            (BitOrder::MsbFirst, [0xb, 0xc]),
+            (BitOrder::LsbFirst, [0xd, 0x3]),
+        ] {
+            let (res, tags) = run_to_eof(&[1, 0, 1, 1, 1, 1], &[tag(5)], |prev| {
+                BitsToSymbols::new(prev, 16, order)
+            })?;
+            assert_eq!(res, want, "{order:?}");
+            assert_eq!(tags, &[tag(1)], "{order:?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Block parameters that can be changed while the graph is running.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// An `f64` parameter, shared between a block and its handles.
+///
+/// Clones share the value. A block keeps one, and hands out clones wrapped
+/// in its own handle type, so that the application can change the parameter
+/// from another thread. A change is seen the next time the block reads the
+/// value, typically at the start of its next `work()` call.
+#[derive(Clone, Debug)]
+pub struct Param {
+    // Stored as f64 bits.
+    value: Arc<AtomicU64>,
+}
+
+impl Param {
+    /// Create new parameter, with initial value.
+    pub fn new(value: f64) -> Self {
+        Self {
+            value: Arc::new(AtomicU64::new(value.to_bits())),
+        }
+    }
+
+    /// Set new value.
+    pub fn set(&self, value: f64) {
+        self.value.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Get current value.
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.value.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared() {
+        let a = Param::new(1.5);
+        let b = a.clone();
+        assert_eq!(b.get(), 1.5);
+        b.set(-2.25);
+        assert_eq!(a.get(), -2.25);
+    }
+}
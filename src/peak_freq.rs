@@ -0,0 +1,165 @@
+/*! Find the peak frequency of FFT frames.
+
+Takes magnitude frames, e.g. from [FftStream][crate::fft_stream::FftStream]
+followed by a magnitude block, and outputs the frequency of the strongest
+bin of each frame.
+*/
+use crate::block::{Block, BlockRet};
+use crate::fft::bin_to_freq;
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::{Error, Float};
+
+/// Tag key for the peak frequency, in Hz.
+pub const TAG_PEAK_FREQ: &str = "peak_freq";
+
+/// Peak frequency block.
+///
+/// Outputs one sample per `size` input samples, which is the frequency of
+/// the highest bin, tagged with [TAG_PEAK_FREQ]. Parabolic interpolation
+/// using the neighbouring bins gives sub-bin accuracy. It works best on
+/// windowed FFT output.
+///
+/// Input frames are either in FFT order (DC first), or fftshifted (most
+/// negative frequency first).
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct PeakFreq {
+    #[rustradio(in)]
+    src: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    size: usize,
+    samp_rate: f64,
+    shifted: bool,
+}
+
+impl PeakFreq {
+    /// Create new PeakFreq block, for frames of `size` bins.
+    ///
+    /// Returns error if `size` is zero.
+    pub fn new(
+        src: ReadStream<Float>,
+        size: usize,
+        samp_rate: f64,
+        shifted: bool,
+    ) -> Result<(Self, ReadStream<Float>), Error> {
+        if size == 0 {
+            return Err(Error::new("PeakFreq: size must not be zero"));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                size,
+                samp_rate,
+                shifted,
+            },
+            dr,
+        ))
+    }
+
+    /// Return the interpolated peak frequency of a frame.
+    pub fn peak(&self, frame: &[Float]) -> Float {
+        let n = frame.len();
+        let bin = frame
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        // Neighbouring bins wrap around, in both orders.
+        let a = frame[(bin + n - 1) % n];
+        let b = frame[bin];
+        let c = frame[(bin + 1) % n];
+        let den = a - 2.0 * b + c;
+        let delta = if den == 0.0 { 0.0 } else { 0.5 * (a - c) / den };
+        let width = self.samp_rate / n as f64;
+        (bin_to_freq(bin, n, self.samp_rate, self.shifted) + delta as f64 * width) as Float
+    }
+}
+
+impl Block for PeakFreq {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let mut ret = BlockRet::Noop;
+        loop {
+            let (i, _tags) = self.src.read_buf()?;
+            if i.len() < self.size {
+                return Ok(ret);
+            }
+            let mut o = self.dst.write_buf()?;
+            if o.is_empty() {
+                return Ok(BlockRet::OutputFull);
+            }
+            let freq = self.peak(&i.slice()[..self.size]);
+            o.slice()[0] = freq;
+            o.produce(
+                1,
+                &[Tag::new(0, TAG_PEAK_FREQ.into(), TagValue::Float(freq))],
+            );
+            i.consume(self.size);
+            ret = BlockRet::Ok;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+    use crate::fft::{FftBackend, RustFft};
+    use crate::Complex;
+
+    // Magnitudes of the Hann windowed FFT of a tone.
+    fn frame(freq: f64, size: usize, samp_rate: f64, shifted: bool) -> Vec<Float> {
+        let mut buf: Vec<Complex> = (0..size)
+            .map(|n| {
+                let w = 0.5 - 0.5 * (2.0 * std::f64::consts::PI * n as f64 / size as f64).cos();
+                let ph = 2.0 * std::f64::consts::PI * freq * n as f64 / samp_rate;
+                Complex::from_polar(w as Float, ph as Float)
+            })
+            .collect();
+        RustFft::new(size).process(&mut buf);
+        if shifted {
+            buf.rotate_right(size / 2);
+        }
+        buf.iter().map(|c| c.norm()).collect()
+    }
+
+    #[test]
+    fn tone() -> crate::Result<()> {
+        let size = 256;
+        let samp_rate = 8000.0;
+        let width = samp_rate / size as f64;
+        for shifted in [false, true] {
+            let freqs = [1000.0, 1234.5, -2010.0, 3.0, 3900.0];
+            let input: Vec<Float> = freqs
+                .iter()
+                .flat_map(|&f| frame(f, size, samp_rate, shifted))
+                .collect();
+            let (mut src, prev) = VectorSource::new(input);
+            let (mut b, out) = PeakFreq::new(prev, size, samp_rate, shifted)?;
+            src.work()?;
+            b.work()?;
+            let (res, tags) = out.read_buf()?;
+            assert_eq!(res.len(), freqs.len());
+            assert_eq!(tags.len(), freqs.len());
+            for (n, (got, want)) in res.iter().zip(freqs).enumerate() {
+                let err = (*got as f64 - want).abs();
+                assert!(
+                    err < 0.1 * width,
+                    "shifted {shifted}: got {got}, want {want}"
+                );
+                assert_eq!(tags[n].pos(), n);
+                assert_eq!(tags[n].val(), &TagValue::Float(*got));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn zero_size() {
+        let (_, prev) = crate::stream::new_stream();
+        assert!(PeakFreq::new(prev, 0, 8000.0, false).is_err());
+    }
+}
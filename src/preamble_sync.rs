@@ -0,0 +1,192 @@
+/*! Find a known preamble, and output the payload following it.
+
+Correlates the input against the preamble, one sample per symbol. When the
+normalized correlation goes above a threshold, the strongest peak within
+one preamble length is taken as the packet start, and the fixed length
+payload after the preamble is output as a PDU.
+*/
+use log::debug;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagValue};
+use crate::{Complex, Error, Float};
+
+/// Tag key for the normalized correlation of the preamble, from 0 to 1.
+pub const TAG_CORRELATION: &str = "preamble:correlation";
+
+/// Tag key for the estimated phase of the preamble, in radians.
+///
+/// Multiply the payload by `e^(-j·phase)` to derotate it.
+pub const TAG_PHASE: &str = "preamble:phase";
+
+/// Tag key for the stream position where the preamble starts.
+pub const TAG_OFFSET: &str = "preamble:offset";
+
+/// Preamble detector, outputting the payloads that follow.
+///
+/// Payloads are output as is, not derotated. The correlation, phase, and
+/// position are added as tags at position 0.
+///
+/// Preambles that are closer to the end of the stream than a preamble
+/// length plus the payload are never found.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct PreambleSync {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<Complex>>,
+    // Conjugated preamble.
+    preamble: Vec<Complex>,
+    preamble_energy: Float,
+    payload_len: usize,
+    threshold: Float,
+    // Stream position of the first sample in the input buffer.
+    pos: u64,
+}
+
+impl PreambleSync {
+    /// Create new PreambleSync block.
+    ///
+    /// `threshold` is the normalized correlation needed for a match, from
+    /// 0 to 1.
+    pub fn new(
+        src: ReadStream<Complex>,
+        preamble: &[Complex],
+        payload_len: usize,
+        threshold: Float,
+    ) -> (Self, NCReadStream<Vec<Complex>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (
+            Self {
+                src,
+                dst,
+                preamble: preamble.iter().map(|s| s.conj()).collect(),
+                preamble_energy: preamble.iter().map(|s| s.norm_sqr()).sum(),
+                payload_len,
+                threshold,
+                pos: 0,
+            },
+            dr,
+        )
+    }
+
+    // Normalized correlation of the preamble at the start of `data`.
+    fn correlate(&self, data: &[Complex]) -> (Float, Complex) {
+        let (sum, energy) = data
+            .iter()
+            .zip(&self.preamble)
+            .fold((Complex::default(), 0.0), |(sum, energy), (s, p)| {
+                (sum + s * p, energy + s.norm_sqr())
+            });
+        let norm = (energy * self.preamble_energy).sqrt();
+        if norm == 0.0 {
+            return (0.0, sum);
+        }
+        (sum.norm() / norm, sum)
+    }
+}
+
+impl Block for PreambleSync {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (input, _) = self.src.read_buf()?;
+        let plen = self.preamble.len();
+        // Room to search one preamble length for the peak, and then the
+        // preamble and payload.
+        let need = 2 * plen + self.payload_len;
+        let data = input.slice();
+        if data.len() < need {
+            return Ok(BlockRet::Noop);
+        }
+        let mut k = 0;
+        while k + need <= data.len() {
+            let (c, _) = self.correlate(&data[k..]);
+            if c < self.threshold {
+                k += 1;
+                continue;
+            }
+            // Overlapping matches: take the strongest.
+            let (peak, c, sum) = (k..k + plen.max(1))
+                .map(|m| {
+                    let (c, sum) = self.correlate(&data[m..]);
+                    (m, c, sum)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap(); // unwrap: range is never empty.
+            let offset = self.pos + peak as u64;
+            debug!("PreambleSync: found preamble at {offset}, correlation {c}");
+            let start = peak + plen;
+            let payload = data[start..start + self.payload_len].to_vec();
+            self.dst.push(
+                payload,
+                &[
+                    Tag::new(0, TAG_CORRELATION.into(), TagValue::Float(c)),
+                    Tag::new(0, TAG_PHASE.into(), TagValue::Float(sum.arg())),
+                    Tag::new(0, TAG_OFFSET.into(), TagValue::U64(offset)),
+                ],
+            );
+            k = start + self.payload_len;
+        }
+        input.consume(k);
+        self.pos += k as u64;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    // Deterministic noise, uniform in [-1, 1).
+    fn noise(state: &mut u32) -> Float {
+        *state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (*state >> 8) as Float / (1 << 23) as Float - 1.0
+    }
+
+    fn bpsk(bits: u64, n: usize) -> Vec<Complex> {
+        (0..n)
+            .map(|i| Complex::new(if (bits >> i) & 1 == 1 { 1.0 } else { -1.0 }, 0.0))
+            .collect()
+    }
+
+    #[test]
+    fn find_payload() -> Result<(), Error> {
+        let preamble = bpsk(0xf3a5_1c6d, 32);
+        let payload = bpsk(0x5a17, 16);
+        let phase: Float = 0.7;
+        let rot = Complex::from_polar(1.0, phase);
+
+        let mut state = 1;
+        let mut input: Vec<Complex> = (0..400)
+            .map(|_| Complex::new(noise(&mut state), noise(&mut state)) * 0.1)
+            .collect();
+        for (i, s) in preamble.iter().chain(&payload).enumerate() {
+            input[100 + i] += s * rot;
+        }
+
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = PreambleSync::new(prev, &preamble, payload.len(), 0.7);
+        src.work()?;
+        b.work()?;
+        let (got, tags) = out.pop().expect("no payload found");
+        assert!(out.pop().is_none());
+
+        let tag = |key| tags.iter().find(|t| t.key() == key).unwrap().val().clone();
+        assert_eq!(tag(TAG_OFFSET), TagValue::U64(100));
+        let TagValue::Float(c) = tag(TAG_CORRELATION) else {
+            panic!("bad correlation tag");
+        };
+        assert!(c > 0.9, "correlation {c}");
+        let TagValue::Float(p) = tag(TAG_PHASE) else {
+            panic!("bad phase tag");
+        };
+        assert!((p - phase).abs() < 0.1, "phase {p}");
+
+        let derot = Complex::from_polar(1.0, -p);
+        let bits: Vec<bool> = got.iter().map(|s| (s * derot).re > 0.0).collect();
+        let want: Vec<bool> = payload.iter().map(|s| s.re > 0.0).collect();
+        assert_eq!(bits, want);
+        Ok(())
+    }
+}
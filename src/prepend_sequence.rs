@@ -0,0 +1,88 @@
+/*! Prepend a fixed sequence to each PDU.
+
+Useful on the transmit side, to add a preamble or sync word (as samples or
+bits) before each packet. [PreambleSync][crate::preamble_sync::PreambleSync]
+is the receive side of this.
+*/
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, Tag, TagValue};
+use crate::Error;
+
+/// Tag key marking the start of the sync sequence.
+pub const TAG_SYNC: &str = "sync";
+
+/// Prepend sequence block.
+///
+/// Each output PDU is the sequence followed by the input PDU. It gets a
+/// [TAG_SYNC] tag at position 0, with the sequence length as value. Tags
+/// from the input are moved along with the payload.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct PrependSequence<T: Clone + Send + Sync> {
+    #[rustradio(in)]
+    src: NCReadStream<Vec<T>>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<T>>,
+    seq: Vec<T>,
+}
+
+impl<T: Clone + Send + Sync> PrependSequence<T> {
+    /// Create new PrependSequence block.
+    pub fn new(src: NCReadStream<Vec<T>>, seq: Vec<T>) -> (Self, NCReadStream<Vec<T>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (Self { src, dst, seq }, dr)
+    }
+}
+
+impl<T: Clone + Send + Sync> Block for PrependSequence<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let mut ret = BlockRet::Noop;
+        while let Some((payload, tags)) = self.src.pop() {
+            let n = self.seq.len();
+            let mut out = Vec::with_capacity(n + payload.len());
+            out.extend_from_slice(&self.seq);
+            out.extend(payload);
+            let mut otags = vec![Tag::new(0, TAG_SYNC.into(), TagValue::U64(n as u64))];
+            otags.extend(
+                tags.into_iter()
+                    .map(|t| Tag::new(t.pos() + n, t.key().into(), t.val().clone())),
+            );
+            self.dst.push(out, &otags);
+            ret = BlockRet::Ok;
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::new_nocopy_stream;
+
+    #[test]
+    fn prepend() -> anyhow::Result<()> {
+        let (w, r) = new_nocopy_stream();
+        w.push(
+            vec![1u8, 0, 0, 1],
+            &[Tag::new(1, "id".into(), TagValue::U64(7))],
+        );
+        w.push(vec![1u8], &[]);
+        let (mut b, out) = PrependSequence::new(r, vec![0u8, 1, 1, 0, 1]);
+        b.work()?;
+
+        let (got, tags) = out.pop().expect("missing first PDU");
+        assert_eq!(got, [0, 1, 1, 0, 1, 1, 0, 0, 1]);
+        assert_eq!(
+            tags,
+            [
+                Tag::new(0, TAG_SYNC.into(), TagValue::U64(5)),
+                Tag::new(6, "id".into(), TagValue::U64(7)),
+            ]
+        );
+        let (got, tags) = out.pop().expect("missing second PDU");
+        assert_eq!(got, [0, 1, 1, 0, 1, 1]);
+        assert_eq!(tags, [Tag::new(0, TAG_SYNC.into(), TagValue::U64(5))]);
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+}
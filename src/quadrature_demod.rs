@@ -26,15 +26,43 @@ QuadratureDemod by about 4x.
  */
 use anyhow::Result;
 
+use crate::param::Param;
 use crate::stream::{ReadStream, WriteStream};
 use crate::{Complex, Float};
 
+/// Handle for changing the gain of a running [QuadratureDemod] or [FastFM].
+#[derive(Clone)]
+pub struct GainHandle {
+    gain: Param,
+}
+
+impl GainHandle {
+    fn new(gain: Float) -> Self {
+        Self {
+            gain: Param::new(gain as f64),
+        }
+    }
+
+    /// Set new gain.
+    ///
+    /// Takes effect from the next sample the block processes.
+    pub fn set_gain(&self, gain: Float) {
+        self.gain.set(gain as f64);
+    }
+
+    /// Get current gain.
+    pub fn gain(&self) -> Float {
+        self.gain.get() as Float
+    }
+}
+
 /// Quadrature demod, the core of an FM demodulator.
+///
+/// The gain can be changed while running, via a [GainHandle].
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, new, sync)]
+#[rustradio(crate, sync)]
 pub struct QuadratureDemod {
-    gain: Float,
-    #[rustradio(default)]
+    gain: GainHandle,
     last: Complex,
     #[rustradio(in)]
     src: ReadStream<Complex>,
@@ -43,15 +71,40 @@ pub struct QuadratureDemod {
 }
 
 impl QuadratureDemod {
+    /// Create new QuadratureDemod block.
+    pub fn new(src: ReadStream<Complex>, gain: Float) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                gain: GainHandle::new(gain),
+                last: Complex::default(),
+                src,
+                dst,
+            },
+            dr,
+        )
+    }
+
+    /// Set new gain.
+    pub fn set_gain(&self, gain: Float) {
+        self.gain.set_gain(gain);
+    }
+
+    /// Return a handle for changing the gain.
+    pub fn gain_handle(&self) -> GainHandle {
+        self.gain.clone()
+    }
+
     fn process_sync(&mut self, s: Complex) -> Float {
         let t = s * self.last.conj();
         self.last = s;
+        let gain = self.gain.gain();
 
         #[cfg(feature = "fast-math")]
-        return self.gain * fast_math::atan2(t.im, t.re);
+        return gain * fast_math::atan2(t.im, t.re);
 
         #[cfg(not(feature = "fast-math"))]
-        return self.gain * t.im.atan2(t.re);
+        return gain * t.im.atan2(t.re);
     }
 }
 
@@ -83,25 +136,134 @@ impl QuadratureDemod {
 ///
 /// Lyons has an more general version of this algorithm, also on page
 /// 760, but it's not implemented here.
+///
+/// The gain defaults to 1.0, and can be changed while running, via a
+/// [GainHandle].
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, new, sync)]
+#[rustradio(crate, sync)]
 pub struct FastFM {
     #[rustradio(in)]
     src: ReadStream<Complex>,
     #[rustradio(out)]
     dst: WriteStream<Float>,
-    #[rustradio(default)]
+    gain: GainHandle,
     q1: Complex,
-    #[rustradio(default)]
     q2: Complex,
 }
 
 impl FastFM {
+    /// Create new FastFM block.
+    pub fn new(src: ReadStream<Complex>) -> (Self, ReadStream<Float>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                src,
+                dst,
+                gain: GainHandle::new(1.0),
+                q1: Complex::default(),
+                q2: Complex::default(),
+            },
+            dr,
+        )
+    }
+
+    /// Set new gain.
+    pub fn set_gain(&self, gain: Float) {
+        self.gain.set_gain(gain);
+    }
+
+    /// Return a handle for changing the gain.
+    pub fn gain_handle(&self) -> GainHandle {
+        self.gain.clone()
+    }
+
     fn process_sync(&mut self, s: Complex) -> Float {
         let top = (s.im - self.q2.im) * self.q1.re;
         let bottom = (s.re - self.q2.re) * self.q1.im;
         self.q2 = self.q1;
         self.q1 = s;
-        top - bottom
+        self.gain.gain() * (top - bottom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::stream::WriteStream;
+
+    // FM signal, with a sine wave as the message.
+    fn fm(range: std::ops::Range<usize>) -> Vec<Complex> {
+        let mut phase: Float = 0.0;
+        let mut out = Vec::new();
+        for n in 0..range.end {
+            phase += 0.3 * (n as Float * 0.05).sin();
+            if range.contains(&n) {
+                out.push(Complex::from_polar(1.0, phase));
+            }
+        }
+        out
+    }
+
+    fn write(w: &WriteStream<Complex>, data: &[Complex]) -> crate::Result<()> {
+        let mut o = w.write_buf()?;
+        o.fill_from_slice(data);
+        o.produce(data.len(), &[]);
+        Ok(())
+    }
+
+    // Demodulate half the signal, change gain to 2.5, then demodulate the
+    // rest.
+    fn run<B: Block>(
+        make: impl FnOnce(ReadStream<Complex>) -> (B, ReadStream<Float>, GainHandle),
+    ) -> crate::Result<Vec<Float>> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out, handle) = make(r);
+        write(&w, &fm(0..500))?;
+        b.work()?;
+        handle.set_gain(2.5);
+        assert_eq!(handle.gain(), 2.5);
+        write(&w, &fm(500..1000))?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        Ok(res.slice().to_vec())
+    }
+
+    fn check(got: &[Float], want: &[Float]) {
+        assert_eq!(got.len(), 1000);
+        // Skip the first samples, since there is no history yet.
+        for (n, (g, w)) in got.iter().zip(want).enumerate().skip(2) {
+            let scale = if n < 500 { 1.0 } else { 2.5 };
+            assert!((g - scale * w).abs() < 1e-4, "sample {n}: {g} vs {w}");
+        }
+    }
+
+    #[test]
+    fn set_gain() -> crate::Result<()> {
+        // Reference output, with constant gain.
+        let (mut src, prev) = crate::blocks::VectorSource::new(fm(0..1000));
+        let (mut b, out) = QuadratureDemod::new(prev, 1.0);
+        src.work()?;
+        b.work()?;
+        let want = out.read_buf()?.0.slice().to_vec();
+        let got = run(|r| {
+            let (b, out) = QuadratureDemod::new(r, 1.0);
+            let h = b.gain_handle();
+            (b, out, h)
+        })?;
+        check(&got, &want);
+
+        let (mut src, prev) = crate::blocks::VectorSource::new(fm(0..1000));
+        let (mut b, out) = FastFM::new(prev);
+        src.work()?;
+        b.work()?;
+        let want = out.read_buf()?.0.slice().to_vec();
+        let got = run(|r| {
+            let (b, out) = FastFM::new(r);
+            let h = b.gain_handle();
+            (b, out, h)
+        })?;
+        check(&got, &want);
+        Ok(())
     }
 }
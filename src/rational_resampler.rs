@@ -1,13 +1,16 @@
 //! Resample by a fractional amount.
 /*
-* Unlike the rational resampler in GNURadio, this one doesn't filter.
+* Unlike the rational resampler in GNURadio, this one doesn't filter by
+* default. Use RationalResamplerBuilder::antialias() to filter.
  */
 use anyhow::Result;
 use log::trace;
 
 use crate::block::{Block, BlockRet};
+use crate::fir::FIR;
 use crate::stream::{ReadStream, WriteStream};
-use crate::Error;
+use crate::window::WindowType;
+use crate::{Error, Float};
 
 fn gcd(mut a: usize, mut b: usize) -> usize {
     while b != 0 {
@@ -18,13 +21,113 @@ fn gcd(mut a: usize, mut b: usize) -> usize {
     a
 }
 
+// Antialias filter, only calculated for input samples that make output.
+trait Antialias<T>: Send {
+    // Add an input sample to the filter history.
+    fn push(&mut self, s: T);
+
+    // Filter output at the latest input sample.
+    fn value(&self) -> T;
+}
+
+struct AntialiasFir<T: Copy> {
+    fir: FIR<T>,
+    ntaps: usize,
+    history: Vec<T>,
+}
+
+impl<T> Antialias<T> for AntialiasFir<T>
+where
+    T: Copy + Default + std::ops::Mul<T, Output = T> + std::ops::Add<T, Output = T> + Send,
+{
+    fn push(&mut self, s: T) {
+        if self.history.len() >= 2 * self.ntaps {
+            self.history.drain(..self.history.len() - self.ntaps + 1);
+        }
+        self.history.push(s);
+    }
+
+    fn value(&self) -> T {
+        self.fir
+            .filter(&self.history[self.history.len() - self.ntaps..])
+    }
+}
+
+/// Builder for RationalResampler.
+pub struct RationalResamplerBuilder {
+    interp: usize,
+    deci: usize,
+    antialias: Option<(Option<Float>, Float, WindowType)>,
+}
+
+impl RationalResamplerBuilder {
+    /// Create new builder.
+    pub fn new(interp: usize, deci: usize) -> Self {
+        Self {
+            interp,
+            deci,
+            antialias: None,
+        }
+    }
+
+    /// Low pass filter the input before resampling.
+    ///
+    /// `cutoff` and `twidth` are in cycles per input sample, i.e. relative
+    /// to the input sample rate. If the cutoff is not given, it's set to
+    /// the lower of the input and output Nyquist frequencies, minus half
+    /// the transition width.
+    pub fn antialias(mut self, cutoff: Option<Float>, twidth: Float, window: WindowType) -> Self {
+        self.antialias = Some((cutoff, twidth, window));
+        self
+    }
+
+    /// Build RationalResampler.
+    pub fn build<T>(self, src: ReadStream<T>) -> Result<(RationalResampler<T>, ReadStream<T>)>
+    where
+        T: Copy
+            + Default
+            + From<Float>
+            + std::ops::Mul<T, Output = T>
+            + std::ops::Add<T, Output = T>
+            + Send
+            + 'static,
+    {
+        let (mut b, dr) = RationalResampler::new(src, self.interp, self.deci)?;
+        if let Some((cutoff, twidth, window)) = self.antialias {
+            let nyquist = 0.5 * Float::min(1.0, self.interp as Float / self.deci as Float);
+            let cutoff = cutoff.unwrap_or(nyquist - twidth / 2.0);
+            if cutoff <= 0.0 || cutoff >= 0.5 {
+                return Err(Error::new(&format!(
+                    "RationalResampler: antialias cutoff {cutoff} out of range"
+                ))
+                .into());
+            }
+            let taps: Vec<T> = crate::fir::low_pass(1.0, cutoff, twidth, &window)
+                .into_iter()
+                .map(T::from)
+                .collect();
+            let ntaps = taps.len();
+            b.filter = Some(Box::new(AntialiasFir {
+                fir: FIR::new(&taps),
+                ntaps,
+                history: vec![T::default(); ntaps - 1],
+            }));
+        }
+        Ok((b, dr))
+    }
+}
+
 /// Resample by a fractional amount.
+///
+/// By default no filtering is done, so the input must already be band
+/// limited. See [RationalResamplerBuilder::antialias].
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct RationalResampler<T: Copy> {
     deci: i64,
     interp: i64,
     counter: i64,
+    filter: Option<Box<dyn Antialias<T>>>,
 
     #[rustradio(in)]
     src: ReadStream<T>,
@@ -52,6 +155,7 @@ impl<T: Copy> RationalResampler<T> {
                 interp: i64::try_from(interp)?,
                 deci: i64::try_from(deci)?,
                 counter: 0,
+                filter: None,
                 src,
                 dst,
             },
@@ -77,8 +181,19 @@ impl<T: Copy> Block for RationalResampler<T> {
         'outer: for s in i.iter() {
             taken += 1;
             self.counter += self.interp;
+            if let Some(f) = &mut self.filter {
+                f.push(*s);
+            }
+            if self.counter <= 0 {
+                // Dropped by decimation, so no need to filter it.
+                continue;
+            }
+            let s = match &self.filter {
+                Some(f) => f.value(),
+                None => *s,
+            };
             while self.counter > 0 {
-                o.slice()[opos] = *s;
+                o.slice()[opos] = s;
                 self.counter -= self.deci;
                 opos += 1;
                 if opos == o.len() {
@@ -116,6 +231,75 @@ mod tests {
         Ok(())
     }
 
+    // Power of the component at the given frequency, in cycles per sample.
+    fn power(data: &[Complex], freq: Float) -> Float {
+        let rad = 2.0 * std::f64::consts::PI as Float * freq;
+        let sum: Complex = data
+            .iter()
+            .enumerate()
+            .map(|(n, x)| x * Complex::from_polar(1.0, -rad * n as Float))
+            .sum();
+        (sum / data.len() as Float).norm_sqr()
+    }
+
+    #[test]
+    fn antialias() -> Result<()> {
+        // Wanted tone at 0.02, and an out of band tone at 0.4, cycles per
+        // input sample.
+        let tone = |f: f64, n: usize| {
+            let phase = (2.0 * std::f64::consts::PI * f * n as f64) % (2.0 * std::f64::consts::PI);
+            Complex::from_polar(1.0, phase as Float)
+        };
+        let input: Vec<_> = (0..8000).map(|n| tone(0.02, n) + tone(0.4, n)).collect();
+        let run = |b: RationalResamplerBuilder| -> Result<Float> {
+            let (mut src, prev) = VectorSource::new(input.clone());
+            let (mut resamp, out) = b.build(prev)?;
+            src.work()?;
+            resamp.work()?;
+            let (res, _) = out.read_buf()?;
+            // Skip filter startup. With deci 4 the wanted tone is at 0.08,
+            // and the alias at -0.4, cycles per output sample.
+            let res = &res.slice()[100..];
+            Ok(power(res, -0.4) / power(res, 0.08))
+        };
+        let unfiltered = run(RationalResamplerBuilder::new(1, 4))?;
+        assert!(unfiltered > 0.5, "{unfiltered}");
+        let filtered =
+            run(RationalResamplerBuilder::new(1, 4).antialias(None, 0.05, WindowType::Hamming))?;
+        assert!(filtered < 0.001, "{filtered}");
+
+        let (_, prev) = VectorSource::<Complex>::new(vec![]);
+        assert!(RationalResamplerBuilder::new(1, 4)
+            .antialias(Some(0.6), 0.05, WindowType::Hamming)
+            .build(prev)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn antialias_decimate() -> Result<()> {
+        // Same as filtering every sample, and then decimating.
+        let input: Vec<Float> = (0..1000).map(|n| ((n * 7) % 13) as Float).collect();
+        let taps = crate::fir::low_pass(1.0, 0.1, 0.05, &WindowType::Hamming);
+        let fir = FIR::new(&taps);
+        let padded: Vec<Float> = std::iter::repeat(0.0)
+            .take(taps.len() - 1)
+            .chain(input.iter().copied())
+            .collect();
+        let want: Vec<Float> = fir.filter_n(&padded).into_iter().step_by(4).collect();
+
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut resamp, out) = RationalResamplerBuilder::new(1, 4)
+            .antialias(Some(0.1), 0.05, WindowType::Hamming)
+            .build(prev)?;
+        src.work()?;
+        resamp.work()?;
+        let (res, _) = out.read_buf()?;
+        assert!(res.len() > 200, "{}", res.len());
+        assert_eq!(res.slice(), &want[..res.len()]);
+        Ok(())
+    }
+
     #[test]
     fn foo() -> Result<()> {
         runtest(10, 1, 1, 10)?;
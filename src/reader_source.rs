@@ -0,0 +1,226 @@
+//! Read stream from any [Read], optionally with tags.
+use std::io::{BufRead, Read};
+
+use anyhow::Result;
+use log::trace;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream, TAG_REPEAT};
+use crate::{Error, Sample};
+
+/// Read stream from any [Read].
+///
+/// Samples are read in the same raw format as
+/// [FileSource][crate::file_source::FileSource]. If a tag reader is set
+/// with [ReaderSource::set_tag_reader], tags written by
+/// [WriterSink][crate::writer_sink::WriterSink] are read from it, and
+/// attached to the samples at the same positions.
+///
+/// With [ReaderSource::set_repeat], the data is repeated, and the first
+/// sample of each pass is tagged with [TAG_REPEAT].
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct ReaderSource<T: Copy> {
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    reader: Box<dyn Read + Send>,
+    tag_reader: Option<Box<dyn BufRead + Send>>,
+    // Partial sample read.
+    buf: Vec<u8>,
+    // Samples produced so far.
+    pos: usize,
+    // Next tag, read but not yet reached.
+    next_tag: Option<Tag>,
+    // Opens the data again, for the next pass.
+    reopen: Option<Reopen>,
+    repeat_count: u64,
+    // Samples produced in the current pass.
+    pass_pos: usize,
+}
+
+type Reopen = Box<dyn FnMut() -> Result<Box<dyn Read + Send>> + Send>;
+
+impl<T: Copy> ReaderSource<T> {
+    /// Create new ReaderSource block.
+    pub fn new(reader: Box<dyn Read + Send>) -> (Self, ReadStream<T>) {
+        let (dst, dr) = crate::stream::new_stream();
+        (
+            Self {
+                dst,
+                reader,
+                tag_reader: None,
+                buf: Vec::new(),
+                pos: 0,
+                next_tag: None,
+                reopen: None,
+                repeat_count: 0,
+                pass_pos: 0,
+            },
+            dr,
+        )
+    }
+
+    /// Also read tags, from a separate reader.
+    pub fn set_tag_reader(&mut self, r: Box<dyn BufRead + Send>) {
+        self.tag_reader = Some(r);
+    }
+
+    /// Repeat the data, calling `reopen` for a new reader at each EOF.
+    ///
+    /// Tags from the tag reader are not repeated.
+    pub fn set_repeat(
+        &mut self,
+        reopen: impl FnMut() -> Result<Box<dyn Read + Send>> + Send + 'static,
+    ) {
+        self.reopen = Some(Box::new(reopen));
+    }
+
+    // Return the tags for the next n samples.
+    fn read_tags(&mut self, n: usize) -> Result<Vec<Tag>, Error> {
+        let Some(r) = &mut self.tag_reader else {
+            return Ok(vec![]);
+        };
+        let mut tags = Vec::new();
+        loop {
+            let tag = match self.next_tag.take() {
+                Some(tag) => tag,
+                None => {
+                    let mut line = String::new();
+                    if r.read_line(&mut line)? == 0 {
+                        break;
+                    }
+                    let line = line.trim_end_matches('\n');
+                    if line.is_empty() {
+                        continue;
+                    }
+                    line.parse()?
+                }
+            };
+            if tag.pos() >= self.pos + n {
+                self.next_tag = Some(tag);
+                break;
+            }
+            // Out of order tags are attached to the first sample.
+            let pos = tag.pos().saturating_sub(self.pos);
+            tags.push(Tag::new(pos, tag.key().into(), tag.val().clone()));
+        }
+        Ok(tags)
+    }
+}
+
+impl<T> Block for ReaderSource<T>
+where
+    T: Copy + Sample<Type = T>,
+{
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let mut o = self.dst.write_buf()?;
+        let size = T::size();
+        if o.is_empty() {
+            return Ok(BlockRet::OutputFull);
+        }
+        let mut buffer = vec![0; o.len() * size - self.buf.len()];
+        let n = self.reader.read(&mut buffer)?;
+        if n == 0 {
+            return match &mut self.reopen {
+                // Don't spin on data too short to hold even one sample.
+                Some(reopen) if self.pass_pos > 0 => {
+                    self.reader = reopen()?;
+                    self.buf.clear();
+                    self.repeat_count += 1;
+                    self.pass_pos = 0;
+                    Ok(BlockRet::Ok)
+                }
+                _ => Ok(BlockRet::Finished),
+            };
+        }
+        self.buf.extend(&buffer[..n]);
+        let have = self.buf.len() / size;
+        if have == 0 {
+            return Ok(BlockRet::Ok);
+        }
+        let v = self
+            .buf
+            .chunks_exact(size)
+            .map(|d| T::parse(d))
+            .collect::<Result<Vec<_>>>()?;
+        self.buf.drain(0..(have * size));
+        let mut tags = self.read_tags(have)?;
+        if self.reopen.is_some() && self.pass_pos == 0 {
+            tags.insert(
+                0,
+                Tag::new(0, TAG_REPEAT.to_string(), TagValue::U64(self.repeat_count)),
+            );
+        }
+        self.pass_pos += have;
+        o.fill_from_slice(&v);
+        trace!("ReaderSource: Produced {have}");
+        o.produce(have, &tags);
+        self.pos += have;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use std::sync::{Arc, Mutex};
+
+    use crate::blocks::WriterSink;
+    use crate::stream::TagValue;
+    use crate::Float;
+
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buf {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend(data);
+            Ok(data.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let data = Buf::default();
+        let tag_data = Buf::default();
+        let (w, r) = crate::stream::new_stream::<Float>();
+        let mut sink = WriterSink::new(r, Box::new(data.clone()));
+        sink.set_tag_writer(Box::new(tag_data.clone()));
+        let want_tags = vec![
+            Tag::new(0, "start".into(), TagValue::Bool(true)),
+            Tag::new(2, "freq".into(), TagValue::Float(1.5)),
+            Tag::new(4, "name".into(), TagValue::String("a: b=c".into())),
+            Tag::new(6, "bytes".into(), TagValue::Bytes(vec![1, 2, 255])),
+        ];
+        // Write in two chunks, to check the positions are absolute.
+        let second_tags: Vec<_> = want_tags[2..]
+            .iter()
+            .map(|t| Tag::new(t.pos() - 4, t.key().into(), t.val().clone()))
+            .collect();
+        for (samples, tags) in [
+            (vec![1.0, 2.0, 3.0, 4.0], want_tags[..2].to_vec()),
+            (vec![5.0, 6.0, 7.0], second_tags),
+        ] {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(&samples);
+            wb.produce(samples.len(), &tags);
+            sink.work()?;
+        }
+        sink.flush()?;
+
+        let data = data.0.lock().unwrap().clone();
+        let tag_data = tag_data.0.lock().unwrap().clone();
+        let (mut src, out) = ReaderSource::<Float>::new(Box::new(Cursor::new(data)));
+        src.set_tag_reader(Box::new(Cursor::new(tag_data)));
+        src.work()?;
+        assert_eq!(src.work()?, BlockRet::Finished);
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(tags, want_tags);
+        Ok(())
+    }
+}
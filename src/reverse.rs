@@ -0,0 +1,150 @@
+/*! Reverse bit order within bytes, or byte order within groups of bytes.
+
+Useful between blocks, protocols, and external tools that disagree on
+LSB or MSB first, or on endianness.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::Error;
+
+/// Reverse the order of the bits in each byte.
+///
+/// E.g. `0b0000_0001` becomes `0b1000_0000`.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new, sync)]
+pub struct BitReverse {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+}
+
+impl BitReverse {
+    fn process_sync(&self, x: u8) -> u8 {
+        x.reverse_bits()
+    }
+}
+
+/// Reverse the order of bytes within each group of `group` bytes.
+///
+/// E.g. with a group size of 4, this swaps between big and little endian
+/// 32 bit words. Bytes that don't yet make up a whole group are kept in
+/// the stream until the rest arrives. On EOF, any remaining bytes are
+/// reversed as a short group.
+///
+/// Tags stay at their position in the stream, not with their byte.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, flush)]
+pub struct ByteReverse {
+    #[rustradio(in)]
+    src: ReadStream<u8>,
+    #[rustradio(out)]
+    dst: WriteStream<u8>,
+    group: usize,
+}
+
+impl ByteReverse {
+    /// Create new ByteReverse block.
+    ///
+    /// Returns error if `group` is zero.
+    pub fn new(src: ReadStream<u8>, group: usize) -> Result<(Self, ReadStream<u8>), Error> {
+        if group == 0 {
+            return Err(Error::new("ByteReverse: group must be positive"));
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((Self { src, dst, group }, dr))
+    }
+
+    // Reverse the first n input bytes, in groups, with the last group
+    // allowed to be short.
+    fn reverse(&mut self, n: usize) -> Result<(), Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        for (from, to) in i.slice()[..n]
+            .chunks(self.group)
+            .zip(o.slice()[..n].chunks_mut(self.group))
+        {
+            to.copy_from_slice(from);
+            to.reverse();
+        }
+        let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        i.consume(n);
+        o.produce(n, &tags);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let n = self.src.read_buf()?.0.len();
+        // Whole groups are left to work().
+        if n > 0 && n < self.group && self.dst.write_buf()?.len() >= n {
+            self.reverse(n)?;
+        }
+        Ok(())
+    }
+}
+
+impl Block for ByteReverse {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let n = {
+            let (i, _) = self.src.read_buf()?;
+            let o = self.dst.write_buf()?;
+            std::cmp::min(i.len(), o.len()) / self.group * self.group
+        };
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        self.reverse(n)?;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    #[test]
+    fn bit_reverse() -> Result<()> {
+        let (mut src, prev) = VectorSource::new(vec![0b0000_0001u8, 0b1100_1010, 0xff, 0]);
+        let (mut b, out) = BitReverse::new(prev);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[0b1000_0000, 0b0101_0011, 0xff, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn byte_reverse() -> Result<()> {
+        let (mut src, prev) = VectorSource::new((1..=10u8).collect());
+        let (mut b, out) = ByteReverse::new(prev, 4)?;
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        // Last two bytes are not a whole group.
+        assert_eq!(res.slice(), &[4, 3, 2, 1, 8, 7, 6, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn byte_reverse_zero_group() {
+        let (_, prev) = crate::stream::new_stream::<u8>();
+        assert!(ByteReverse::new(prev, 0).is_err());
+    }
+
+    #[test]
+    fn byte_reverse_eof() -> Result<()> {
+        let (src, prev) = VectorSource::new((1..=10u8).collect());
+        let (b, out) = ByteReverse::new(prev, 4)?;
+        use crate::graph::{Graph, GraphRunner};
+        let mut g = Graph::new();
+        g.add(Box::new(src));
+        g.add(Box::new(b));
+        g.run()?;
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &[4, 3, 2, 1, 8, 7, 6, 5, 10, 9]);
+        Ok(())
+    }
+}
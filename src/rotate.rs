@@ -0,0 +1,154 @@
+/*! Rotate and scale complex samples by a constant.
+
+Like [MultiplyConst][crate::multiply_const::MultiplyConst], but with the
+constant given as magnitude and phase, which can be changed while running.
+Useful for e.g. aligning the phase of two receivers.
+*/
+use crate::block::{Block, BlockRet};
+use crate::param::Param;
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Handle for changing the magnitude and phase of a running [Rotate].
+///
+/// Changes are applied to whole calls to the block's `work()`, so a
+/// magnitude and phase set together may still be seen one at a time.
+#[derive(Clone)]
+pub struct RotateHandle {
+    mag: Param,
+    // Degrees.
+    phase: Param,
+}
+
+impl RotateHandle {
+    /// Set new magnitude.
+    pub fn set_mag(&self, mag: f64) {
+        self.mag.set(mag);
+    }
+
+    /// Set new phase, in degrees.
+    pub fn set_phase(&self, degrees: f64) {
+        self.phase.set(degrees);
+    }
+
+    /// Get current magnitude.
+    pub fn mag(&self) -> f64 {
+        self.mag.get()
+    }
+
+    /// Get current phase, in degrees.
+    pub fn phase(&self) -> f64 {
+        self.phase.get()
+    }
+
+    fn factor(&self) -> Complex {
+        let c = num_complex::Complex::<f64>::from_polar(self.mag(), self.phase().to_radians());
+        Complex::new(c.re as Float, c.im as Float)
+    }
+}
+
+/// Multiply by `mag * exp(j * phase)`.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct Rotate {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    handle: RotateHandle,
+}
+
+impl Rotate {
+    /// Create new Rotate block, with the phase in degrees.
+    pub fn new(src: ReadStream<Complex>, mag: f64, phase: f64) -> (Self, ReadStream<Complex>) {
+        let (dst, dr) = crate::stream::new_stream();
+        let handle = RotateHandle {
+            mag: Param::new(mag),
+            phase: Param::new(phase),
+        };
+        (Self { src, dst, handle }, dr)
+    }
+
+    /// Set new magnitude.
+    pub fn set_mag(&self, mag: f64) {
+        self.handle.set_mag(mag);
+    }
+
+    /// Set new phase, in degrees.
+    pub fn set_phase(&self, degrees: f64) {
+        self.handle.set_phase(degrees);
+    }
+
+    /// Return a handle for changing the magnitude and phase.
+    pub fn handle(&self) -> RotateHandle {
+        self.handle.clone()
+    }
+}
+
+impl Block for Rotate {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, mut tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let f = self.handle.factor();
+        o.fill_from_iter(i.iter().take(n).map(|s| s * f));
+        tags.retain(|t| t.pos() < n);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(w: &WriteStream<Complex>, data: &[Complex]) -> crate::Result<()> {
+        let mut o = w.write_buf()?;
+        o.fill_from_slice(data);
+        o.produce(data.len(), &[]);
+        Ok(())
+    }
+
+    #[test]
+    fn rotate() -> crate::Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = Rotate::new(r, 1.0, 90.0);
+        let h = b.handle();
+        write(&w, &[Complex::new(1.0, 0.0), Complex::new(0.0, 2.0)])?;
+        b.work()?;
+
+        h.set_mag(3.0);
+        assert_eq!(h.mag(), 3.0);
+        write(&w, &[Complex::new(1.0, 0.0)])?;
+        b.work()?;
+
+        b.set_phase(-45.0);
+        assert_eq!(h.phase(), -45.0);
+        write(&w, &[Complex::new(1.0, 1.0)])?;
+        b.work()?;
+
+        let (res, _) = out.read_buf()?;
+        crate::tests::assert_almost_equal_complex(
+            res.slice(),
+            &[
+                Complex::new(0.0, 1.0),
+                Complex::new(-2.0, 0.0),
+                Complex::new(0.0, 3.0),
+                Complex::new(3.0 * std::f32::consts::SQRT_2, 0.0),
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn partial_output_tags() -> crate::Result<()> {
+        crate::tests::check_partial_output_tags(|prev| Ok(Rotate::new(prev, 1.0, 90.0)))
+    }
+}
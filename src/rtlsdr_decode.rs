@@ -52,7 +52,7 @@ mod tests {
     #[test]
     fn empty() -> crate::Result<()> {
         let (mut src, src_out) = VectorSource::new(vec![]);
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         let (mut dec, dec_out) = RtlSdrDecode::new(src_out);
         assert_eq!(dec.work()?, BlockRet::Noop);
         let (res, _) = dec_out.read_buf()?;
@@ -64,7 +64,7 @@ mod tests {
     fn some_input() -> crate::Result<()> {
         let (mut src, src_out) = VectorSource::new(vec![0, 10, 20, 10, 0, 13]);
         assert_eq!(src.work()?, BlockRet::Ok);
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         let (mut dec, dec_out) = RtlSdrDecode::new(src_out);
         assert_eq!(dec.work()?, BlockRet::Ok);
         let (res, _) = dec_out.read_buf()?;
@@ -93,7 +93,7 @@ mod tests {
     fn uneven() -> crate::Result<()> {
         let (mut src, src_out) = VectorSource::new(vec![0, 10, 20, 10, 0]);
         assert_eq!(src.work()?, BlockRet::Ok);
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         let (mut dec, dec_out) = RtlSdrDecode::new(src_out);
         assert_eq!(dec.work()?, BlockRet::Ok);
         let (res, _) = dec_out.read_buf()?;
@@ -106,7 +106,7 @@ mod tests {
         // Input is pairs of bytes. Output is complex, meaning a 4x increase. That won't fit.
         let (mut src, src_out) = VectorSource::new(vec![0; crate::stream::DEFAULT_STREAM_SIZE]);
         assert_eq!(src.work()?, BlockRet::Ok);
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         let (mut dec, dec_out) = RtlSdrDecode::new(src_out);
         for n in 0..4 {
             eprintln!("loop iter: {n}");
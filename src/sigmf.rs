@@ -3,17 +3,16 @@
 /*
  * TODO:
  * create sink block.
- * add sigmf archive (tar) support.
  */
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::io::Write;
+use std::io::{Read, Write};
 
 const DATATYPE_CF32: &str = "cf32";
 const VERSION: &str = "1.1.0";
 
 use crate::block::{Block, BlockRet};
-use crate::file_source::FileSource;
+use crate::reader_source::ReaderSource;
 use crate::stream::ReadStream;
 use crate::{Complex, Error, Float, Sample};
 
@@ -152,14 +151,202 @@ pub struct SigMF {
     global: Global,
 
     /// Capture segments.
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     captures: Vec<Capture>,
 
     /// Annotations on the data.
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     annotations: Vec<Annotation>,
 }
 
+impl Capture {
+    /// Create new capture segment, starting at the given sample.
+    pub fn new(sample_start: u64) -> Self {
+        Self {
+            core_sample_start: sample_start,
+            ..Default::default()
+        }
+    }
+
+    /// Set global index.
+    pub fn global_index(mut self, v: u64) -> Self {
+        self.core_global_index = Some(v);
+        self
+    }
+
+    /// Set header bytes.
+    pub fn header_bytes(mut self, v: u64) -> Self {
+        self.core_header_bytes = Some(v);
+        self
+    }
+
+    /// Set frequency.
+    pub fn frequency(mut self, v: f64) -> Self {
+        self.core_frequency = Some(v);
+        self
+    }
+
+    /// Set ISO8601 datetime.
+    pub fn datetime(mut self, v: &str) -> Self {
+        self.core_datetime = Some(v.to_string());
+        self
+    }
+}
+
+impl Annotation {
+    /// Create new annotation, starting at the given sample.
+    pub fn new(sample_start: u64) -> Self {
+        Self {
+            core_sample_start: sample_start,
+            core_sample_count: None,
+            core_generator: None,
+            core_label: None,
+            core_comment: None,
+            core_freq_lower_edge: None,
+            core_freq_upper_edge: None,
+            core_uuid: None,
+        }
+    }
+
+    /// Set sample count.
+    pub fn sample_count(mut self, v: u64) -> Self {
+        self.core_sample_count = Some(v);
+        self
+    }
+
+    /// Set generator.
+    pub fn generator(mut self, v: &str) -> Self {
+        self.core_generator = Some(v.to_string());
+        self
+    }
+
+    /// Set label.
+    pub fn label(mut self, v: &str) -> Self {
+        self.core_label = Some(v.to_string());
+        self
+    }
+
+    /// Set comment.
+    pub fn comment(mut self, v: &str) -> Self {
+        self.core_comment = Some(v.to_string());
+        self
+    }
+
+    /// Set frequency lower edge.
+    pub fn freq_lower_edge(mut self, v: f64) -> Self {
+        self.core_freq_lower_edge = Some(v);
+        self
+    }
+
+    /// Set frequency upper edge.
+    pub fn freq_upper_edge(mut self, v: f64) -> Self {
+        self.core_freq_upper_edge = Some(v);
+        self
+    }
+
+    /// Set UUID.
+    pub fn uuid(mut self, v: &str) -> Self {
+        self.core_uuid = Some(v.to_string());
+        self
+    }
+}
+
+impl Global {
+    /// Create new global object, for the given datatype, e.g. `cf32`.
+    pub fn new(datatype: &str) -> Self {
+        Self {
+            core_datatype: datatype.to_string(),
+            core_version: VERSION.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Set sample rate.
+    pub fn sample_rate(mut self, v: f64) -> Self {
+        self.core_sample_rate = Some(v);
+        self
+    }
+
+    /// Set number of channels.
+    pub fn num_channels(mut self, v: u64) -> Self {
+        self.core_num_channels = Some(v);
+        self
+    }
+
+    /// Set SHA512 of the data.
+    pub fn sha512(mut self, v: &str) -> Self {
+        self.core_sha512 = Some(v.to_string());
+        self
+    }
+
+    /// Set description.
+    pub fn description(mut self, v: &str) -> Self {
+        self.core_description = Some(v.to_string());
+        self
+    }
+
+    /// Set author.
+    pub fn author(mut self, v: &str) -> Self {
+        self.core_author = Some(v.to_string());
+        self
+    }
+
+    /// Set recorder software.
+    pub fn recorder(mut self, v: &str) -> Self {
+        self.core_recorder = Some(v.to_string());
+        self
+    }
+
+    /// Set license.
+    pub fn license(mut self, v: &str) -> Self {
+        self.core_license = Some(v.to_string());
+        self
+    }
+
+    /// Set hardware.
+    pub fn hw(mut self, v: &str) -> Self {
+        self.core_hw = Some(v.to_string());
+        self
+    }
+}
+
+impl SigMF {
+    /// Create a builder for a SigMF document.
+    pub fn builder(global: Global) -> SigMFBuilder {
+        SigMFBuilder {
+            sigmf: SigMF {
+                global,
+                captures: Vec::new(),
+                annotations: Vec::new(),
+            },
+        }
+    }
+}
+
+/// Builder for [SigMF].
+pub struct SigMFBuilder {
+    sigmf: SigMF,
+}
+
+impl SigMFBuilder {
+    /// Add a capture segment.
+    pub fn capture(mut self, c: Capture) -> Self {
+        self.sigmf.captures.push(c);
+        self
+    }
+
+    /// Add an annotation.
+    pub fn annotation(mut self, a: Annotation) -> Self {
+        self.sigmf.annotations.push(a);
+        self
+    }
+
+    /// Build the SigMF document.
+    pub fn build(self) -> SigMF {
+        self.sigmf
+    }
+}
+
 /// Parse metadata for SigMF file.
 pub fn parse_meta(base: &str) -> Result<SigMF> {
     //let base = "data/1876954_7680KSPS_srsRAN_Project_gnb_short.sigmf";
@@ -168,22 +355,74 @@ pub fn parse_meta(base: &str) -> Result<SigMF> {
     Ok(serde_json::from_reader(reader)?)
 }
 
+/// Format time as RFC3339, in UTC, with second precision.
+fn rfc3339(t: std::time::SystemTime) -> String {
+    let secs = t
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Days since epoch to civil date. See
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Options for [write_with].
+#[derive(Default, Debug, Clone)]
+pub struct WriteOptions {
+    datetime: Option<String>,
+    global_index: Option<u64>,
+}
+
+impl WriteOptions {
+    /// Set ISO8601 capture datetime. Default is the current time.
+    pub fn datetime(mut self, v: &str) -> Self {
+        self.datetime = Some(v.to_string());
+        self
+    }
+
+    /// Set global index of the first sample, e.g. to time correlate
+    /// several recordings from the same sample stream.
+    pub fn global_index(mut self, v: u64) -> Self {
+        self.global_index = Some(v);
+        self
+    }
+}
+
 /// Write metadata file.
+///
+/// The capture datetime is set to the current time.
 pub fn write(fname: &str, samp_rate: f64, freq: f64) -> Result<()> {
-    let data = SigMF {
-        global: Global {
-            core_version: VERSION.to_string(),
-            core_datatype: DATATYPE_CF32.to_string(),
-            core_sample_rate: Some(samp_rate),
-            ..Default::default()
-        },
-        captures: vec![Capture {
-            core_sample_start: 0,
-            core_frequency: Some(freq),
-            ..Default::default()
-        }],
-        annotations: Vec::new(),
-    };
+    write_with(fname, samp_rate, freq, WriteOptions::default())
+}
+
+/// Write metadata file, with options.
+pub fn write_with(fname: &str, samp_rate: f64, freq: f64, opts: WriteOptions) -> Result<()> {
+    let datetime = opts
+        .datetime
+        .unwrap_or_else(|| rfc3339(std::time::SystemTime::now()));
+    let mut capture = Capture::new(0).frequency(freq).datetime(&datetime);
+    if let Some(v) = opts.global_index {
+        capture = capture.global_index(v);
+    }
+    let data = SigMF::builder(Global::new(DATATYPE_CF32).sample_rate(samp_rate))
+        .capture(capture)
+        .build();
 
     // Serialize the data to a JSON string.
     let serialized = serde_json::to_string(&data).unwrap();
@@ -194,10 +433,91 @@ pub fn write(fname: &str, samp_rate: f64, freq: f64) -> Result<()> {
     Ok(())
 }
 
+/// Recording in a SigMF archive.
+#[cfg(feature = "tar")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recording {
+    /// Recording number, in the order they are in the archive.
+    Index(usize),
+
+    /// Base name of the recording, e.g. `collection/name` for the files
+    /// `collection/name.sigmf-meta` and `collection/name.sigmf-data`. The
+    /// last component alone (`name`) also works.
+    Base(String),
+}
+
+// Recording found in an archive.
+#[cfg(feature = "tar")]
+struct ArchiveRecording {
+    base: String,
+    meta: Option<SigMF>,
+    // Data file offset and size, in the archive.
+    data: Option<(u64, u64)>,
+}
+
+#[cfg(feature = "tar")]
+impl ArchiveRecording {
+    fn matches(&self, r: &Recording, index: usize) -> bool {
+        match r {
+            Recording::Index(i) => *i == index,
+            Recording::Base(b) => self.base == *b || self.base.rsplit('/').next() == Some(b),
+        }
+    }
+}
+
+// Read the recordings in an archive. Only ones with a metadata file are
+// returned.
+#[cfg(feature = "tar")]
+fn read_archive(path: &str) -> Result<Vec<ArchiveRecording>> {
+    let mut archive = tar::Archive::new(std::fs::File::open(path)?);
+    let mut recs: Vec<ArchiveRecording> = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let (base, is_meta) = if let Some(b) = name.strip_suffix(".sigmf-meta") {
+            (b, true)
+        } else if let Some(b) = name.strip_suffix(".sigmf-data") {
+            (b, false)
+        } else {
+            continue;
+        };
+        let n = match recs.iter().position(|r| r.base == base) {
+            Some(n) => n,
+            None => {
+                recs.push(ArchiveRecording {
+                    base: base.to_string(),
+                    meta: None,
+                    data: None,
+                });
+                recs.len() - 1
+            }
+        };
+        if is_meta {
+            recs[n].meta = Some(serde_json::from_reader(&mut entry)?);
+        } else {
+            recs[n].data = Some((entry.raw_file_position(), entry.size()));
+        }
+    }
+    recs.retain(|r| r.meta.is_some());
+    Ok(recs)
+}
+
+/// List the recordings in a SigMF archive.
+///
+/// Returns the base names, in archive order. These can be used with
+/// [Recording::Base].
+#[cfg(feature = "tar")]
+pub fn list_recordings(path: &str) -> Result<Vec<String>> {
+    Ok(read_archive(path)?.into_iter().map(|r| r.base).collect())
+}
+
 /// SigMF source builder.
 pub struct SigMFSourceBuilder<T: Copy + Type> {
     filename: String,
     sample_rate: Option<f64>,
+    #[cfg(feature = "tar")]
+    recording: Option<Recording>,
+    repeat: bool,
     dummy: std::marker::PhantomData<T>,
 }
 
@@ -207,6 +527,9 @@ impl<T: Default + Copy + Type> SigMFSourceBuilder<T> {
         Self {
             filename,
             sample_rate: None,
+            #[cfg(feature = "tar")]
+            recording: None,
+            repeat: false,
             dummy: std::marker::PhantomData,
         }
     }
@@ -215,9 +538,41 @@ impl<T: Default + Copy + Type> SigMFSourceBuilder<T> {
         self.sample_rate = Some(rate);
         self
     }
+    /// Select recording, for archives with more than one.
+    ///
+    /// Setting this implies that the filename is an archive.
+    #[cfg(feature = "tar")]
+    pub fn recording(mut self, r: Recording) -> Self {
+        self.recording = Some(r);
+        self
+    }
+    /// Set whether to repeat the recording.
+    ///
+    /// When repeating, the first sample of each pass through the recording
+    /// is tagged with [TAG_REPEAT][crate::stream::TAG_REPEAT].
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
     /// Build a SigMFSource.
+    ///
+    /// With the `tar` feature, if the filename is an existing file, and not
+    /// the base name of a metadata and data file pair, it's read as an
+    /// archive.
     pub fn build(self) -> Result<(SigMFSource<T>, ReadStream<T>)> {
-        SigMFSource::new(&self.filename, self.sample_rate)
+        #[cfg(feature = "tar")]
+        if self.recording.is_some()
+            || (std::path::Path::new(&self.filename).is_file()
+                && !std::path::Path::new(&format!("{}-meta", self.filename)).exists())
+        {
+            return SigMFSource::open_archive(
+                &self.filename,
+                self.recording,
+                self.sample_rate,
+                self.repeat,
+            );
+        }
+        SigMFSource::open_file(&self.filename, self.sample_rate, self.repeat)
     }
 }
 
@@ -226,7 +581,7 @@ impl<T: Default + Copy + Type> SigMFSourceBuilder<T> {
 #[rustradio(crate)]
 pub struct SigMFSource<T: Copy> {
     // TODO: Can't continue to delegate reading the data, because tags.
-    file_source: FileSource<T>,
+    source: ReaderSource<T>,
     sample_rate: Option<f64>,
 }
 
@@ -267,7 +622,82 @@ impl Type for Float {
 impl<T: Default + Copy + Type> SigMFSource<T> {
     /// Create a new SigMF source block.
     pub fn new(filename: &str, samp_rate: Option<f64>) -> Result<(Self, ReadStream<T>)> {
+        Self::open_file(filename, samp_rate, false)
+    }
+
+    fn open_file(
+        filename: &str,
+        samp_rate: Option<f64>,
+        repeat: bool,
+    ) -> Result<(Self, ReadStream<T>)> {
         let meta = parse_meta(filename)?;
+        let data = format!["{}-data", filename];
+        let open =
+            move || -> Result<Box<dyn Read + Send>> { Ok(Box::new(std::fs::File::open(&data)?)) };
+        Self::from_parts(filename, meta, open, samp_rate, repeat)
+    }
+
+    /// Create a new SigMF source block, reading a recording from an
+    /// archive.
+    ///
+    /// If no recording is selected, the archive must contain exactly one.
+    #[cfg(feature = "tar")]
+    pub fn from_archive(
+        path: &str,
+        recording: Option<Recording>,
+        samp_rate: Option<f64>,
+    ) -> Result<(Self, ReadStream<T>)> {
+        Self::open_archive(path, recording, samp_rate, false)
+    }
+
+    #[cfg(feature = "tar")]
+    fn open_archive(
+        path: &str,
+        recording: Option<Recording>,
+        samp_rate: Option<f64>,
+        repeat: bool,
+    ) -> Result<(Self, ReadStream<T>)> {
+        let recs = read_archive(path)?;
+        let rec = match &recording {
+            None if recs.len() == 1 => recs.into_iter().next().unwrap(),
+            None => {
+                return Err(Error::new(&format!(
+                    "sigmf archive {path} has {} recordings, need to select one",
+                    recs.len()
+                ))
+                .into())
+            }
+            Some(r) => recs
+                .into_iter()
+                .enumerate()
+                .find(|(n, rec)| rec.matches(r, *n))
+                .map(|(_, rec)| rec)
+                .ok_or_else(|| {
+                    Error::new(&format!("sigmf archive {path} has no recording {r:?}"))
+                })?,
+        };
+        let name = format!("{path}:{}", rec.base);
+        let Some((offset, size)) = rec.data else {
+            return Err(Error::new(&format!("sigmf recording {name} has no data file")).into());
+        };
+        let path = path.to_string();
+        let open = move || -> Result<Box<dyn Read + Send>> {
+            use std::io::Seek;
+            let mut f = std::fs::File::open(&path)?;
+            f.seek(std::io::SeekFrom::Start(offset))?;
+            Ok(Box::new(f.take(size)))
+        };
+        // unwrap: only recordings with metadata are returned.
+        Self::from_parts(&name, rec.meta.unwrap(), open, samp_rate, repeat)
+    }
+
+    fn from_parts(
+        filename: &str,
+        meta: SigMF,
+        mut open: impl FnMut() -> Result<Box<dyn Read + Send>> + Send + 'static,
+        samp_rate: Option<f64>,
+        repeat: bool,
+    ) -> Result<(Self, ReadStream<T>)> {
         if let Some(samp_rate) = samp_rate {
             if let Some(t) = meta.global.core_sample_rate {
                 if t != samp_rate {
@@ -288,11 +718,14 @@ impl<T: Default + Copy + Type> SigMFSource<T> {
             ))
             .into());
         }
-        let (file_source, dr) = FileSource::new(&format!["{}-data", filename], false)?;
+        let (mut source, dr) = ReaderSource::new(open()?);
+        if repeat {
+            source.set_repeat(open);
+        }
         Ok((
             Self {
                 sample_rate: meta.global.core_sample_rate,
-                file_source,
+                source,
             },
             dr,
         ))
@@ -308,6 +741,199 @@ where
     T: Sample<Type = T> + Copy + std::fmt::Debug + Type,
 {
     fn work(&mut self) -> Result<BlockRet, Error> {
-        self.file_source.work()
+        self.source.work()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder() -> Result<()> {
+        let data = SigMF::builder(
+            Global::new(DATATYPE_CF32)
+                .sample_rate(1024000.0)
+                .author("Someone")
+                .description("Test recording")
+                .hw("RTL-SDR"),
+        )
+        .capture(
+            Capture::new(0)
+                .frequency(144800000.0)
+                .datetime("2024-01-01T00:00:00Z"),
+        )
+        .annotation(
+            Annotation::new(100)
+                .sample_count(50)
+                .label("burst")
+                .freq_lower_edge(144790000.0)
+                .freq_upper_edge(144810000.0),
+        )
+        .build();
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&data)?)?;
+        let global = &json["global"];
+        assert_eq!(global["core:datatype"], "cf32");
+        assert_eq!(global["core:version"], VERSION);
+        assert_eq!(global["core:sample_rate"], 1024000.0);
+        assert_eq!(global["core:author"], "Someone");
+        assert_eq!(global["core:description"], "Test recording");
+        assert_eq!(global["core:hw"], "RTL-SDR");
+        assert!(global.get("core:license").is_none());
+        let capture = &json["captures"][0];
+        assert_eq!(capture["core:sample_start"], 0);
+        assert_eq!(capture["core:frequency"], 144800000.0);
+        assert_eq!(capture["core:datetime"], "2024-01-01T00:00:00Z");
+        let annotation = &json["annotations"][0];
+        assert_eq!(annotation["core:sample_start"], 100);
+        assert_eq!(annotation["core:sample_count"], 50);
+        assert_eq!(annotation["core:label"], "burst");
+        assert_eq!(annotation["core:freq_lower_edge"], 144790000.0);
+        assert_eq!(annotation["core:freq_upper_edge"], 144810000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn write_capture() -> Result<()> {
+        let tmpd = tempfile::tempdir()?;
+        let base = tmpd.path().join("rec.sigmf").display().to_string();
+        write_with(
+            &format!("{base}-meta"),
+            1024000.0,
+            144800000.0,
+            WriteOptions::default()
+                .datetime("2024-06-01T12:34:56Z")
+                .global_index(123456),
+        )?;
+        let meta = parse_meta(&base)?;
+        assert_eq!(meta.global.core_sample_rate, Some(1024000.0));
+        let c = &meta.captures[0];
+        assert_eq!(c.core_frequency, Some(144800000.0));
+        assert_eq!(c.core_datetime.as_deref(), Some("2024-06-01T12:34:56Z"));
+        assert_eq!(c.core_global_index, Some(123456));
+
+        // Defaults.
+        write(&format!("{base}-meta"), 1024000.0, 144800000.0)?;
+        let meta = parse_meta(&base)?;
+        let c = &meta.captures[0];
+        assert_eq!(c.core_global_index, None);
+        let dt = c.core_datetime.as_ref().unwrap();
+        assert_eq!(dt.len(), 20, "{dt}");
+        assert!(dt.ends_with('Z'), "{dt}");
+
+        let t = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(951827696);
+        assert_eq!(rfc3339(t), "2000-02-29T12:34:56Z");
+        Ok(())
+    }
+
+    // Create an archive with two float recordings.
+    #[cfg(feature = "tar")]
+    fn write_archive(path: &std::path::Path) -> Result<()> {
+        let mut b = tar::Builder::new(std::fs::File::create(path)?);
+        for (base, data) in [
+            ("coll/a", vec![1.0 as Float, 2.0, 3.0]),
+            ("coll/b", vec![4.0, 5.0]),
+        ] {
+            let meta = serde_json::to_vec(
+                &SigMF::builder(Global::new("rf32_le").sample_rate(1000.0)).build(),
+            )?;
+            let data: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+            for (suffix, content) in [("meta", meta), ("data", data)] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                b.append_data(
+                    &mut header,
+                    format!("{base}.sigmf-{suffix}"),
+                    content.as_slice(),
+                )?;
+            }
+        }
+        b.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn repeat() -> Result<()> {
+        use crate::stream::{TagValue, TAG_REPEAT};
+        let tmpd = tempfile::tempdir()?;
+        let base = tmpd.path().join("rec.sigmf").display().to_string();
+        std::fs::write(
+            format!("{base}-meta"),
+            serde_json::to_vec(&SigMF::builder(Global::new("rf32_le")).build())?,
+        )?;
+        let data: Vec<u8> = [1.0 as Float, 2.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        std::fs::write(format!("{base}-data"), data)?;
+
+        let (mut src, out) = SigMFSourceBuilder::<Float>::new(base)
+            .repeat(true)
+            .build()?;
+        let mut got: Vec<Float> = Vec::new();
+        let mut tags = Vec::new();
+        while got.len() < 6 {
+            assert_eq!(src.work()?, BlockRet::Ok);
+            let (res, t) = out.read_buf()?;
+            for t in t {
+                assert_eq!(t.key(), TAG_REPEAT);
+                tags.push((got.len() + t.pos(), t.val().clone()));
+            }
+            got.extend(res.slice());
+            let n = res.len();
+            res.consume(n);
+        }
+        assert_eq!(got, [1.0, 2.0, 1.0, 2.0, 1.0, 2.0]);
+        assert_eq!(
+            tags,
+            [
+                (0, TagValue::U64(0)),
+                (2, TagValue::U64(1)),
+                (4, TagValue::U64(2)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "tar")]
+    fn archive() -> Result<()> {
+        let tmpd = tempfile::tempdir()?;
+        let path = tmpd.path().join("coll.sigmf");
+        write_archive(&path)?;
+        let path = path.display().to_string();
+        assert_eq!(list_recordings(&path)?, ["coll/a", "coll/b"]);
+
+        for (r, want) in [
+            (Recording::Index(0), vec![1.0, 2.0, 3.0]),
+            (Recording::Base("coll/b".into()), vec![4.0, 5.0]),
+            (Recording::Base("a".into()), vec![1.0, 2.0, 3.0]),
+            (Recording::Index(1), vec![4.0, 5.0]),
+        ] {
+            let (mut src, out) = SigMFSourceBuilder::<Float>::new(path.clone())
+                .recording(r)
+                .sample_rate(1000.0)
+                .build()?;
+            assert_eq!(src.sample_rate(), Some(1000.0));
+            src.work()?;
+            assert_eq!(src.work()?, BlockRet::Finished);
+            let (res, _) = out.read_buf()?;
+            assert_eq!(res.slice(), want);
+        }
+
+        // Must select one.
+        let err = SigMFSourceBuilder::<Float>::new(path.clone())
+            .build()
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("has 2 recordings"), "{err}");
+        assert!(SigMFSourceBuilder::<Float>::new(path)
+            .recording(Recording::Index(2))
+            .build()
+            .is_err());
+        Ok(())
     }
 }
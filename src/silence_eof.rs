@@ -0,0 +1,161 @@
+/*! End the stream after a period of silence.
+
+For processing recordings, where the end of the signal should end the
+graph, even if the file continues.
+*/
+use crate::block::{Block, BlockRet};
+use crate::graph::CancellationToken;
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Sample types that have a power.
+pub trait SamplePower: Copy {
+    /// Return the power of the sample.
+    fn power(&self) -> Float;
+}
+
+impl SamplePower for Float {
+    fn power(&self) -> Float {
+        self * self
+    }
+}
+
+impl SamplePower for Complex {
+    fn power(&self) -> Float {
+        self.norm_sqr()
+    }
+}
+
+/// Silence EOF block.
+///
+/// Samples pass through unchanged. The power is smoothed with an
+/// exponential moving average, with weight `alpha` for each new sample.
+/// Once the smoothed power has been below `threshold` for `samples`
+/// samples in a row, the samples up to that point are output, and the
+/// block returns EOF.
+///
+/// Downstream blocks then see EOF on their inputs, but upstream blocks
+/// don't know that nobody is reading anymore. If the upstream blocks don't
+/// end on their own, set a [CancellationToken] with
+/// [`set_cancel_token()`][SilenceEof::set_cancel_token], to stop the whole
+/// graph.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct SilenceEof<T: SamplePower> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    threshold: Float,
+    samples: usize,
+    alpha: Float,
+    // Number of samples in a row below the threshold.
+    #[rustradio(default)]
+    silent: usize,
+    #[rustradio(default)]
+    level: Option<Float>,
+    #[rustradio(default)]
+    done: bool,
+    #[rustradio(default)]
+    cancel: Option<CancellationToken>,
+}
+
+impl<T: SamplePower> SilenceEof<T> {
+    /// Cancel the graph too, when the silence is detected.
+    pub fn set_cancel_token(&mut self, token: CancellationToken) {
+        self.cancel = Some(token);
+    }
+
+    /// Return the current smoothed power.
+    pub fn level(&self) -> Option<Float> {
+        self.level
+    }
+
+    // Update the level, and return true if the silence is long enough.
+    fn update(&mut self, s: T) -> bool {
+        let p = s.power();
+        let level = match self.level {
+            None => p,
+            Some(l) => l + self.alpha * (p - l),
+        };
+        self.level = Some(level);
+        if level < self.threshold {
+            self.silent += 1;
+        } else {
+            self.silent = 0;
+        }
+        self.silent >= self.samples
+    }
+}
+
+impl<T: SamplePower> Block for SilenceEof<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        if self.done {
+            return Ok(BlockRet::EOF);
+        }
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let mut n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        for (pos, s) in i.iter().take(n).enumerate() {
+            if self.update(*s) {
+                n = pos + 1;
+                self.done = true;
+                if let Some(cancel) = &self.cancel {
+                    cancel.cancel();
+                }
+                break;
+            }
+        }
+        let tags: Vec<Tag> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{ConstantSource, NullSink, VectorSource};
+    use crate::graph::{Graph, GraphRunner};
+
+    #[test]
+    fn silence() -> crate::Result<()> {
+        let input: Vec<Float> = (0..1000)
+            .map(|n| (n as Float * 0.1).sin())
+            .chain(std::iter::repeat(0.0).take(5000))
+            .collect();
+        let (mut src, prev) = VectorSource::new(input.clone());
+        let (mut b, out) = SilenceEof::new(prev, 0.01, 1000, 0.1);
+        src.work()?;
+        assert_eq!(b.work()?, BlockRet::Ok);
+        assert_eq!(b.work()?, BlockRet::EOF);
+        let (res, _) = out.read_buf()?;
+        assert_eq!(res.slice(), &input[..res.len()]);
+        // Power 0.5 decays below 0.01 in about 40 samples, then 1000
+        // silent samples.
+        assert!((2030..2050).contains(&res.len()), "{}", res.len());
+        Ok(())
+    }
+
+    #[test]
+    fn cancel_graph() -> crate::Result<()> {
+        // Silent, and never ending.
+        let (src, prev) = ConstantSource::new(Complex::default());
+        let (mut b, prev) = SilenceEof::new(prev, 0.01, 1000, 0.1);
+        let mut g = Graph::new();
+        b.set_cancel_token(g.cancel_token());
+        g.add(Box::new(src));
+        g.add(Box::new(b));
+        g.add(Box::new(NullSink::new(prev)));
+        g.run()?;
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
 //! Skip samples, then stream at full speed.
+//!
+//! [Skip] skips a fixed number of samples, [SkipUntilTag] skips until a
+//! tag is seen.
 use anyhow::Result;
 
 use crate::block::{Block, BlockRet};
@@ -39,3 +42,121 @@ impl<T: Copy + std::fmt::Debug> Block for Skip<T> {
         Ok(BlockRet::Ok)
     }
 }
+
+/// Skip samples until a tag is seen, then stream at full speed.
+///
+/// Samples before the tag are dropped, along with their tags. By default
+/// the sample with the tag, and the tag itself, are passed on. See
+/// `set_drop_trigger()`.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct SkipUntilTag<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    key: String,
+    #[rustradio(default)]
+    drop_trigger: bool,
+    #[rustradio(default)]
+    triggered: bool,
+}
+
+impl<T: Copy> SkipUntilTag<T> {
+    /// Also drop the sample with the triggering tag.
+    pub fn set_drop_trigger(&mut self, v: bool) {
+        self.drop_trigger = v;
+    }
+
+    /// Return true if the tag has been seen.
+    pub fn triggered(&self) -> bool {
+        self.triggered
+    }
+}
+
+impl<T: Copy> Block for SkipUntilTag<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+
+        if self.triggered {
+            let mut o = self.dst.write_buf()?;
+            let len = std::cmp::min(i.len(), o.len());
+            if len == 0 {
+                return Ok(BlockRet::OutputFull);
+            }
+            o.slice()[..len].copy_from_slice(&i.slice()[..len]);
+            let tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < len).collect();
+            o.produce(len, &tags);
+            i.consume(len);
+            return Ok(BlockRet::Ok);
+        }
+
+        match tags.iter().find(|t| t.key() == self.key) {
+            None => {
+                let n = i.len();
+                i.consume(n);
+            }
+            Some(t) => {
+                self.triggered = true;
+                i.consume(t.pos() + usize::from(self.drop_trigger));
+            }
+        }
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{new_stream, Tag, TagValue};
+
+    fn run(drop_trigger: bool) -> Result<(Vec<u32>, Vec<Tag>)> {
+        let (w, r) = new_stream();
+        let (mut b, out) = SkipUntilTag::new(r, "start".into());
+        b.set_drop_trigger(drop_trigger);
+        let input: Vec<u32> = (0..10).collect();
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&input);
+        wb.produce(
+            input.len(),
+            &[
+                Tag::new(2, "other".into(), TagValue::Bool(true)),
+                Tag::new(4, "start".into(), TagValue::Bool(true)),
+                Tag::new(6, "other".into(), TagValue::Bool(false)),
+            ],
+        );
+        assert!(!b.triggered());
+        while b.work()? == BlockRet::Ok {}
+        assert!(b.triggered());
+        let (res, tags) = out.read_buf()?;
+        Ok((res.slice().to_vec(), tags))
+    }
+
+    #[test]
+    fn skip_until_tag() -> Result<()> {
+        let (res, tags) = run(false)?;
+        assert_eq!(res, vec![4, 5, 6, 7, 8, 9]);
+        assert_eq!(
+            tags,
+            vec![
+                Tag::new(0, "start".into(), TagValue::Bool(true)),
+                Tag::new(2, "other".into(), TagValue::Bool(false)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn drop_trigger() -> Result<()> {
+        let (res, tags) = run(true)?;
+        assert_eq!(res, vec![5, 6, 7, 8, 9]);
+        assert_eq!(
+            tags,
+            vec![Tag::new(1, "other".into(), TagValue::Bool(false))]
+        );
+        Ok(())
+    }
+}
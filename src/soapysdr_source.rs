@@ -2,10 +2,13 @@
 use anyhow::Result;
 use log::debug;
 
-use crate::block::{Block, BlockRet};
-use crate::stream::{ReadStream, WriteStream};
+use crate::block::{Block, BlockLog, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
 use crate::{Complex, Error};
 
+/// Tag key added to the first sample after an overflow, if enabled.
+pub const OVERFLOW_TAG: &str = "soapy:overflow";
+
 impl From<soapysdr::Error> for Error {
     fn from(e: soapysdr::Error) -> Self {
         Error::new(&format!("Soapy SDR Error: {}", e))
@@ -20,6 +23,8 @@ pub struct SoapySdrSourceBuilder {
     igain: f64,
     samp_rate: f64,
     freq: f64,
+    tag_overflow: bool,
+    max_read: Option<usize>,
 }
 
 impl SoapySdrSourceBuilder {
@@ -42,6 +47,24 @@ impl SoapySdrSourceBuilder {
         self.igain = igain;
         self
     }
+    /// Tag the first sample after a device overflow with [OVERFLOW_TAG].
+    ///
+    /// An overflow means samples were dropped, so there's a
+    /// discontinuity in the stream.
+    pub fn tag_overflow(mut self, tag_overflow: bool) -> Self {
+        self.tag_overflow = tag_overflow;
+        self
+    }
+    /// Read at most this many samples per call to `work()`.
+    ///
+    /// By default as many samples as fit in the output stream are
+    /// requested. With a large stream, that can mean waiting for a lot of
+    /// samples before passing any of them on. Samples are still read
+    /// directly into the stream buffer.
+    pub fn max_read(mut self, max_read: Option<usize>) -> Self {
+        self.max_read = max_read;
+        self
+    }
     /// Build the source object.
     pub fn build(self) -> Result<(SoapySdrSource, ReadStream<Complex>)> {
         let dev = soapysdr::Device::new(&*self.dev)?;
@@ -90,7 +113,17 @@ impl SoapySdrSourceBuilder {
         let mut stream = dev.rx_stream(&[self.channel])?;
         stream.activate(None)?;
         let (dst, dr) = crate::stream::new_stream();
-        Ok((SoapySdrSource { stream, dst }, dr))
+        Ok((
+            SoapySdrSource {
+                stream,
+                dst,
+                tag_overflow: self.tag_overflow,
+                max_read: self.max_read,
+                overflow_pending: false,
+                overflows: 0,
+            },
+            dr,
+        ))
     }
 }
 
@@ -101,6 +134,67 @@ pub struct SoapySdrSource {
     stream: soapysdr::RxStream<Complex>,
     #[rustradio(out)]
     dst: WriteStream<Complex>,
+    tag_overflow: bool,
+    max_read: Option<usize>,
+    overflow_pending: bool,
+    overflows: u64,
+}
+
+impl SoapySdrSource {
+    /// Number of device overflows seen so far.
+    pub fn overflows(&self) -> u64 {
+        self.overflows
+    }
+}
+
+impl Drop for SoapySdrSource {
+    fn drop(&mut self) {
+        if self.overflows > 0 {
+            self.log_info(format_args!("{} overflows", self.overflows));
+        }
+    }
+}
+
+/// What a stream read resulted in.
+#[derive(Debug, PartialEq)]
+enum ReadStatus {
+    /// Read this many samples.
+    Samples(usize),
+
+    /// No samples available yet.
+    Timeout,
+
+    /// Device dropped samples, because we didn't read fast enough.
+    Overflow,
+}
+
+fn read_status(res: Result<usize, soapysdr::Error>) -> Result<ReadStatus, Error> {
+    match res {
+        Ok(n) => Ok(ReadStatus::Samples(n)),
+        Err(e) => match e.code {
+            soapysdr::ErrorCode::Timeout => Ok(ReadStatus::Timeout),
+            soapysdr::ErrorCode::Overflow => Ok(ReadStatus::Overflow),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// Tags for samples just read, given if an overflow happened before them.
+fn overflow_tags(overflow_pending: bool, tag_overflow: bool) -> Vec<Tag> {
+    if overflow_pending && tag_overflow {
+        vec![Tag::new(0, OVERFLOW_TAG.into(), TagValue::Bool(true))]
+    } else {
+        vec![]
+    }
+}
+
+/// Number of samples to read, given the free space in the output stream.
+fn read_len(free: usize, max_read: Option<usize>) -> usize {
+    match max_read {
+        // Reading zero samples would never make progress.
+        Some(max) => std::cmp::min(free, max.max(1)),
+        None => free,
+    }
 }
 
 fn ai_string(ai: &soapysdr::ArgInfo) -> String {
@@ -114,16 +208,70 @@ impl Block for SoapySdrSource {
     fn work(&mut self) -> Result<BlockRet, Error> {
         let timeout_us = 10_000;
         let mut o = self.dst.write_buf()?;
-        let n = match self.stream.read(&mut [&mut o.slice()], timeout_us) {
-            Ok(x) => x,
-            Err(e) => {
-                if e.code == soapysdr::ErrorCode::Timeout {
-                    return Ok(BlockRet::Ok);
-                }
-                return Err(e.into());
+        let len = read_len(o.len(), self.max_read);
+        let n = match read_status(self.stream.read(&mut [&mut o.slice()[..len]], timeout_us))? {
+            ReadStatus::Samples(n) => n,
+            ReadStatus::Timeout => return Ok(BlockRet::Ok),
+            ReadStatus::Overflow => {
+                self.overflows += 1;
+                self.overflow_pending = true;
+                self.log_warn(format_args!("overflow, samples dropped"));
+                return Ok(BlockRet::Ok);
             }
         };
-        o.produce(n, &[]);
+        if n == 0 {
+            return Ok(BlockRet::Ok);
+        }
+        let tags = overflow_tags(self.overflow_pending, self.tag_overflow);
+        self.overflow_pending = false;
+        o.produce(n, &tags);
         Ok(BlockRet::Ok)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(code: soapysdr::ErrorCode) -> soapysdr::Error {
+        soapysdr::Error {
+            code,
+            message: "test".into(),
+        }
+    }
+
+    #[test]
+    fn status() -> Result<()> {
+        assert_eq!(read_status(Ok(10))?, ReadStatus::Samples(10));
+        assert_eq!(
+            read_status(Err(err(soapysdr::ErrorCode::Timeout)))?,
+            ReadStatus::Timeout
+        );
+        assert_eq!(
+            read_status(Err(err(soapysdr::ErrorCode::Overflow)))?,
+            ReadStatus::Overflow
+        );
+        assert!(read_status(Err(err(soapysdr::ErrorCode::StreamError))).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn chunk() {
+        assert_eq!(read_len(1000, None), 1000);
+        assert_eq!(read_len(1000, Some(100)), 100);
+        assert_eq!(read_len(50, Some(100)), 50);
+        assert_eq!(read_len(1000, Some(0)), 1);
+        assert_eq!(read_len(0, Some(100)), 0);
+    }
+
+    #[test]
+    fn tags() {
+        assert!(overflow_tags(false, false).is_empty());
+        assert!(overflow_tags(false, true).is_empty());
+        assert!(overflow_tags(true, false).is_empty());
+        assert_eq!(
+            overflow_tags(true, true),
+            vec![Tag::new(0, OVERFLOW_TAG.into(), TagValue::Bool(true))]
+        );
+    }
+}
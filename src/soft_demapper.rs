@@ -0,0 +1,171 @@
+/*! Soft demapping of constellation symbols into bit LLRs.
+
+Turns received symbols into log-likelihood ratios (LLRs), one per coded
+bit, for use with a soft decision decoder.
+
+The max-log approximation is used. For each bit, the LLR is
+
+```text
+LLR = (min |r-s|² for s where bit is 1 - min |r-s|² for s where bit is 0) / noise_var
+```
+
+So positive means the bit is more likely a 0, and negative means it's more
+likely a 1. The magnitude is the confidence.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Soft demapper, outputting per bit LLRs.
+///
+/// The constellation is given as a list of points, indexed by the symbol
+/// value. Bits are output most significant bit first.
+///
+/// E.g. BPSK is `[1, -1]`, mapping bit 0 to `1` and bit 1 to `-1`.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct SoftDemapper {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dst: WriteStream<Float>,
+    constellation: Vec<Complex>,
+    bits: usize,
+    noise_var: Float,
+}
+
+impl SoftDemapper {
+    /// Create new SoftDemapper block.
+    ///
+    /// The constellation size must be a power of two, and at least 2.
+    pub fn new(
+        src: ReadStream<Complex>,
+        constellation: Vec<Complex>,
+        noise_var: Float,
+    ) -> Result<(Self, ReadStream<Float>)> {
+        let len = constellation.len();
+        if len < 2 || !len.is_power_of_two() {
+            return Err(Error::new(&format!(
+                "SoftDemapper: constellation size must be a power of two, got {len}"
+            ))
+            .into());
+        }
+        let (dst, dr) = crate::stream::new_stream();
+        Ok((
+            Self {
+                src,
+                dst,
+                bits: len.trailing_zeros() as usize,
+                constellation,
+                noise_var,
+            },
+            dr,
+        ))
+    }
+
+    /// Set the noise variance estimate.
+    pub fn set_noise_variance(&mut self, noise_var: Float) {
+        self.noise_var = noise_var;
+    }
+
+    fn demap(&self, r: Complex, out: &mut [Float]) {
+        for (bit, o) in out.iter_mut().enumerate() {
+            let mask = 1 << (self.bits - bit - 1);
+            let (mut d0, mut d1) = (Float::INFINITY, Float::INFINITY);
+            for (sym, s) in self.constellation.iter().enumerate() {
+                let d = (r - s).norm_sqr();
+                if sym & mask == 0 {
+                    d0 = d0.min(d);
+                } else {
+                    d1 = d1.min(d);
+                }
+            }
+            *o = (d1 - d0) / self.noise_var;
+        }
+    }
+}
+
+impl Block for SoftDemapper {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, _tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len() / self.bits);
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        for (r, out) in i.iter().take(n).zip(o.slice().chunks_exact_mut(self.bits)) {
+            self.demap(*r, out);
+        }
+        i.consume(n);
+        o.produce(n * self.bits, &[]);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    fn run(
+        constellation: Vec<Complex>,
+        input: Vec<Complex>,
+        noise_var: Float,
+    ) -> Result<Vec<Float>> {
+        let (_, out, _) = crate::tests::run_block(input, |prev| {
+            SoftDemapper::new(prev, constellation, noise_var)
+        })?;
+        Ok(out)
+    }
+
+    #[test]
+    fn bpsk() -> Result<()> {
+        let bpsk = vec![Complex::new(1.0, 0.0), Complex::new(-1.0, 0.0)];
+        let input: Vec<_> = [0.9, 0.1, -0.1, -2.0, 0.5]
+            .into_iter()
+            .map(|v| Complex::new(v, 0.3))
+            .collect();
+        let got = run(bpsk.clone(), input.clone(), 0.5)?;
+        assert_eq!(got.len(), input.len());
+        for (r, llr) in input.iter().zip(&got) {
+            // Positive LLR means bit 0, which is the positive symbol.
+            assert_eq!(r.re > 0.0, *llr > 0.0, "{r} => {llr}");
+            // For BPSK, LLR is 4r/noise_var.
+            assert!((llr - 4.0 * r.re / 0.5).abs() < 1e-4, "{r} => {llr}");
+        }
+
+        // Less noise, more confidence.
+        let quiet = run(bpsk, input, 0.25)?;
+        for (a, b) in got.iter().zip(&quiet) {
+            assert!((b - 2.0 * a).abs() < 1e-4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn qpsk() -> Result<()> {
+        // Gray coded QPSK.
+        let qpsk = vec![
+            Complex::new(1.0, 1.0),
+            Complex::new(1.0, -1.0),
+            Complex::new(-1.0, 1.0),
+            Complex::new(-1.0, -1.0),
+        ];
+        let got = run(qpsk, vec![Complex::new(-0.8, 0.2)], 1.0)?;
+        assert_eq!(got.len(), 2);
+        // First bit is the sign of re, second the sign of im.
+        assert!(got[0] < 0.0);
+        assert!(got[1] > 0.0);
+        // Closer to the re boundary than the im boundary.
+        assert!(got[0].abs() > got[1].abs());
+        Ok(())
+    }
+
+    #[test]
+    fn bad_constellation() {
+        let (_, prev) = VectorSource::<Complex>::new(vec![]);
+        assert!(SoftDemapper::new(prev, vec![Complex::default(); 3], 1.0).is_err());
+    }
+}
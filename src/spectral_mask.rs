@@ -0,0 +1,253 @@
+/*! Filter specified in the frequency domain, as gain per FFT bin.
+
+E.g. to notch out narrowband interferers:
+
+```
+use rustradio::Complex;
+use rustradio::blocks::{ConstantSource, SpectralMask};
+let (src, prev) = ConstantSource::new(Complex::new(0.0, 0.0));
+// 1024 bins at 1Msps is about 1kHz per bin. Remove 100kHz ±5kHz.
+let (mask, prev) = SpectralMask::builder(1024)
+    .freqs(1_000_000.0, 95_000.0, 105_000.0, 0.0)
+    .build(prev);
+```
+*/
+use std::ops::Range;
+
+use rustfft::FftPlanner;
+
+use crate::block::{Block, BlockCommand, BlockEOF, BlockName, BlockPorts, BlockRet, PortInfo};
+use crate::fft_filter::{FftFilter, FftFilterHandle};
+use crate::stream::ReadStream;
+use crate::window::WindowType;
+use crate::{Complex, Error, Float};
+
+// Turn per bin gains into filter taps, by frequency sampling.
+//
+// The inverse FFT of the gains is the impulse response, centered on sample
+// zero. It's rotated to the middle of the taps, to make it causal, and
+// windowed, to smooth the response between bin centers.
+fn mask_taps(gains: &[Float]) -> Vec<Complex> {
+    let n = gains.len();
+    let mut h: Vec<Complex> = gains.iter().map(|&g| Complex::new(g, 0.0)).collect();
+    FftPlanner::new().plan_fft_inverse(n).process(&mut h);
+    h.rotate_right(n / 2);
+    let window = WindowType::Hamming.make_window(n);
+    h.iter()
+        .zip(window.coeffs())
+        .map(|(t, w)| t * (w / n as Float))
+        .collect()
+}
+
+/// Builder for [SpectralMask].
+pub struct SpectralMaskBuilder {
+    gains: Vec<Float>,
+}
+
+impl SpectralMaskBuilder {
+    /// Create new builder, with `nbins` bins, all with gain 1.0.
+    ///
+    /// More bins give a sharper mask, but a longer delay.
+    pub fn new(nbins: usize) -> Self {
+        Self {
+            gains: vec![1.0; nbins],
+        }
+    }
+
+    /// Set gain of a range of bins.
+    ///
+    /// Bins are in FFT order: bin 0 is DC, followed by the positive
+    /// frequencies, and then the negative frequencies. Bins past the end
+    /// are ignored.
+    pub fn bins(mut self, range: Range<usize>, gain: Float) -> Self {
+        let end = std::cmp::min(range.end, self.gains.len());
+        if range.start < end {
+            self.gains[range.start..end].fill(gain);
+        }
+        self
+    }
+
+    /// Set gain of all bins with center frequency from `low` to `high`
+    /// inclusive, in Hz. Negative frequencies are below the center.
+    pub fn freqs(mut self, samp_rate: Float, low: Float, high: Float, gain: Float) -> Self {
+        let n = self.gains.len();
+        for (k, g) in self.gains.iter_mut().enumerate() {
+            let bin = if k < n.div_ceil(2) {
+                k as Float
+            } else {
+                k as Float - n as Float
+            };
+            let freq = bin * samp_rate / n as Float;
+            if (low..=high).contains(&freq) {
+                *g = gain;
+            }
+        }
+        self
+    }
+
+    /// Build SpectralMask block.
+    pub fn build(self, src: ReadStream<Complex>) -> (SpectralMask, ReadStream<Complex>) {
+        SpectralMask::new(src, &self.gains)
+    }
+}
+
+/// Handle for changing the gains of a running [SpectralMask].
+#[derive(Clone)]
+pub struct SpectralMaskHandle(FftFilterHandle);
+
+impl SpectralMaskHandle {
+    /// Set new gains. The number of bins may change.
+    pub fn set_gains(&self, gains: &[Float]) {
+        self.0.set_taps(&mask_taps(gains));
+    }
+}
+
+/// Spectral mask block.
+///
+/// Applies a gain per frequency bin, e.g. zero to notch out narrowband
+/// interferers. It's a freely shaped filter, where instead of taps the
+/// frequency response is given.
+///
+/// The gains are turned into filter taps for an inner
+/// [FftFilter][crate::fft_filter::FftFilter]. Between bin centers the
+/// response is smoothed by a Hamming window, so a notch should be a few bins
+/// wide to reach full depth.
+pub struct SpectralMask {
+    filter: FftFilter,
+}
+
+impl SpectralMask {
+    /// Create new SpectralMask block, given gain per bin, in FFT order.
+    ///
+    /// See [SpectralMaskBuilder::bins] for the bin order.
+    pub fn new(src: ReadStream<Complex>, gains: &[Float]) -> (Self, ReadStream<Complex>) {
+        let (filter, dr) = FftFilter::new(src, &mask_taps(gains));
+        (Self { filter }, dr)
+    }
+
+    /// Create a builder, for setting gains by bin or frequency ranges.
+    pub fn builder(nbins: usize) -> SpectralMaskBuilder {
+        SpectralMaskBuilder::new(nbins)
+    }
+
+    /// Return a handle for changing the gains.
+    pub fn handle(&self) -> SpectralMaskHandle {
+        SpectralMaskHandle(self.filter.handle())
+    }
+
+    /// Set new gains. The number of bins may change.
+    pub fn set_gains(&self, gains: &[Float]) {
+        self.handle().set_gains(gains);
+    }
+
+    /// Return the delay through the block, in samples.
+    ///
+    /// Gains set via a handle are only counted once the block has picked
+    /// them up.
+    pub fn delay_samples(&self) -> Float {
+        // The taps are centered on tap `ntaps/2`, not on `(ntaps-1)/2` as
+        // FftFilter assumes.
+        (self.filter.delay_samples() + 0.5).floor()
+    }
+}
+
+impl BlockName for SpectralMask {
+    fn block_name(&self) -> &str {
+        "SpectralMask"
+    }
+}
+
+impl BlockEOF for SpectralMask {
+    fn eof(&mut self) -> bool {
+        self.filter.eof()
+    }
+}
+
+impl BlockCommand for SpectralMask {}
+
+impl BlockPorts for SpectralMask {
+    fn ports(&self) -> (Vec<PortInfo>, Vec<PortInfo>) {
+        self.filter.ports()
+    }
+    fn samples_consumed(&self) -> u64 {
+        self.filter.samples_consumed()
+    }
+    fn samples_produced(&self) -> u64 {
+        self.filter.samples_produced()
+    }
+}
+
+impl Block for SpectralMask {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        self.filter.work()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    // Amplitude of a tone in a signal.
+    fn amplitude(data: &[Complex], freq: Float) -> Float {
+        let sum: Complex = data
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                s * Complex::from_polar(
+                    1.0,
+                    -2.0 * std::f64::consts::PI as Float * freq * i as Float,
+                )
+            })
+            .sum();
+        sum.norm() / data.len() as Float
+    }
+
+    #[test]
+    fn notch_one_tone() -> crate::Result<()> {
+        // 64 bins of 1kHz each.
+        let samp_rate = 64_000.0;
+        let (f1, f2) = (5_000.0, -12_000.0);
+        let input: Vec<Complex> = (0..8192)
+            .map(|i| {
+                let t = i as Float / samp_rate;
+                let w = 2.0 * std::f64::consts::PI as Float;
+                Complex::from_polar(1.0, w * f1 * t) + Complex::from_polar(0.5, w * f2 * t)
+            })
+            .collect();
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = SpectralMask::builder(64)
+            .freqs(samp_rate, -14_000.0, -10_000.0, 0.0)
+            .build(prev);
+        assert_eq!(b.delay_samples(), 32.0);
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        // Skip the start, while the filter fills up.
+        let res = &res.slice()[64..];
+        assert!(res.len() > 4000, "{}", res.len());
+        let a1 = amplitude(res, f1 / samp_rate);
+        let a2 = amplitude(res, f2 / samp_rate);
+        assert!((a1 - 1.0).abs() < 0.01, "kept tone amplitude {a1}");
+        assert!(a2 < 0.005, "notched tone amplitude {a2}");
+        Ok(())
+    }
+
+    #[test]
+    fn set_gains() -> crate::Result<()> {
+        let input = vec![Complex::new(1.0, 0.0); 4096];
+        let (mut src, prev) = VectorSource::new(input);
+        let (mut b, out) = SpectralMask::builder(64).build(prev);
+        // Notch DC, picked up on the next work().
+        b.handle()
+            .set_gains(&[&[0.0; 4][..], &[1.0; 56], &[0.0; 4]].concat());
+        src.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        let res = &res.slice()[64..];
+        assert!(res.len() > 2000, "{}", res.len());
+        let a = amplitude(res, 0.0);
+        assert!(a < 0.005, "DC amplitude {a}");
+        Ok(())
+    }
+}
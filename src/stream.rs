@@ -4,7 +4,9 @@ Blocks are connected with streams. A block can have zero or more input
 streams, and write to zero or more output streams.
 */
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 
 use crate::circular_buffer;
 use crate::{Error, Float, Len};
@@ -12,6 +14,14 @@ use crate::{Error, Float, Len};
 /// Tag position in the current stream.
 pub type TagPos = usize;
 
+/// Tag key for the start of each pass through the data, from sources set
+/// to repeat it, e.g. [VectorSource][crate::vector_source::VectorSource],
+/// [FileSource][crate::file_source::FileSource], and
+/// [SigMFSource][crate::sigmf::SigMFSource].
+///
+/// The value is a `U64` count of the passes before it, starting at zero.
+pub const TAG_REPEAT: &str = "source:repeat";
+
 /// Enum of tag values.
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum TagValue {
@@ -26,6 +36,56 @@ pub enum TagValue {
 
     /// U64 value.
     U64(u64),
+
+    /// Bytes value.
+    Bytes(Vec<u8>),
+}
+
+/// Format as `Type:value`, e.g. `Float:1.5`. Bytes are formatted as hex.
+impl std::fmt::Display for TagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TagValue::String(v) => write!(f, "String:{v}"),
+            TagValue::Float(v) => write!(f, "Float:{v}"),
+            TagValue::Bool(v) => write!(f, "Bool:{v}"),
+            TagValue::U64(v) => write!(f, "U64:{v}"),
+            TagValue::Bytes(v) => {
+                write!(f, "Bytes:")?;
+                for b in v {
+                    write!(f, "{b:02x}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parse the format written by `Display`.
+impl std::str::FromStr for TagValue {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || Error::new(&format!("invalid tag value {s:?}"));
+        let (typ, val) = s.split_once(':').ok_or_else(bad)?;
+        Ok(match typ {
+            "String" => TagValue::String(val.to_string()),
+            "Float" => TagValue::Float(val.parse().map_err(|_| bad())?),
+            "Bool" => TagValue::Bool(val.parse().map_err(|_| bad())?),
+            "U64" => TagValue::U64(val.parse().map_err(|_| bad())?),
+            "Bytes" => {
+                if val.len() % 2 != 0 || !val.is_ascii() {
+                    return Err(bad());
+                }
+                TagValue::Bytes(
+                    (0..val.len())
+                        .step_by(2)
+                        .map(|i| u8::from_str_radix(&val[i..i + 2], 16))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| bad())?,
+                )
+            }
+            _ => return Err(bad()),
+        })
+    }
 }
 
 /// Tags associated with a stream.
@@ -60,8 +120,148 @@ impl Tag {
     }
 }
 
+/// Lookup of tags by position.
+///
+/// For the position sorted tags returned by e.g. `ReadStream::read_buf()`.
+/// Lookups are binary searches, so the tags must be sorted by position.
+///
+/// ```
+/// use rustradio::stream::{Tag, TagSlice, TagValue};
+/// let tags = vec![
+///     Tag::new(2, "a".into(), TagValue::Bool(true)),
+///     Tag::new(5, "b".into(), TagValue::U64(1)),
+/// ];
+/// assert!(tags.find_tag(2, "a").is_some());
+/// assert_eq!(tags.tags_at(3).count(), 0);
+/// ```
+pub trait TagSlice {
+    /// Return the tags at `pos`.
+    fn tags_at(&self, pos: TagPos) -> std::slice::Iter<'_, Tag>;
+
+    /// Return the first tag at `pos` with the given key.
+    fn find_tag(&self, pos: TagPos, key: &str) -> Option<&Tag> {
+        self.tags_at(pos).find(|t| t.key() == key)
+    }
+}
+
+impl TagSlice for [Tag] {
+    fn tags_at(&self, pos: TagPos) -> std::slice::Iter<'_, Tag> {
+        let start = self.partition_point(|t| t.pos() < pos);
+        let end = start + self[start..].partition_point(|t| t.pos() == pos);
+        self[start..end].iter()
+    }
+}
+
+/// Format as `pos:key=Type:value`, e.g. `10:burst=Bool:true`.
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}={}", self.pos, self.key, self.val)
+    }
+}
+
+/// Parse the format written by `Display`.
+///
+/// Keys may contain `:`, but not `=`.
+impl std::str::FromStr for Tag {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || Error::new(&format!("invalid tag {s:?}"));
+        let (pos, rest) = s.split_once(':').ok_or_else(bad)?;
+        let (key, val) = rest.split_once('=').ok_or_else(bad)?;
+        Ok(Tag::new(
+            pos.parse().map_err(|_| bad())?,
+            key.to_string(),
+            val.parse()?,
+        ))
+    }
+}
+
+/// Identifies a stream.
+///
+/// Both ends of a stream have the same id. Ids are only unique among
+/// streams that currently exist.
+pub type StreamId = usize;
+
+/// A block port, i.e. a stream end, an optional stream end, or a `Vec` of
+/// stream ends.
+///
+/// Used by the derive macro to list a block's streams.
+pub trait StreamPort {
+    /// Return the ids of the connected streams.
+    fn stream_ids(&self) -> Vec<StreamId>;
+
+    /// Return the total number of samples, or objects, that have passed
+    /// through this end of the stream.
+    ///
+    /// For the read end that's how many have been consumed, and for the
+    /// write end how many have been produced.
+    fn samples(&self) -> u64;
+}
+
+impl<P: StreamPort> StreamPort for Option<P> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        self.as_ref().map_or(Vec::new(), StreamPort::stream_ids)
+    }
+    fn samples(&self) -> u64 {
+        self.as_ref().map_or(0, StreamPort::samples)
+    }
+}
+
+impl<P: StreamPort> StreamPort for Vec<P> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        self.iter().flat_map(StreamPort::stream_ids).collect()
+    }
+    fn samples(&self) -> u64 {
+        self.iter().map(StreamPort::samples).sum()
+    }
+}
+
 pub(crate) const DEFAULT_STREAM_SIZE: usize = 409600;
 
+/// Default max number of objects in a stream of noncopyable objects.
+pub const DEFAULT_NOCOPY_CAPACITY: usize = 1000;
+
+/// Max number of references to a stream's buffer while getting a read or
+/// write buffer, when using [RefcountCheck::Strict].
+///
+/// The read end, the write end, and the buffer being created.
+pub const MAX_STREAM_REFS: usize = 3;
+
+/** How strictly to check the number of references to a stream's buffer.
+
+Normally a stream's underlying buffer is referenced by the read end, the
+write end, and at most one outstanding `BufferReader` or `BufferWriter`
+per end. Holding on to a `BufferReader` while calling `read_buf()` again is
+a bug, since the same samples would be handed out twice.
+
+Counting references is only a debug aid, not a safety requirement. It
+can't tell a leaked `BufferReader` apart from the other end's
+`BufferWriter` being in use from another thread, so it's off by default.
+Strict mode is useful in tests and single threaded graphs.
+*/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefcountCheck {
+    /// Don't check the number of references.
+    #[default]
+    Lenient,
+
+    /// Return error from `read_buf()` and `write_buf()` if the buffer has
+    /// more than [MAX_STREAM_REFS] references.
+    Strict,
+}
+
+impl RefcountCheck {
+    fn check<T>(self, circ: &Arc<circular_buffer::Buffer<T>>) -> Result<(), Error> {
+        let refs = Arc::strong_count(circ);
+        if self == RefcountCheck::Strict && refs > MAX_STREAM_REFS {
+            return Err(Error::new(&format!(
+                "stream buffer has {refs} references, max is {MAX_STREAM_REFS}. Leaked a BufferReader or BufferWriter?"
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// ReadStream is the reading side of a stream.
 ///
 /// From the ReadStream you can get windows into the current stream by calling
@@ -69,6 +269,24 @@ pub(crate) const DEFAULT_STREAM_SIZE: usize = 409600;
 #[derive(Debug)]
 pub struct ReadStream<T> {
     circ: Arc<circular_buffer::Buffer<T>>,
+    refcount_check: RefcountCheck,
+}
+
+impl<T> ReadStream<T> {
+    /// Return the id of the stream.
+    #[must_use]
+    pub fn id(&self) -> StreamId {
+        Arc::as_ptr(&self.circ) as StreamId
+    }
+}
+
+impl<T> StreamPort for ReadStream<T> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        vec![self.id()]
+    }
+    fn samples(&self) -> u64 {
+        self.circ.total_consumed()
+    }
 }
 
 impl<T: Copy> ReadStream<T> {
@@ -80,7 +298,10 @@ impl<T: Copy> ReadStream<T> {
         let mut wb = circ.clone().write_buf().unwrap();
         wb.fill_from_slice(data);
         wb.produce(data.len(), &[]);
-        Self { circ }
+        Self {
+            circ,
+            refcount_check: RefcountCheck::default(),
+        }
     }
 
     /// Return total length of underlying circular buffer (before the
@@ -90,10 +311,46 @@ impl<T: Copy> ReadStream<T> {
         self.circ.total_size()
     }
 
+    /// Return number of samples ready to be read.
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.circ.used()
+    }
+
+    /// Return how full the stream is, from 0.0 (empty) to 1.0 (full).
+    ///
+    /// A stream that's often full means the reading block is a
+    /// bottleneck.
+    #[must_use]
+    pub fn fill_fraction(&self) -> f32 {
+        self.circ.fill_fraction()
+    }
+
+    /// Return number of tags dropped because of the tag limit.
+    #[must_use]
+    pub fn dropped_tags(&self) -> usize {
+        self.circ.dropped_tags()
+    }
+
     /// Return a BufferReader allowing you to read from the stream, and
     /// "consume" from it.
     pub fn read_buf(&self) -> Result<(circular_buffer::BufferReader<T>, Vec<Tag>), Error> {
-        Ok(Arc::clone(&self.circ).read_buf()?)
+        let circ = Arc::clone(&self.circ);
+        self.refcount_check.check(&circ)?;
+        Ok(circ.read_buf()?)
+    }
+
+    /// Block until at least `need` samples can be read, or the stream's
+    /// wait timeout expires.
+    ///
+    /// Returns true if the samples are available.
+    pub fn wait_for_read(&self, need: usize) -> bool {
+        self.circ.wait_for_read(need)
+    }
+
+    /// Set how strictly to check references in `read_buf()`.
+    pub fn set_refcount_check(&mut self, check: RefcountCheck) {
+        self.refcount_check = check;
     }
 
     /// Return true if there is nothing more ever to read from the stream.
@@ -104,11 +361,13 @@ impl<T: Copy> ReadStream<T> {
             return false;
         }
         // TODO: can we remove this needless clone?
-        match Arc::clone(&self.circ).read_buf() {
-            Ok((b, _)) if !b.is_empty() => false,
+        let empty = match Arc::clone(&self.circ).read_buf() {
+            Ok((b, _)) => b.is_empty(),
             Err(_) => false,
-            Ok(_) => Arc::strong_count(&self.circ) == 1,
-        }
+        };
+        // The BufferReader is dropped by now, so it no longer holds a
+        // reference.
+        empty && Arc::strong_count(&self.circ) == 1
     }
 }
 
@@ -116,6 +375,24 @@ impl<T: Copy> ReadStream<T> {
 #[derive(Debug)]
 pub struct WriteStream<T> {
     circ: Arc<circular_buffer::Buffer<T>>,
+    refcount_check: RefcountCheck,
+}
+
+impl<T> WriteStream<T> {
+    /// Return the id of the stream.
+    #[must_use]
+    pub fn id(&self) -> StreamId {
+        Arc::as_ptr(&self.circ) as StreamId
+    }
+}
+
+impl<T> StreamPort for WriteStream<T> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        vec![self.id()]
+    }
+    fn samples(&self) -> u64 {
+        self.circ.total_produced()
+    }
 }
 
 impl<T: Copy> WriteStream<T> {
@@ -125,9 +402,43 @@ impl<T: Copy> WriteStream<T> {
         self.circ.free()
     }
 
+    /// Return how full the stream is, from 0.0 (empty) to 1.0 (full).
+    #[must_use]
+    pub fn fill_fraction(&self) -> f32 {
+        self.circ.fill_fraction()
+    }
+
+    /// Limit how many unread tags the stream holds.
+    ///
+    /// See [`circular_buffer::Buffer::set_tag_limit()`].
+    pub fn set_tag_limit(&self, max: Option<usize>, overflow: circular_buffer::TagOverflow) {
+        self.circ.set_tag_limit(max, overflow);
+    }
+
+    /// Return number of tags dropped because of the tag limit.
+    #[must_use]
+    pub fn dropped_tags(&self) -> usize {
+        self.circ.dropped_tags()
+    }
+
     /// Return a BufferWriter for writing to the stream.
     pub fn write_buf(&self) -> Result<circular_buffer::BufferWriter<T>, Error> {
-        Ok(Arc::clone(&self.circ).write_buf()?)
+        let circ = Arc::clone(&self.circ);
+        self.refcount_check.check(&circ)?;
+        Ok(circ.write_buf()?)
+    }
+
+    /// Block until at least `need` samples can be written, or the stream's
+    /// wait timeout expires.
+    ///
+    /// Returns true if the space is available.
+    pub fn wait_for_write(&self, need: usize) -> bool {
+        self.circ.wait_for_write(need)
+    }
+
+    /// Set how strictly to check references in `write_buf()`.
+    pub fn set_refcount_check(&mut self, check: RefcountCheck) {
+        self.refcount_check = check;
     }
 }
 
@@ -139,18 +450,85 @@ impl<T: Copy> WriteStream<T> {
 /// Basically anything that GNU Radio would *not* call a message port.
 #[must_use]
 pub fn new_stream<T>() -> (WriteStream<T>, ReadStream<T>) {
-    let circ = Arc::new(circular_buffer::Buffer::new(DEFAULT_STREAM_SIZE).unwrap());
-    (WriteStream { circ: circ.clone() }, ReadStream { circ })
+    new_stream_with_wait_timeout(circular_buffer::DEFAULT_WAIT_TIMEOUT)
+}
+
+/// Create a new stream, with a max time to block in `wait_for_read()` and
+/// `wait_for_write()`.
+///
+/// Latency sensitive graphs can use a shorter timeout than the default.
+#[must_use]
+pub fn new_stream_with_wait_timeout<T>(
+    timeout: std::time::Duration,
+) -> (WriteStream<T>, ReadStream<T>) {
+    let circ =
+        Arc::new(circular_buffer::Buffer::with_wait_timeout(DEFAULT_STREAM_SIZE, timeout).unwrap());
+    (
+        WriteStream {
+            circ: circ.clone(),
+            refcount_check: RefcountCheck::default(),
+        },
+        ReadStream {
+            circ,
+            refcount_check: RefcountCheck::default(),
+        },
+    )
+}
+
+// State shared by the two ends of a noncopy stream.
+struct NCShared<T> {
+    // Objects, and their tags.
+    q: Mutex<VecDeque<(T, Vec<Tag>)>>,
+    // Notified on push, pop, and when either end is dropped.
+    cond: Condvar,
+    // Set when either end is dropped.
+    closed: AtomicBool,
+    wait_timeout: std::time::Duration,
+}
+
+impl<T> NCShared<T> {
+    // Wait until `done` returns true for the queue, the other end is
+    // dropped, or the wait timeout expires. Returns the final `done()`.
+    fn wait(&self, done: impl Fn(&VecDeque<(T, Vec<Tag>)>) -> bool) -> bool {
+        let q = self.q.lock().unwrap();
+        let (q, _) = self
+            .cond
+            .wait_timeout_while(q, self.wait_timeout, |q| {
+                !done(q) && !self.closed.load(Ordering::Relaxed)
+            })
+            .unwrap();
+        done(&q)
+    }
+
+    // Mark the stream as closed, and wake up any waiter.
+    fn close(&self) {
+        // Hold the lock, so that the waiter either sees the flag, or is
+        // already waiting and gets the notification.
+        let _q = self.q.lock().unwrap();
+        self.closed.store(true, Ordering::Relaxed);
+        self.cond.notify_all();
+    }
 }
 
 /// A stream of noncopyable objects (e.g. Vec / PDUs).
 pub struct NCReadStream<T> {
-    q: Arc<Mutex<VecDeque<T>>>,
+    shared: Arc<NCShared<T>>,
+    capacity: usize,
+    // Objects popped.
+    count: AtomicU64,
 }
 
 /// A stream of noncopyable objects (e.g. Vec / PDUs).
+///
+/// The capacity is not enforced by `push()`, which never fails. Writers
+/// that want flow control should check `remaining()` before pushing, and
+/// return `BlockRet::OutputFull` if there's no room, or block in
+/// `wait_for_write()`.
 pub struct NCWriteStream<T> {
-    q: Arc<Mutex<VecDeque<T>>>,
+    shared: Arc<NCShared<T>>,
+    capacity: usize,
+    // Objects pushed.
+    count: AtomicU64,
 }
 
 /// Create a new stream for data elements that do not implement Copy.
@@ -159,43 +537,416 @@ pub struct NCWriteStream<T> {
 /// which you would not want to just copy willy nilly.
 #[must_use]
 pub fn new_nocopy_stream<T>() -> (NCWriteStream<T>, NCReadStream<T>) {
-    let q = Arc::new(Mutex::new(VecDeque::new()));
-    (NCWriteStream { q: q.clone() }, NCReadStream { q })
+    new_nocopy_stream_with_capacity(DEFAULT_NOCOPY_CAPACITY)
+}
+
+/// Create a new stream for data elements that do not implement Copy, with a
+/// given capacity.
+///
+/// Capacity is in number of objects (e.g. packets), not bytes.
+#[must_use]
+pub fn new_nocopy_stream_with_capacity<T>(capacity: usize) -> (NCWriteStream<T>, NCReadStream<T>) {
+    let shared = Arc::new(NCShared {
+        q: Mutex::new(VecDeque::new()),
+        cond: Condvar::new(),
+        closed: AtomicBool::new(false),
+        wait_timeout: circular_buffer::DEFAULT_WAIT_TIMEOUT,
+    });
+    (
+        NCWriteStream {
+            shared: shared.clone(),
+            capacity,
+            count: AtomicU64::new(0),
+        },
+        NCReadStream {
+            shared,
+            capacity,
+            count: AtomicU64::new(0),
+        },
+    )
+}
+
+impl<T> StreamPort for NCReadStream<T> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        vec![self.id()]
+    }
+    fn samples(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
 }
 
 impl<T> NCReadStream<T> {
+    /// Return the id of the stream.
+    #[must_use]
+    pub fn id(&self) -> StreamId {
+        Arc::as_ptr(&self.shared) as StreamId
+    }
+
     /// Pop one sample.
     /// Ideally this should only be NoCopy.
     #[must_use]
     pub fn pop(&self) -> Option<(T, Vec<Tag>)> {
-        // TODO: attach tags.
-        self.q.lock().unwrap().pop_front().map(|v| (v, Vec::new()))
+        let ret = self.shared.q.lock().unwrap().pop_front();
+        if ret.is_some() {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.shared.cond.notify_all();
+        }
+        ret
     }
 
     /// Return true if there is nothing more ever to read from the stream.
     #[must_use]
     pub fn eof(&self) -> bool {
-        if !self.q.lock().unwrap().is_empty() {
+        if !self.shared.q.lock().unwrap().is_empty() {
             false
         } else {
-            Arc::strong_count(&self.q) == 1
+            Arc::strong_count(&self.shared) == 1
         }
     }
+
+    /// Max number of objects the stream is meant to hold.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of objects currently in the stream.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shared.q.lock().unwrap().len()
+    }
+
+    /// Return true if there are currently no objects in the stream.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shared.q.lock().unwrap().is_empty()
+    }
+
+    /// Block until at least `need` objects can be popped.
+    ///
+    /// Gives up when the wait timeout expires, or the write end is
+    /// dropped. Returns true if the objects are available.
+    pub fn wait_for_read(&self, need: usize) -> bool {
+        self.shared.wait(|q| q.len() >= need)
+    }
+}
+
+impl<T> Drop for NCReadStream<T> {
+    fn drop(&mut self) {
+        self.shared.close();
+    }
+}
+
+impl<T> StreamPort for NCWriteStream<T> {
+    fn stream_ids(&self) -> Vec<StreamId> {
+        vec![self.id()]
+    }
+    fn samples(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
 }
 
 impl<T> NCWriteStream<T> {
+    /// Return the id of the stream.
+    #[must_use]
+    pub fn id(&self) -> StreamId {
+        Arc::as_ptr(&self.shared) as StreamId
+    }
+
+    /// Create a new stream with a given capacity.
+    ///
+    /// Same as `new_nocopy_stream_with_capacity()`.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> (Self, NCReadStream<T>) {
+        new_nocopy_stream_with_capacity(capacity)
+    }
+
     /// Push one sample, handing off ownership.
     /// Ideally this should only be NoCopy.
     ///
-    /// TODO: Actually store the tags.
-    pub fn push(&self, val: T, _tags: &[Tag]) {
-        self.q.lock().unwrap().push_back(val);
+    /// Tag positions are relative to the start of the object, e.g. the
+    /// index into a `Vec`.
+    pub fn push(&self, val: T, tags: &[Tag]) {
+        self.shared
+            .q
+            .lock()
+            .unwrap()
+            .push_back((val, tags.to_vec()));
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.shared.cond.notify_all();
+    }
+
+    /// Max number of objects the stream is meant to hold.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of objects currently in the stream.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shared.q.lock().unwrap().len()
+    }
+
+    /// Return true if there are currently no objects in the stream.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shared.q.lock().unwrap().is_empty()
+    }
+
+    /// Number of objects that can be pushed before reaching capacity.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.capacity.saturating_sub(self.len())
+    }
+
+    /// Block until at least `need` objects can be pushed without going
+    /// over capacity.
+    ///
+    /// Gives up when the wait timeout expires, or the read end is
+    /// dropped, so that the caller can check for cancellation. Returns
+    /// true if the space is available.
+    pub fn wait_for_write(&self, need: usize) -> bool {
+        self.shared.wait(|q| q.len() + need <= self.capacity)
+    }
+}
+
+impl<T> Drop for NCWriteStream<T> {
+    fn drop(&mut self) {
+        self.shared.close();
     }
 }
 
 impl<T: Len> NCReadStream<T> {
     /// Get the size of the front packet.
     pub fn peek_size(&self) -> Option<usize> {
-        self.q.lock().unwrap().front().map(|e| e.len())
+        self.shared.q.lock().unwrap().front().map(|(e, _)| e.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_lookup() {
+        let tag = |pos, key: &str| Tag::new(pos, key.into(), TagValue::U64(pos as u64));
+        let tags = [
+            tag(0, "a"),
+            tag(3, "a"),
+            tag(3, "b"),
+            tag(3, "a"),
+            tag(7, "c"),
+        ];
+        let keys = |pos| tags.tags_at(pos).map(|t| t.key()).collect::<Vec<_>>();
+        assert_eq!(keys(0), ["a"]);
+        assert!(keys(1).is_empty());
+        assert_eq!(keys(3), ["a", "b", "a"]);
+        assert_eq!(keys(7), ["c"]);
+        assert!(keys(8).is_empty());
+
+        assert_eq!(tags.find_tag(3, "b"), Some(&tags[2]));
+        assert_eq!(tags.find_tag(3, "a"), Some(&tags[1]));
+        assert_eq!(tags.find_tag(3, "c"), None);
+        assert_eq!(tags.find_tag(7, "c"), Some(&tags[4]));
+        assert_eq!(([] as [Tag; 0]).find_tag(0, "a"), None);
+    }
+
+    #[test]
+    fn tag_string_round_trip() -> anyhow::Result<()> {
+        for val in [
+            TagValue::String("hello: world=1".into()),
+            TagValue::String("".into()),
+            TagValue::Float(1.5),
+            TagValue::Float(-0.1),
+            TagValue::Bool(true),
+            TagValue::Bool(false),
+            TagValue::U64(u64::MAX),
+            TagValue::Bytes(vec![0x00, 0xab, 0x7f]),
+            TagValue::Bytes(vec![]),
+        ] {
+            let s = val.to_string();
+            assert_eq!(s.parse::<TagValue>()?, val, "{s}");
+            let tag = Tag::new(42, "ax25:src".into(), val);
+            let s = tag.to_string();
+            assert_eq!(s.parse::<Tag>()?, tag, "{s}");
+        }
+        assert_eq!(TagValue::Float(1.5).to_string(), "Float:1.5");
+        assert_eq!(
+            Tag::new(10, "burst".into(), TagValue::Bytes(vec![1, 0xff])).to_string(),
+            "10:burst=Bytes:01ff"
+        );
+        for bad in ["Float", "Float:x", "Int:1", "Bytes:abc", "Bool:yes"] {
+            assert!(bad.parse::<TagValue>().is_err(), "{bad}");
+        }
+        for bad in ["burst=Bool:true", "x:burst=Bool:true", "10:burst"] {
+            assert!(bad.parse::<Tag>().is_err(), "{bad}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn eof() -> anyhow::Result<()> {
+        let (w, r) = new_stream::<u8>();
+        let mut wb = w.write_buf()?;
+        wb.slice()[0] = 1;
+        wb.produce(1, &[]);
+        assert!(!r.eof());
+        drop(w);
+        assert!(!r.eof());
+        let (rb, _) = r.read_buf()?;
+        rb.consume(1);
+        assert!(r.eof());
+        Ok(())
+    }
+
+    #[test]
+    fn fill_level() -> anyhow::Result<()> {
+        let (w, r) = new_stream::<u32>();
+        let total = r.total_size();
+        assert_eq!(r.available(), 0);
+        assert_eq!(r.fill_fraction(), 0.0);
+        assert_eq!(w.free(), total);
+
+        let mut wb = w.write_buf()?;
+        let n = total / 4;
+        wb.fill_from_slice(&vec![1; n]);
+        wb.produce(n, &[]);
+        assert_eq!(r.available(), n);
+        assert_eq!(w.free(), total - n);
+        assert_eq!(r.fill_fraction(), 0.25);
+        assert_eq!(w.fill_fraction(), 0.25);
+
+        let (rb, _) = r.read_buf()?;
+        rb.consume(n / 2);
+        assert_eq!(r.available(), n - n / 2);
+        assert_eq!(r.fill_fraction(), 0.125);
+        Ok(())
+    }
+
+    #[test]
+    fn refcount_check() -> anyhow::Result<()> {
+        let (mut w, mut r) = new_stream::<u8>();
+        // Hold on to a write buffer, making read_buf() create a fourth
+        // reference.
+        let wb = w.write_buf()?;
+        assert!(r.read_buf().is_ok());
+
+        r.set_refcount_check(RefcountCheck::Strict);
+        assert!(r.read_buf().is_err());
+        drop(wb);
+        assert!(r.read_buf().is_ok());
+
+        // Same for the write end.
+        let rb = r.read_buf()?;
+        assert!(w.write_buf().is_ok());
+        w.set_refcount_check(RefcountCheck::Strict);
+        assert!(w.write_buf().is_err());
+        drop(rb);
+        assert!(w.write_buf().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn wait_timeout() -> anyhow::Result<()> {
+        let (w, r) = new_stream_with_wait_timeout::<u8>(std::time::Duration::from_millis(20));
+
+        // Nothing arrives, so the wait gives up.
+        assert!(!r.wait_for_read(1));
+        assert!(r.read_buf()?.0.is_empty());
+
+        // Space is available right away.
+        assert!(w.wait_for_write(1));
+
+        // Data written by another thread is seen by the waiter.
+        let (tx, rx) = std::sync::mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            rx.recv().unwrap();
+            let mut wb = w.write_buf().unwrap();
+            wb.slice()[0] = 42;
+            wb.produce(1, &[]);
+            w
+        });
+        tx.send(())?;
+        while !r.wait_for_read(1) {}
+        let _w = writer.join().unwrap();
+        assert_eq!(r.read_buf()?.0.slice(), &[42]);
+        Ok(())
+    }
+
+    #[test]
+    fn nocopy_wait() {
+        use std::sync::mpsc::channel;
+        let (w, r) = NCWriteStream::<u32>::with_capacity(2);
+        assert!(w.wait_for_write(2));
+        w.push(1, &[]);
+        w.push(2, &[]);
+
+        // Full, and nobody consumes.
+        assert!(!w.wait_for_write(1));
+
+        // Space made by the consumer is seen by the waiting writer.
+        let (tx, rx) = channel();
+        let consumer = std::thread::spawn(move || {
+            rx.recv().unwrap();
+            assert_eq!(r.pop().unwrap().0, 1);
+            r
+        });
+        tx.send(()).unwrap();
+        while !w.wait_for_write(1) {}
+        let r = consumer.join().unwrap();
+        w.push(3, &[]);
+        assert!(!w.wait_for_write(1));
+
+        // Gives up when the reader goes away.
+        drop(r);
+        assert!(!w.wait_for_write(1));
+
+        // Read side.
+        let (w, r) = new_nocopy_stream::<u32>();
+        assert!(!r.wait_for_read(1));
+        let (tx, rx) = channel();
+        let producer = std::thread::spawn(move || {
+            rx.recv().unwrap();
+            w.push(1, &[]);
+        });
+        tx.send(()).unwrap();
+        while !r.wait_for_read(1) {}
+        producer.join().unwrap();
+        assert_eq!(r.pop().unwrap().0, 1);
+
+        // Writer is gone, so nothing more arrives.
+        assert!(!r.wait_for_read(1));
+    }
+
+    #[test]
+    fn nocopy_capacity() {
+        let (w, r) = new_nocopy_stream::<Vec<u8>>();
+        assert_eq!(w.capacity(), DEFAULT_NOCOPY_CAPACITY);
+        assert_eq!(r.capacity(), DEFAULT_NOCOPY_CAPACITY);
+
+        let (w, r) = NCWriteStream::<Vec<u8>>::with_capacity(3);
+        assert_eq!(w.capacity(), 3);
+        assert_eq!(r.capacity(), 3);
+        assert_eq!(w.remaining(), 3);
+        assert!(r.is_empty());
+
+        w.push(vec![1], &[]);
+        w.push(vec![2], &[]);
+        assert_eq!(w.len(), 2);
+        assert_eq!(r.len(), 2);
+        assert_eq!(w.remaining(), 1);
+
+        w.push(vec![3], &[]);
+        assert_eq!(w.remaining(), 0);
+
+        // Capacity is advisory.
+        w.push(vec![4], &[]);
+        assert_eq!(w.remaining(), 0);
+        assert_eq!(r.len(), 4);
+
+        let _ = r.pop().unwrap();
+        let _ = r.pop().unwrap();
+        assert_eq!(w.remaining(), 1);
     }
 }
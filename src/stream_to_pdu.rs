@@ -35,10 +35,12 @@ use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagPos, TagVal
 use crate::{Error, Sample};
 
 /// Stream to PDU block.
-// TODO: implement proper EOF.
+///
+/// If the stream ends while waiting for the tail of a burst, the burst is
+/// delivered as is.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate, nevereof)]
-pub struct StreamToPdu<T> {
+#[rustradio(crate, flush)]
+pub struct StreamToPdu<T: Copy> {
     #[rustradio(in)]
     src: ReadStream<T>,
     #[rustradio(out)]
@@ -50,7 +52,7 @@ pub struct StreamToPdu<T> {
     tail: usize,
 }
 
-impl<T> StreamToPdu<T> {
+impl<T: Copy> StreamToPdu<T> {
     /// Make new Stream to PDU block.
     pub fn new(
         src: ReadStream<T>,
@@ -72,6 +74,19 @@ impl<T> StreamToPdu<T> {
             dr,
         )
     }
+
+    /// Deliver burst that ended, but where the tail was cut short.
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.endcounter.take().is_some() {
+            let burst = std::mem::take(&mut self.buf);
+            debug!(
+                "StreamToPdu> flushing burst of size {} samples",
+                burst.len()
+            );
+            self.dst.push(burst, &[]);
+        }
+        Ok(())
+    }
 }
 
 fn get_tag_val_bool(tags: &HashMap<(TagPos, String), Tag>, pos: TagPos, key: &str) -> Option<bool> {
@@ -0,0 +1,165 @@
+//! Periodically tag a stream.
+//!
+//! Useful as a heartbeat, or for injecting periodic markers, e.g. for a
+//! recorder to start a new file.
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::Error;
+
+/// How often to strobe.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrobeInterval {
+    /// Every this many samples, starting with the first.
+    Samples(u64),
+
+    /// Every this much wall clock time, starting with the first sample.
+    ///
+    /// The tag is added to the first sample passed through after the time
+    /// has elapsed.
+    Time(Duration),
+}
+
+/// Pass samples through, periodically adding a tag.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct Strobe<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    interval: StrobeInterval,
+    key: String,
+    val: TagValue,
+    // Number of samples passed through.
+    #[rustradio(default)]
+    pos: u64,
+    // Sample position of the next strobe, for StrobeInterval::Samples.
+    #[rustradio(default)]
+    next: u64,
+    // Time of the next strobe, for StrobeInterval::Time.
+    #[rustradio(default)]
+    next_time: Option<Instant>,
+}
+
+impl<T: Copy> Block for Strobe<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let mut tags: Vec<_> = tags.into_iter().filter(|t| t.pos() < n).collect();
+        let end = self.pos + n as u64;
+        match self.interval {
+            StrobeInterval::Samples(every) => {
+                while self.next < end {
+                    let pos = (self.next - self.pos) as usize;
+                    tags.push(Tag::new(pos, self.key.clone(), self.val.clone()));
+                    self.next += every.max(1);
+                }
+            }
+            StrobeInterval::Time(every) => {
+                let now = Instant::now();
+                if self.next_time.map_or(true, |t| now >= t) {
+                    tags.push(Tag::new(0, self.key.clone(), self.val.clone()));
+                    self.next_time = Some(now + every);
+                }
+            }
+        }
+        o.slice()[..n].copy_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        self.pos = end;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::VectorSource;
+
+    #[test]
+    fn samples() -> Result<()> {
+        let (mut src, prev) = VectorSource::new((0..25u32).collect());
+        let (mut b, out) = Strobe::new(
+            prev,
+            StrobeInterval::Samples(10),
+            "heartbeat".into(),
+            TagValue::Bool(true),
+        );
+        src.work()?;
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 25);
+        let got: Vec<_> = tags
+            .iter()
+            .filter(|t| t.key() == "heartbeat")
+            .map(|t| t.pos())
+            .collect();
+        assert_eq!(got, &[0, 10, 20]);
+        Ok(())
+    }
+
+    #[test]
+    fn samples_split_work() -> Result<()> {
+        let (w, r) = crate::stream::new_stream::<u8>();
+        let (mut b, out) = Strobe::new(
+            r,
+            StrobeInterval::Samples(4),
+            "mark".into(),
+            TagValue::U64(1),
+        );
+        let mut got = Vec::new();
+        let mut base = 0;
+        for len in [3, 3, 7] {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(&vec![0; len]);
+            wb.produce(len, &[]);
+            b.work()?;
+            let (res, tags) = out.read_buf()?;
+            got.extend(tags.iter().map(|t| base + t.pos()));
+            let n = res.len();
+            base += n;
+            res.consume(n);
+        }
+        assert_eq!(got, &[0, 4, 8, 12]);
+        Ok(())
+    }
+
+    #[test]
+    fn time() -> Result<()> {
+        let (w, r) = crate::stream::new_stream::<u8>();
+        let (mut b, out) = Strobe::new(
+            r,
+            StrobeInterval::Time(Duration::from_millis(50)),
+            "mark".into(),
+            TagValue::U64(1),
+        );
+        let mut count = 0;
+        for _ in 0..3 {
+            let mut wb = w.write_buf()?;
+            wb.fill_from_slice(&[0]);
+            wb.produce(1, &[]);
+            b.work()?;
+            let (res, tags) = out.read_buf()?;
+            count += tags.len();
+            let n = res.len();
+            res.consume(n);
+        }
+        // Only the first sample is tagged, since little time passed.
+        assert_eq!(count, 1);
+        std::thread::sleep(Duration::from_millis(60));
+        let mut wb = w.write_buf()?;
+        wb.fill_from_slice(&[0]);
+        wb.produce(1, &[]);
+        b.work()?;
+        assert_eq!(out.read_buf()?.1.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+/*! Suppress repeated tags.
+
+Some blocks add the same tag over and over, e.g. a lock status on every
+buffer. Downstream blocks that only care about changes can put this block
+in between.
+*/
+use std::collections::HashMap;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::Error;
+
+/// Tag debounce block.
+///
+/// Samples pass through unchanged. A tag is only forwarded if its key
+/// hasn't been seen before, if its value differs from the last forwarded
+/// tag with the same key, or if at least `window` samples have passed since
+/// that tag was forwarded.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct TagDebounce<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    window: u64,
+    // Last forwarded value, and stream position, per key.
+    #[rustradio(default)]
+    last: HashMap<String, (TagValue, u64)>,
+    #[rustradio(default)]
+    pos: u64,
+}
+
+impl<T: Copy> TagDebounce<T> {
+    // Return true if the tag should be forwarded, and remember it if so.
+    fn check(&mut self, tag: &Tag) -> bool {
+        let pos = self.pos + tag.pos() as u64;
+        if let Some((val, last)) = self.last.get(tag.key()) {
+            if val == tag.val() && pos - last < self.window {
+                return false;
+            }
+        }
+        self.last
+            .insert(tag.key().to_string(), (tag.val().clone(), pos));
+        true
+    }
+}
+
+impl<T: Copy> Block for TagDebounce<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        if i.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = std::cmp::min(i.len(), o.len());
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let tags: Vec<Tag> = tags
+            .into_iter()
+            .filter(|t| t.pos() < n && self.check(t))
+            .collect();
+        o.fill_from_slice(&i.slice()[..n]);
+        o.produce(n, &tags);
+        i.consume(n);
+        self.pos += n as u64;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce() -> crate::Result<()> {
+        let (w, r) = crate::stream::new_stream();
+        {
+            let mut o = w.write_buf()?;
+            o.fill_from_slice(&[0u8; 100]);
+            let mut tags: Vec<Tag> = (0..100)
+                .map(|n| {
+                    let locked = !(50..60).contains(&n);
+                    Tag::new(n, "ref_locked".into(), TagValue::Bool(locked))
+                })
+                .collect();
+            tags.push(Tag::new(3, "other".into(), TagValue::Bool(true)));
+            o.produce(100, &tags);
+        }
+        let (mut b, out) = TagDebounce::new(r, 30);
+        b.work()?;
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.len(), 100);
+        let tag = |pos, key: &str, val| Tag::new(pos, key.into(), TagValue::Bool(val));
+        assert_eq!(
+            tags,
+            [
+                tag(0, "ref_locked", true),
+                tag(3, "other", true),
+                // Window elapsed.
+                tag(30, "ref_locked", true),
+                // Value changes.
+                tag(50, "ref_locked", false),
+                tag(60, "ref_locked", true),
+                tag(90, "ref_locked", true),
+            ]
+        );
+        Ok(())
+    }
+}
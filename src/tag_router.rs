@@ -0,0 +1,177 @@
+/*! Route bursts to one of several outputs, based on a tag.
+
+Bursts are marked in the stream the same way as for
+[StreamToPdu][crate::stream_to_pdu::StreamToPdu]: a tag with value `true`
+where they start, and `false` where they end. A second tag, anywhere within
+the burst, selects which output the burst is sent to.
+*/
+use log::{debug, warn};
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, TagValue};
+use crate::{Complex, Error};
+
+/// Tag router block.
+///
+/// The route tag value must be a `TagValue::U64`, and is the index of the
+/// output. If there are several route tags in a burst, the last one wins.
+/// Bursts with no route, or a route out of range, are dropped with a
+/// warning.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub struct TagRouter {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(out)]
+    dsts: Vec<NCWriteStream<Vec<Complex>>>,
+    burst_tag: String,
+    route_tag: String,
+    max_size: usize,
+    buf: Vec<Complex>,
+    in_burst: bool,
+    route: Option<u64>,
+}
+
+impl TagRouter {
+    /// Create new TagRouter block, with `outputs` outputs.
+    ///
+    /// Bursts longer than `max_size` samples are discarded.
+    pub fn new(
+        src: ReadStream<Complex>,
+        burst_tag: String,
+        route_tag: String,
+        outputs: usize,
+        max_size: usize,
+    ) -> (Self, Vec<NCReadStream<Vec<Complex>>>) {
+        let (dsts, drs) = (0..outputs)
+            .map(|_| crate::stream::new_nocopy_stream())
+            .unzip();
+        (
+            Self {
+                src,
+                dsts,
+                burst_tag,
+                route_tag,
+                max_size,
+                buf: Vec::new(),
+                in_burst: false,
+                route: None,
+            },
+            drs,
+        )
+    }
+
+    fn deliver(&mut self) {
+        let burst = std::mem::take(&mut self.buf);
+        let Some(route) = self.route.take() else {
+            warn!(
+                "TagRouter: dropping burst of {} samples with no route",
+                burst.len()
+            );
+            return;
+        };
+        match usize::try_from(route).ok().and_then(|r| self.dsts.get(r)) {
+            Some(dst) => {
+                debug!(
+                    "TagRouter: routing burst of {} samples to output {route}",
+                    burst.len()
+                );
+                dst.push(burst, &[]);
+            }
+            None => warn!(
+                "TagRouter: dropping burst of {} samples with route {route} out of range, have {} outputs",
+                burst.len(),
+                self.dsts.len()
+            ),
+        }
+    }
+}
+
+impl Block for TagRouter {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (input, mut tags) = self.src.read_buf()?;
+        if input.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        tags.sort_by_key(|t| t.pos());
+        let mut tags = tags.iter().peekable();
+        for (i, sample) in input.iter().enumerate() {
+            let mut start = false;
+            let mut end = false;
+            let mut route = None;
+            while let Some(tag) = tags.next_if(|t| t.pos() == i) {
+                match tag.val() {
+                    TagValue::Bool(b) if tag.key() == self.burst_tag => {
+                        start |= *b;
+                        end |= !*b;
+                    }
+                    TagValue::U64(r) if tag.key() == self.route_tag => route = Some(*r),
+                    _ => {}
+                }
+            }
+            if start {
+                if self.in_burst {
+                    warn!("TagRouter: burst start without end of previous burst");
+                }
+                self.in_burst = true;
+                self.buf.clear();
+                self.route = None;
+            }
+            if self.in_burst && route.is_some() {
+                self.route = route;
+            }
+            if self.in_burst {
+                self.buf.push(*sample);
+                if self.buf.len() > self.max_size {
+                    warn!("TagRouter: discarding burst longer than {}", self.max_size);
+                    self.buf.clear();
+                    self.in_burst = false;
+                }
+            }
+            if end && self.in_burst {
+                self.in_burst = false;
+                self.deliver();
+            }
+        }
+        let n = input.len();
+        input.consume(n);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::Tag;
+    use crate::testutil::PatternSource;
+
+    #[test]
+    fn route_bursts() -> Result<(), Error> {
+        let data: Vec<Complex> = (0..20).map(|n| Complex::new(n as f32, 0.0)).collect();
+        let tags = vec![
+            Tag::new(2, "burst".into(), TagValue::Bool(true)),
+            Tag::new(3, "route".into(), TagValue::U64(1)),
+            Tag::new(5, "burst".into(), TagValue::Bool(false)),
+            Tag::new(8, "route".into(), TagValue::U64(0)),
+            Tag::new(8, "burst".into(), TagValue::Bool(true)),
+            Tag::new(10, "burst".into(), TagValue::Bool(false)),
+            // Out of range.
+            Tag::new(12, "burst".into(), TagValue::Bool(true)),
+            Tag::new(12, "route".into(), TagValue::U64(2)),
+            Tag::new(14, "burst".into(), TagValue::Bool(false)),
+        ];
+        let (mut src, prev) = PatternSource::new(data.clone(), 1, tags);
+        src.set_max_chunk(7);
+        let (mut b, outs) = TagRouter::new(prev, "burst".into(), "route".into(), 2, 100);
+        while src.work()? == BlockRet::Ok {
+            b.work()?;
+        }
+        let (got, _) = outs[0].pop().expect("no burst on output 0");
+        assert_eq!(got, &data[8..=10]);
+        assert!(outs[0].pop().is_none());
+        let (got, _) = outs[1].pop().expect("no burst on output 1");
+        assert_eq!(got, &data[2..=5]);
+        assert!(outs[1].pop().is_none());
+        Ok(())
+    }
+}
@@ -51,7 +51,7 @@ where
             .map_err(|e| -> anyhow::Error { e.into() })?;
         if n == 0 {
             warn!("TCP connection closed?");
-            return Ok(BlockRet::EOF);
+            return Ok(BlockRet::Finished);
         }
         let mut v = Vec::with_capacity(n / size + 1);
 
@@ -68,7 +68,7 @@ where
         }
         self.buf.extend(&buffer[n - remaining..n]);
         let n = v.len();
-        o.fill_from_iter(v);
+        o.fill_from_slice(&v);
         o.produce(n, &[]);
         Ok(BlockRet::Ok)
     }
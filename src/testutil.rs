@@ -0,0 +1,121 @@
+//! Helpers for block tests.
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, WriteStream};
+use crate::Error;
+
+/// Source of a repeating pattern, with tags at given positions.
+///
+/// Tag positions are absolute, counting from the first sample output, and
+/// may be beyond the first repetition of the pattern.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate)]
+pub(crate) struct PatternSource<T: Copy> {
+    #[rustradio(out)]
+    dst: WriteStream<T>,
+    pattern: Vec<T>,
+    // Total number of samples to output.
+    len: usize,
+    // Sorted by position.
+    tags: Vec<Tag>,
+    max_chunk: usize,
+    // Samples output so far.
+    pos: usize,
+}
+
+impl<T: Copy> PatternSource<T> {
+    /// Create new PatternSource, outputting the pattern `repeat` times.
+    pub(crate) fn new(pattern: Vec<T>, repeat: usize, mut tags: Vec<Tag>) -> (Self, ReadStream<T>) {
+        let (dst, dr) = crate::stream::new_stream();
+        tags.sort_by_key(|t| t.pos());
+        (
+            Self {
+                dst,
+                len: pattern.len() * repeat,
+                pattern,
+                tags,
+                max_chunk: usize::MAX,
+                pos: 0,
+            },
+            dr,
+        )
+    }
+
+    /// Output at most this many samples per `work()` call.
+    ///
+    /// Useful for testing that blocks handle input split across calls.
+    pub(crate) fn set_max_chunk(&mut self, n: usize) {
+        self.max_chunk = n;
+    }
+}
+
+impl<T: Copy> Block for PatternSource<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        if self.pos == self.len {
+            return Ok(BlockRet::Finished);
+        }
+        let mut o = self.dst.write_buf()?;
+        let n = o.len().min(self.max_chunk).min(self.len - self.pos);
+        if n == 0 {
+            return Ok(BlockRet::OutputFull);
+        }
+        let plen = self.pattern.len();
+        o.fill_from_iter((self.pos..self.pos + n).map(|i| self.pattern[i % plen]));
+        let tags: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|t| (self.pos..self.pos + n).contains(&t.pos()))
+            .map(|t| Tag::new(t.pos() - self.pos, t.key().into(), t.val().clone()))
+            .collect();
+        o.produce(n, &tags);
+        self.pos += n;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::TagValue;
+    use crate::{Complex, Float};
+
+    fn tag(pos: usize) -> Tag {
+        Tag::new(pos, "t".into(), TagValue::U64(pos as u64))
+    }
+
+    #[test]
+    fn pattern() -> Result<()> {
+        let (mut src, out) = PatternSource::new(vec![1u8, 2, 3], 3, vec![tag(7), tag(0), tag(4)]);
+        assert_eq!(src.work()?, BlockRet::Ok);
+        assert_eq!(src.work()?, BlockRet::Finished);
+        let (res, tags) = out.read_buf()?;
+        assert_eq!(res.slice(), &[1, 2, 3, 1, 2, 3, 1, 2, 3]);
+        assert_eq!(tags, vec![tag(0), tag(4), tag(7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn chunked() -> Result<()> {
+        let pattern = vec![Complex::new(1.0, -1.0), Complex::new(0.0, 2.0)];
+        let (mut src, out) = PatternSource::new(pattern.clone(), 5, vec![tag(3), tag(9)]);
+        src.set_max_chunk(4);
+        let mut got: Vec<Complex> = Vec::new();
+        let mut got_tags = Vec::new();
+        while src.work()? == BlockRet::Ok {
+            let (res, tags) = out.read_buf()?;
+            assert!(res.len() <= 4);
+            got_tags.extend(tags.iter().map(|t| (got.len() + t.pos(), t.val().clone())));
+            got.extend(res.slice());
+            let n = res.len();
+            res.consume(n);
+        }
+        assert_eq!(got, pattern.repeat(5));
+        assert_eq!(got_tags, vec![(3, TagValue::U64(3)), (9, TagValue::U64(9))]);
+
+        let (mut src, out) = PatternSource::<Float>::new(vec![0.5], 0, vec![]);
+        assert_eq!(src.work()?, BlockRet::Finished);
+        assert!(out.read_buf()?.0.is_empty());
+        Ok(())
+    }
+}
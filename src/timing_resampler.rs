@@ -0,0 +1,175 @@
+/*! Resample with a rate driven by a control stream.
+
+Unlike [RationalResampler][crate::rational_resampler::RationalResampler],
+the resampling rate isn't fixed. Instead a second input stream
+adjusts the step for every input sample. This can be used to follow a
+recovered clock, e.g. as the output of a timing error detector loop.
+
+Output samples are linearly interpolated between input samples.
+*/
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag, TagSlice, WriteStream};
+use crate::{Complex, Error, Float};
+
+/// Linear interpolator, outputting samples at a fractional step through
+/// the input.
+///
+/// Also used by [ClockSync][crate::clock_sync::ClockSync].
+#[derive(Default)]
+pub(crate) struct LinearInterpolator<T> {
+    // Position of the next output sample, in input samples, relative to
+    // the current input sample.
+    acc: f64,
+    last: T,
+}
+
+impl<T> LinearInterpolator<T>
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T>,
+    T: std::ops::Mul<Float, Output = T>,
+{
+    /// Create new interpolator, starting at input sample `last`.
+    pub(crate) fn new(last: T) -> Self {
+        Self { acc: 0.0, last }
+    }
+
+    /// Take one input sample, with `step` input samples per output sample,
+    /// writing output samples to `out`.
+    ///
+    /// Returns the number of samples written, or None without changing any
+    /// state if `out` is too small.
+    pub(crate) fn push(&mut self, cur: T, step: f64, out: &mut [T]) -> Option<usize> {
+        let mut acc = self.acc;
+        let mut n = 0;
+        while acc <= 0.0 {
+            let mu = (acc + 1.0) as Float;
+            *out.get_mut(n)? = self.last + (cur - self.last) * mu;
+            n += 1;
+            acc += step;
+        }
+        self.acc = acc - 1.0;
+        self.last = cur;
+        Some(n)
+    }
+}
+
+/// Resample with a rate driven by a control stream.
+///
+/// `step` is the nominal number of input samples per output sample. For
+/// each input sample, the value on the `control` stream is added to the
+/// step. So a constant control of `0.0` and a step of `2.0` is the same as
+/// decimating by two.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, new)]
+pub struct TimingResampler {
+    #[rustradio(in)]
+    src: ReadStream<Complex>,
+    #[rustradio(in)]
+    control: ReadStream<Float>,
+    #[rustradio(out)]
+    dst: WriteStream<Complex>,
+    step: Float,
+    #[rustradio(default)]
+    interp: LinearInterpolator<Complex>,
+}
+
+impl Block for TimingResampler {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (input, tags) = self.src.read_buf()?;
+        let (control, _) = self.control.read_buf()?;
+        let n = std::cmp::min(input.len(), control.len());
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        let mut o = self.dst.write_buf()?;
+        let oslice = o.slice();
+        let mut otags = Vec::new();
+        let mut opos = 0;
+        let mut taken = 0;
+        for (i, (cur, c)) in input.iter().zip(control.iter()).take(n).enumerate() {
+            let step = self.step + c;
+            if step <= 0.0 {
+                return Err(Error::new(&format!(
+                    "TimingResampler: step must be positive, got {step}"
+                )));
+            }
+            let Some(count) = self.interp.push(*cur, step as f64, &mut oslice[opos..]) else {
+                break;
+            };
+            for tag in tags.tags_at(i) {
+                otags.push(Tag::new(opos, tag.key().into(), tag.val().clone()));
+            }
+            opos += count;
+            taken += 1;
+        }
+        input.consume(taken);
+        control.consume(taken);
+        o.produce(opos, &otags);
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{RationalResampler, VectorSource};
+    use crate::tests::assert_almost_equal_complex;
+
+    fn run(input: &[Complex], step: Float, control: Float) -> Result<Vec<Complex>> {
+        let (mut src, src_out) = VectorSource::new(input.to_vec());
+        let (mut ctrl, ctrl_out) = VectorSource::new(vec![control; input.len()]);
+        let (mut b, out) = TimingResampler::new(src_out, ctrl_out, step);
+        src.work()?;
+        ctrl.work()?;
+        b.work()?;
+        let (res, _) = out.read_buf()?;
+        Ok(res.slice().to_vec())
+    }
+
+    #[test]
+    fn constant_control_matches_rational() -> Result<()> {
+        let input: Vec<_> = (0..100)
+            .map(|i| Complex::new(i as Float, -(i as Float)))
+            .collect();
+        for deci in [1, 2, 3, 5] {
+            let (mut src, src_out) = VectorSource::new(input.clone());
+            let (mut resamp, rout) = RationalResampler::new(src_out, 1, deci)?;
+            src.work()?;
+            resamp.work()?;
+            let want = rout.read_buf()?.0.slice().to_vec();
+
+            // Step all in the nominal step.
+            assert_almost_equal_complex(&run(&input, deci as Float, 0.0)?, &want);
+
+            // Step all in the control stream.
+            assert_almost_equal_complex(&run(&input, 0.0, deci as Float)?, &want);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn output_full() {
+        let mut interp = LinearInterpolator::<Float>::default();
+        let mut out = [0.0; 4];
+        assert_eq!(interp.push(1.0, 0.25, &mut out), Some(1));
+        assert_eq!(out[0], 1.0);
+        // Four outputs per input now, which don't fit.
+        assert_eq!(interp.push(2.0, 0.25, &mut out[..3]), None);
+        assert_eq!(interp.push(2.0, 0.25, &mut out), Some(4));
+        assert_eq!(out, [1.25, 1.5, 1.75, 2.0]);
+    }
+
+    #[test]
+    fn interpolate() -> Result<()> {
+        let input: Vec<_> = (0..4).map(|i| Complex::new(i as Float, 0.0)).collect();
+        let got = run(&input, 1.0, -0.5)?;
+        let want: Vec<_> = [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0]
+            .into_iter()
+            .map(|v| Complex::new(v, 0.0))
+            .collect();
+        assert_almost_equal_complex(&got, &want);
+        Ok(())
+    }
+}
@@ -22,7 +22,7 @@ g.add(Box::new(sink));
 */
 use anyhow::Result;
 
-use crate::block::{Block, BlockRet};
+use crate::block::{Block, BlockEOF, BlockRet};
 use crate::stream::{ReadStream, WriteStream};
 use crate::Error;
 
@@ -33,6 +33,7 @@ use crate::Error;
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate, noeof)]
 pub struct ToText<T: Copy> {
+    #[rustradio(in)]
     srcs: Vec<ReadStream<T>>,
     #[rustradio(out)]
     dst: WriteStream<u8>,
@@ -46,6 +47,12 @@ impl<T: Copy> ToText<T> {
     }
 }
 
+impl<T: Copy> BlockEOF for ToText<T> {
+    fn eof(&mut self) -> bool {
+        self.srcs.iter().any(ReadStream::eof)
+    }
+}
+
 impl<T: Copy + std::fmt::Debug> Block for ToText<T> {
     fn work(&mut self) -> Result<BlockRet, Error> {
         // TODO: This implementation locks and unlocks a lot, as it
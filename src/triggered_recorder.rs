@@ -0,0 +1,169 @@
+/*! Record samples around a trigger tag.
+
+Keeps a ring buffer of the most recent samples, so that when a trigger tag
+arrives, the recording can start some time *before* the trigger. Useful
+when the trigger is a bit late, e.g. a burst detector that needs to see
+some of the burst before it triggers.
+
+The recordings are output as PDUs, which can be written to files with
+[PduWriter][crate::pdu_writer::PduWriter].
+*/
+use std::collections::VecDeque;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{NCReadStream, NCWriteStream, ReadStream, Tag, TagSlice, TagValue};
+use crate::{Complex, Error};
+
+/// Tag key marking the trigger sample in the recording. The value is the
+/// position of the trigger in the input stream.
+pub const TAG_TRIGGER: &str = "trigger";
+
+/// Triggered recorder block.
+///
+/// Each recording is up to `pre` samples from before the trigger, the
+/// sample with the trigger tag, and `post` samples after it. Triggers
+/// arriving while recording are ignored.
+///
+/// Any tag with the trigger key counts, whatever its value.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, flush)]
+pub struct TriggeredRecorder<T: Copy = Complex> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    #[rustradio(out)]
+    dst: NCWriteStream<Vec<T>>,
+    trigger: String,
+    pre: usize,
+    post: usize,
+    ring: VecDeque<T>,
+    // Recording in progress, its tags, and samples left to record.
+    recording: Option<(Vec<T>, Vec<Tag>, usize)>,
+    // Stream position of the next input sample.
+    pos: u64,
+}
+
+impl<T: Copy> TriggeredRecorder<T> {
+    /// Create new TriggeredRecorder block.
+    pub fn new(
+        src: ReadStream<T>,
+        trigger: String,
+        pre: usize,
+        post: usize,
+    ) -> (Self, NCReadStream<Vec<T>>) {
+        let (dst, dr) = crate::stream::new_nocopy_stream();
+        (
+            Self {
+                src,
+                dst,
+                trigger,
+                pre,
+                post,
+                ring: VecDeque::with_capacity(pre + 1),
+                recording: None,
+                pos: 0,
+            },
+            dr,
+        )
+    }
+
+    /// Deliver recording in progress, even though it's short.
+    fn flush(&mut self) -> Result<(), Error> {
+        if let Some((data, tags, _)) = self.recording.take() {
+            self.dst.push(data, &tags);
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy> Block for TriggeredRecorder<T> {
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (input, tags) = self.src.read_buf()?;
+        if input.is_empty() {
+            return Ok(BlockRet::Noop);
+        }
+        for (i, &sample) in input.iter().enumerate() {
+            if self.recording.is_none() && tags.find_tag(i, &self.trigger).is_some() {
+                let mut data: Vec<T> = self.ring.drain(..).collect();
+                let rtags = vec![Tag::new(
+                    data.len(),
+                    TAG_TRIGGER.into(),
+                    TagValue::U64(self.pos + i as u64),
+                )];
+                data.push(sample);
+                self.recording = Some((data, rtags, self.post));
+            } else if let Some((data, _, left)) = &mut self.recording {
+                data.push(sample);
+                *left -= 1;
+            } else {
+                self.ring.push_back(sample);
+                if self.ring.len() > self.pre {
+                    self.ring.pop_front();
+                }
+            }
+            if matches!(self.recording, Some((_, _, 0))) {
+                self.flush()?;
+            }
+        }
+        let n = input.len();
+        input.consume(n);
+        self.pos += n as u64;
+        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Float;
+
+    fn write(w: &crate::stream::WriteStream<Complex>, data: &[Complex], tags: &[Tag]) {
+        let mut o = w.write_buf().unwrap();
+        o.fill_from_slice(data);
+        o.produce(data.len(), tags);
+    }
+
+    #[test]
+    fn pre_roll() -> crate::Result<()> {
+        let samples: Vec<Complex> = (0..100).map(|n| Complex::new(n as Float, 0.0)).collect();
+        let trig = |pos| Tag::new(pos, "burst".into(), TagValue::Bool(true));
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = TriggeredRecorder::new(r, "burst".into(), 5, 3);
+        // Trigger at 30, ignored trigger at 32, and trigger at 60 in the
+        // next write.
+        write(&w, &samples[..50], &[trig(30), trig(32)]);
+        b.work()?;
+        write(&w, &samples[50..], &[trig(10)]);
+        b.work()?;
+
+        let (data, tags) = out.pop().expect("missing first recording");
+        let want: Vec<Complex> = (25..34).map(|n| Complex::new(n as Float, 0.0)).collect();
+        assert_eq!(data, want);
+        assert_eq!(tags, [Tag::new(5, TAG_TRIGGER.into(), TagValue::U64(30))]);
+
+        let (data, tags) = out.pop().expect("missing second recording");
+        assert_eq!(data, &samples[55..64]);
+        assert_eq!(tags, [Tag::new(5, TAG_TRIGGER.into(), TagValue::U64(60))]);
+        assert!(out.pop().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn short() -> crate::Result<()> {
+        let samples: Vec<Complex> = (0..10).map(|n| Complex::new(n as Float, 0.0)).collect();
+        let (w, r) = crate::stream::new_stream();
+        let (mut b, out) = TriggeredRecorder::new(r, "t".into(), 5, 100);
+        // Not enough pre-roll, nor post.
+        write(
+            &w,
+            &samples,
+            &[Tag::new(2, "t".into(), TagValue::Bool(true))],
+        );
+        b.work()?;
+        assert!(out.pop().is_none());
+        b.flush()?;
+        let (data, tags) = out.pop().expect("missing recording");
+        assert_eq!(data, samples);
+        assert_eq!(tags[0].pos(), 2);
+        Ok(())
+    }
+}
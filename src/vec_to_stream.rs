@@ -1,44 +1,184 @@
 /*! Vector to stream block.
 
 Turn stream of e.g. `Vec<u8>` to stream of `u8`.
+
+Vectors larger than the available output space are written over several
+calls to `work()`.
  */
-use crate::block::{Block, BlockRet};
-use crate::stream::{NCReadStream, ReadStream, WriteStream};
+use crate::block::{Block, BlockEOF, BlockRet};
+use crate::stream::{NCReadStream, ReadStream, Tag, WriteStream};
 use crate::Error;
 
 /// Block for vector to stream.
+///
+/// Tags on the vector are attached to the output samples at their position
+/// in the vector. Tags positioned past the end are attached to the last
+/// sample. With `set_tags_at_start(true)` all tags are instead attached to
+/// the first sample of the vector.
 #[derive(rustradio_macros::Block)]
-#[rustradio(crate)]
+#[rustradio(crate, noeof)]
 pub struct VecToStream<T> {
+    #[rustradio(in)]
     src: NCReadStream<Vec<T>>,
+    #[rustradio(out)]
     dst: WriteStream<T>,
+    // Vector being written, its tags, and how much is written.
+    current: Option<(Vec<T>, Vec<Tag>)>,
+    pos: usize,
+    tags_at_start: bool,
 }
 
 impl<T> VecToStream<T> {
     /// Create new VecToStream.
     pub fn new(src: NCReadStream<Vec<T>>) -> (Self, ReadStream<T>) {
         let (dst, dr) = crate::stream::new_stream();
-        (Self { src, dst }, dr)
+        (
+            Self {
+                src,
+                dst,
+                current: None,
+                pos: 0,
+                tags_at_start: false,
+            },
+            dr,
+        )
+    }
+
+    /// Attach all tags to the first sample of each vector.
+    pub fn set_tags_at_start(&mut self, v: bool) {
+        self.tags_at_start = v;
+    }
+}
+
+impl<T> BlockEOF for VecToStream<T> {
+    fn eof(&mut self) -> bool {
+        self.current.is_none() && self.src.eof()
     }
 }
 
 impl<T: Copy> Block for VecToStream<T> {
     fn work(&mut self) -> Result<BlockRet, Error> {
-        let n = match self.src.peek_size() {
-            None => return Ok(BlockRet::Noop),
-            Some(x) => x,
-        };
-        let mut o = self.dst.write_buf()?;
-        if n > o.len() {
-            return Ok(BlockRet::Ok);
+        let mut ret = BlockRet::Noop;
+        loop {
+            let (v, tags) = match &self.current {
+                Some(x) => x,
+                None => match self.src.pop() {
+                    None => return Ok(ret),
+                    Some((v, _)) if v.is_empty() => continue,
+                    Some(x) => {
+                        self.pos = 0;
+                        self.current.insert(x)
+                    }
+                },
+            };
+            let mut o = self.dst.write_buf()?;
+            if o.is_empty() {
+                return Ok(BlockRet::OutputFull);
+            }
+            let n = std::cmp::min(o.len(), v.len() - self.pos);
+            let range = self.pos..(self.pos + n);
+            let otags: Vec<_> = tags
+                .iter()
+                .filter_map(|t| {
+                    let pos = if self.tags_at_start {
+                        0
+                    } else {
+                        std::cmp::min(t.pos(), v.len() - 1)
+                    };
+                    range
+                        .contains(&pos)
+                        .then(|| Tag::new(pos - self.pos, t.key().into(), t.val().clone()))
+                })
+                .collect();
+            o.fill_from_slice(&v[range]);
+            o.produce(n, &otags);
+            self.pos += n;
+            if self.pos == v.len() {
+                self.current = None;
+            }
+            ret = BlockRet::Ok;
         }
-        let (v, _tags) = self
-            .src
-            .pop()
-            .expect("we just checked the size. It must exist");
-        // TODO: write start and end tags.
-        o.fill_from_iter(v);
-        o.produce(n, &[]);
-        Ok(BlockRet::Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::{new_nocopy_stream, TagValue};
+
+    fn tag(pos: usize) -> Tag {
+        Tag::new(pos, "pos".into(), TagValue::U64(pos as u64))
+    }
+
+    // Run block, reading output as it's produced. Returns output and tags,
+    // with tag positions in the total output.
+    fn run(
+        b: &mut VecToStream<u32>,
+        out: &ReadStream<u32>,
+    ) -> anyhow::Result<(Vec<u32>, Vec<Tag>)> {
+        let mut got = Vec::new();
+        let mut got_tags = Vec::new();
+        let mut calls = 0;
+        while b.work()? == BlockRet::OutputFull || calls == 0 {
+            calls += 1;
+            let (i, tags) = out.read_buf()?;
+            for t in tags {
+                got_tags.push(Tag::new(
+                    t.pos() + got.len(),
+                    t.key().into(),
+                    t.val().clone(),
+                ));
+            }
+            got.extend(i.slice());
+            let n = i.len();
+            i.consume(n);
+        }
+        let (i, tags) = out.read_buf()?;
+        for t in tags {
+            got_tags.push(Tag::new(
+                t.pos() + got.len(),
+                t.key().into(),
+                t.val().clone(),
+            ));
+        }
+        got.extend(i.slice());
+        Ok((got, got_tags))
+    }
+
+    #[test]
+    fn larger_than_output() -> anyhow::Result<()> {
+        let (w, r) = new_nocopy_stream();
+        let (mut b, out) = VecToStream::new(r);
+        let size = out.total_size() + 1234;
+        let v: Vec<u32> = (0..size as u32).collect();
+        w.push(v.clone(), &[tag(0), tag(size - 1), tag(size + 10)]);
+        w.push(vec![1, 2, 3], &[tag(1)]);
+
+        let (got, tags) = run(&mut b, &out)?;
+        let mut want = v;
+        want.extend([1, 2, 3]);
+        assert_eq!(got, want);
+        assert_eq!(
+            tags.iter().map(|t| t.pos()).collect::<Vec<_>>(),
+            vec![0, size - 1, size - 1, size + 1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn tags_at_start() -> anyhow::Result<()> {
+        let (w, r) = new_nocopy_stream();
+        let (mut b, out) = VecToStream::new(r);
+        b.set_tags_at_start(true);
+        w.push(vec![1u32, 2, 3], &[tag(1), tag(2)]);
+        w.push(vec![], &[tag(0)]);
+        w.push(vec![4, 5], &[tag(1)]);
+        let (got, tags) = run(&mut b, &out)?;
+        assert_eq!(got, vec![1, 2, 3, 4, 5]);
+        assert_eq!(
+            tags.iter().map(|t| t.pos()).collect::<Vec<_>>(),
+            vec![0, 0, 3]
+        );
+        Ok(())
     }
 }
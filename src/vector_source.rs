@@ -2,7 +2,7 @@
 use anyhow::Result;
 
 use crate::block::{Block, BlockRet};
-use crate::stream::{ReadStream, Tag, TagValue, WriteStream};
+use crate::stream::{ReadStream, Tag, TagValue, WriteStream, TAG_REPEAT};
 use crate::Error;
 
 /// Repeat or counts.
@@ -36,6 +36,14 @@ impl<T: Copy> VectorSourceBuilder<T> {
         self.block.set_repeat(Repeat::Infinite);
         self
     }
+    /// Insert `n` default (zero) samples between repetitions.
+    pub fn gap(mut self, n: usize) -> VectorSourceBuilder<T>
+    where
+        T: Default,
+    {
+        self.block.set_gap(n);
+        self
+    }
     /// Build the VectorSource.
     pub fn build(self) -> (VectorSource<T>, ReadStream<T>) {
         (self.block, self.out)
@@ -43,6 +51,11 @@ impl<T: Copy> VectorSourceBuilder<T> {
 }
 
 /// Generate values from a fixed vector.
+///
+/// Each repetition starts with `VectorSource::start` and
+/// `VectorSource::repeat` tags, and if set to repeat, with a [TAG_REPEAT]
+/// tag. If there's a gap between repetitions, its start is tagged with
+/// `VectorSource::gap`, with the number of the repetition before it.
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct VectorSource<T>
@@ -55,6 +68,9 @@ where
     repeat: Repeat,
     repeat_count: u64,
     pos: usize,
+    gap: Vec<T>,
+    // Position in the gap, if currently in one.
+    gap_pos: Option<usize>,
 }
 
 impl<T: Copy> VectorSource<T> {
@@ -70,6 +86,8 @@ impl<T: Copy> VectorSource<T> {
                 repeat: Repeat::Finite(1),
                 pos: 0,
                 repeat_count: 0,
+                gap: Vec::new(),
+                gap_pos: None,
             },
             dr,
         )
@@ -79,6 +97,44 @@ impl<T: Copy> VectorSource<T> {
     pub fn set_repeat(&mut self, r: Repeat) {
         self.repeat = r;
     }
+
+    /// Set number of default (zero) samples between repetitions.
+    pub fn set_gap(&mut self, n: usize)
+    where
+        T: Default,
+    {
+        self.gap = vec![T::default(); n];
+    }
+
+    // Return true if there are more repetitions to output.
+    fn more(&self) -> bool {
+        match self.repeat {
+            Repeat::Finite(repeat) => self.repeat_count < repeat,
+            Repeat::Infinite => true,
+        }
+    }
+
+    // Write (some of) the gap.
+    fn work_gap(&mut self, gap_pos: usize) -> Result<BlockRet, Error> {
+        let tags = if gap_pos == 0 {
+            vec![Tag::new(
+                0,
+                "VectorSource::gap".to_string(),
+                TagValue::U64(self.repeat_count - 1),
+            )]
+        } else {
+            vec![]
+        };
+        let mut os = self.dst.write_buf()?;
+        if os.is_empty() {
+            return Ok(BlockRet::OutputFull);
+        }
+        let n = std::cmp::min(os.len(), self.gap.len() - gap_pos);
+        os.fill_from_slice(&self.gap[gap_pos..(gap_pos + n)]);
+        os.produce(n, &tags);
+        self.gap_pos = Some(gap_pos + n).filter(|&p| p < self.gap.len());
+        Ok(BlockRet::Ok)
+    }
 }
 
 impl<T> Block for VectorSource<T>
@@ -87,12 +143,13 @@ where
 {
     fn work(&mut self) -> Result<BlockRet, Error> {
         if self.data.is_empty() {
-            return Ok(BlockRet::EOF);
+            return Ok(BlockRet::Finished);
         }
-        if let Repeat::Finite(repeat) = self.repeat {
-            if self.repeat_count == repeat {
-                return Ok(BlockRet::EOF);
-            }
+        if let Some(gap_pos) = self.gap_pos {
+            return self.work_gap(gap_pos);
+        }
+        if !self.more() {
+            return Ok(BlockRet::Finished);
         }
         let mut tags = if self.pos == 0 {
             vec![
@@ -106,6 +163,13 @@ where
         } else {
             vec![]
         };
+        if self.pos == 0 && !matches!(self.repeat, Repeat::Finite(1)) {
+            tags.push(Tag::new(
+                0,
+                TAG_REPEAT.to_string(),
+                TagValue::U64(self.repeat_count),
+            ));
+        }
         if self.repeat_count == 0 {
             tags.push(Tag::new(
                 0,
@@ -125,6 +189,9 @@ where
         if self.pos == self.data.len() {
             self.repeat_count += 1;
             self.pos = 0;
+            if !self.gap.is_empty() && self.more() {
+                self.gap_pos = Some(0);
+            }
         }
         Ok(BlockRet::Ok)
     }
@@ -137,7 +204,7 @@ mod tests {
     #[test]
     fn empty() -> Result<()> {
         let (mut src, _) = VectorSource::<u8>::new(vec![]);
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         Ok(())
     }
 
@@ -159,6 +226,32 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn gap() -> Result<()> {
+        let (mut src, os) = VectorSourceBuilder::new(vec![1u8, 2])
+            .repeat(2)
+            .gap(3)
+            .build();
+        while src.work()? != BlockRet::Finished {}
+        let (res, tags) = os.read_buf()?;
+        assert_eq!(res.slice(), &[1, 2, 0, 0, 0, 1, 2]);
+        let tag = |pos, key: &str, val| Tag::new(pos, format!("VectorSource::{key}"), val);
+        assert_eq!(
+            tags,
+            [
+                tag(0, "start", TagValue::Bool(true)),
+                tag(0, "repeat", TagValue::U64(0)),
+                Tag::new(0, TAG_REPEAT.into(), TagValue::U64(0)),
+                tag(0, "first", TagValue::Bool(true)),
+                tag(2, "gap", TagValue::U64(0)),
+                tag(5, "start", TagValue::Bool(true)),
+                tag(5, "repeat", TagValue::U64(1)),
+                Tag::new(5, TAG_REPEAT.into(), TagValue::U64(1)),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn very_large() -> Result<()> {
         let (mut src, os) = VectorSource::new(vec![0u8; crate::stream::DEFAULT_STREAM_SIZE + 100]);
@@ -178,7 +271,7 @@ mod tests {
             let (res, _) = os.read_buf()?;
             assert_eq!(res.len(), 100);
         }
-        assert_eq!(src.work()?, BlockRet::EOF);
+        assert_eq!(src.work()?, BlockRet::Finished);
         Ok(())
     }
 }
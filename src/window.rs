@@ -19,7 +19,8 @@ Notable missing window functions:
 use rustradio::window::WindowType;
 let window_type = WindowType::Hamming;
 let ntaps = 3;
-let taps = window_type.make_window(ntaps).0;
+let window = window_type.make_window(ntaps);
+let taps = window.coeffs();
 assert_eq!(taps.len(), ntaps);
 
 let correct = [0.0869565, 1.0, 0.0869565];
@@ -28,6 +29,15 @@ for (x,y) in taps.iter().zip(correct) {
   assert!((x-y).abs() < 0.1);
 }
 ```
+
+Custom windows can be created from arbitrary coefficients:
+
+```
+use rustradio::window::WindowType;
+let window_type = WindowType::Custom(vec![0.5, 1.0, 0.5]);
+let window = window_type.make_window(3);
+assert_eq!(window.coeffs(), &[0.5, 1.0, 0.5]);
+```
 */
 use crate::Float;
 
@@ -57,6 +67,11 @@ pub enum WindowType {
     ///
     /// See wikipedia.
     HammingParm(Float),
+
+    /// User provided window coefficients.
+    ///
+    /// The number of coefficients must match the number of taps requested.
+    Custom(Vec<Float>),
 }
 
 impl WindowType {
@@ -70,16 +85,40 @@ impl WindowType {
             WindowType::BlackmanHarris => 92.0,
             WindowType::Hamming => 53.0,
             WindowType::HammingParm(_) => 53.0,
+            // Unknown, so assume it's like the most common one.
+            WindowType::Custom(_) => 53.0,
+        }
+    }
+
+    /// Return the fixed window length, for window types that have one.
+    pub fn fixed_len(&self) -> Option<usize> {
+        match self {
+            WindowType::Custom(c) => Some(c.len()),
+            _ => None,
         }
     }
 
     /// Make a window of a dynamic type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window type is `Custom`, and the number of coefficients
+    /// doesn't match `ntaps`.
     pub fn make_window(&self, ntaps: usize) -> Window {
         match self {
             WindowType::Blackman => blackman(ntaps),
             WindowType::BlackmanHarris => blackman_harris(ntaps),
             WindowType::Hamming => hamming(ntaps, DEFAULT_HAMMING_PARM),
             WindowType::HammingParm(parm) => hamming(ntaps, *parm),
+            WindowType::Custom(c) => {
+                assert_eq!(
+                    c.len(),
+                    ntaps,
+                    "custom window has {} coefficients, but {ntaps} taps requested",
+                    c.len()
+                );
+                Window::from_coeffs(c.clone())
+            }
         }
     }
 }
@@ -88,7 +127,34 @@ impl WindowType {
 /// operations.
 ///
 /// https://en.wikipedia.org/wiki/Window_function
-pub struct Window(pub Vec<Float>);
+pub struct Window(Vec<Float>);
+
+impl Window {
+    /// Create window from arbitrary coefficients.
+    pub fn from_coeffs(coeffs: Vec<Float>) -> Self {
+        Self(coeffs)
+    }
+
+    /// Window coefficients.
+    pub fn coeffs(&self) -> &[Float] {
+        &self.0
+    }
+
+    /// Consume the window, returning the coefficients.
+    pub fn into_coeffs(self) -> Vec<Float> {
+        self.0
+    }
+
+    /// Number of coefficients.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return true if the window has no coefficients.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
 
 /// Create Hamming window.
 ///
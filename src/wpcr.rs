@@ -45,7 +45,9 @@ use crate::{Complex, Error, Float};
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct Midpointer {
+    #[rustradio(in)]
     src: NCReadStream<Vec<Float>>,
+    #[rustradio(out)]
     dst: NCWriteStream<Vec<Float>>,
 }
 impl Midpointer {
@@ -108,7 +110,9 @@ impl WpcrBuilder {
 #[derive(rustradio_macros::Block)]
 #[rustradio(crate)]
 pub struct Wpcr {
+    #[rustradio(in)]
     src: NCReadStream<Vec<Float>>,
+    #[rustradio(out)]
     dst: NCWriteStream<Vec<Float>>,
     samp_rate: Option<Float>,
 }
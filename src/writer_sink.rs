@@ -0,0 +1,84 @@
+//! Write stream to any [Write], optionally with tags.
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::block::{Block, BlockRet};
+use crate::stream::{ReadStream, Tag};
+use crate::{Error, Sample};
+
+/// Write stream to any [Write].
+///
+/// Samples are written in the same raw format as
+/// [FileSink][crate::file_sink::FileSink]. Tags are dropped, unless a tag
+/// writer is set with [WriterSink::set_tag_writer].
+///
+/// Tags are written one per line to the tag writer, in the format parsed by
+/// [Tag]'s `FromStr`, with the position being the absolute sample position
+/// in the stream. [ReaderSource][crate::reader_source::ReaderSource] can
+/// read them back.
+///
+/// String tag values containing newlines can't be read back.
+#[derive(rustradio_macros::Block)]
+#[rustradio(crate, flush)]
+pub struct WriterSink<T: Copy> {
+    #[rustradio(in)]
+    src: ReadStream<T>,
+    writer: Box<dyn Write + Send>,
+    tag_writer: Option<Box<dyn Write + Send>>,
+    // Samples written so far.
+    pos: usize,
+}
+
+impl<T: Copy> WriterSink<T> {
+    /// Create new WriterSink block.
+    pub fn new(src: ReadStream<T>, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            src,
+            writer,
+            tag_writer: None,
+            pos: 0,
+        }
+    }
+
+    /// Also write tags, to a separate writer.
+    pub fn set_tag_writer(&mut self, w: Box<dyn Write + Send>) {
+        self.tag_writer = Some(w);
+    }
+
+    /// Flush the writers.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        if let Some(w) = &mut self.tag_writer {
+            w.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Block for WriterSink<T>
+where
+    T: Copy + Sample<Type = T>,
+{
+    fn work(&mut self) -> Result<BlockRet, Error> {
+        let (i, tags) = self.src.read_buf()?;
+        let n = i.len();
+        if n == 0 {
+            return Ok(BlockRet::Noop);
+        }
+        if let Some(w) = &mut self.tag_writer {
+            for tag in tags.iter().filter(|t| t.pos() < n) {
+                let tag = Tag::new(self.pos + tag.pos(), tag.key().into(), tag.val().clone());
+                writeln!(w, "{tag}")?;
+            }
+        }
+        let mut v = Vec::with_capacity(T::size() * n);
+        i.iter().for_each(|s: &T| {
+            v.extend(&s.serialize());
+        });
+        self.writer.write_all(&v)?;
+        i.consume(n);
+        self.pos += n;
+        Ok(BlockRet::Ok)
+    }
+}
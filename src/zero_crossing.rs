@@ -34,7 +34,7 @@ pub struct ZeroCrossing {
     src: ReadStream<Float>,
     #[rustradio(out)]
     dst: WriteStream<Float>,
-    // TODO: should this also be tagged `out`?
+    #[rustradio(out)]
     out_clock: Option<WriteStream<Float>>,
 }
 